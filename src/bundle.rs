@@ -0,0 +1,217 @@
+use crate::loader;
+use crate::parser::frontmatter;
+use crate::runtime::address::ImportRef;
+use anyhow::{Context, Result};
+use serde_yaml_ng::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Output format of `html6 bundle`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BundleFormat {
+    /// .hnmd with component sources inlined under a `components:` frontmatter section
+    Hnmd,
+    /// Serialized `Document` with a `components` map of parsed `ComponentDef`s
+    Json,
+}
+
+/// Sources collected from a document's import graph
+struct CollectedSources {
+    /// Component name → .hnmc source
+    components: BTreeMap<String, String>,
+    /// Imports that point at Nostr and stay imports in the bundle
+    remote_imports: BTreeMap<String, String>,
+}
+
+/// Bundle a document and every local component it imports into one file
+pub fn bundle(path: &str, format: BundleFormat) -> Result<String> {
+    match format {
+        BundleFormat::Hnmd => bundle_hnmd(path),
+        BundleFormat::Json => bundle_json(path),
+    }
+}
+
+/// Bundle into a single .hnmd with a `components:` frontmatter section
+pub fn bundle_hnmd(path: &str) -> Result<String> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path))?;
+    let collected = collect_sources(Path::new(path))?;
+
+    let (fm_str, body) = match frontmatter::split_source(&source) {
+        Some((fm, body)) => (fm, body),
+        None => ("", source.as_str()),
+    };
+
+    let mut fm = if fm_str.trim().is_empty() {
+        Mapping::new()
+    } else {
+        let value: Value = serde_yaml_ng::from_str(fm_str).context("Failed to parse YAML frontmatter")?;
+        value
+            .as_mapping()
+            .cloned()
+            .context("Frontmatter must be a YAML mapping")?
+    };
+
+    let imports_key = Value::String("imports".to_string());
+    let components_key = Value::String("components".to_string());
+
+    // Already-bundled components stay, new ones are added
+    let mut components = fm
+        .get(&components_key)
+        .and_then(|v| v.as_mapping())
+        .cloned()
+        .unwrap_or_default();
+    for (name, component_source) in &collected.components {
+        components.insert(Value::String(name.clone()), Value::String(component_source.clone()));
+    }
+
+    fm.remove(&imports_key);
+    if !collected.remote_imports.is_empty() {
+        let mut imports = Mapping::new();
+        for (name, reference) in &collected.remote_imports {
+            imports.insert(Value::String(name.clone()), Value::String(reference.clone()));
+        }
+        fm.insert(imports_key, Value::Mapping(imports));
+    }
+    if !components.is_empty() {
+        fm.insert(components_key, Value::Mapping(components));
+    }
+
+    let fm_yaml = serde_yaml_ng::to_string(&Value::Mapping(fm)).context("Failed to write frontmatter")?;
+    let body = body.trim_start_matches('\n');
+
+    Ok(format!("---\n{}---\n\n{}", fm_yaml, body))
+}
+
+/// Bundle into a JSON `Document` whose `components` map holds every parsed component
+pub fn bundle_json(path: &str) -> Result<String> {
+    let (mut doc, registry) = loader::load_hnmd_unresolved(path, None)?;
+
+    for name in registry.list_components() {
+        if let Some(component_def) = registry.get(name) {
+            doc.components.insert(name.to_string(), component_def.clone());
+        }
+    }
+
+    // Only Nostr imports are left to resolve at load time
+    let mut remote_imports = HashMap::new();
+    for (name, address) in registry.unresolved_addresses() {
+        doc.components.remove(name);
+        remote_imports.insert(name.clone(), address.to_reference());
+    }
+    doc.imports = remote_imports;
+
+    serde_json::to_string_pretty(&doc).context("Failed to serialize bundle")
+}
+
+/// Walk local imports (transitively) and read every component source
+fn collect_sources(root: &Path) -> Result<CollectedSources> {
+    let mut collected = CollectedSources {
+        components: BTreeMap::new(),
+        remote_imports: BTreeMap::new(),
+    };
+    // Component name → file it came from (names are global in the registry)
+    let mut origins: HashMap<String, PathBuf> = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(file) = pending.pop() {
+        let source = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let base = file.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+
+        for (name, import) in frontmatter::parse_source_imports(&source)? {
+            let relative = match ImportRef::parse(&import)? {
+                ImportRef::Address(_) => {
+                    collected.remote_imports.insert(name, import);
+                    continue;
+                }
+                ImportRef::Path(relative) => relative,
+            };
+
+            let resolved = if relative.starts_with("./") || relative.starts_with("../") {
+                base.join(&relative)
+            } else {
+                PathBuf::from(&relative)
+            };
+            let canonical = fs::canonicalize(&resolved)
+                .with_context(|| format!("Failed to resolve component '{}' at {}", name, resolved.display()))?;
+
+            match origins.get(&name) {
+                Some(existing) if existing == &canonical => continue,
+                Some(existing) => {
+                    return Err(anyhow::anyhow!(
+                        "Component name '{}' is imported from both {} and {}",
+                        name,
+                        existing.display(),
+                        canonical.display()
+                    ));
+                }
+                None => {}
+            }
+
+            let component_source = fs::read_to_string(&canonical)
+                .with_context(|| format!("Failed to read component {}", canonical.display()))?;
+            origins.insert(name.clone(), canonical.clone());
+            collected.components.insert(name, component_source);
+            pending.push(canonical);
+        }
+    }
+
+    Ok(collected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("html6-bundle-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_bundle_hnmd_loads_without_component_files() {
+        let bundled = bundle("apps/feed.hnmd", BundleFormat::Hnmd).unwrap();
+        assert!(bundled.contains("components:"));
+        assert!(!bundled.contains("./components/Feed.hnmc"));
+
+        let path = write_temp("feed.hnmd", &bundled);
+        let (doc, registry) = loader::load_hnmd(&path).unwrap();
+        let (original, _) = loader::load_hnmd("apps/feed.hnmd").unwrap();
+
+        assert!(registry.contains("Feed"));
+        assert!(registry.contains("Profile"));
+        assert_eq!(doc.body, original.body);
+        assert_eq!(doc.frontmatter, original.frontmatter);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_bundle_json_loads_without_component_files() {
+        let bundled = bundle("apps/feed.hnmd", BundleFormat::Json).unwrap();
+
+        let path = write_temp("feed.json", &bundled);
+        let (doc, registry) = loader::load_hnmd(&path).unwrap();
+
+        assert!(doc.imports.is_empty());
+        assert_eq!(registry.list_components().len(), 2);
+        assert!(registry.contains("Profile"));
+        // Expression props survive the JSON roundtrip
+        let (original, original_registry) = loader::load_hnmd("apps/feed.hnmd").unwrap();
+        assert_eq!(doc.body, original.body);
+        assert_eq!(registry.get("Feed"), original_registry.get("Feed"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_bundle_is_stable() {
+        // Bundling a bundle changes nothing
+        let once = bundle("apps/feed.hnmd", BundleFormat::Hnmd).unwrap();
+        let path = write_temp("stable.hnmd", &once);
+        let twice = bundle(&path, BundleFormat::Hnmd).unwrap();
+        assert_eq!(once, twice);
+        let _ = fs::remove_file(path);
+    }
+}
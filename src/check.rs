@@ -0,0 +1,690 @@
+use crate::loader;
+use crate::parser::ast::{
+    is_template, template_parts, Document, Node, NodeStyle, ParseError, PropValue, Span, StyleRules, TemplatePart,
+    STYLE_PROPERTIES,
+};
+use crate::renderer::style::{font_weight, StyleSheet};
+use crate::runtime::context::to_jq_expr;
+use crate::runtime::{ComponentRegistry, JaqEvaluator};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Queries QueryRuntime derives on its own (see `QueryRuntime::to_json`)
+const DERIVED_QUERIES: &[&str] = &["profiles", "enrichedFeed"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by `html6 check`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column (in characters)
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            severity,
+            self.message
+        )
+    }
+}
+
+/// Load a document (and its local components) and report everything that would fail at runtime
+/// Load errors are reported as a diagnostic rather than an Err
+pub fn check_file(path: &str) -> Result<Vec<Diagnostic>> {
+    let source = fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path))?;
+
+    let (doc, registry) = match loader::load_hnmd_unresolved(path, None) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            // Parse errors in the document itself know where they happened
+            let span = e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<ParseError>())
+                .map(|parse_error| parse_error.span)
+                .unwrap_or_else(|| Span::new(1, 1, 0));
+            return Ok(vec![Diagnostic {
+                file: PathBuf::from(path),
+                line: span.line,
+                column: span.column,
+                severity: Severity::Error,
+                message: format!("{:#}", e),
+            }]);
+        }
+    };
+
+    Ok(check_document(Path::new(path), &source, &doc, &registry))
+}
+
+/// Check a loaded document and its components; `source` is the document text (for locations)
+pub fn check_document(
+    path: &Path,
+    source: &str,
+    doc: &Document,
+    registry: &ComponentRegistry,
+) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(doc, registry);
+    let mut file = SourceFile::new(path, source.to_string(), true);
+
+    checker.check_frontmatter(&mut file);
+    checker.check_styles(&mut file, &doc.frontmatter.styles);
+    checker.check_nodes(&mut file, &doc.body);
+    let document_styles = checker.styles.clone();
+
+    let mut names = registry.list_components();
+    names.sort();
+    for name in names {
+        let Some(component_def) = registry.get(name) else { continue };
+
+        // Bundled components live inside the document itself
+        let mut component_file = match registry.source_path(name) {
+            Some(component_path) => match fs::read_to_string(component_path) {
+                Ok(text) => SourceFile::new(component_path, text, true),
+                Err(_) => continue,
+            },
+            // Spans of bundled components are relative to their inlined source
+            None => SourceFile::new(path, source.to_string(), false),
+        };
+        // A component's classes only apply inside it
+        checker.styles = document_styles.for_component(&component_def.styles);
+        checker.check_styles(&mut component_file, &component_def.styles);
+        checker.check_nodes(&mut component_file, &component_def.body);
+    }
+
+    checker.check_unused_state(&file);
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    doc: &'a Document,
+    registry: &'a ComponentRegistry,
+    /// Filters, pipes, component queries and derived queries
+    known_queries: HashSet<String>,
+    used_state: HashSet<String>,
+    evaluator: JaqEvaluator,
+    /// Theme and the classes in scope for the file being checked
+    styles: StyleSheet,
+    query_re: Regex,
+    state_re: Regex,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(doc: &'a Document, registry: &'a ComponentRegistry) -> Self {
+        let mut known_queries: HashSet<String> = doc
+            .frontmatter
+            .filters
+            .keys()
+            .chain(doc.frontmatter.pipes.keys())
+            .cloned()
+            .collect();
+        for name in registry.list_components() {
+            if let Some(component_def) = registry.get(name) {
+                known_queries.extend(component_def.queries.keys().cloned());
+            }
+        }
+        known_queries.extend(DERIVED_QUERIES.iter().map(|q| q.to_string()));
+
+        Self {
+            doc,
+            registry,
+            known_queries,
+            used_state: HashSet::new(),
+            evaluator: JaqEvaluator::new(),
+            styles: StyleSheet::new(&doc.frontmatter.theme, doc.frontmatter.styles.clone()),
+            query_re: root_field_regex("queries"),
+            state_re: root_field_regex("state"),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, file: &SourceFile, offset: usize, severity: Severity, message: String) {
+        let (line, column) = file.line_column(offset);
+        self.diagnostics.push(Diagnostic {
+            file: file.path.clone(),
+            line,
+            column,
+            severity,
+            message,
+        });
+    }
+
+    fn check_frontmatter(&mut self, file: &mut SourceFile) {
+        let doc = self.doc;
+        let frontmatter = &doc.frontmatter;
+
+        let mut pipe_ids: Vec<&String> = frontmatter.pipes.keys().collect();
+        pipe_ids.sort();
+        for id in pipe_ids {
+            let pipe = &frontmatter.pipes[id];
+            let offset = file.offset_of(pipe.span).unwrap_or_else(|| file.find_key(id));
+
+            if !frontmatter.filters.contains_key(&pipe.from) && !frontmatter.pipes.contains_key(&pipe.from) {
+                self.report(
+                    file,
+                    offset,
+                    Severity::Error,
+                    format!("pipe '{}' reads from '{}', which is not a filter or pipe", id, pipe.from),
+                );
+            }
+            if let Err(e) = self.evaluator.check(&pipe.jq) {
+                self.report(file, offset, Severity::Error, format!("pipe '{}' has invalid jq: {}", id, e));
+            }
+        }
+
+        let mut patterns: Vec<&String> = frontmatter.routes.keys().collect();
+        patterns.sort();
+        for pattern in patterns {
+            let target = &frontmatter.routes[pattern];
+            let dir = file.path.parent().unwrap_or(Path::new(""));
+            if !dir.join(target).is_file() {
+                let offset = file.find_key(pattern);
+                self.report(
+                    file,
+                    offset,
+                    Severity::Error,
+                    format!("route '{}' points to missing file '{}'", pattern, target),
+                );
+            }
+        }
+
+        // Filters and actions can use state in their templates
+        let templates: Vec<String> = frontmatter
+            .filters
+            .values()
+            .filter_map(|f| serde_json::to_string(f).ok())
+            .chain(frontmatter.actions.values().filter_map(|a| serde_json::to_string(a).ok()))
+            .collect();
+        for template in templates {
+            self.mark_state_used(&template);
+        }
+    }
+
+    /// Class definitions from a `styles:` section
+    fn check_styles(&mut self, file: &mut SourceFile, styles: &std::collections::HashMap<String, StyleRules>) {
+        let mut classes: Vec<&String> = styles.keys().collect();
+        classes.sort();
+        for class in classes {
+            let mut rules: Vec<(&String, &String)> = styles[class].iter().collect();
+            rules.sort();
+            for (property, value) in rules {
+                if let Some(message) = self.style_problem(property, value) {
+                    let offset = file.find_key(class);
+                    self.report(file, offset, Severity::Warning, format!("style '{}': {}", class, message));
+                }
+            }
+        }
+    }
+
+    /// `class` and style properties of a stack or `<text>`
+    fn check_node_style(&mut self, file: &mut SourceFile, style: &NodeStyle) {
+        if let Some(class) = &style.class {
+            if is_template(class) {
+                for part in template_parts(class) {
+                    if let TemplatePart::Expr(expr) = part {
+                        self.check_expr(file, expr, None);
+                    }
+                }
+            } else {
+                for name in class.split_whitespace() {
+                    if !self.styles.has_class(name) {
+                        let offset = file.find(name);
+                        self.report(file, offset, Severity::Warning, format!("unknown class '{}'", name));
+                    }
+                }
+            }
+        }
+
+        let mut properties: Vec<(&String, &PropValue)> = style.properties.iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(b.0));
+        for (property, value) in properties {
+            match value {
+                PropValue::Expression(expr) => self.check_expr(file, expr, None),
+                PropValue::Literal(literal) => {
+                    if let Some(message) = self.style_problem(property, literal) {
+                        let offset = file.find(property);
+                        self.report(file, offset, Severity::Warning, message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// What is wrong with a literal style property, if anything
+    fn style_problem(&self, property: &str, value: &str) -> Option<String> {
+        let valid = match property {
+            "color" | "background" | "border_color" => self.styles.parse_color(value).is_some(),
+            "font_size" => self.styles.parse_font_size(value).is_some(),
+            "font_weight" => font_weight(value).is_some(),
+            "padding" => self.styles.parse_spacing(value).is_some(),
+            "border_width" | "radius" => value.parse::<f64>().is_ok(),
+            _ => return Some(format!("unknown style property '{}' (expected one of {})", property, STYLE_PROPERTIES.join(", "))),
+        };
+        (!valid).then(|| format!("invalid {} '{}'", property, value))
+    }
+
+    fn check_nodes(&mut self, file: &mut SourceFile, nodes: &[Node]) {
+        for node in nodes {
+            self.check_node(file, node);
+        }
+    }
+
+    fn check_node(&mut self, file: &mut SourceFile, node: &Node) {
+        match node {
+            Node::Expr { expression, span } | Node::Json { value: expression, span } => {
+                self.check_expr(file, expression, *span);
+            }
+            Node::Each { from, key, on_end, children, span, .. } => {
+                self.check_expr(file, from, *span);
+                if let Some(key) = key {
+                    self.check_expr(file, key, *span);
+                }
+                if let Some(on_end) = on_end {
+                    self.check_action(file, on_end, *span);
+                }
+                self.check_nodes(file, children);
+            }
+            Node::If { value, children, else_children, span } => {
+                self.check_expr(file, value, *span);
+                self.check_nodes(file, children);
+                if let Some(else_children) = else_children {
+                    self.check_nodes(file, else_children);
+                }
+            }
+            Node::Button { on_click, children, span } => {
+                if let Some(on_click) = on_click {
+                    self.check_action(file, on_click, *span);
+                }
+                self.check_nodes(file, children);
+            }
+            Node::CustomComponent { name, props, children, span } => {
+                let offset = file
+                    .offset_of(*span)
+                    .unwrap_or_else(|| file.find(&format!("<{}", name)));
+                let queued = self.registry.unresolved_addresses().iter().any(|(n, _)| n == name);
+                if !self.registry.contains(name) && !queued {
+                    self.report(file, offset, Severity::Error, format!("unknown component '{}'", name));
+                }
+
+                let mut prop_names: Vec<&String> = props.keys().collect();
+                prop_names.sort();
+                for prop_name in prop_names {
+                    if let PropValue::Expression(expr) = &props[prop_name] {
+                        self.check_expr(file, expr, *span);
+                    }
+                }
+                self.check_nodes(file, children);
+            }
+            Node::Image { src, alt, fit, span, .. } => {
+                for part in template_parts(src).into_iter().chain(template_parts(alt)) {
+                    if let TemplatePart::Expr(expr) = part {
+                        self.check_expr(file, expr, *span);
+                    }
+                }
+                if let Some(fit) = fit {
+                    if crate::renderer::widgets::object_fit(fit).is_none() {
+                        let offset = file.offset_of(*span).unwrap_or_else(|| file.find(fit));
+                        self.report(file, offset, Severity::Warning, format!("unknown image fit '{}'", fit));
+                    }
+                }
+            }
+            Node::List { items, .. } => {
+                for item in items {
+                    if let Some(bind) = &item.bind {
+                        self.check_expr(file, bind, None);
+                    }
+                    self.check_nodes(file, &item.children);
+                }
+            }
+            Node::Table { rows, .. } => {
+                for cell in rows.iter().flat_map(|row| &row.cells) {
+                    self.check_nodes(file, &cell.children);
+                }
+            }
+            Node::VStack { children, layout } | Node::HStack { children, layout } => {
+                use crate::renderer::widgets::{cross_axis_alignment, main_axis_alignment};
+
+                let align = layout.align.as_ref().filter(|a| cross_axis_alignment(a).is_none());
+                let justify = layout.justify.as_ref().filter(|j| main_axis_alignment(j).is_none());
+                for (attr, value) in [("align", align), ("justify", justify)] {
+                    if let Some(value) = value {
+                        let offset = file.find(&format!("{}=\"{}\"", attr, value));
+                        self.report(file, offset, Severity::Warning, format!("unknown {} '{}'", attr, value));
+                    }
+                }
+                self.check_node_style(file, &layout.style);
+                self.check_nodes(file, children);
+            }
+            Node::TextBlock { children, style } => {
+                self.check_node_style(file, style);
+                self.check_nodes(file, children);
+            }
+            Node::Heading { children, .. }
+            | Node::Paragraph { children }
+            | Node::Strong { children }
+            | Node::Emphasis { children }
+            | Node::Strikethrough { children }
+            | Node::Link { children, .. }
+            | Node::Blockquote { children }
+            | Node::FootnoteDefinition { children, .. }
+            | Node::Grid { children, .. } => self.check_nodes(file, children),
+            Node::Text { .. }
+            | Node::InlineCode { .. }
+            | Node::FootnoteReference { .. }
+            | Node::CodeBlock { .. }
+            | Node::Input { .. }
+            | Node::Spacer { .. }
+            | Node::ThematicBreak => {}
+        }
+    }
+
+    fn check_expr(&mut self, file: &mut SourceFile, expr: &str, span: Option<Span>) {
+        let offset = file.offset_of(span).unwrap_or_else(|| file.find(expr));
+
+        if let Err(e) = self.evaluator.check(&to_jq_expr(expr)) {
+            self.report(file, offset, Severity::Error, format!("invalid expression `{}`: {}", expr, e));
+        }
+
+        let undefined: Vec<String> = self
+            .query_re
+            .captures_iter(expr)
+            .map(|caps| caps[1].to_string())
+            .filter(|id| !self.known_queries.contains(id))
+            .collect();
+        for id in undefined {
+            self.report(file, offset, Severity::Error, format!("undefined query '{}'", id));
+        }
+
+        self.mark_state_used(expr);
+    }
+
+    fn check_action(&mut self, file: &mut SourceFile, on_click: &str, span: Option<Span>) {
+        if let Some(target) = crate::navigation::navigate_target(on_click) {
+            self.check_expr(file, target, span);
+            return;
+        }
+        if let Some(query) = crate::runtime::query::load_more_target(on_click) {
+            if !self.known_queries.contains(query) {
+                let offset = file.offset_of(span).unwrap_or_else(|| file.find(on_click));
+                self.report(file, offset, Severity::Error, format!("loadMore names undefined query '{}'", query));
+            }
+            return;
+        }
+
+        let trimmed = on_click.trim();
+        let id = trimmed.strip_prefix("actions.").unwrap_or(trimmed);
+        if !self.doc.frontmatter.actions.contains_key(id) {
+            let offset = file.offset_of(span).unwrap_or_else(|| file.find(on_click));
+            self.report(
+                file,
+                offset,
+                Severity::Error,
+                format!("button on_click names missing action '{}'", id),
+            );
+        }
+    }
+
+    fn mark_state_used(&mut self, text: &str) {
+        for caps in self.state_re.captures_iter(text) {
+            self.used_state.insert(caps[1].to_string());
+        }
+    }
+
+    fn check_unused_state(&mut self, file: &SourceFile) {
+        let doc = self.doc;
+        let mut unused: Vec<&String> = doc
+            .frontmatter
+            .state
+            .keys()
+            .filter(|key| !self.used_state.contains(*key))
+            .collect();
+        unused.sort();
+        for key in unused {
+            let offset = file.find_key(key);
+            self.report(file, offset, Severity::Warning, format!("state '{}' is never used", key));
+        }
+    }
+}
+
+/// Matches `<root>.<field>` (optionally `.`-prefixed) and captures the field
+fn root_field_regex(root: &str) -> Regex {
+    Regex::new(&format!(r"(?:^|[^A-Za-z0-9_$.])\.?{}\.([A-Za-z_][A-Za-z0-9_]*)", root)).unwrap()
+}
+
+/// Source text used to turn AST items back into line/column positions
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+    /// Whether node spans are offsets into `text`
+    trust_spans: bool,
+    /// Byte offset where the body starts (after the frontmatter)
+    body_start: usize,
+    /// Where the previous body match was, so repeated snippets resolve in document order
+    cursor: usize,
+}
+
+impl SourceFile {
+    fn new(path: &Path, text: String, trust_spans: bool) -> Self {
+        let body_start = crate::parser::frontmatter::split_source(&text)
+            .map(|(_, body)| text.len() - body.len())
+            .unwrap_or(0);
+        Self {
+            path: path.to_path_buf(),
+            text,
+            trust_spans,
+            body_start,
+            cursor: body_start,
+        }
+    }
+
+    /// Offset of a parsed span, moving the search cursor along with it
+    fn offset_of(&mut self, span: Option<Span>) -> Option<usize> {
+        let offset = span.filter(|_| self.trust_spans)?.offset;
+        self.cursor = offset.min(self.text.len());
+        Some(self.cursor)
+    }
+
+    /// Offset of a body snippet, searching forward from the previous match
+    fn find(&mut self, needle: &str) -> usize {
+        let found = self.text[self.cursor..]
+            .find(needle)
+            .map(|i| i + self.cursor)
+            .or_else(|| self.text[self.body_start..].find(needle).map(|i| i + self.body_start))
+            .or_else(|| self.text.find(needle));
+
+        match found {
+            Some(offset) => {
+                self.cursor = offset;
+                offset
+            }
+            None => self.body_start,
+        }
+    }
+
+    /// Offset of a `key:` line inside the frontmatter
+    fn find_key(&self, key: &str) -> usize {
+        let key_re = Regex::new(&format!(r"(?m)^[ \t]*{}[ \t]*:", regex::escape(key))).unwrap();
+        key_re
+            .find(&self.text[..self.body_start])
+            .map(|m| m.start() + (m.as_str().len() - m.as_str().trim_start().len()))
+            .unwrap_or(0)
+    }
+
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let span = Span::at(&self.text, offset);
+        (span.line, span.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_source(name: &str, source: &str) -> Vec<Diagnostic> {
+        let path = std::env::temp_dir().join(format!("html6-check-{}-{}", std::process::id(), name));
+        fs::write(&path, source).unwrap();
+        let diagnostics = check_file(path.to_str().unwrap()).unwrap();
+        let _ = fs::remove_file(path);
+        diagnostics
+    }
+
+    #[test]
+    fn test_example_apps_are_clean() {
+        for app in ["apps/hello.hnmd", "apps/feed.hnmd", "apps/test_component.hnmd"] {
+            let diagnostics = check_file(app).unwrap();
+            assert!(diagnostics.is_empty(), "{}: {:?}", app, diagnostics);
+        }
+    }
+
+    #[test]
+    fn test_reports_runtime_mistakes() {
+        let source = r#"---
+filters:
+  feed:
+    kinds: [1]
+
+pipes:
+  names:
+    from: fed
+    jq: ".feed | map(.content)"
+
+actions:
+  post:
+    kind: 1
+    content: "{form.message}"
+
+state:
+  used: 1
+  unused: 2
+---
+
+# {state.used}
+
+{queries.missing[0].content}
+
+{state.used | ]}
+
+<button on_click={actions.publish} label="Go" />
+
+<Nope />
+
+<img src={queries.avatars[0]} fit="stretch" />
+
+<hstack justify="middle">
+</hstack>
+
+<button on_click={loadMore(feed)} label="More" />
+
+<button on_click={loadMore(posts)} label="More" />
+"#;
+        let diagnostics = check_source("mistakes.hnmd", source);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+
+        assert!(messages.iter().any(|m| m.contains("reads from 'fed'")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m == "undefined query 'missing'"));
+        assert!(messages.iter().any(|m| m.starts_with("invalid expression `state.used | ]`")));
+        assert!(messages.iter().any(|m| m.contains("missing action 'publish'")));
+        assert!(messages.iter().any(|m| m == "unknown component 'Nope'"));
+        assert!(messages.iter().any(|m| m == "undefined query 'avatars'"));
+        assert!(messages.iter().any(|m| m == "unknown image fit 'stretch'"));
+        assert!(messages.iter().any(|m| m == "unknown justify 'middle'"));
+        assert!(messages.iter().any(|m| m == "loadMore names undefined query 'posts'"));
+        assert!(!messages.iter().any(|m| m.contains("'feed'")), "{:?}", messages);
+
+        let unused = diagnostics.iter().find(|d| d.message == "state 'unused' is never used").unwrap();
+        assert_eq!(unused.severity, Severity::Warning);
+        assert_eq!((unused.line, unused.column), (18, 3));
+
+        let missing = diagnostics.iter().find(|d| d.message == "undefined query 'missing'").unwrap();
+        assert_eq!((missing.line, missing.column), (23, 1));
+    }
+
+    #[test]
+    fn test_reports_style_mistakes() {
+        let source = r##"---
+theme:
+  palette:
+    brand: "#7c3aed"
+styles:
+  card:
+    background: surface
+    padding: huge
+---
+
+<vstack class="card missing" style="colour: red; color: brand">
+  <text color={queries.nope[0].color} font_weight="heavy">Hi</text>
+</vstack>
+"##;
+        let messages: Vec<String> = check_source("styles.hnmd", source).iter().map(|d| d.message.clone()).collect();
+        assert!(messages.iter().any(|m| m == "style 'card': invalid padding 'huge'"), "{:?}", messages);
+        assert!(messages.iter().any(|m| m == "unknown class 'missing'"));
+        assert!(messages.iter().any(|m| m.starts_with("unknown style property 'colour'")));
+        assert!(messages.iter().any(|m| m == "undefined query 'nope'"));
+        assert!(messages.iter().any(|m| m == "invalid font_weight 'heavy'"));
+        // Palette names are colors
+        assert!(!messages.iter().any(|m| m.contains("brand")));
+    }
+
+    #[test]
+    fn test_load_errors_are_diagnostics() {
+        let diagnostics = check_source("broken.hnmd", "---\nstate: [\n---\n\n# Hi\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn test_reports_routing_mistakes() {
+        let source = r#"---
+routes:
+  /feed: feed.hnmd
+  /nowhere/:id: does-not-exist.hnmd
+---
+
+<button on_click={navigate("/thread/" + queries.gone[0].id)} label="Open" />
+"#;
+        let path = std::env::temp_dir().join(format!("html6-check-routes-{}", std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("feed.hnmd"), "# Feed\n").unwrap();
+        fs::write(path.join("app.hnmd"), source).unwrap();
+        let diagnostics = check_file(path.join("app.hnmd").to_str().unwrap()).unwrap();
+        let _ = fs::remove_dir_all(&path);
+
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "route '/nowhere/:id' points to missing file 'does-not-exist.hnmd'".to_string(),
+                "undefined query 'gone'".to_string(),
+            ]
+        );
+    }
+}
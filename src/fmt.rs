@@ -0,0 +1,233 @@
+use crate::loader;
+use crate::parser::component_def::parse_component;
+use crate::parser::decompile::{decompile, decompile_component};
+use crate::parser::frontmatter::split_source;
+use crate::parser::mdx::unkept_constructs;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Kind of source file, picked by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// `.hnmd` document
+    Document,
+    /// `.hnmc` component
+    Component,
+}
+
+impl SourceKind {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("hnmc") => SourceKind::Component,
+            _ => SourceKind::Document,
+        }
+    }
+}
+
+/// Rewrite source into the canonical layout
+///
+/// The result is re-parsed and must give the same AST as the input, so formatting never changes meaning.
+/// Sources with content the AST doesn't keep (frontmatter comments, reference links, ...) are refused.
+pub fn format_source(source: &str, kind: SourceKind) -> Result<String> {
+    if kind == SourceKind::Document && source.trim_start().starts_with('{') {
+        anyhow::bail!("JSON bundles are not formatted");
+    }
+    let unkept = unkept_content(source);
+    if !unkept.is_empty() {
+        anyhow::bail!("Not formatted: formatting would drop {}", unkept.join(", "));
+    }
+
+    match kind {
+        SourceKind::Document => {
+            let doc = loader::parse_hnmd(source)?;
+            let formatted = single_trailing_newline(decompile(&doc));
+            let reparsed = loader::parse_hnmd(&formatted).context("Formatted document does not parse")?;
+            if reparsed != doc {
+                anyhow::bail!("Formatting would change the document");
+            }
+            Ok(formatted)
+        }
+        SourceKind::Component => {
+            let def = parse_component(source).map_err(|e| anyhow::anyhow!(e))?;
+            let formatted = single_trailing_newline(decompile_component(&def));
+            let reparsed = parse_component(&formatted)
+                .map_err(|e| anyhow::anyhow!("Formatted component does not parse: {}", e))?;
+            if reparsed != def {
+                anyhow::bail!("Formatting would change the component");
+            }
+            Ok(formatted)
+        }
+    }
+}
+
+/// What the source has that parsing drops, by name
+fn unkept_content(source: &str) -> Vec<&'static str> {
+    let (frontmatter, body) = split_source(source).unwrap_or(("", source));
+    let mut unkept = Vec::new();
+    if has_yaml_comments(frontmatter) {
+        unkept.push("frontmatter comments");
+    }
+    unkept.extend(unkept_constructs(body));
+    unkept
+}
+
+/// Whether YAML has `#` comments, outside quoted strings and block strings (`|`, `>`)
+fn has_yaml_comments(yaml: &str) -> bool {
+    let mut block_indent: Option<usize> = None;
+    for line in yaml.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if let Some(block) = block_indent {
+            if trimmed.is_empty() || indent > block {
+                continue;
+            }
+            block_indent = None;
+        }
+
+        let mut quote = None;
+        let mut prev = ' ';
+        for c in trimmed.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '#' && prev.is_whitespace() => return true,
+                None if (c == '"' || c == '\'') && (prev.is_whitespace() || ":[{,".contains(prev)) => quote = Some(c),
+                None => {}
+            }
+            prev = c;
+        }
+
+        let header = trimmed.trim_end().trim_end_matches(|c: char| c == '-' || c == '+' || c.is_ascii_digit());
+        if header.ends_with('|') || header.ends_with('>') {
+            block_indent = Some(indent);
+        }
+    }
+    false
+}
+
+/// Files end with exactly one newline (or are empty)
+fn single_trailing_newline(source: String) -> String {
+    let trimmed = source.trim_end();
+    if trimmed.is_empty() { String::new() } else { format!("{}\n", trimmed) }
+}
+
+/// Format a file in place (or only compare when `check` is set); returns whether it was not already formatted
+pub fn format_file(path: &str, check: bool) -> Result<bool> {
+    let source = fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path))?;
+    let formatted = format_source(&source, SourceKind::of(Path::new(path))).with_context(|| path.to_string())?;
+
+    if formatted == source {
+        return Ok(false);
+    }
+    if !check {
+        fs::write(path, &formatted).with_context(|| format!("Failed to write file: {}", path))?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_is_canonical() {
+        let source = r#"---
+state:
+  b: 2
+  a: "one"
+filters:
+  feed: { kinds: [1], limit: 5 }
+---
+# Title
+<vstack   flex="1">
+<Profile
+  pubkey={note.pubkey}   name="x" />
+</vstack>
+"#;
+        let formatted = format_source(source, SourceKind::Document).unwrap();
+        assert_eq!(
+            formatted,
+            r#"---
+filters:
+  feed:
+    kinds: [1]
+    limit: 5
+
+state:
+  a: "one"
+  b: 2
+
+---
+
+# Title
+
+<vstack flex="1">
+<Profile name="x" pubkey={note.pubkey} />
+
+</vstack>
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_breaks_and_nested_text() {
+        let source = "# Title\n\n---\n\n<vstack>\n<hstack>\n<vstack>\nDeep text\n</vstack>\n</hstack>\n</vstack>\n";
+        let formatted = format_source(source, SourceKind::Document).unwrap();
+        assert!(formatted.contains("\n***\n"));
+        assert!(formatted.lines().all(|line| !line.starts_with("    ")), "{}", formatted);
+        assert_eq!(loader::parse_hnmd(&formatted).unwrap(), loader::parse_hnmd(source).unwrap());
+    }
+
+    #[test]
+    fn test_format_refuses_unkept_content() {
+        let source = r##"---
+# Shown on the home screen
+state:
+  title: "#1 app" # the best
+---
+
+See [the docs][docs].
+
+[docs]: https://example.com/docs
+"##;
+        let err = format_source(source, SourceKind::Document).unwrap_err().to_string();
+        assert!(err.contains("frontmatter comments"), "{}", err);
+        assert!(err.contains("reference links"), "{}", err);
+        assert!(err.contains("link reference definitions"), "{}", err);
+
+        // `#` in strings isn't a comment, so this one round-trips
+        let source = "---\nstate:\n  title: \"#1 app\"\n  intro: |\n    # Not a comment\n---\n\nSee [the docs](https://example.com/docs).\n";
+        let formatted = format_source(source, SourceKind::Document).unwrap();
+        assert_eq!(loader::parse_hnmd(&formatted).unwrap(), loader::parse_hnmd(source).unwrap());
+        assert_eq!(format_source(&formatted, SourceKind::Document).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        for path in ["apps/hello.hnmd", "apps/feed.hnmd", "apps/test_incremental.hnmd", "apps/components/Feed.hnmc"] {
+            let kind = SourceKind::of(Path::new(path));
+            let once = format_source(&fs::read_to_string(path).unwrap(), kind).unwrap();
+            let twice = format_source(&once, kind).unwrap();
+            assert_eq!(once, twice, "{} is not stable", path);
+        }
+    }
+
+    #[test]
+    fn test_format_file_check_mode() {
+        let dir = std::env::temp_dir().join(format!("html6-fmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.hnmd");
+        let path_str = path.to_str().unwrap();
+        fs::write(&path, "#   Hello\nWorld").unwrap();
+
+        assert!(format_file(path_str, true).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#   Hello\nWorld");
+
+        assert!(format_file(path_str, false).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Hello\n\nWorld\n");
+        assert!(!format_file(path_str, true).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
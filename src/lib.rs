@@ -1,5 +1,10 @@
+pub mod bundle;
+pub mod check;
+pub mod fmt;
 pub mod loader;
+pub mod navigation;
 pub mod parser;
+pub mod publish;
 pub mod reconciler;
 pub mod renderer;
 pub mod runtime;
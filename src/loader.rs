@@ -1,11 +1,41 @@
+use crate::parser::ast::Span;
+use crate::parser::component_def::parse_component;
 use crate::parser::{frontmatter, mdx};
-use crate::runtime::ComponentRegistry;
+use crate::runtime::{AddressLoader, AppAddress, ComponentRegistry, ImportRef, SourceCache};
 use anyhow::{Context, Result};
 use std::fs;
 use std::path::Path;
 
 /// Load and parse a .hnmd file, returning both document and component registry
 pub fn load_hnmd(path: &str) -> Result<(crate::parser::ast::Document, ComponentRegistry)> {
+    let (doc, registry) = load_hnmd_unresolved(path, None)?;
+
+    if let Some((name, address)) = registry.unresolved_addresses().first() {
+        return Err(anyhow::anyhow!(
+            "Component '{}' is imported from Nostr ({}); load with load_hnmd_with_loader",
+            name, address.coordinate()
+        ));
+    }
+
+    Ok((doc, registry))
+}
+
+/// Load a .hnmd file whose imports may point at Nostr addresses
+pub async fn load_hnmd_with_loader(
+    path: &str,
+    loader: &AddressLoader,
+    cache: Option<SourceCache>,
+) -> Result<(crate::parser::ast::Document, ComponentRegistry)> {
+    let (doc, mut registry) = load_hnmd_unresolved(path, cache)?;
+    registry.resolve_addresses(loader).await?;
+    Ok((doc, registry))
+}
+
+/// Load a .hnmd file and its local components, leaving Nostr imports queued in the registry
+pub fn load_hnmd_unresolved(
+    path: &str,
+    cache: Option<SourceCache>,
+) -> Result<(crate::parser::ast::Document, ComponentRegistry)> {
     let content = fs::read_to_string(path)
         .with_context(|| format!("Failed to read file: {}", path))?;
 
@@ -16,30 +46,80 @@ pub fn load_hnmd(path: &str) -> Result<(crate::parser::ast::Document, ComponentR
         .parent()
         .unwrap_or_else(|| Path::new("."));
     let mut registry = ComponentRegistry::new(base_path);
+    if let Some(cache) = cache {
+        registry = registry.with_cache(cache);
+    }
+
+    // Bundled components come first so imports of the same name don't hit the filesystem
+    for (name, component_def) in &doc.components {
+        registry.insert(name.clone(), component_def.clone());
+    }
 
     // Load all imported components
     for (name, import_path) in &doc.imports {
-        registry.load_component(name, import_path)
+        registry.load_import(name, import_path)
             .map_err(|e| anyhow::anyhow!("Failed to load component '{}' from '{}': {}", name, import_path, e))?;
     }
 
     Ok((doc, registry))
 }
 
+/// Load a whole .hnmd document published as an addressable Nostr event
+/// (naddr1..., nostr:naddr1... or nostr:<kind>:<pubkey>:<identifier>)
+pub async fn load_hnmd_from_address(
+    reference: &str,
+    loader: &AddressLoader,
+    cache: Option<SourceCache>,
+) -> Result<(crate::parser::ast::Document, ComponentRegistry)> {
+    let address = AppAddress::parse(reference)?;
+    let content = loader.load_source(&address, cache.as_ref()).await?;
+
+    let doc = parse_hnmd(&content)
+        .with_context(|| format!("Failed to parse document {}", address.coordinate()))?;
+
+    // Remote documents have no directory, so every import must be an address too
+    let mut registry = ComponentRegistry::new(".");
+    if let Some(cache) = cache {
+        registry = registry.with_cache(cache);
+    }
+
+    for (name, component_def) in &doc.components {
+        registry.insert(name.clone(), component_def.clone());
+    }
+
+    for (name, import_path) in &doc.imports {
+        if registry.contains(name) {
+            continue;
+        }
+        if !ImportRef::parse(import_path)?.is_address() {
+            return Err(anyhow::anyhow!(
+                "Document {} imports local file '{}' for '{}'",
+                address.coordinate(), import_path, name
+            ));
+        }
+        registry.load_import(name, import_path)?;
+    }
+
+    registry.resolve_addresses(loader).await?;
+
+    Ok((doc, registry))
+}
+
 /// Parse HNMD content (frontmatter + markdown)
+/// Content starting with `{` is read as a JSON bundle (serialized Document with components)
 pub fn parse_hnmd(content: &str) -> Result<crate::parser::ast::Document> {
+    if content.trim_start().starts_with('{') {
+        return serde_json::from_str(content).context("Failed to parse JSON document bundle");
+    }
+
     // Check if content starts with ---
     let has_frontmatter = content.trim_start().starts_with("---");
 
     let (frontmatter_str, body_str) = if has_frontmatter {
-        // Split on --- delimiters
-        let parts: Vec<&str> = content.splitn(3, "---").collect();
-
-        if parts.len() == 3 {
-            // Has frontmatter: empty, frontmatter, body
-            (parts[1].trim(), parts[2].trim())
-        } else {
-            return Err(anyhow::anyhow!("Incomplete frontmatter (missing closing ---)"));
+        // Split on --- delimiter lines
+        match frontmatter::split_source(content) {
+            Some((fm, body)) => (fm.trim(), body.trim()),
+            None => return Err(anyhow::anyhow!("Incomplete frontmatter (missing closing ---)")),
         }
     } else {
         // No frontmatter, entire content is body
@@ -50,7 +130,11 @@ pub fn parse_hnmd(content: &str) -> Result<crate::parser::ast::Document> {
     let frontmatter = if frontmatter_str.is_empty() {
         crate::parser::ast::Frontmatter::new()
     } else {
-        frontmatter::parse_frontmatter(frontmatter_str)?
+        frontmatter::parse_frontmatter_at(
+            frontmatter_str,
+            Span::of(content, frontmatter_str),
+            &frontmatter::entry_spans(content),
+        )?
     };
 
     // Parse imports from frontmatter
@@ -60,11 +144,22 @@ pub fn parse_hnmd(content: &str) -> Result<crate::parser::ast::Document> {
         frontmatter::parse_imports(frontmatter_str)?
     };
 
+    // Parse components bundled into the frontmatter
+    let mut components = std::collections::HashMap::new();
+    if !frontmatter_str.is_empty() {
+        for (name, source) in frontmatter::parse_components(frontmatter_str)? {
+            let component_def = parse_component(&source)
+                .map_err(|e| anyhow::anyhow!("Failed to parse bundled component '{}': {}", name, e))?;
+            components.insert(name, component_def);
+        }
+    }
+
     // Parse markdown body with MDX
-    let body = mdx::parse_body(body_str)?;
+    let body = mdx::parse_body_at(body_str, Span::of(content, body_str))?;
 
     let mut doc = crate::parser::ast::Document::new(frontmatter, body);
     doc.imports = imports;
+    doc.components = components;
 
     Ok(doc)
 }
@@ -201,7 +296,7 @@ state:
             let has_expr = children.iter().any(|child| matches!(child, Node::Expr { .. }));
             assert!(has_expr, "Heading should contain an Expr node for {{state.appName}}, but got: {:?}", children);
 
-            if let Some(Node::Expr { expression }) = children.iter().find(|c| matches!(c, Node::Expr { .. })) {
+            if let Some(Node::Expr { expression, .. }) = children.iter().find(|c| matches!(c, Node::Expr { .. })) {
                 assert_eq!(expression, "state.appName");
 
                 // Now test evaluation
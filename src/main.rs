@@ -2,145 +2,477 @@
 #![windows_subsystem = "windows"]
 
 use html6::{loader, reconciler, renderer, runtime::RuntimeContext, runtime::query::QueryRuntime, runtime::ComponentRegistry};
-use masonry::core::{ErasedAction, WidgetId, WidgetTag};
+use html6::parser::ast::{with_location, Document};
+use html6::navigation::{self, History, LinkTarget, Page, Router};
+use html6::parser::ast::Theme;
+use html6::renderer::vendored::{EndReached, LinkClicked, RichText, TextInput};
+use html6::renderer::{Appearance, StyleSheet};
+use html6::runtime::{AddressLoader, AppAddress, DecodedImage, EventPolicy, EventStore, ImageLoader, NostrClient, SourceCache};
+use masonry::core::{ErasedAction, NewWidget, Properties, WidgetId, WidgetTag};
 use masonry::dpi::LogicalSize;
-use masonry::peniko::color::AlphaColor;
-use masonry::peniko::Color;
 use masonry::properties::{Background, BorderColor, BorderWidth, ContentColor, DisabledContentColor, CaretColor, SelectionColor};
 use masonry::theme;
-use masonry::widgets::{Button, Flex, Label, Portal, TextArea};
-use masonry_winit::app::{AppDriver, DriverCtx, MasonryUserEvent, NewWindow, WindowId};
+use masonry::widgets::{Button, CheckboxToggled, Flex, Image, Label, Portal, TextArea};
+use masonry_winit::app::{AppDriver, DriverCtx, MasonryState, MasonryUserEvent, NewWindow, WindowId};
+use masonry_winit::winit;
+use masonry_winit::winit::application::ApplicationHandler;
+use masonry_winit::winit::event::{DeviceEvent, DeviceId, StartCause, WindowEvent};
+use masonry_winit::winit::event_loop::{ActiveEventLoop, EventLoopProxy};
 use masonry_winit::winit::window::Window;
-use notify::{Watcher, RecursiveMode, Event};
-use std::sync::{mpsc, Arc};
+use notify::{Watcher, RecommendedWatcher, RecursiveMode, Event};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::path::Path;
 
 const CONTENT_TAG: WidgetTag<Flex> = WidgetTag::new("content");
+const ROOT_TAG: WidgetTag<Flex> = WidgetTag::new("root");
+const TOOLBAR_TAG: WidgetTag<Flex> = WidgetTag::new("toolbar");
+
+const DEFAULT_RELAY: &str = "wss://relay.damus.io";
 
 struct Driver {
     window_id: WindowId,
-    hnmd_path: String,
+    /// Visited documents; the current one is on screen
+    history: History<Page>,
+    /// `routes:` of every document opened so far
+    router: Router,
+    /// Runs the Nostr subscriptions of the current page
+    runtime: tokio::runtime::Runtime,
+    /// Document that shows `nostr:` links (gets the decoded entity as `nostr`)
+    nostr_viewer: Option<String>,
+    /// Command external links are handed to
+    opener: String,
+    /// Never connect to relays; documents, components and queries come from local caches
+    offline: bool,
+    /// Events saved across runs (None when it can't be opened)
+    event_store: Option<EventStore>,
+    /// Rules query events must pass
+    policy: EventPolicy,
+    back_button: WidgetId,
+    forward_button: WidgetId,
+    theme_button: WidgetId,
+    /// Built-in theme pages are drawn with
+    appearance: Appearance,
+    /// Whether `appearance` follows the desktop setting (until the theme button is pressed)
+    follow_system: bool,
+    proxy: EventLoopProxy<MasonryUserEvent>,
+    /// Watches the current document for changes while alive (None for remote documents)
+    _watcher: Option<RecommendedWatcher>,
     widget_states: Vec<reconciler::WidgetState>,
     render_ctx: Option<renderer::RenderContext>,
+    /// Queries of the current page; closed when leaving it
     query_runtime: Option<Arc<QueryRuntime>>,
     registry: ComponentRegistry,
+    /// Document fetched from Nostr (there is no file to reload from)
+    remote_doc: Option<Document>,
+    /// Values written by bound widgets (e.g. task checkboxes), by state path; kept across reloads
+    state_writes: HashMap<String, serde_json::Value>,
+    /// Text typed into the page's inputs, put back when they are rebuilt
+    form_values: HashMap<String, String>,
+    /// Remote images, delivered to their placeholders as `ImageLoaded` actions
+    images: renderer::RemoteImages,
 }
 
 // Custom action to trigger reload
 #[derive(Debug)]
 struct ReloadAction;
 
+/// A remote image arrived for the placeholder `Image` the action is addressed to
+#[derive(Debug)]
+struct ImageLoaded(Arc<DecodedImage>);
+
+/// The desktop switched between light and dark
+#[derive(Debug)]
+struct SystemAppearance(Appearance);
+
+/// A move through the navigation history
+enum Step {
+    Visit(Page),
+    Back,
+    Forward,
+}
+
 impl AppDriver for Driver {
     fn on_action(
         &mut self,
         window_id: WindowId,
         ctx: &mut DriverCtx<'_, '_>,
-        _widget_id: WidgetId,
+        widget_id: WidgetId,
         action: ErasedAction,
     ) {
         debug_assert_eq!(window_id, self.window_id, "unknown window");
 
         // Check if this is a reload action
         if action.is::<ReloadAction>() {
-            println!("🔄 Reloading UI...");
-
-            // Reload and parse file
-            match loader::load_hnmd(&self.hnmd_path) {
-                Ok((doc, _registry)) => {
-                    print_ast(&doc);
-
-                    // Create runtime context from frontmatter state
-                    let mut runtime_ctx = RuntimeContext::with_state(doc.frontmatter.state.clone());
-
-                    // Update with latest query data if available
-                    if let Some(qr) = &self.query_runtime {
-                        let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                        let queries_json = rt.block_on(async {
-                            qr.to_json().await
-                        });
-
-                        // Debug: Show what we're loading
-                        if let Some(obj) = queries_json.as_object() {
-                            for (key, value) in obj {
-                                if let Some(arr) = value.as_array() {
-                                    println!("  📊 Reloading with {}: {} events", key, arr.len());
-                                }
-                            }
-                        }
+            self.reload(window_id, ctx);
+        } else if action.is::<ImageLoaded>() {
+            let ImageLoaded(image) = *action.downcast::<ImageLoaded>().unwrap();
+            let render_root = ctx.render_root(window_id);
+            // The placeholder may be gone if the page changed while the image loaded
+            if render_root.get_widget(widget_id).is_some() {
+                render_root.edit_widget(widget_id, |mut widget| {
+                    Image::set_image_data(&mut widget.downcast::<Image>(), renderer::image_brush(&image));
+                });
+            }
+        } else if action.is::<SystemAppearance>() {
+            let SystemAppearance(appearance) = *action.downcast::<SystemAppearance>().unwrap();
+            if self.follow_system && appearance != self.appearance {
+                println!("🌓 System theme is now {}", appearance.name());
+                self.set_appearance(appearance, window_id, ctx);
+            }
+        } else if action.is::<CheckboxToggled>() {
+            let CheckboxToggled(checked) = *action.downcast::<CheckboxToggled>().unwrap();
+            let path = self.render_ctx.as_ref().and_then(|render_ctx| render_ctx.binding(widget_id));
+            if let Some(path) = path {
+                println!("☑️  {} = {}", path, checked);
+                self.state_writes.insert(path, serde_json::Value::Bool(checked));
+                self.reload(window_id, ctx);
+            }
+        } else if action.is::<EndReached>() {
+            if let Some(query) = self.render_ctx.as_ref().and_then(|render_ctx| render_ctx.load_more(widget_id)) {
+                self.load_more(query, window_id);
+            }
+        } else if action.is::<LinkClicked>() {
+            let LinkClicked(url) = *action.downcast::<LinkClicked>().unwrap();
+            println!("🔗 {}", url);
+            self.navigate(&url, window_id, ctx);
+        } else if widget_id == self.back_button {
+            self.go(Step::Back, window_id, ctx);
+        } else if widget_id == self.forward_button {
+            self.go(Step::Forward, window_id, ctx);
+        } else if widget_id == self.theme_button {
+            self.follow_system = false;
+            self.set_appearance(self.appearance.toggled(), window_id, ctx);
+        } else if let Some(target) = self.render_ctx.as_ref().and_then(|render_ctx| render_ctx.navigation(widget_id)) {
+            println!("🧭 navigate({})", target);
+            self.navigate(&target, window_id, ctx);
+        } else if let Some(query) = self.render_ctx.as_ref().and_then(|render_ctx| render_ctx.load_more(widget_id)) {
+            self.load_more(query, window_id);
+        }
+    }
+}
+
+impl Driver {
+    /// Go to a link or app path: documents, routes and `nostr:` entities in the app, everything else externally
+    fn navigate(&mut self, target: &str, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) {
+        let page = match navigation::resolve_link(target, &self.history.current().location) {
+            Ok(LinkTarget::Document(path)) => Page::new(path),
+            Ok(LinkTarget::Route(path)) => match self.router.resolve(&path) {
+                Some(page) => page,
+                None => {
+                    eprintln!("⚠️  No route matches {}", path);
+                    return;
+                }
+            },
+            Ok(LinkTarget::Nostr(entity)) => match &self.nostr_viewer {
+                Some(viewer) => Page::new(viewer.clone()).with_nostr(entity),
+                None => {
+                    eprintln!("⚠️  No --nostr-viewer document to show {}", target);
+                    return;
+                }
+            },
+            Ok(LinkTarget::External(url)) => {
+                if let Err(e) = navigation::open_external(&self.opener, &url) {
+                    eprintln!("❌ {}", e);
+                }
+                return;
+            }
+            Err(e) => {
+                eprintln!("⚠️  {}", e);
+                return;
+            }
+        };
+
+        self.go(Step::Visit(page), window_id, ctx);
+    }
+
+    /// Move through the history and show the page we land on; if it fails to open, stay put
+    fn go(&mut self, step: Step, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) {
+        // Coming back to this page restores what its widgets wrote
+        self.history.current_mut().state_writes = self.state_writes.clone();
+
+        let went_back = matches!(step, Step::Back);
+        let moved = match step {
+            Step::Visit(page) => {
+                self.history.push(page);
+                true
+            }
+            Step::Back => self.history.back().is_some(),
+            Step::Forward => self.history.forward().is_some(),
+        };
+        if !moved {
+            return;
+        }
+
+        let page = self.history.current().clone();
+        if let Err(e) = self.open_page(&page, window_id, ctx) {
+            eprintln!("❌ {}", e);
+            // A failed visit stays around as a forward entry
+            if went_back {
+                self.history.forward();
+            } else {
+                self.history.back();
+            }
+        }
+    }
+
+    /// Replace the content with another document, with its own state and queries
+    fn open_page(&mut self, page: &Page, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) -> anyhow::Result<()> {
+        println!("🧭 Opening {}\n", page.location);
+
+        let is_remote = AppAddress::is_address(&page.location);
+        let (doc, registry) = if is_remote {
+            load_remote_document(&page.location, &self.policy, self.offline)
+        } else {
+            load_local_document(&page.location, &self.policy, self.offline)
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", page.location, e))?;
 
-                        runtime_ctx.queries = queries_json;
+        // The previous page's subscriptions end with it
+        if let Some(qr) = self.query_runtime.take() {
+            self.runtime.block_on(qr.close());
+            println!("  🔌 Closed queries of the previous page");
+        }
+        for pattern in self.router.add_routes(&doc.frontmatter.routes, &page.location) {
+            eprintln!("⚠️  Ignoring route '{}': a document fetched from Nostr can't route to local files", pattern);
+        }
+        let runtime_ctx = page_context(&doc, page, self.appearance);
+        self.query_runtime = start_queries(&self.runtime, &doc, &runtime_ctx, self.event_store.as_ref(), &self.policy, self.offline);
+        if let Some(qr) = &self.query_runtime {
+            spawn_query_refresh(qr, self.window_id, self.proxy.clone());
+        }
+        self._watcher = if is_remote {
+            None
+        } else {
+            watch_file(&page.location, self.window_id, self.proxy.clone())
+        };
+        self.registry = registry;
+        self.remote_doc = if is_remote { Some(doc) } else { None };
+        self.state_writes = page.state_writes.clone();
+        self.form_values.clear();
+        self.render_ctx = None;
+        self.widget_states.clear();
+
+        // Drop every widget; reloading against an empty arena adds them all back
+        let render_root = ctx.render_root(window_id);
+        render_root.edit_widget_with_tag(CONTENT_TAG, |mut content_flex| {
+            while content_flex.widget.len() > 0 {
+                Flex::remove_child(&mut content_flex, 0);
+            }
+        });
+        self.reload(window_id, ctx);
+        Ok(())
+    }
+
+    /// Fetch the next page of older events for a query in the background, reloading when it starts and ends
+    fn load_more(&mut self, query: String, window_id: WindowId) {
+        let Some(qr) = self.query_runtime.clone() else {
+            eprintln!("⚠️  loadMore({}): this page has no queries", query);
+            return;
+        };
+        let page = match self.runtime.block_on(qr.start_load_more(&query)) {
+            Ok(Some(page)) => page,
+            // Already loading, at the end, or nothing to page from yet
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("⚠️  {}", e);
+                return;
+            }
+        };
+        println!("📜 loadMore({})", query);
+        // Show `loadingMore` while the page is fetched
+        send_reload(window_id, &self.proxy);
+
+        let proxy = self.proxy.clone();
+        self.runtime.spawn(async move {
+            match qr.fetch_more(&query, page).await {
+                Ok(added) => println!("  📜 {} older events for '{}'", added, query),
+                Err(e) => eprintln!("❌ loadMore({}) failed: {}", query, e),
+            }
+            send_reload(window_id, &proxy);
+        });
+    }
+
+    /// Redraw the window with the light or dark theme, keeping state, typed text and queries
+    fn set_appearance(&mut self, appearance: Appearance, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) {
+        println!("🌓 Switching to the {} theme", appearance.name());
+        self.save_inputs(window_id, ctx);
+        self.appearance = appearance;
+
+        // Every widget has the old colors baked in, so the whole page is built again
+        // (the render context stays, so lists keep their scroll positions)
+        self.widget_states.clear();
+        let render_root = ctx.render_root(window_id);
+        render_root.edit_widget_with_tag(CONTENT_TAG, |mut content_flex| {
+            while content_flex.widget.len() > 0 {
+                Flex::remove_child(&mut content_flex, 0);
+            }
+        });
+        self.reload(window_id, ctx);
+
+        // Window background and toolbar, with the page's own theme entries
+        let styles = match &self.render_ctx {
+            Some(render_ctx) => (*render_ctx.styles).clone(),
+            None => StyleSheet::with_appearance(appearance, &Theme::default(), HashMap::new()),
+        };
+        let buttons = toolbar_buttons(&styles);
+        [self.back_button, self.forward_button, self.theme_button] = buttons.each_ref().map(|button| button.id());
+        let render_root = ctx.render_root(window_id);
+        render_root.edit_widget_with_tag(ROOT_TAG, |mut root| {
+            root.insert_prop(Background::Color(styles.color("background")));
+        });
+        render_root.edit_widget_with_tag(TOOLBAR_TAG, |mut toolbar| {
+            while toolbar.widget.len() > 0 {
+                Flex::remove_child(&mut toolbar, 0);
+            }
+            for button in buttons {
+                Flex::add_child(&mut toolbar, button);
+            }
+        });
+    }
+
+    /// Remember what was typed into the page's inputs
+    fn save_inputs(&mut self, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) {
+        let Some(render_ctx) = &self.render_ctx else { return };
+        let fields = render_ctx.fields();
+        let render_root = ctx.render_root(window_id);
+        for (id, name) in fields {
+            // Inputs from earlier renders may be gone
+            if render_root.get_widget(id).is_some() {
+                let text = render_root.edit_widget(id, |mut widget| TextInput::text(&mut widget.downcast::<TextInput>()));
+                self.form_values.insert(name, text);
+            }
+        }
+    }
+
+    /// Re-render the document with the latest state and query results, rebuilding only what changed
+    fn reload(&mut self, window_id: WindowId, ctx: &mut DriverCtx<'_, '_>) {
+        println!("🔄 Reloading UI...");
+
+        // Reload and parse file (remote documents are re-rendered from memory)
+        let loaded = match &self.remote_doc {
+            Some(doc) => Ok((doc.clone(), self.registry.clone())),
+            None => loader::load_hnmd_unresolved(&self.history.current().location, None),
+        };
+        match loaded {
+            Ok((doc, _registry)) => {
+                print_ast(&doc);
+
+                // Create runtime context from frontmatter state, then replay values written by widgets
+                let mut runtime_ctx = page_context(&doc, self.history.current(), self.appearance);
+                for (path, value) in &self.state_writes {
+                    if let Err(e) = runtime_ctx.set_state(path, value.clone()) {
+                        eprintln!("⚠️  {}", e);
                     }
+                }
+                for (name, text) in &self.form_values {
+                    runtime_ctx.set_form_field(name, text.clone());
+                }
 
-                    let render_ctx = renderer::RenderContext::new(runtime_ctx)
-                        .with_registry(self.registry.clone());
-
-                    // Debug: print state changes
-                    println!("  🔍 New state: {:?}", doc.frontmatter.state);
-
-                    self.render_ctx = Some(render_ctx.clone());
-
-                    // Reconcile old and new AST with context for expr hash tracking
-                    let arena = reconciler::WidgetArena {
-                        states: self.widget_states.clone(),
-                        generations: vec![0; self.widget_states.len().max(doc.body.len())],
-                    };
-                    let mut reconcile_ctx = self.render_ctx.clone();
-                    let (new_arena, ops) = reconciler::reconcile_arena(
-                        &arena,
-                        &doc.body,
-                        &mut reconcile_ctx,
-                    );
-                    let new_states = new_arena.states;
-
-                    // Count operations for reporting
-                    let keeps = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Keep)).count();
-                    let rebuilds = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Rebuild)).count();
-                    let adds = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Add)).count();
-                    let removes = self.widget_states.len().saturating_sub(doc.body.len());
-
-                    println!("  📊 Reconciliation: {} kept, {} rebuilt, {} added, {} removed",
-                        keeps, rebuilds, adds, removes);
-                    println!("  📐 Ops: {} ops for {} nodes (old had {} nodes)",
-                        ops.len(), doc.body.len(), self.widget_states.len());
-
-                    // Apply updates using positional reconciliation
-                    let render_root = ctx.render_root(window_id);
-                    render_root.edit_widget_with_tag(CONTENT_TAG, |mut content_flex| {
-                        // Process each position
-                        for (idx, (node, op)) in doc.body.iter().zip(ops.iter()).enumerate() {
-                            match op {
-                                reconciler::ReconcileOp::Keep => {
-                                    // Widget at this position unchanged - leave it alone
-                                }
-                                reconciler::ReconcileOp::Rebuild => {
-                                    // Widget at this position changed - replace it
-                                    Flex::remove_child(&mut content_flex, idx);
-                                    Flex::insert_child(&mut content_flex, idx, renderer::build_widget_with_context(node, self.render_ctx.clone()));
-                                }
-                                reconciler::ReconcileOp::Add => {
-                                    // New position (list grew) - append
-                                    Flex::add_child(&mut content_flex, renderer::build_widget_with_context(node, self.render_ctx.clone()));
-                                }
-                                reconciler::ReconcileOp::Remove => {
-                                    // Not used in generational approach
-                                }
+                // Update with latest query data if available
+                if let Some(qr) = &self.query_runtime {
+                    let queries_json = self.runtime.block_on(async {
+                        qr.to_json().await
+                    });
+
+                    // Debug: Show what we're loading
+                    if let Some(obj) = queries_json.as_object() {
+                        for (key, value) in obj {
+                            if let Some(arr) = value.as_array() {
+                                println!("  📊 Reloading with {}: {} events", key, arr.len());
                             }
                         }
+                    }
 
-                        // Shrink if list got smaller
-                        while content_flex.widget.len() > doc.body.len() {
-                            Flex::remove_child(&mut content_flex, doc.body.len());
+                    runtime_ctx.queries = queries_json;
+                }
+
+                let mut render_ctx = renderer::RenderContext::new(runtime_ctx)
+                    .with_registry(self.registry.clone())
+                    .with_images(self.images.clone())
+                    .with_styles(StyleSheet::with_appearance(self.appearance, &doc.frontmatter.theme, doc.frontmatter.styles.clone()));
+
+                // Widgets that are kept still report their actions by their old ids
+                if let Some(previous) = &self.render_ctx {
+                    render_ctx.bindings = previous.bindings.clone();
+                    render_ctx.navigations = previous.navigations.clone();
+                    render_ctx.load_mores = previous.load_mores.clone();
+                    render_ctx.fields = previous.fields.clone();
+                    render_ctx.lists = previous.lists.clone();
+                }
+
+                // Debug: print state changes
+                println!("  🔍 New state: {:?}", doc.frontmatter.state);
+
+                self.render_ctx = Some(render_ctx.clone());
+
+                // Reconcile old and new AST with context for expr hash tracking
+                let arena = reconciler::WidgetArena {
+                    states: self.widget_states.clone(),
+                    generations: vec![0; self.widget_states.len().max(doc.body.len())],
+                };
+                let mut reconcile_ctx = self.render_ctx.clone();
+                let (new_arena, ops) = reconciler::reconcile_arena(
+                    &arena,
+                    &doc.body,
+                    &mut reconcile_ctx,
+                );
+                let new_states = new_arena.states;
+
+                // Count operations for reporting
+                let keeps = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Keep)).count();
+                let rebuilds = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Rebuild)).count();
+                let adds = ops.iter().filter(|op| matches!(op, reconciler::ReconcileOp::Add)).count();
+                let removes = self.widget_states.len().saturating_sub(doc.body.len());
+
+                println!("  📊 Reconciliation: {} kept, {} rebuilt, {} added, {} removed",
+                    keeps, rebuilds, adds, removes);
+                println!("  📐 Ops: {} ops for {} nodes (old had {} nodes)",
+                    ops.len(), doc.body.len(), self.widget_states.len());
+
+                // Apply updates using positional reconciliation
+                let render_root = ctx.render_root(window_id);
+                render_root.edit_widget_with_tag(CONTENT_TAG, |mut content_flex| {
+                    // Process each position
+                    for (idx, (node, op)) in doc.body.iter().zip(ops.iter()).enumerate() {
+                        match op {
+                            reconciler::ReconcileOp::Keep => {
+                                // Widget at this position unchanged - leave it alone
+                            }
+                            reconciler::ReconcileOp::Rebuild => {
+                                // Widget at this position changed - replace it
+                                Flex::remove_child(&mut content_flex, idx);
+                                Flex::insert_child(&mut content_flex, idx, renderer::build_widget_with_context(node, self.render_ctx.clone()));
+                            }
+                            reconciler::ReconcileOp::Add => {
+                                // New position (list grew) - append
+                                Flex::add_child(&mut content_flex, renderer::build_widget_with_context(node, self.render_ctx.clone()));
+                            }
+                            reconciler::ReconcileOp::Remove => {
+                                // Not used in generational approach
+                            }
                         }
-                    });
+                    }
 
-                    // Update stored states
-                    self.widget_states = new_states;
+                    // Shrink if list got smaller
+                    while content_flex.widget.len() > doc.body.len() {
+                        Flex::remove_child(&mut content_flex, doc.body.len());
+                    }
+                });
 
-                    println!("✅ UI updated incrementally!\n");
-                }
-                Err(e) => {
-                    eprintln!("❌ Failed to reload: {}\n", e);
+                // Replaced widgets are gone from the tree; forget what they did
+                if let Some(render_ctx) = &self.render_ctx {
+                    let render_root = ctx.render_root(window_id);
+                    render_ctx.prune(|id| render_root.get_widget(id).is_some());
                 }
+
+                // Update stored states
+                self.widget_states = new_states;
+
+                println!("✅ UI updated incrementally!\n");
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to reload: {}\n", e);
             }
         }
     }
@@ -178,90 +510,575 @@ fn node_type(node: &html6::parser::ast::Node) -> String {
         Node::Text { value } => format!("Text('{}')", value.chars().take(20).collect::<String>()),
         Node::Strong { .. } => "Strong".to_string(),
         Node::Emphasis { .. } => "Emphasis".to_string(),
+        Node::Strikethrough { .. } => "Strikethrough".to_string(),
         Node::List { ordered, .. } => if *ordered { "OrderedList".to_string() } else { "UnorderedList".to_string() },
         Node::Link { url, .. } => format!("Link({})", url),
+        Node::InlineCode { .. } => "InlineCode".to_string(),
+        Node::FootnoteReference { label } => format!("FootnoteReference({})", label),
+        Node::FootnoteDefinition { label, .. } => format!("FootnoteDefinition({})", label),
+        Node::CodeBlock { lang, .. } => format!("CodeBlock({})", lang.as_deref().unwrap_or("")),
+        Node::Blockquote { .. } => "Blockquote".to_string(),
+        Node::Table { rows, .. } => format!("Table({} rows)", rows.len()),
         Node::Image { src, .. } => format!("Image({})", src),
-        Node::Expr { expression } => format!("Expr({})", expression),
+        Node::Expr { expression, .. } => format!("Expr({})", expression),
         Node::Each { from, as_name, .. } => format!("Each({} as {})", from, as_name),
         Node::If { value, .. } => format!("If({})", value),
         Node::Button { .. } => "Button".to_string(),
         Node::Input { name, .. } => format!("Input({})", name),
         Node::VStack { children, .. } => format!("VStack({} children)", children.len()),
         Node::HStack { children, .. } => format!("HStack({} children)", children.len()),
+        Node::TextBlock { .. } => "TextBlock".to_string(),
         Node::Grid { .. } => "Grid".to_string(),
-        Node::Json { value } => format!("Json({})", value),
+        Node::Json { value, .. } => format!("Json({})", value),
         Node::Spacer { .. } => "Spacer".to_string(),
+        Node::ThematicBreak => "ThematicBreak".to_string(),
         Node::CustomComponent { name, .. } => format!("CustomComponent({})", name),
     }
 }
 
-fn main() {
-    // Get file path from command line args or use default
-    let args: Vec<String> = std::env::args().collect();
-    let hnmd_file = if args.len() > 1 {
-        args[1].as_str()
-    } else {
-        "apps/hello.hnmd"
-    };
+/// Relays to fetch documents and components from (none when offline)
+fn document_relays(offline: bool) -> Vec<String> {
+    if offline { vec![] } else { vec![DEFAULT_RELAY.to_string()] }
+}
 
-    println!("📂 Loading: {}\n", hnmd_file);
+/// Load a local .hnmd file; imports that point at Nostr are fetched from the default relay
+fn load_local_document(path: &str, policy: &EventPolicy, offline: bool) -> anyhow::Result<(Document, ComponentRegistry)> {
+    let (doc, mut registry) = loader::load_hnmd_unresolved(path, Some(SourceCache::open_default()))?;
+
+    if !registry.unresolved_addresses().is_empty() {
+        println!("🔌 Fetching components from Nostr...");
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(async {
+            let client = NostrClient::new(document_relays(offline)).await?;
+            let address_loader = AddressLoader::new(Arc::new(client)).with_offline(offline).with_policy(policy.clone());
+            registry.resolve_addresses(&address_loader).await
+        })?;
+    }
 
-    // Load and parse .hnmd file
-    let (doc, registry) = loader::load_hnmd(hnmd_file)
-        .expect(&format!("Failed to load {}", hnmd_file));
+    Ok((doc, registry))
+}
 
-    // Print AST on startup
-    print_ast(&doc);
+/// Fetch a whole .hnmd document (and its components) from Nostr (or only the source cache when offline)
+fn load_remote_document(reference: &str, policy: &EventPolicy, offline: bool) -> anyhow::Result<(Document, ComponentRegistry)> {
+    println!("🔌 Fetching document from Nostr...");
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let client = NostrClient::new(document_relays(offline)).await?;
+        let address_loader = AddressLoader::new(Arc::new(client)).with_offline(offline).with_policy(policy.clone());
+        loader::load_hnmd_from_address(reference, &address_loader, Some(SourceCache::open_default())).await
+    })
+}
 
-    // Create runtime context from frontmatter state
-    let runtime_ctx = RuntimeContext::with_state(doc.frontmatter.state.clone());
+/// Runtime context for a freshly loaded page: frontmatter state, route parameters, the theme and the `nostr` entity it shows
+fn page_context(doc: &Document, page: &Page, appearance: Appearance) -> RuntimeContext {
+    let mut runtime_ctx = RuntimeContext::with_state(doc.frontmatter.state.clone()).with_theme(appearance.name());
+    if let Some(params) = &page.route {
+        runtime_ctx = runtime_ctx.with_route(params.clone());
+    }
+    match &page.nostr {
+        Some(entity) => runtime_ctx.with_local("nostr", entity.clone()),
+        None => runtime_ctx,
+    }
+}
 
-    // Initialize QueryRuntime if there are filters
-    let query_runtime = if !doc.frontmatter.filters.is_empty() {
-        println!("🔌 Initializing Nostr client...");
+/// Connect to the default relay and subscribe to the document's filters (None when it has none)
+/// Stored events show up right away; offline, they are all there is
+fn start_queries(
+    runtime: &tokio::runtime::Runtime,
+    doc: &Document,
+    runtime_ctx: &RuntimeContext,
+    store: Option<&EventStore>,
+    policy: &EventPolicy,
+    offline: bool,
+) -> Option<Arc<QueryRuntime>> {
+    if doc.frontmatter.filters.is_empty() {
+        return None;
+    }
+    println!("🔌 Initializing Nostr client...");
+
+    let query_runtime = runtime.block_on(async {
+        // Create QueryRuntime
+        let mut qr = QueryRuntime::new()
+            .await
+            .expect("Failed to create QueryRuntime")
+            .with_offline(offline)
+            .with_policy(policy.clone());
+        if let Some(store) = store {
+            qr = qr.with_store(store.clone());
+        }
 
-        // Create tokio runtime for async operations
+        // Add relay
+        if offline {
+            println!("  📴 Offline: showing stored events only");
+        } else {
+            match qr.add_relay(DEFAULT_RELAY).await {
+                Ok(()) => println!("  ✓ Connected to {}", DEFAULT_RELAY),
+                Err(e) => eprintln!("⚠️  Failed to add relay {}: {}", DEFAULT_RELAY, e),
+            }
+        }
+
+        // Subscribe to all filters in frontmatter; a failed one shows up in `queries.$meta.<id>.error`
+        for (id, filter_def) in &doc.frontmatter.filters {
+            println!("  📡 Subscribing to filter '{}'...", id);
+            if let Err(e) = qr.subscribe_ast_filter(id, filter_def, runtime_ctx).await {
+                eprintln!("❌ {}: {}", with_location(filter_def.span, format!("Failed to subscribe to filter '{}'", id)), e);
+            }
+        }
+
+        Arc::new(qr)
+    });
+
+    Some(query_runtime)
+}
+
+/// Ask the window to reload
+fn send_reload(window_id: WindowId, proxy: &EventLoopProxy<MasonryUserEvent>) {
+    let action: masonry::core::ErasedAction = Box::new(ReloadAction);
+    let widget_id = WidgetId::next();
+    let user_event = MasonryUserEvent::Action(window_id, action, widget_id);
+    let _ = proxy.send_event(user_event);
+}
+
+/// Watch for query updates and refresh the UI while stored events load (or until the page is left)
+fn spawn_query_refresh(qr: &Arc<QueryRuntime>, window_id: WindowId, proxy: EventLoopProxy<MasonryUserEvent>) {
+    let qr = Arc::downgrade(qr);
+    std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+        let mut seen = 0;
+
+        // Poll for query updates every 500ms
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            let Some(qr) = qr.upgrade() else { break };
+
+            let (version, loading) = rt.block_on(async { (qr.get_version().await, qr.is_loading().await) });
+            if version != seen {
+                seen = version;
+                println!("  🔄 Query updated, refreshing UI...");
+                send_reload(window_id, &proxy);
+            }
+
+            // Every relay has sent its stored events (or failed); live events don't refresh
+            if !loading {
+                break;
+            }
+        }
+    });
+}
+
+/// Masonry's event loop, plus telling the driver when the desktop switches between light and dark
+struct App<'a> {
+    masonry: MasonryState<'a>,
+    driver: Driver,
+}
+
+impl ApplicationHandler<MasonryUserEvent> for App<'_> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.masonry.handle_resumed(event_loop);
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        self.masonry.handle_suspended(event_loop);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: winit::window::WindowId, event: WindowEvent) {
+        if let WindowEvent::ThemeChanged(theme) = &event {
+            let appearance = match theme {
+                winit::window::Theme::Light => Appearance::Light,
+                winit::window::Theme::Dark => Appearance::Dark,
+            };
+            // Handled like any other action, where the driver can rebuild the page
+            let action = MasonryUserEvent::Action(self.driver.window_id, Box::new(SystemAppearance(appearance)), WidgetId::next());
+            let _ = self.driver.proxy.send_event(action);
+        }
+        self.masonry.handle_window_event(event_loop, window_id, event, &mut self.driver);
+    }
 
-        let query_runtime = rt.block_on(async {
-            // Create QueryRuntime
-            let mut qr = QueryRuntime::new().await.expect("Failed to create QueryRuntime");
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, device_id: DeviceId, event: DeviceEvent) {
+        self.masonry.handle_device_event(event_loop, device_id, event, &mut self.driver);
+    }
 
-            // Add relay
-            qr.add_relay("wss://relay.damus.io").await.expect("Failed to add relay");
-            println!("  ✓ Connected to wss://relay.damus.io");
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: MasonryUserEvent) {
+        self.masonry.handle_user_event(event_loop, event, &mut self.driver);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.masonry.handle_about_to_wait(event_loop);
+    }
 
-            // Subscribe to all filters in frontmatter
-            for (id, filter_def) in &doc.frontmatter.filters {
-                println!("  📡 Subscribing to filter '{}'...", id);
-                let _ = qr.subscribe_ast_filter(id, filter_def, &runtime_ctx).await
-                    .expect(&format!("Failed to subscribe to filter '{}'", id));
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.masonry.handle_new_events(event_loop, cause);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.masonry.handle_exiting(event_loop);
+    }
+
+    fn memory_warning(&mut self, event_loop: &ActiveEventLoop) {
+        self.masonry.handle_memory_warning(event_loop);
+    }
+}
+
+/// Back, Forward and the light/dark switch, drawn with the theme
+fn toolbar_buttons(styles: &StyleSheet) -> [NewWidget<Button>; 3] {
+    let button = |text: &str| {
+        let label = NewWidget::new_with_props(Label::new(text), Properties::new().with(ContentColor::new(styles.color("text"))));
+        let props = Properties::new()
+            .with(Background::Color(styles.color("button")))
+            .with(BorderColor { color: styles.color("control_border") })
+            .with(BorderWidth { width: 1.0 });
+        NewWidget::new_with_props(Button::new(label), props)
+    };
+    let switch = match styles.appearance {
+        Appearance::Light => "☾ Dark",
+        Appearance::Dark => "☀ Light",
+    };
+    [button("← Back"), button("Forward →"), button(switch)]
+}
+
+/// Reload the window whenever the file changes; dropping the watcher stops it
+fn watch_file(path: &str, window_id: WindowId, proxy: EventLoopProxy<MasonryUserEvent>) -> Option<RecommendedWatcher> {
+    let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        if let Ok(event) = res {
+            if event.kind.is_modify() {
+                println!("\n📝 File changed detected...");
+                send_reload(window_id, &proxy);
             }
+        }
+    });
 
-            Arc::new(qr)
-        });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("⚠️  Failed to create file watcher: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = watcher.watch(Path::new(path), RecursiveMode::NonRecursive) {
+        eprintln!("⚠️  Failed to watch {}: {}", path, e);
+        return None;
+    }
+    Some(watcher)
+}
+
+/// Options for viewing a document
+struct ViewOptions {
+    document: String,
+    nostr_viewer: Option<String>,
+    opener: String,
+    /// Light or dark; None follows the desktop
+    theme: Option<Appearance>,
+    /// Never connect to relays
+    offline: bool,
+    /// Rules query events must pass (`--block` adds authors to drop)
+    policy: EventPolicy,
+}
+
+/// `html6 [app.hnmd | naddr] [--nostr-viewer <viewer.hnmd>] [--opener <command>] [--theme light|dark|system] [--offline] [--block <pubkey>]...`
+fn parse_view_args(args: &[String]) -> anyhow::Result<ViewOptions> {
+    let mut options = ViewOptions {
+        document: "apps/hello.hnmd".to_string(),
+        nostr_viewer: None,
+        opener: navigation::default_opener().to_string(),
+        theme: None,
+        offline: false,
+        policy: EventPolicy::default(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--nostr-viewer" => {
+                options.nostr_viewer = Some(iter.next().ok_or_else(|| anyhow::anyhow!("--nostr-viewer needs a document"))?.clone())
+            }
+            "--opener" => options.opener = iter.next().ok_or_else(|| anyhow::anyhow!("--opener needs a command"))?.clone(),
+            "--theme" => {
+                let name = iter.next().ok_or_else(|| anyhow::anyhow!("--theme needs light, dark or system"))?;
+                options.theme = match name.as_str() {
+                    "system" => None,
+                    other => Some(Appearance::parse(other).ok_or_else(|| anyhow::anyhow!("Unknown theme '{}' (light, dark or system)", other))?),
+                };
+            }
+            "--offline" => options.offline = true,
+            "--block" => {
+                let key = iter.next().ok_or_else(|| anyhow::anyhow!("--block needs a pubkey (npub or hex)"))?;
+                let pubkey = nostr_sdk::prelude::PublicKey::parse(key).map_err(|e| anyhow::anyhow!("Invalid pubkey '{}': {}", key, e))?;
+                options.policy.blocked_pubkeys.insert(pubkey);
+            }
+            other if other.starts_with("--") => return Err(anyhow::anyhow!("Unknown option: {}", other)),
+            other => options.document = other.to_string(),
+        }
+    }
+
+    Ok(options)
+}
+
+/// `html6 publish app.hnmd [--dry-run] [--relay <url>]... [--nsec <key>]`
+fn run_publish(args: &[String]) -> anyhow::Result<()> {
+    use html6::publish;
+    use nostr_sdk::prelude::Keys;
+
+    let mut path = None;
+    let mut dry_run = false;
+    let mut relays = Vec::new();
+    let mut secret = std::env::var("HTML6_NSEC").ok();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--relay" => relays.push(iter.next().ok_or_else(|| anyhow::anyhow!("--relay needs a URL"))?.clone()),
+            "--nsec" => secret = Some(iter.next().ok_or_else(|| anyhow::anyhow!("--nsec needs a key"))?.clone()),
+            other if path.is_none() => path = Some(other.to_string()),
+            other => return Err(anyhow::anyhow!("Unexpected argument: {}", other)),
+        }
+    }
+
+    let path = path.ok_or_else(|| anyhow::anyhow!("Usage: html6 publish <app.hnmd> [--dry-run] [--relay <url>]... [--nsec <key>]"))?;
+    if relays.is_empty() {
+        relays.push(DEFAULT_RELAY.to_string());
+    }
+
+    let keys = match secret {
+        Some(secret) => Keys::parse(&secret).map_err(|e| anyhow::anyhow!("Invalid secret key: {}", e))?,
+        None if dry_run => {
+            eprintln!("⚠️  No key given (--nsec or HTML6_NSEC); signing with a throwaway key");
+            Keys::generate()
+        }
+        None => return Err(anyhow::anyhow!("Publishing needs a key: pass --nsec or set HTML6_NSEC")),
+    };
+
+    let plan = publish::build_plan(&path, &keys)?;
+
+    if dry_run {
+        println!("{}", publish::plan_to_json(&plan));
+    } else {
+        println!("📤 Publishing {} events to {}...", plan.sources.len(), relays.join(", "));
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(publish::send_plan(&plan, relays.clone()))?;
+    }
+
+    let mut root = plan.root().address.clone();
+    root.relays = relays;
+    eprintln!("🔗 {}", root.to_naddr()?);
+
+    Ok(())
+}
+
+/// `html6 bundle app.hnmd [--json] [-o <file>]`
+fn run_bundle(args: &[String]) -> anyhow::Result<()> {
+    use html6::bundle::{self, BundleFormat};
+
+    let mut path = None;
+    let mut format = BundleFormat::Hnmd;
+    let mut out = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => format = BundleFormat::Json,
+            "-o" | "--out" => out = Some(iter.next().ok_or_else(|| anyhow::anyhow!("{} needs a file", arg))?.clone()),
+            other if path.is_none() => path = Some(other.to_string()),
+            other => return Err(anyhow::anyhow!("Unexpected argument: {}", other)),
+        }
+    }
+
+    let path = path.ok_or_else(|| anyhow::anyhow!("Usage: html6 bundle <app.hnmd> [--json] [-o <file>]"))?;
+    let bundled = bundle::bundle(&path, format)?;
+
+    match out {
+        Some(out) => {
+            std::fs::write(&out, bundled)?;
+            eprintln!("📦 Bundled {} → {}", path, out);
+        }
+        None => print!("{}", bundled),
+    }
+
+    Ok(())
+}
+
+/// `html6 check app.hnmd...`; returns whether any errors were found
+fn run_check(args: &[String]) -> anyhow::Result<bool> {
+    use html6::check;
 
-        // Spawn tokio runtime in background thread to keep it alive
-        std::thread::spawn(move || {
-            rt.block_on(async {
-                // Keep runtime alive
-                loop {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    if args.is_empty() {
+        return Err(anyhow::anyhow!("Usage: html6 check <app.hnmd>..."));
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for path in args {
+        for diagnostic in check::check_file(path)? {
+            if diagnostic.is_error() {
+                errors += 1;
+            } else {
+                warnings += 1;
+            }
+            eprintln!("{}", diagnostic);
+        }
+    }
+
+    if errors == 0 && warnings == 0 {
+        eprintln!("✅ No problems found");
+    } else {
+        eprintln!("{} error(s), {} warning(s)", errors, warnings);
+    }
+
+    Ok(errors > 0)
+}
+
+/// `html6 fmt [--check] <file>...`; returns whether any file was not formatted
+fn run_fmt(args: &[String]) -> anyhow::Result<bool> {
+    use html6::fmt;
+
+    let check = args.iter().any(|a| a == "--check");
+    let paths: Vec<&String> = args.iter().filter(|a| a.as_str() != "--check").collect();
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("Usage: html6 fmt [--check] <file.hnmd|file.hnmc>..."));
+    }
+
+    let mut unformatted = 0;
+    for path in paths {
+        if fmt::format_file(path, check)? {
+            unformatted += 1;
+            if check {
+                eprintln!("❌ {} is not formatted", path);
+            } else {
+                eprintln!("✨ Formatted {}", path);
+            }
+        }
+    }
+
+    if check && unformatted == 0 {
+        eprintln!("✅ All files formatted");
+    }
+
+    Ok(check && unformatted > 0)
+}
+
+fn main() {
+    // Get file path from command line args or use default
+    let args: Vec<String> = std::env::args().collect();
+
+    // Subcommands
+    match args.get(1).map(|s| s.as_str()) {
+        Some("publish") => {
+            if let Err(e) = run_publish(&args[2..]) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("check") => {
+            match run_check(&args[2..]) {
+                Ok(false) => return,
+                Ok(true) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(2);
                 }
-            });
-        });
+            }
+        }
+        Some("fmt") => {
+            match run_fmt(&args[2..]) {
+                Ok(false) => return,
+                Ok(true) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some("bundle") => {
+            if let Err(e) = run_bundle(&args[2..]) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
 
-        Some(query_runtime)
+    let options = match parse_view_args(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    let hnmd_file = options.document.as_str();
+
+    println!("📂 Loading: {}\n", hnmd_file);
+
+    // Load and parse .hnmd file (or fetch it from Nostr when given an address)
+    let is_remote = AppAddress::is_address(hnmd_file);
+    let (doc, registry) = if is_remote {
+        load_remote_document(hnmd_file, &options.policy, options.offline)
     } else {
-        None
+        load_local_document(hnmd_file, &options.policy, options.offline)
+    }
+    .expect(&format!("Failed to load {}", hnmd_file));
+
+    // Print AST on startup
+    print_ast(&doc);
+
+    // Light or dark, from --theme or the desktop
+    let follow_system = options.theme.is_none();
+    let appearance = options.theme.or_else(Appearance::system).unwrap_or_default();
+    println!("🌓 Using the {} theme\n", appearance.name());
+
+    // Create runtime context from frontmatter state
+    let page = Page::new(hnmd_file);
+    let runtime_ctx = page_context(&doc, &page, appearance);
+
+    // Events from earlier runs, shown before relays answer
+    let event_store = match EventStore::open_default() {
+        Ok(store) => Some(store),
+        Err(e) => {
+            eprintln!("⚠️  Failed to open the event store: {}", e);
+            None
+        }
     };
 
-    // Start with empty query results - they'll be populated as events arrive
-    let runtime_ctx_with_queries = runtime_ctx.clone();
-    println!("  ⚡ Starting UI with empty queries (will update as events arrive)...");
+    // Initialize QueryRuntime if there are filters
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let query_runtime = start_queries(&runtime, &doc, &runtime_ctx, event_store.as_ref(), &options.policy, options.offline);
+
+    // The app's routes; documents opened later can add their own
+    let mut router = Router::new();
+    for pattern in router.add_routes(&doc.frontmatter.routes, hnmd_file) {
+        eprintln!("⚠️  Ignoring route '{}': a document fetched from Nostr can't route to local files", pattern);
+    }
 
+    // Start with the stored query results - the rest are populated as events arrive
+    let mut runtime_ctx_with_queries = runtime_ctx.clone();
+    if let Some(qr) = &query_runtime {
+        runtime.block_on(qr.populate_context(&mut runtime_ctx_with_queries));
+    }
+    println!("  ⚡ Starting UI with stored queries (will update as events arrive)...");
+
+    // Create event loop
+    let event_loop = masonry_winit::app::EventLoop::with_user_event()
+        .build()
+        .unwrap();
+
+    // Get proxy for sending events from other threads
+    let proxy = event_loop.create_proxy();
+    let window_id = WindowId::next();
+
+    // Remote images load in the background and are swapped into their placeholders
+    let image_loader = ImageLoader::new(runtime.handle().clone()).with_disk_cache(ImageLoader::default_dir());
+    let image_proxy = proxy.clone();
+    let images = renderer::RemoteImages::new(image_loader, move |widget_id, image| {
+        let action = MasonryUserEvent::Action(window_id, Box::new(ImageLoaded(image)), widget_id);
+        let _ = image_proxy.send_event(action);
+    });
+
+    let styles = StyleSheet::with_appearance(appearance, &doc.frontmatter.theme, doc.frontmatter.styles.clone());
     let render_ctx = renderer::RenderContext::new(runtime_ctx_with_queries)
-        .with_registry(registry.clone());
+        .with_registry(registry.clone())
+        .with_images(images.clone())
+        .with_styles(styles.clone());
 
     // Build initial widget states for reconciliation with context
     let mut initial_ctx = Some(render_ctx.clone());
@@ -270,8 +1087,21 @@ fn main() {
     // Build widget tree from AST with tag for updates and context
     let content = renderer::build_document_widget_with_context(&doc.body, Some(render_ctx.clone()), Some(CONTENT_TAG));
 
+    // Back/forward through followed links and the theme switch, above the scrolling content
+    let buttons = toolbar_buttons(&styles);
+    let [back_id, forward_id, theme_id] = buttons.each_ref().map(|button| button.id());
+    let mut toolbar = Flex::row();
+    for button in buttons {
+        toolbar = toolbar.with_child(button);
+    }
+
     // Wrap in Portal for scrolling
-    let root_widget = masonry::core::NewWidget::new(Portal::new(content));
+    let root_widget = NewWidget::new_with_tag(
+        Flex::column()
+            .with_child(NewWidget::new_with_tag(toolbar, TOOLBAR_TAG))
+            .with_flex_child(NewWidget::new(Portal::new(content)), 1.0),
+        ROOT_TAG,
+    );
 
     // Create window
     let window_size = LogicalSize::new(600.0, 800.0);
@@ -280,128 +1110,86 @@ fn main() {
         .with_resizable(true)
         .with_min_inner_size(window_size);
 
-    // Clone query_runtime for the background thread before moving it to Driver
-    let query_runtime_for_bg = query_runtime.clone();
+    // Refresh the UI when the file changes (remote documents have no file to watch)
+    let watcher = if is_remote {
+        None
+    } else {
+        watch_file(hnmd_file, window_id, proxy.clone())
+    };
+
+    // Refresh the UI when query results arrive
+    if let Some(qr) = &query_runtime {
+        spawn_query_refresh(qr, window_id, proxy.clone());
+    }
 
     let driver = Driver {
-        window_id: WindowId::next(),
-        hnmd_path: hnmd_file.to_string(),
+        window_id,
+        history: History::new(page),
+        router,
+        runtime,
+        nostr_viewer: options.nostr_viewer,
+        opener: options.opener,
+        offline: options.offline,
+        event_store,
+        policy: options.policy,
+        back_button: back_id,
+        forward_button: forward_id,
+        theme_button: theme_id,
+        appearance,
+        follow_system,
+        proxy,
+        _watcher: watcher,
         widget_states: initial_states,
         render_ctx: Some(render_ctx),
         query_runtime,
         registry,
+        remote_doc: if is_remote { Some(doc.clone()) } else { None },
+        state_writes: HashMap::new(),
+        form_values: HashMap::new(),
+        images,
     };
 
-    // Create custom theme with black text on light gray background
+    // Fallback widget colours from the starting theme. These are set once, so the builder
+    // also colours each text widget from the active theme, and a theme switch's rebuild recolours them
     let mut properties = theme::default_property_set();
-    properties.insert::<Label, _>(ContentColor::new(Color::from_rgb8(0, 0, 0)));
-    properties.insert::<Label, _>(DisabledContentColor(ContentColor::new(Color::from_rgb8(100, 100, 100))));
+    properties.insert::<Label, _>(ContentColor::new(styles.color("text")));
+    properties.insert::<Label, _>(DisabledContentColor(ContentColor::new(styles.color("muted"))));
+
+    // Paragraph text, with the same selection colour as text inputs
+    properties.insert::<RichText, _>(ContentColor::new(styles.color("text")));
+    properties.insert::<RichText, _>(SelectionColor { color: styles.color("selection") });
 
     // Style buttons with border and darker gray background
-    properties.insert::<Button, _>(Background::Color(Color::from_rgb8(192, 192, 192)));
-    properties.insert::<Button, _>(BorderColor { color: Color::from_rgb8(128, 128, 128) });
+    properties.insert::<Button, _>(Background::Color(styles.color("button")));
+    properties.insert::<Button, _>(BorderColor { color: styles.color("control_border") });
     properties.insert::<Button, _>(BorderWidth { width: 1.0 });
 
-    // Style text inputs with black text and cursor
-    properties.insert::<TextArea<true>, _>(ContentColor::new(Color::from_rgb8(0, 0, 0)));
-    properties.insert::<TextArea<false>, _>(ContentColor::new(Color::from_rgb8(0, 0, 0)));
-    properties.insert::<TextArea<true>, _>(CaretColor { color: AlphaColor::from_rgb8(0, 0, 0) });
-    properties.insert::<TextArea<false>, _>(CaretColor { color: AlphaColor::from_rgb8(0, 0, 0) });
-
-    // Style selection to be blue with good contrast
-    properties.insert::<TextArea<true>, _>(SelectionColor { color: AlphaColor::from_rgb8(173, 214, 255) });
-    properties.insert::<TextArea<false>, _>(SelectionColor { color: AlphaColor::from_rgb8(200, 200, 200) });
-
-    // Create event loop
-    let event_loop = masonry_winit::app::EventLoop::with_user_event()
-        .build()
-        .unwrap();
-
-    // Get proxy for sending events from other threads
-    let proxy = event_loop.create_proxy();
-
-    // Set up file watcher with proxy
-    let watch_path = hnmd_file.to_string();
-    let (file_tx, file_rx) = mpsc::channel();
-    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
-        if let Ok(event) = res {
-            if event.kind.is_modify() {
-                let _ = file_tx.send(());
-            }
-        }
-    }).expect("Failed to create file watcher");
-
-    watcher.watch(Path::new(&watch_path), RecursiveMode::NonRecursive)
-        .expect("Failed to watch file");
-
-    // Spawn thread to watch for file changes and send reload actions
-    let window_id = driver.window_id;
-    let proxy_clone = proxy.clone();
-    std::thread::spawn(move || {
-        // Keep watcher alive
-        let _watcher = watcher;
-
-        loop {
-            if file_rx.recv().is_ok() {
-                println!("\n📝 File changed detected...");
-
-                // Send reload action via event loop proxy
-                let action: masonry::core::ErasedAction = Box::new(ReloadAction);
-                let widget_id = WidgetId::next();
-                let user_event = MasonryUserEvent::Action(window_id, action, widget_id);
-                let _ = proxy_clone.send_event(user_event);
-            }
-        }
-    });
-
-    // Spawn thread to watch for query updates and trigger UI refreshes
-    if let Some(qr) = query_runtime_for_bg.as_ref() {
-        let qr_clone = Arc::clone(qr);
-        let window_id = driver.window_id;
-
-        std::thread::spawn(move || {
-            // Poll for query updates every 500ms
-            loop {
-                std::thread::sleep(std::time::Duration::from_millis(500));
-
-                // Check if we have any queries with events
-                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                let has_events = rt.block_on(async {
-                    let queries_json = qr_clone.to_json().await;
-                    if let Some(obj) = queries_json.as_object() {
-                        obj.values().any(|v| v.as_array().map(|a| !a.is_empty()).unwrap_or(false))
-                    } else {
-                        false
-                    }
-                });
-
-                if has_events {
-                    println!("  🔄 Query updated, refreshing UI...");
-
-                    // Send reload action to update UI
-                    let action: masonry::core::ErasedAction = Box::new(ReloadAction);
-                    let widget_id = WidgetId::next();
-                    let user_event = MasonryUserEvent::Action(window_id, action, widget_id);
-                    let _ = proxy.send_event(user_event);
-
-                    // Only refresh once when events first arrive
-                    break;
-                }
-            }
-        });
-    }
-
-    // Run app
-    masonry_winit::app::run_with(
-        event_loop,
-        vec![NewWindow::new_with_id(
-            driver.window_id,
-            window_attributes,
-            root_widget.erased(),
-        )
-        .with_base_color(AlphaColor::from_rgb8(236, 236, 236))], // Classic light gray
-        driver,
-        properties,
+    // Style text inputs' text and cursor
+    properties.insert::<TextArea<true>, _>(ContentColor::new(styles.color("text")));
+    properties.insert::<TextArea<false>, _>(ContentColor::new(styles.color("text")));
+    properties.insert::<TextArea<true>, _>(CaretColor { color: styles.color("text") });
+    properties.insert::<TextArea<false>, _>(CaretColor { color: styles.color("text") });
+
+    // Selection with good contrast
+    properties.insert::<TextArea<true>, _>(SelectionColor { color: styles.color("selection") });
+    properties.insert::<TextArea<false>, _>(SelectionColor { color: styles.color("selection_inactive") });
+
+    // Run app, then finish writing received events
+    // (like masonry_winit::app::run_with, but the desktop's light/dark changes reach the driver)
+    let event_store = driver.event_store.clone();
+    let windows = vec![NewWindow::new_with_id(
+        driver.window_id,
+        window_attributes,
+        root_widget.erased(),
     )
-    .unwrap();
+    .with_base_color(styles.color("background"))];
+    let mut app = App {
+        masonry: MasonryState::new(event_loop.create_proxy(), windows, properties),
+        driver,
+    };
+    app.driver.on_start(&mut app.masonry);
+    event_loop.run_app(&mut app).unwrap();
+    if let Some(store) = event_store {
+        store.flush();
+    }
 }
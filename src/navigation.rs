@@ -0,0 +1,443 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::runtime::AppAddress;
+
+/// Where a clicked link leads
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    /// Another .hnmd document, opened inside the app
+    Document(String),
+    /// An app path like `/profile/<pubkey>`, looked up in the `routes:` of loaded documents
+    Route(String),
+    /// A `nostr:` entity, shown by the configured viewer document
+    Nostr(Value),
+    /// An http(s) or mailto URL, handed to the opener command
+    External(String),
+}
+
+/// Schemes handed to the opener command; links with any other scheme are refused
+const EXTERNAL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Scheme of a URL like `https://...` or `mailto:...` (one-letter schemes are Windows drives)
+fn url_scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let valid = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    valid.then_some(scheme)
+}
+
+/// Fail unless the URL has one of the `EXTERNAL_SCHEMES`
+fn check_external(url: &str) -> Result<()> {
+    match url_scheme(url) {
+        Some(scheme) if EXTERNAL_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) => Ok(()),
+        Some(scheme) => Err(anyhow!("Links with the '{}:' scheme are not opened: '{}'", scheme, url)),
+        None => Err(anyhow!("Not an external URL: '{}'", url)),
+    }
+}
+
+/// Classify a link found in the document at `base`.
+/// Relative paths resolve against the directory of `base`.
+pub fn resolve_link(url: &str, base: &str) -> Result<LinkTarget> {
+    let url = url.trim();
+
+    if url.starts_with("nostr:") {
+        return decode_nostr_uri(url).map(LinkTarget::Nostr);
+    }
+    if url_scheme(url).is_some() {
+        check_external(url)?;
+        return Ok(LinkTarget::External(url.to_string()));
+    }
+
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    if path.starts_with('/') && !path.ends_with(".hnmd") {
+        return Ok(LinkTarget::Route(url.to_string()));
+    }
+    if !path.ends_with(".hnmd") {
+        return Err(anyhow!("Don't know how to open link '{}'", url));
+    }
+    if Path::new(path).is_absolute() {
+        return Ok(LinkTarget::Document(path.to_string()));
+    }
+    if AppAddress::is_address(base) {
+        return Err(anyhow!("Relative link '{}' in a document fetched from Nostr", url));
+    }
+
+    Ok(LinkTarget::Document(relative_to(base, path)))
+}
+
+/// Resolve a path against the directory of the document at `base`
+fn relative_to(base: &str, path: &str) -> String {
+    let dir = Path::new(base).parent().unwrap_or(Path::new(""));
+    dir.join(path).to_string_lossy().into_owned()
+}
+
+/// Target expression of a `navigate(<expr>)` button action
+pub fn navigate_target(on_click: &str) -> Option<&str> {
+    let inner = on_click.trim().strip_prefix("navigate(")?.strip_suffix(')')?;
+    Some(inner.trim())
+}
+
+/// Match a path against a route pattern; `:name` segments capture their value
+pub fn match_route(pattern: &str, path: &str) -> Option<Map<String, Value>> {
+    let path = path.split(['#', '?']).next().unwrap_or(path);
+    let mut pattern_segments = pattern.split('/').filter(|s| !s.is_empty());
+    let mut path_segments = path.split('/').filter(|s| !s.is_empty());
+
+    let mut params = Map::new();
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return Some(params),
+            (Some(expected), Some(segment)) => match expected.strip_prefix(':') {
+                Some(name) => {
+                    params.insert(name.to_string(), json!(segment));
+                }
+                None if expected == segment => {}
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+/// Route table gathered from the `routes:` frontmatter of loaded documents
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    /// (pattern, file) in the order they were added
+    routes: Vec<(String, String)>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a document's routes, with files relative to the document at `base`.
+    /// A pattern that is already known keeps its first file.
+    /// A document fetched from Nostr may only route to Nostr addresses; returns the patterns skipped for naming files.
+    pub fn add_routes(&mut self, routes: &HashMap<String, String>, base: &str) -> Vec<String> {
+        let remote = AppAddress::is_address(base);
+        let mut patterns: Vec<&String> = routes.keys().collect();
+        patterns.sort();
+
+        let mut skipped = Vec::new();
+        for pattern in patterns {
+            let target = routes[pattern].trim();
+            let target = if AppAddress::is_address(target) {
+                target.to_string()
+            } else if remote {
+                skipped.push(pattern.clone());
+                continue;
+            } else {
+                relative_to(base, target)
+            };
+            if self.routes.iter().all(|(known, _)| known != pattern) {
+                self.routes.push((pattern.clone(), target));
+            }
+        }
+        skipped
+    }
+
+    /// Page for an app path; literal segments beat parameters when several patterns match
+    pub fn resolve(&self, path: &str) -> Option<Page> {
+        let literal_segments = |pattern: &str| pattern.split('/').filter(|s| !s.is_empty() && !s.starts_with(':')).count();
+
+        self.routes
+            .iter()
+            .filter_map(|(pattern, file)| match_route(pattern, path).map(|params| (pattern, file, params)))
+            .max_by_key(|(pattern, _, _)| literal_segments(pattern))
+            .map(|(_, file, params)| Page::new(file.clone()).with_route(Value::Object(params)))
+    }
+}
+
+/// Decode a `nostr:` URI (npub, nprofile, note, nevent, naddr) into the JSON
+/// the viewer document sees as `nostr`
+pub fn decode_nostr_uri(uri: &str) -> Result<Value> {
+    let entity = Nip21::parse(uri).map_err(|e| anyhow!("Invalid nostr URI '{}': {}", uri, e))?;
+    let relays = |relays: &[RelayUrl]| relays.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+
+    let mut value = match entity {
+        Nip21::Pubkey(pubkey) => json!({ "type": "profile", "pubkey": pubkey.to_hex(), "relays": [] }),
+        Nip21::Profile(profile) => json!({
+            "type": "profile",
+            "pubkey": profile.public_key.to_hex(),
+            "relays": relays(&profile.relays),
+        }),
+        Nip21::EventId(id) => json!({ "type": "event", "id": id.to_hex(), "relays": [] }),
+        Nip21::Event(event) => json!({
+            "type": "event",
+            "id": event.event_id.to_hex(),
+            "author": event.author.map(|pk| pk.to_hex()),
+            "kind": event.kind.map(|k| k.as_u16()),
+            "relays": relays(&event.relays),
+        }),
+        Nip21::Coordinate(naddr) => json!({
+            "type": "address",
+            "kind": naddr.coordinate.kind.as_u16(),
+            "pubkey": naddr.coordinate.public_key.to_hex(),
+            "identifier": naddr.coordinate.identifier,
+            "relays": relays(&naddr.relays),
+        }),
+    };
+    value["uri"] = json!(uri);
+    Ok(value)
+}
+
+/// Hand an external URL to the opener command (e.g. `xdg-open`, or `firefox --new-tab`)
+/// Only http(s) and mailto URLs are opened
+pub fn open_external(opener: &str, url: &str) -> Result<()> {
+    check_external(url)?;
+    let mut parts = opener.split_whitespace();
+    let program = parts.next().ok_or_else(|| anyhow!("Empty opener command"))?;
+    std::process::Command::new(program)
+        .args(parts)
+        .arg(url)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run opener '{}': {}", opener, e))?;
+    Ok(())
+}
+
+/// The platform's default command for opening URLs
+pub fn default_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// One entry in the navigation history
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// File path or Nostr address of the document
+    pub location: String,
+    /// Decoded `nostr:` entity when the page is the viewer for one
+    pub nostr: Option<Value>,
+    /// Parameters of the route that led here (`route` in expressions)
+    pub route: Option<Value>,
+    /// State written by widgets on this page, restored when coming back to it
+    pub state_writes: HashMap<String, Value>,
+}
+
+impl Page {
+    pub fn new(location: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            nostr: None,
+            route: None,
+            state_writes: HashMap::new(),
+        }
+    }
+
+    pub fn with_nostr(mut self, entity: Value) -> Self {
+        self.nostr = Some(entity);
+        self
+    }
+
+    pub fn with_route(mut self, params: Value) -> Self {
+        self.route = Some(params);
+        self
+    }
+}
+
+/// Back/forward history, like a browser's
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    entries: Vec<T>,
+    index: usize,
+}
+
+impl<T> History<T> {
+    pub fn new(first: T) -> Self {
+        Self { entries: vec![first], index: 0 }
+    }
+
+    pub fn current(&self) -> &T {
+        &self.entries[self.index]
+    }
+
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.entries[self.index]
+    }
+
+    /// Visit a new entry; anything ahead of the current one is dropped
+    pub fn push(&mut self, entry: T) {
+        self.entries.truncate(self.index + 1);
+        self.entries.push(entry);
+        self.index += 1;
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.index + 1 < self.entries.len()
+    }
+
+    /// Step back; returns the new current entry
+    pub fn back(&mut self) -> Option<&T> {
+        if !self.can_go_back() {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.current())
+    }
+
+    /// Step forward; returns the new current entry
+    pub fn forward(&mut self) -> Option<&T> {
+        if !self.can_go_forward() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_link() {
+        assert_eq!(
+            resolve_link("other.hnmd", "apps/hello.hnmd").unwrap(),
+            LinkTarget::Document("apps/other.hnmd".to_string())
+        );
+        assert_eq!(
+            resolve_link("../docs/a.hnmd#top", "apps/hello.hnmd").unwrap(),
+            LinkTarget::Document("apps/../docs/a.hnmd".to_string())
+        );
+        assert_eq!(
+            resolve_link("https://example.com", "apps/hello.hnmd").unwrap(),
+            LinkTarget::External("https://example.com".to_string())
+        );
+        assert_eq!(
+            resolve_link("/profile/abc", "apps/hello.hnmd").unwrap(),
+            LinkTarget::Route("/profile/abc".to_string())
+        );
+        assert_eq!(
+            resolve_link("mailto:me@example.com", "apps/hello.hnmd").unwrap(),
+            LinkTarget::External("mailto:me@example.com".to_string())
+        );
+        assert!(resolve_link("notes.txt", "apps/hello.hnmd").is_err());
+        assert!(resolve_link("other.hnmd", "naddr1xyz").is_err());
+    }
+
+    #[test]
+    fn test_refuse_other_schemes() {
+        for url in ["file:///etc/passwd", "foo://x", "javascript:alert(1)"] {
+            let err = resolve_link(url, "apps/hello.hnmd").unwrap_err();
+            assert!(err.to_string().contains("scheme"), "{}", err);
+            assert!(open_external("true", url).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_nostr_uri() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        let uri = format!("nostr:{}", npub);
+
+        match resolve_link(&uri, "apps/hello.hnmd").unwrap() {
+            LinkTarget::Nostr(entity) => {
+                assert_eq!(entity["type"], "profile");
+                assert_eq!(entity["pubkey"], keys.public_key().to_hex());
+                assert_eq!(entity["uri"], uri.as_str());
+            }
+            other => panic!("expected a nostr entity, got {:?}", other),
+        }
+
+        let coordinate = Coordinate::new(Kind::from(30023), keys.public_key()).identifier("post");
+        let naddr = Nip19Coordinate::new(coordinate, Vec::<RelayUrl>::new()).to_bech32().unwrap();
+        let entity = decode_nostr_uri(&format!("nostr:{}", naddr)).unwrap();
+        assert_eq!(entity["type"], "address");
+        assert_eq!(entity["kind"], 30023);
+        assert_eq!(entity["identifier"], "post");
+
+        assert!(decode_nostr_uri("nostr:nonsense").is_err());
+    }
+
+    #[test]
+    fn test_match_route() {
+        let params = match_route("/profile/:pubkey", "/profile/abc?tab=notes").unwrap();
+        assert_eq!(Value::Object(params), json!({ "pubkey": "abc" }));
+        assert_eq!(match_route("/", "/").unwrap().len(), 0);
+        assert!(match_route("/profile/:pubkey", "/profile").is_none());
+        assert!(match_route("/profile/:pubkey", "/thread/abc").is_none());
+        assert!(match_route("/profile/:pubkey", "/profile/abc/notes").is_none());
+    }
+
+    #[test]
+    fn test_router() {
+        let mut routes = HashMap::new();
+        routes.insert("/profile/:pubkey".to_string(), "profile.hnmd".to_string());
+        routes.insert("/profile/me".to_string(), "me.hnmd".to_string());
+
+        let mut router = Router::new();
+        router.add_routes(&routes, "apps/feed.hnmd");
+
+        // A later document can't take over a known pattern
+        let mut other = HashMap::new();
+        other.insert("/profile/:pubkey".to_string(), "other.hnmd".to_string());
+        router.add_routes(&other, "apps/thread.hnmd");
+
+        let page = router.resolve("/profile/abc").unwrap();
+        assert_eq!(page.location, "apps/profile.hnmd");
+        assert_eq!(page.route, Some(json!({ "pubkey": "abc" })));
+        assert_eq!(router.resolve("/profile/me").unwrap().location, "apps/me.hnmd");
+        assert!(router.resolve("/thread/abc").is_none());
+    }
+
+    #[test]
+    fn test_remote_document_routes() {
+        let mut routes = HashMap::new();
+        routes.insert("/secrets".to_string(), "/home/me/secrets.hnmd".to_string());
+        routes.insert("/local".to_string(), "local.hnmd".to_string());
+        routes.insert("/post".to_string(), "naddr1post".to_string());
+
+        // A fetched document can't make the app open local files
+        let mut router = Router::new();
+        assert_eq!(router.add_routes(&routes, "naddr1app"), vec!["/local", "/secrets"]);
+        assert!(router.resolve("/secrets").is_none());
+        assert!(router.resolve("/local").is_none());
+        assert_eq!(router.resolve("/post").unwrap().location, "naddr1post");
+
+        // A local document can route to both
+        let mut router = Router::new();
+        assert!(router.add_routes(&routes, "apps/feed.hnmd").is_empty());
+        assert_eq!(router.resolve("/local").unwrap().location, "apps/local.hnmd");
+        assert_eq!(router.resolve("/post").unwrap().location, "naddr1post");
+    }
+
+    #[test]
+    fn test_navigate_target() {
+        assert_eq!(navigate_target("navigate(\"/profile/\" + note.pubkey)"), Some("\"/profile/\" + note.pubkey"));
+        assert_eq!(navigate_target("actions.post"), None);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new("a");
+        assert!(!history.can_go_back());
+        assert_eq!(history.back(), None);
+
+        history.push("b");
+        history.push("c");
+        assert_eq!(history.back(), Some(&"b"));
+        assert_eq!(history.back(), Some(&"a"));
+        assert_eq!(history.forward(), Some(&"b"));
+
+        // Visiting a new page drops the forward entries
+        history.push("d");
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back(), Some(&"b"));
+        assert_eq!(history.current(), &"b");
+    }
+}
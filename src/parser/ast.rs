@@ -1,5 +1,94 @@
+use crate::parser::component_def::ComponentDef;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+/// Position of a node or frontmatter entry in its source file
+/// Spans never take part in equality, so the same content parsed at a different
+/// position compares equal (a hand-built node's `None` still differs from a parsed `Some`)
+#[derive(Debug, Clone, Copy, Default, Eq)]
+pub struct Span {
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column (in characters)
+    pub column: usize,
+    /// Byte offset from the start of the file
+    pub offset: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, offset: usize) -> Self {
+        Self { line, column, offset }
+    }
+
+    /// Span of a byte offset into `source`
+    pub fn at(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            offset,
+        }
+    }
+
+    /// Span where `part`, a subslice of `source` (e.g. its trimmed body), starts
+    pub fn of(source: &str, part: &str) -> Self {
+        let offset = (part.as_ptr() as usize).saturating_sub(source.as_ptr() as usize);
+        Self::at(source, offset.min(source.len()))
+    }
+
+    /// Translate a span relative to a snippet that starts at `self` in the file
+    pub fn offset_by(&self, relative: Span) -> Self {
+        Self {
+            line: self.line + relative.line - 1,
+            column: if relative.line == 1 {
+                self.column + relative.column - 1
+            } else {
+                relative.column
+            },
+            offset: self.offset + relative.offset,
+        }
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Prefix a message with `line:column` when the span is known
+pub fn with_location(span: Option<Span>, message: impl fmt::Display) -> String {
+    match span {
+        Some(span) => format!("{}: {}", span, message),
+        None => message.to_string(),
+    }
+}
+
+/// Parse error at a known position
+#[derive(Debug, Clone, Error)]
+#[error("{span}: {message}")]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(span: Span, message: impl fmt::Display) -> Self {
+        Self {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
 
 /// Top-level HNMD document
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,6 +98,9 @@ pub struct Document {
     /// Component imports (name → file path)
     #[serde(default)]
     pub imports: HashMap<String, String>,
+    /// Components bundled into the document itself (name → definition)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub components: HashMap<String, ComponentDef>,
     /// Frontmatter containing filters, pipes, actions, and state
     pub frontmatter: Frontmatter,
     /// Markdown body rendered as nodes
@@ -20,6 +112,7 @@ impl Document {
         Self {
             version: "1.0.0".to_string(),
             imports: HashMap::new(),
+            components: HashMap::new(),
             frontmatter,
             body,
         }
@@ -41,6 +134,39 @@ pub struct Frontmatter {
     /// App-local state with initial values
     #[serde(default)]
     pub state: HashMap<String, serde_json::Value>,
+    /// Route patterns (e.g. `/profile/:pubkey`) → .hnmd files, relative to this document
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub routes: HashMap<String, String>,
+    /// Palette, font sizes and spacing scale, over the built-in theme
+    #[serde(default, skip_serializing_if = "Theme::is_empty")]
+    pub theme: Theme,
+    /// Named style classes (class → property → value) for `class="..."`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub styles: HashMap<String, StyleRules>,
+}
+
+/// Style properties of one class: `color`, `background`, `font_size`, `padding`, ...
+/// Values are literals, or names from the theme (e.g. `color: primary`, `padding: md`)
+pub type StyleRules = HashMap<String, String>;
+
+/// `theme:` frontmatter section; each entry adds to or overrides the built-in theme
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Theme {
+    /// Color names → CSS colors (e.g. `primary: "#007aff"`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub palette: HashMap<String, String>,
+    /// Font size names → pixels (e.g. `body: 18`, `h1: 40`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub font_sizes: HashMap<String, f64>,
+    /// Spacing names → pixels (e.g. `md: 12`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub spacing: HashMap<String, f64>,
+}
+
+impl Theme {
+    pub fn is_empty(&self) -> bool {
+        self.palette.is_empty() && self.font_sizes.is_empty() && self.spacing.is_empty()
+    }
 }
 
 impl Frontmatter {
@@ -71,6 +197,16 @@ impl Frontmatter {
         self.state.insert(key.into(), value);
         self
     }
+
+    pub fn with_route(mut self, pattern: impl Into<String>, file: impl Into<String>) -> Self {
+        self.routes.insert(pattern.into(), file.into());
+        self
+    }
+
+    pub fn with_style(mut self, class: impl Into<String>, rules: StyleRules) -> Self {
+        self.styles.insert(class.into(), rules);
+        self
+    }
 }
 
 /// Nostr filter definition
@@ -103,6 +239,9 @@ pub struct Filter {
     /// Maximum number of events
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<usize>,
+    /// Where the filter is defined
+    #[serde(skip)]
+    pub span: Option<Span>,
 }
 
 impl Filter {
@@ -117,6 +256,7 @@ impl Filter {
             since: None,
             until: None,
             limit: None,
+            span: None,
         }
     }
 
@@ -149,6 +289,9 @@ pub struct Pipe {
     pub from: String,
     /// jq expression to transform the data
     pub jq: String,
+    /// Where the pipe is defined
+    #[serde(skip)]
+    pub span: Option<Span>,
 }
 
 impl Pipe {
@@ -156,6 +299,7 @@ impl Pipe {
         Self {
             from: from.into(),
             jq: jq.into(),
+            span: None,
         }
     }
 }
@@ -170,6 +314,9 @@ pub struct Action {
     /// Event tags
     #[serde(default)]
     pub tags: Vec<Vec<String>>,
+    /// Where the action is defined
+    #[serde(skip)]
+    pub span: Option<Span>,
 }
 
 impl Action {
@@ -178,6 +325,7 @@ impl Action {
             kind,
             content: content.into(),
             tags: Vec::new(),
+            span: None,
         }
     }
 
@@ -213,6 +361,10 @@ pub enum Node {
     Emphasis {
         children: Vec<Node>,
     },
+    /// Struck-through text (`~~gone~~`)
+    Strikethrough {
+        children: Vec<Node>,
+    },
     /// Unordered list
     List {
         ordered: bool,
@@ -223,16 +375,60 @@ pub enum Node {
         url: String,
         children: Vec<Node>,
     },
-    /// Image
+    /// Inline code span
+    InlineCode {
+        value: String,
+    },
+    /// Footnote call (`[^label]`)
+    FootnoteReference {
+        label: String,
+    },
+    /// Footnote body (`[^label]: ...`)
+    FootnoteDefinition {
+        label: String,
+        children: Vec<Node>,
+    },
+    /// Fenced code block
+    CodeBlock {
+        /// Language from the info string (e.g. "rust")
+        #[serde(skip_serializing_if = "Option::is_none")]
+        lang: Option<String>,
+        value: String,
+    },
+    /// Block quote
+    Blockquote {
+        children: Vec<Node>,
+    },
+    /// Horizontal rule (`---`)
+    ThematicBreak,
+    /// Table; the first row is the header
+    Table {
+        /// Alignment of each column
+        align: Vec<ColumnAlign>,
+        rows: Vec<TableRow>,
+    },
+    /// Image; `src` and `alt` can interpolate `{expr}` templates
     Image {
         src: String,
         alt: String,
+        /// Box size in pixels (100x100 when unset)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        width: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        height: Option<f64>,
+        /// How the image fills its box: "contain" (default), "cover", "fill", "none" or "scale-down"
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fit: Option<String>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
 
     // Expression interpolation
     /// Expression that evaluates at runtime: {queries.feed[0].content}
     Expr {
         expression: String,
+        #[serde(skip)]
+        span: Option<Span>,
     },
 
     // Component nodes
@@ -242,7 +438,18 @@ pub enum Node {
         from: String,
         /// Variable name for iteration
         as_name: String,
+        /// Expression identifying an item across updates (the item's `id` by default)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        /// Height of the scrolling box when only the rows in view are built (`virtual`)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        viewport: Option<f64>,
+        /// Action run when a `virtual` list is scrolled near its end (`loadMore(...)`)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        on_end: Option<String>,
         children: Vec<Node>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
     /// Conditional rendering
     If {
@@ -253,6 +460,8 @@ pub enum Node {
         /// Optional nodes to render when falsy
         #[serde(skip_serializing_if = "Option::is_none")]
         else_children: Option<Vec<Node>>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
     /// Button component
     Button {
@@ -260,6 +469,8 @@ pub enum Node {
         #[serde(skip_serializing_if = "Option::is_none")]
         on_click: Option<String>,
         children: Vec<Node>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
     /// Text input field
     Input {
@@ -268,42 +479,50 @@ pub enum Node {
         /// Placeholder text
         #[serde(skip_serializing_if = "Option::is_none")]
         placeholder: Option<String>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
     /// Vertical stack layout
     VStack {
         children: Vec<Node>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        width: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        height: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        flex: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        align: Option<String>,  // "start", "center", "end"
+        #[serde(flatten)]
+        layout: StackLayout,
     },
     /// Horizontal stack layout
     HStack {
         children: Vec<Node>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        width: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        height: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        flex: Option<f64>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        align: Option<String>,
+        #[serde(flatten)]
+        layout: StackLayout,
+    },
+    /// Styled block of text (`<text>`)
+    TextBlock {
+        children: Vec<Node>,
+        #[serde(flatten)]
+        style: NodeStyle,
     },
-    /// Grid layout
+    /// Grid layout; children flow into rows, and `<each>` children give one cell per item
     Grid {
         /// Number of columns
         #[serde(skip_serializing_if = "Option::is_none")]
         columns: Option<usize>,
+        /// Instead of `columns`: as many columns as fit at this width or wider
+        #[serde(skip_serializing_if = "Option::is_none")]
+        min_column_width: Option<f64>,
+        /// Space between rows and columns, unless `row_gap`/`column_gap` say otherwise
+        #[serde(skip_serializing_if = "Option::is_none")]
+        gap: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        row_gap: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        column_gap: Option<f64>,
         children: Vec<Node>,
     },
     /// JSON debug viewer
     Json {
         /// Expression that evaluates to any value
         value: String,
+        #[serde(skip)]
+        span: Option<Span>,
     },
     /// Spacer for layout
     Spacer {
@@ -319,18 +538,153 @@ pub enum Node {
         props: HashMap<String, PropValue>,
         /// Children nodes (for components that accept children)
         children: Vec<Node>,
+        #[serde(skip)]
+        span: Option<Span>,
     },
 }
 
+/// Sizing and alignment of a `<vstack>`/`<hstack>`; sizes are in pixels
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StackLayout {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<f64>,
+    /// Share of the parent stack's leftover space
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flex: Option<f64>,
+    /// Cross-axis alignment: "start", "center", "end", "stretch" or "baseline"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub align: Option<String>,
+    /// Main-axis alignment: "start", "center", "end", "space-between", "space-around" or "space-evenly"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub justify: Option<String>,
+    /// Space between children
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap: Option<f64>,
+    /// Space inside the edges
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub padding: Option<f64>,
+    /// Columns covered as a grid cell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<usize>,
+    /// Rows covered as a grid cell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_span: Option<usize>,
+    #[serde(flatten)]
+    pub style: NodeStyle,
+}
+
+/// `class` and style properties of a stack or `<text>`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeStyle {
+    /// Space-separated class names from `styles:` (a `{expr}` template when dynamic)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Properties from `style="color: red; padding: md"` and attributes like `color={expr}`;
+    /// these win over the classes
+    #[serde(default, rename = "style", skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, PropValue>,
+}
+
+impl NodeStyle {
+    pub fn is_empty(&self) -> bool {
+        self.class.is_none() && self.properties.is_empty()
+    }
+
+    /// Whether the style changes with runtime data
+    pub fn is_dynamic(&self) -> bool {
+        self.class.as_deref().is_some_and(is_template)
+            || self.properties.values().any(|value| matches!(value, PropValue::Expression(_)))
+    }
+}
+
+/// Style properties a node can set
+pub const STYLE_PROPERTIES: &[&str] = &[
+    "color",
+    "background",
+    "font_size",
+    "font_weight",
+    "border_color",
+    "border_width",
+    "radius",
+    "padding",
+];
+
+/// Parse a `style` attribute: `color: red; font_size: 20`
+pub fn parse_style_attr(style: &str) -> Vec<(String, String)> {
+    style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(name, value)| (name.trim().replace('-', "_"), value.trim().to_string()))
+        .filter(|(name, value)| !name.is_empty() && !value.is_empty())
+        .collect()
+}
+
 /// List item node
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ListItem {
     pub children: Vec<Node>,
+    /// Task list state: `- [x]` is `Some(true)`, `- [ ]` is `Some(false)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// State path the task checkbox writes to (`- [ ] {state.done} ...`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind: Option<String>,
+}
+
+impl ListItem {
+    /// Create a plain (non-task) list item
+    pub fn new(children: Vec<Node>) -> Self {
+        Self { children, checked: None, bind: None }
+    }
+
+    /// Create a task list item
+    pub fn task(checked: bool, children: Vec<Node>) -> Self {
+        Self { children, checked: Some(checked), bind: None }
+    }
+
+    pub fn with_bind(mut self, path: impl Into<String>) -> Self {
+        self.bind = Some(path.into());
+        self
+    }
+}
+
+/// Table row node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+}
+
+/// Table cell node (inline content)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableCell {
+    pub children: Vec<Node>,
+}
+
+/// Alignment of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnAlign {
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
 }
 
 /// Prop value for custom components
+/// Serialized as a plain string (literal) or `{"expr": "..."}` so JSON roundtrips keep the kind
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(untagged)]
+#[serde(from = "PropValueRepr", into = "PropValueRepr")]
 pub enum PropValue {
     /// Literal string value
     Literal(String),
@@ -338,7 +692,47 @@ pub enum PropValue {
     Expression(String),
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PropValueRepr {
+    Literal(String),
+    Expression { expr: String },
+}
+
+impl From<PropValueRepr> for PropValue {
+    fn from(repr: PropValueRepr) -> Self {
+        match repr {
+            PropValueRepr::Literal(s) => PropValue::Literal(s),
+            PropValueRepr::Expression { expr } => PropValue::Expression(expr),
+        }
+    }
+}
+
+impl From<PropValue> for PropValueRepr {
+    fn from(value: PropValue) -> Self {
+        match value {
+            PropValue::Literal(s) => PropValueRepr::Literal(s),
+            PropValue::Expression(expr) => PropValueRepr::Expression { expr },
+        }
+    }
+}
+
 impl Node {
+    /// Where the node starts in its source file (for nodes that can fail at runtime)
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::Image { span, .. }
+            | Node::Expr { span, .. }
+            | Node::Each { span, .. }
+            | Node::If { span, .. }
+            | Node::Button { span, .. }
+            | Node::Input { span, .. }
+            | Node::Json { span, .. }
+            | Node::CustomComponent { span, .. } => *span,
+            _ => None,
+        }
+    }
+
     /// Create a heading node
     pub fn heading(level: u8, children: Vec<Node>) -> Self {
         Node::Heading { level, children }
@@ -366,10 +760,38 @@ impl Node {
         Node::Emphasis { children }
     }
 
+    /// Create a strikethrough node
+    pub fn strikethrough(children: Vec<Node>) -> Self {
+        Node::Strikethrough { children }
+    }
+
+    /// Create an inline code node
+    pub fn inline_code(value: impl Into<String>) -> Self {
+        Node::InlineCode { value: value.into() }
+    }
+
+    /// Create a code block node
+    pub fn code_block(lang: Option<String>, value: impl Into<String>) -> Self {
+        Node::CodeBlock { lang, value: value.into() }
+    }
+
+    /// Create an image node
+    pub fn image(src: impl Into<String>, alt: impl Into<String>) -> Self {
+        Node::Image {
+            src: src.into(),
+            alt: alt.into(),
+            width: None,
+            height: None,
+            fit: None,
+            span: None,
+        }
+    }
+
     /// Create an expression node
     pub fn expr(expression: impl Into<String>) -> Self {
         Node::Expr {
             expression: expression.into(),
+            span: None,
         }
     }
 
@@ -378,16 +800,48 @@ impl Node {
         Node::Each {
             from: from.into(),
             as_name: as_name.into(),
+            key: None,
+            viewport: None,
+            on_end: None,
             children,
+            span: None,
         }
     }
 
+    /// Create an each node that only builds the rows in view, in a box `height` pixels tall
+    pub fn virtual_each(
+        from: impl Into<String>,
+        as_name: impl Into<String>,
+        key: Option<String>,
+        height: f64,
+        children: Vec<Node>,
+    ) -> Self {
+        Node::Each {
+            from: from.into(),
+            as_name: as_name.into(),
+            key,
+            viewport: Some(height),
+            on_end: None,
+            children,
+            span: None,
+        }
+    }
+
+    /// Run an action when a `virtual` list is scrolled near its end
+    pub fn with_on_end(mut self, action: impl Into<String>) -> Self {
+        if let Node::Each { on_end, .. } = &mut self {
+            *on_end = Some(action.into());
+        }
+        self
+    }
+
     /// Create an if node
     pub fn if_node(value: impl Into<String>, children: Vec<Node>) -> Self {
         Node::If {
             value: value.into(),
             children,
             else_children: None,
+            span: None,
         }
     }
 
@@ -401,12 +855,13 @@ impl Node {
             value: value.into(),
             children,
             else_children: Some(else_children),
+            span: None,
         }
     }
 
     /// Create a button node
     pub fn button(on_click: Option<String>, children: Vec<Node>) -> Self {
-        Node::Button { on_click, children }
+        Node::Button { on_click, children, span: None }
     }
 
     /// Create an input node
@@ -414,32 +869,87 @@ impl Node {
         Node::Input {
             name: name.into(),
             placeholder: None,
+            span: None,
         }
     }
 
     /// Create a vstack node
     pub fn vstack(children: Vec<Node>) -> Self {
-        Node::VStack {
-            children,
-            width: None,
-            height: None,
-            flex: None,
-            align: None,
-        }
+        Node::VStack { children, layout: StackLayout::default() }
     }
 
     /// Create an hstack node
     pub fn hstack(children: Vec<Node>) -> Self {
-        Node::HStack {
+        Node::HStack { children, layout: StackLayout::default() }
+    }
+
+    /// Create a `<text>` node
+    pub fn text_block(children: Vec<Node>, style: NodeStyle) -> Self {
+        Node::TextBlock { children, style }
+    }
+
+    /// Create a grid node with a fixed number of columns
+    pub fn grid(columns: Option<usize>, children: Vec<Node>) -> Self {
+        Node::Grid {
+            columns,
+            min_column_width: None,
+            gap: None,
+            row_gap: None,
+            column_gap: None,
             children,
-            width: None,
-            height: None,
-            flex: None,
-            align: None,
         }
     }
 }
 
+/// Pieces of a `{expr}` template string (e.g. an image `src`), in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart<'a> {
+    Text(&'a str),
+    Expr(&'a str),
+}
+
+/// Split a template into literal text and `{expr}` expressions; braces nest, so
+/// `{ {a: 1} | .a }` is one expression, and an unclosed `{` is plain text
+pub fn template_parts(template: &str) -> Vec<TemplatePart<'_>> {
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut open = None;
+    let mut depth = 0;
+
+    for (i, c) in template.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open.take().unwrap();
+                    if start > text_start {
+                        parts.push(TemplatePart::Text(&template[text_start..start]));
+                    }
+                    parts.push(TemplatePart::Expr(template[start + 1..i].trim()));
+                    text_start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if text_start < template.len() {
+        parts.push(TemplatePart::Text(&template[text_start..]));
+    }
+    parts
+}
+
+/// Whether a string has `{expr}` parts to evaluate
+pub fn is_template(value: &str) -> bool {
+    template_parts(value).iter().any(|part| matches!(part, TemplatePart::Expr(_)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -489,4 +999,50 @@ mod tests {
 
         assert_eq!(doc, parsed);
     }
+
+    #[test]
+    fn test_spans_are_not_serialized() {
+        let node = Node::Expr {
+            expression: "state.x".to_string(),
+            span: Some(Span::new(3, 5, 20)),
+        };
+
+        let json = serde_json::to_value(&node).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "Expr", "expression": "state.x"}));
+
+        // Spans don't take part in equality
+        assert_eq!(node, Node::expr("state.x"));
+    }
+
+    #[test]
+    fn test_template_parts() {
+        assert_eq!(
+            template_parts("https://robohash.org/{note.pubkey}.png"),
+            vec![
+                TemplatePart::Text("https://robohash.org/"),
+                TemplatePart::Expr("note.pubkey"),
+                TemplatePart::Text(".png"),
+            ]
+        );
+        assert_eq!(template_parts("{ {a: 1} | .a }"), vec![TemplatePart::Expr("{a: 1} | .a")]);
+        assert_eq!(template_parts("images/{oops.png"), vec![TemplatePart::Text("images/{oops.png")]);
+
+        assert!(is_template("{props.picture}"));
+        assert!(!is_template("apps/waffle_dog.jpeg"));
+    }
+
+    #[test]
+    fn test_parse_style_attr() {
+        assert_eq!(
+            parse_style_attr("color: red; font-size: 20;; padding:md"),
+            vec![
+                ("color".to_string(), "red".to_string()),
+                ("font_size".to_string(), "20".to_string()),
+                ("padding".to_string(), "md".to_string()),
+            ]
+        );
+
+        // Commas inside a value are kept
+        assert_eq!(parse_style_attr("background: rgb(1, 2, 3)"), vec![("background".to_string(), "rgb(1, 2, 3)".to_string())]);
+    }
 }
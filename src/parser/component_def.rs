@@ -1,4 +1,4 @@
-use crate::parser::ast::{Filter, Node};
+use crate::parser::ast::{Filter, Node, Span, StyleRules};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -14,6 +14,9 @@ pub struct ComponentDef {
     /// Props schema
     #[serde(default)]
     pub props: HashMap<String, PropSchema>,
+    /// Style classes that only apply inside this component (over the document's)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub styles: HashMap<String, StyleRules>,
     /// Component body (markup)
     pub body: Vec<Node>,
 }
@@ -38,6 +41,7 @@ impl ComponentDef {
             imports: HashMap::new(),
             queries: HashMap::new(),
             props: HashMap::new(),
+            styles: HashMap::new(),
             body,
         }
     }
@@ -63,6 +67,11 @@ impl ComponentDef {
         self.imports.insert(name.into(), path.into());
         self
     }
+
+    pub fn with_style(mut self, class: impl Into<String>, rules: StyleRules) -> Self {
+        self.styles.insert(class.into(), rules);
+        self
+    }
 }
 
 /// Parse a .hnmc component file
@@ -71,11 +80,9 @@ pub fn parse_component(content: &str) -> Result<ComponentDef, String> {
     let has_frontmatter = content.trim_start().starts_with("---");
 
     let (frontmatter_str, body_str) = if has_frontmatter {
-        let parts: Vec<&str> = content.splitn(3, "---").collect();
-        if parts.len() == 3 {
-            (parts[1].trim(), parts[2].trim())
-        } else {
-            return Err("Incomplete frontmatter (missing closing ---)".to_string());
+        match crate::parser::frontmatter::split_source(content) {
+            Some((fm, body)) => (fm.trim(), body.trim()),
+            None => return Err("Incomplete frontmatter (missing closing ---)".to_string()),
         }
     } else {
         ("", content.trim())
@@ -131,14 +138,19 @@ pub fn parse_component(content: &str) -> Result<ComponentDef, String> {
         })
         .unwrap_or_default();
 
+    // Extract component-scoped style classes
+    let styles = crate::parser::frontmatter::parse_styles(frontmatter_yaml.get("styles"))
+        .map_err(|e| format!("Invalid component styles: {:#}", e))?;
+
     // Parse body markdown/components
-    let body = crate::parser::mdx::parse_body(&body_str)
+    let body = crate::parser::mdx::parse_body_at(body_str, Span::of(content, body_str))
         .map_err(|e| format!("Failed to parse component body: {}", e))?;
 
     Ok(ComponentDef {
         imports,
         queries,
         props,
+        styles,
         body,
     })
 }
@@ -175,6 +187,24 @@ queries:
         assert!(!component.body.is_empty());
     }
 
+    #[test]
+    fn test_parse_component_styles() {
+        let content = r#"---
+styles:
+  name:
+    font_weight: bold
+    color: primary
+---
+
+<text class="name">Alice</text>
+"#;
+
+        let component = parse_component(content).unwrap();
+        assert_eq!(component.styles["name"].get("color").unwrap(), "primary");
+
+        assert!(parse_component("---\nstyles:\n  name: bold\n---\n\nx\n").is_err());
+    }
+
     #[test]
     fn test_component_def_builder() {
         let component = ComponentDef::new(vec![])
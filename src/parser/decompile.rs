@@ -1,25 +1,113 @@
-use crate::parser::ast::{Action, Document, Filter, Frontmatter, ListItem, Node, Pipe, PropValue};
+use crate::parser::ast::{
+    Action, ColumnAlign, Document, Filter, Frontmatter, ListItem, Node, NodeStyle, Pipe, PropValue, StackLayout,
+    StyleRules, TableRow, Theme,
+};
+use crate::parser::component_def::{ComponentDef, PropSchema};
+use std::collections::HashMap;
 
 /// Decompile a Document AST back to .hnmd format
+///
+/// The output parses back to the same document with `loader::parse_hnmd`.
 pub fn decompile(doc: &Document) -> String {
+    let mut frontmatter = String::new();
+    frontmatter.push_str(&decompile_imports(&doc.imports));
+    frontmatter.push_str(&decompile_components(&doc.components));
+    frontmatter.push_str(&decompile_frontmatter(&doc.frontmatter));
+
+    with_frontmatter(frontmatter, &doc.body)
+}
+
+/// Decompile a ComponentDef back to .hnmc format
+pub fn decompile_component(def: &ComponentDef) -> String {
+    let mut frontmatter = String::new();
+    frontmatter.push_str(&decompile_imports(&def.imports));
+    frontmatter.push_str(&decompile_props(&def.props));
+
+    if !def.queries.is_empty() {
+        frontmatter.push_str("queries:\n");
+        for (id, filter) in sorted(&def.queries) {
+            frontmatter.push_str(&format!("  {}:\n", yaml_scalar(id)));
+            frontmatter.push_str(&decompile_filter(filter, 4));
+        }
+        frontmatter.push('\n');
+    }
+    frontmatter.push_str(&decompile_styles(&def.styles));
+
+    with_frontmatter(frontmatter, &def.body)
+}
+
+/// Join frontmatter (if any) and body
+fn with_frontmatter(frontmatter: String, body: &[Node]) -> String {
     let mut output = String::new();
 
-    // Write frontmatter if not empty
-    if !doc.frontmatter.filters.is_empty()
-        || !doc.frontmatter.pipes.is_empty()
-        || !doc.frontmatter.actions.is_empty()
-        || !doc.frontmatter.state.is_empty()
-    {
+    if !frontmatter.is_empty() {
         output.push_str("---\n");
-        output.push_str(&decompile_frontmatter(&doc.frontmatter));
+        output.push_str(&frontmatter);
         output.push_str("---\n\n");
     }
 
-    // Write body
-    for node in &doc.body {
-        output.push_str(&decompile_node(node, 0));
+    output.push_str(&decompile_blocks(body));
+    output
+}
+
+/// Map entries sorted by key, so output is stable
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Decompile the imports section
+fn decompile_imports(imports: &HashMap<String, String>) -> String {
+    if imports.is_empty() {
+        return String::new();
     }
 
+    let mut output = String::from("imports:\n");
+    for (name, path) in sorted(imports) {
+        output.push_str(&format!("  {}: {}\n", yaml_scalar(name), yaml_string(path)));
+    }
+    output.push('\n');
+    output
+}
+
+/// Decompile bundled components as block scalars holding their .hnmc source
+fn decompile_components(components: &HashMap<String, ComponentDef>) -> String {
+    if components.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("components:\n");
+    for (name, def) in sorted(components) {
+        output.push_str(&format!("  {}: |\n", yaml_scalar(name)));
+        for line in decompile_component(def).lines() {
+            if line.is_empty() {
+                output.push('\n');
+            } else {
+                output.push_str(&format!("    {}\n", line));
+            }
+        }
+    }
+    output.push('\n');
+    output
+}
+
+/// Decompile a component's props schema
+fn decompile_props(props: &HashMap<String, PropSchema>) -> String {
+    if props.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("props:\n");
+    for (name, schema) in sorted(props) {
+        let value = if !schema.required && schema.default.is_none() {
+            yaml_string(&schema.type_name)
+        } else {
+            serde_json::to_string(schema).unwrap()
+        };
+        output.push_str(&format!("  {}: {}\n", yaml_scalar(name), value));
+    }
+    output.push('\n');
     output
 }
 
@@ -30,11 +118,8 @@ fn decompile_frontmatter(fm: &Frontmatter) -> String {
     // Filters section
     if !fm.filters.is_empty() {
         output.push_str("filters:\n");
-        let mut filter_ids: Vec<_> = fm.filters.keys().collect();
-        filter_ids.sort();
-        for id in filter_ids {
-            let filter = &fm.filters[id];
-            output.push_str(&format!("  {}:\n", id));
+        for (id, filter) in sorted(&fm.filters) {
+            output.push_str(&format!("  {}:\n", yaml_scalar(id)));
             output.push_str(&decompile_filter(filter, 4));
         }
         output.push('\n');
@@ -43,11 +128,8 @@ fn decompile_frontmatter(fm: &Frontmatter) -> String {
     // Pipes section
     if !fm.pipes.is_empty() {
         output.push_str("pipes:\n");
-        let mut pipe_ids: Vec<_> = fm.pipes.keys().collect();
-        pipe_ids.sort();
-        for id in pipe_ids {
-            let pipe = &fm.pipes[id];
-            output.push_str(&format!("  {}:\n", id));
+        for (id, pipe) in sorted(&fm.pipes) {
+            output.push_str(&format!("  {}:\n", yaml_scalar(id)));
             output.push_str(&decompile_pipe(pipe, 4));
         }
         output.push('\n');
@@ -56,11 +138,8 @@ fn decompile_frontmatter(fm: &Frontmatter) -> String {
     // Actions section
     if !fm.actions.is_empty() {
         output.push_str("actions:\n");
-        let mut action_ids: Vec<_> = fm.actions.keys().collect();
-        action_ids.sort();
-        for id in action_ids {
-            let action = &fm.actions[id];
-            output.push_str(&format!("  {}:\n", id));
+        for (id, action) in sorted(&fm.actions) {
+            output.push_str(&format!("  {}:\n", yaml_scalar(id)));
             output.push_str(&decompile_action(action, 4));
         }
         output.push('\n');
@@ -69,15 +148,70 @@ fn decompile_frontmatter(fm: &Frontmatter) -> String {
     // State section
     if !fm.state.is_empty() {
         output.push_str("state:\n");
-        let mut state_keys: Vec<_> = fm.state.keys().collect();
-        state_keys.sort();
-        for key in state_keys {
-            let value = &fm.state[key];
-            output.push_str(&format!("  {}: {}\n", key, decompile_json_value(value)));
+        for (key, value) in sorted(&fm.state) {
+            output.push_str(&format!("  {}: {}\n", yaml_scalar(key), decompile_json_value(value)));
         }
         output.push('\n');
     }
 
+    // Routes section
+    if !fm.routes.is_empty() {
+        output.push_str("routes:\n");
+        for (pattern, file) in sorted(&fm.routes) {
+            output.push_str(&format!("  {}: {}\n", yaml_scalar(pattern), yaml_scalar(file)));
+        }
+        output.push('\n');
+    }
+
+    output.push_str(&decompile_theme(&fm.theme));
+    output.push_str(&decompile_styles(&fm.styles));
+
+    output
+}
+
+/// Decompile the theme section
+fn decompile_theme(theme: &Theme) -> String {
+    if theme.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("theme:\n");
+    if !theme.palette.is_empty() {
+        output.push_str("  palette:\n");
+        for (name, color) in sorted(&theme.palette) {
+            output.push_str(&format!("    {}: {}\n", yaml_scalar(name), yaml_string(color)));
+        }
+    }
+    for (section, values) in [("font_sizes", &theme.font_sizes), ("spacing", &theme.spacing)] {
+        if !values.is_empty() {
+            output.push_str(&format!("  {}:\n", section));
+            for (name, value) in sorted(values) {
+                output.push_str(&format!("    {}: {}\n", yaml_scalar(name), value));
+            }
+        }
+    }
+    output.push('\n');
+    output
+}
+
+/// Decompile a styles section (document or component)
+fn decompile_styles(styles: &HashMap<String, StyleRules>) -> String {
+    if styles.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("styles:\n");
+    for (class, rules) in sorted(styles) {
+        if rules.is_empty() {
+            output.push_str(&format!("  {}: {{}}\n", yaml_scalar(class)));
+            continue;
+        }
+        output.push_str(&format!("  {}:\n", yaml_scalar(class)));
+        for (name, value) in sorted(rules) {
+            output.push_str(&format!("    {}: {}\n", yaml_scalar(name), yaml_string(value)));
+        }
+    }
+    output.push('\n');
     output
 }
 
@@ -91,19 +225,23 @@ fn decompile_filter(filter: &Filter, indent: usize) -> String {
     }
 
     if let Some(authors) = &filter.authors {
-        output.push_str(&format!("{}authors: {:?}\n", indent_str, authors));
+        output.push_str(&format!("{}authors: {}\n", indent_str, yaml_list(authors)));
     }
 
     if let Some(ids) = &filter.ids {
-        output.push_str(&format!("{}ids: {:?}\n", indent_str, ids));
+        output.push_str(&format!("{}ids: {}\n", indent_str, yaml_list(ids)));
     }
 
     if let Some(e_tags) = &filter.e_tags {
-        output.push_str(&format!("{}\"#e\": {:?}\n", indent_str, e_tags));
+        output.push_str(&format!("{}\"#e\": {}\n", indent_str, yaml_list(e_tags)));
     }
 
     if let Some(p_tags) = &filter.p_tags {
-        output.push_str(&format!("{}\"#p\": {:?}\n", indent_str, p_tags));
+        output.push_str(&format!("{}\"#p\": {}\n", indent_str, yaml_list(p_tags)));
+    }
+
+    for (tag, values) in sorted(&filter.custom_tags) {
+        output.push_str(&format!("{}{}: {}\n", indent_str, yaml_string(tag), yaml_list(values)));
     }
 
     if let Some(since) = filter.since {
@@ -124,7 +262,13 @@ fn decompile_filter(filter: &Filter, indent: usize) -> String {
 /// Decompile a pipe to YAML
 fn decompile_pipe(pipe: &Pipe, indent: usize) -> String {
     let indent_str = " ".repeat(indent);
-    format!("{}from: {}\n{}jq: \"{}\"\n", indent_str, pipe.from, indent_str, pipe.jq)
+    format!(
+        "{}from: {}\n{}jq: {}\n",
+        indent_str,
+        yaml_scalar(&pipe.from),
+        indent_str,
+        yaml_string(&pipe.jq)
+    )
 }
 
 /// Decompile an action to YAML
@@ -133,12 +277,12 @@ fn decompile_action(action: &Action, indent: usize) -> String {
     let indent_str = " ".repeat(indent);
 
     output.push_str(&format!("{}kind: {}\n", indent_str, action.kind));
-    output.push_str(&format!("{}content: \"{}\"\n", indent_str, action.content));
+    output.push_str(&format!("{}content: {}\n", indent_str, yaml_string(&action.content)));
 
     if !action.tags.is_empty() {
         output.push_str(&format!("{}tags:\n", indent_str));
         for tag in &action.tags {
-            output.push_str(&format!("{}  - {:?}\n", indent_str, tag));
+            output.push_str(&format!("{}  - {}\n", indent_str, yaml_list(tag)));
         }
     }
 
@@ -151,7 +295,7 @@ fn decompile_json_value(value: &serde_json::Value) -> String {
         serde_json::Value::Null => "null".to_string(),
         serde_json::Value::Bool(b) => b.to_string(),
         serde_json::Value::Number(n) => n.to_string(),
-        serde_json::Value::String(s) => format!("\"{}\"", s),
+        serde_json::Value::String(s) => yaml_string(s),
         serde_json::Value::Array(arr) => {
             let items: Vec<String> = arr.iter().map(decompile_json_value).collect();
             format!("[{}]", items.join(", "))
@@ -160,21 +304,291 @@ fn decompile_json_value(value: &serde_json::Value) -> String {
     }
 }
 
-/// Decompile a node to markdown
-fn decompile_node(node: &Node, indent: usize) -> String {
+/// Double-quoted YAML string (JSON string syntax is valid YAML)
+fn yaml_string(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+/// Flow sequence of strings
+fn yaml_list(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|s| yaml_string(s)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Plain YAML scalar when it reads back as the same string, quoted otherwise
+fn yaml_scalar(s: &str) -> String {
+    let plain = s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n"
+        );
+
+    if plain { s.to_string() } else { yaml_string(s) }
+}
+
+/// Whether a node is phrasing content (lives inside a paragraph or heading)
+fn is_inline(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Text { .. }
+            | Node::Strong { .. }
+            | Node::Emphasis { .. }
+            | Node::Strikethrough { .. }
+            | Node::Link { .. }
+            | Node::InlineCode { .. }
+            | Node::FootnoteReference { .. }
+            | Node::Image { .. }
+            | Node::Expr { .. }
+    )
+}
+
+/// Decompile a sequence of block nodes, each followed by a blank line
+fn decompile_blocks(nodes: &[Node]) -> String {
+    nodes.iter().map(decompile_block).collect()
+}
+
+/// Decompile a node at block level
+fn decompile_block(node: &Node) -> String {
+    if is_inline(node) {
+        // A lone image or expression on its own line parses back to the same node
+        format!("{}\n\n", decompile_inline(node))
+    } else {
+        decompile_node(node)
+    }
+}
+
+/// Decompile phrasing content
+fn decompile_inlines(nodes: &[Node]) -> String {
+    let pieces: Vec<String> = nodes.iter().map(decompile_inline).collect();
+    let mut output = String::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let before = output.chars().last();
+        let after = pieces.get(i + 1).and_then(|piece| piece.chars().next());
+        match bare_autolink(node, before, after) {
+            Some(literal) => output.push_str(literal),
+            None => output.push_str(&pieces[i]),
+        }
+    }
+    output
+}
+
+/// The bare URL or email to write for a link that GFM autolinking turns back into the same link
+fn bare_autolink(node: &Node, before: Option<char>, after: Option<char>) -> Option<&str> {
+    let Node::Link { url, children } = node else { return None };
+    let [Node::Text { value }] = children.as_slice() else { return None };
+
+    let literal = if *value == *url {
+        let host = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"));
+        host.is_some_and(|host| host.split('/').next().is_some_and(|domain| domain.contains('.')))
+    } else if value.starts_with("www.") {
+        *url == format!("http://{}", value)
+    } else {
+        value.contains('@') && *url == format!("mailto:{}", value)
+    };
+
+    // Autolinks need whitespace around them and stop before trailing punctuation
+    let separated = before.is_none_or(char::is_whitespace) && after.is_none_or(char::is_whitespace);
+    let plain = value.chars().all(|c| c.is_ascii_alphanumeric() || "-.~:/?#@!$&'*+,;=%".contains(c));
+    let clean_end = value.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '/');
+
+    (literal && separated && plain && clean_end).then_some(value.as_str())
+}
+
+/// Decompile a node inside a paragraph or heading
+fn decompile_inline(node: &Node) -> String {
     match node {
-        Node::Heading { level, children } => {
-            format!("{} {}\n\n", "#".repeat(*level as usize), decompile_children(children, indent))
+        Node::Text { value } => escape_text(value),
+        Node::Strong { children } => format!("**{}**", decompile_inlines(children)),
+        Node::Emphasis { children } => format!("*{}*", decompile_inlines(children)),
+        Node::Strikethrough { children } => format!("~~{}~~", decompile_inlines(children)),
+        Node::FootnoteReference { label } => format!("[^{}]", label),
+        Node::Link { url, children } => format!("[{}]({})", decompile_inlines(children), link_destination(url)),
+        Node::InlineCode { value } => code_span(value),
+        Node::Image { src, alt, width: None, height: None, fit: None, .. } => {
+            format!("![{}]({})", escape_text(alt), link_destination(src))
         }
-        Node::Paragraph { children } => {
-            format!("{}\n\n", decompile_children(children, indent))
+        Node::Image { src, alt, width, height, fit, .. } => {
+            let mut attrs = attr_literal("src", src);
+            if !alt.is_empty() { attrs.push_str(&attr_literal("alt", alt)); }
+            if let Some(w) = width { attrs.push_str(&format!(" width=\"{}\"", w)); }
+            if let Some(h) = height { attrs.push_str(&format!(" height=\"{}\"", h)); }
+            if let Some(f) = fit { attrs.push_str(&attr_literal("fit", f)); }
+            format!("<img{} />", attrs)
         }
-        Node::Text { value } => value.clone(),
-        Node::Strong { children } => {
-            format!("**{}**", decompile_children(children, indent))
+        Node::Expr { expression, .. } => format!("{{{}}}", expression),
+        Node::TextBlock { children, style } if children.iter().all(is_inline) => {
+            format!("<text{}>{}</text>", decompile_style_attrs(style), decompile_inlines(children))
         }
-        Node::Emphasis { children } => {
-            format!("*{}*", decompile_children(children, indent))
+        Node::CustomComponent { name, props, children, .. } if children.iter().all(is_inline) => {
+            if children.is_empty() {
+                format!("<{}{} />", name, decompile_props_attrs(props))
+            } else {
+                format!("<{}{}>{}</{}>", name, decompile_props_attrs(props), decompile_inlines(children), name)
+            }
+        }
+        block => decompile_node(block).trim_end().to_string(),
+    }
+}
+
+/// Escape markdown and MDX syntax characters in text
+fn escape_text(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '~' | '[' | ']' | '{' | '}' | '<' | '>' | '&' | '|') {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Code span with a backtick fence longer than any backtick run inside
+fn code_span(value: &str) -> String {
+    let fence = "`".repeat(longest_run(value, '`') + 1);
+    let padded = value.starts_with('`')
+        || value.ends_with('`')
+        || (value.starts_with(' ') && value.ends_with(' ') && !value.trim().is_empty());
+    if padded {
+        format!("{} {} {}", fence, value, fence)
+    } else {
+        format!("{}{}{}", fence, value, fence)
+    }
+}
+
+/// Length of the longest run of `c` in `text`
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|ch| ch != c).map(str::len).max().unwrap_or(0)
+}
+
+/// Link destination, wrapped in angle brackets when it has spaces or parens
+fn link_destination(url: &str) -> String {
+    if url.is_empty() || url.contains([' ', '(', ')']) {
+        format!("<{}>", url)
+    } else {
+        url.to_string()
+    }
+}
+
+/// JSX attribute with a literal string value
+/// A value with both kinds of quote is written as a string expression, which parses back as a literal
+fn attr_literal(name: &str, value: &str) -> String {
+    if !value.contains('"') {
+        format!(" {}=\"{}\"", name, value)
+    } else if !value.contains('\'') {
+        format!(" {}='{}'", name, value)
+    } else {
+        // Braces are escaped too, since MDX finds the end of an expression by counting them
+        let quoted = serde_json::to_string(value).expect("strings serialize");
+        format!(" {}={{{}}}", name, quoted.replace('{', "\\u007b").replace('}', "\\u007d"))
+    }
+}
+
+/// JSX attributes for a custom component's props, sorted by name
+fn decompile_props_attrs(props: &HashMap<String, PropValue>) -> String {
+    sorted(props)
+        .into_iter()
+        .map(|(key, value)| match value {
+            PropValue::Literal(s) => attr_literal(key, s),
+            PropValue::Expression(expr) => format!(" {}={{{}}}", key, expr),
+        })
+        .collect()
+}
+
+/// Decompile a JSX element with its block children
+/// Children are not indented: nested elements would push markdown text to 4+ spaces, which is an indented code block
+fn decompile_element(tag: &str, attrs: &str, children: &[Node]) -> String {
+    format!("<{}{}>\n{}</{}>\n\n", tag, attrs, decompile_blocks(children), tag)
+}
+
+/// Decompile a table as GFM pipe rows
+fn decompile_table(align: &[ColumnAlign], rows: &[TableRow]) -> String {
+    let row_line = |row: &TableRow| {
+        let cells: Vec<String> = row.cells.iter().map(|cell| decompile_inlines(&cell.children)).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut output = String::new();
+    if let Some((header, body)) = rows.split_first() {
+        output.push_str(&row_line(header));
+        let delimiters: Vec<&str> = align
+            .iter()
+            .map(|a| match a {
+                ColumnAlign::None => "---",
+                ColumnAlign::Left => ":---",
+                ColumnAlign::Center => ":---:",
+                ColumnAlign::Right => "---:",
+            })
+            .collect();
+        output.push_str(&format!("| {} |\n", delimiters.join(" | ")));
+        for row in body {
+            output.push_str(&row_line(row));
+        }
+    }
+    output.push('\n');
+    output
+}
+
+/// Decompile stack layout attributes
+fn decompile_layout_attrs(layout: &StackLayout) -> String {
+    let numbers = [
+        ("width", layout.width),
+        ("height", layout.height),
+        ("min_width", layout.min_width),
+        ("max_width", layout.max_width),
+        ("min_height", layout.min_height),
+        ("max_height", layout.max_height),
+        ("flex", layout.flex),
+    ];
+
+    let mut attrs = String::new();
+    for (name, value) in numbers {
+        if let Some(v) = value { attrs.push_str(&format!(" {}=\"{}\"", name, v)); }
+    }
+    if let Some(a) = &layout.align { attrs.push_str(&attr_literal("align", a)); }
+    if let Some(j) = &layout.justify { attrs.push_str(&attr_literal("justify", j)); }
+    if let Some(g) = layout.gap { attrs.push_str(&format!(" gap=\"{}\"", g)); }
+    if let Some(p) = layout.padding { attrs.push_str(&format!(" padding=\"{}\"", p)); }
+    if let Some(s) = layout.span { attrs.push_str(&format!(" span=\"{}\"", s)); }
+    if let Some(s) = layout.row_span { attrs.push_str(&format!(" row_span=\"{}\"", s)); }
+    attrs.push_str(&decompile_style_attrs(&layout.style));
+    attrs
+}
+
+/// `class`, literal properties as one `style="..."`, and expression properties as their own attributes
+fn decompile_style_attrs(style: &NodeStyle) -> String {
+    let mut attrs = String::new();
+    if let Some(class) = &style.class {
+        attrs.push_str(&attr_literal("class", class));
+    }
+
+    let properties = sorted(&style.properties);
+    let literals: Vec<String> = properties
+        .iter()
+        .filter_map(|(name, value)| match value {
+            PropValue::Literal(v) => Some(format!("{}: {}", name, v)),
+            PropValue::Expression(_) => None,
+        })
+        .collect();
+    if !literals.is_empty() {
+        attrs.push_str(&attr_literal("style", &literals.join("; ")));
+    }
+    for (name, value) in properties {
+        if let PropValue::Expression(expr) = value {
+            attrs.push_str(&format!(" {}={{{}}}", name, expr));
+        }
+    }
+    attrs
+}
+
+/// Decompile a block node to markdown
+fn decompile_node(node: &Node) -> String {
+    match node {
+        Node::Heading { level, children } => {
+            format!("{} {}\n\n", "#".repeat(*level as usize), decompile_inlines(children))
+        }
+        Node::Paragraph { children } => {
+            format!("{}\n\n", decompile_inlines(children))
         }
         Node::List { ordered, items } => {
             let mut output = String::new();
@@ -185,107 +599,94 @@ fn decompile_node(node: &Node, indent: usize) -> String {
                     "- ".to_string()
                 };
                 output.push_str(&marker);
-                output.push_str(&decompile_list_item(item, indent));
+                output.push_str(&decompile_list_item(item, marker.len()));
                 output.push('\n');
             }
             output.push('\n');
             output
         }
-        Node::Link { url, children } => {
-            format!("[{}]({})", decompile_children(children, indent), url)
+        Node::CodeBlock { lang, value } => {
+            let fence = "`".repeat(longest_run(value, '`').max(2) + 1);
+            let info = lang.as_deref().unwrap_or_default();
+            if value.is_empty() {
+                format!("{}{}\n{}\n\n", fence, info, fence)
+            } else {
+                format!("{}{}\n{}\n{}\n\n", fence, info, value, fence)
+            }
         }
-        Node::Image { src, alt } => {
-            format!("![{}]({})", alt, src)
+        Node::Blockquote { children } => {
+            let content = decompile_blocks(children);
+            let quoted: Vec<String> = content
+                .trim_end()
+                .lines()
+                .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                .collect();
+            if quoted.is_empty() { ">\n\n".to_string() } else { format!("{}\n\n", quoted.join("\n")) }
         }
-        Node::Expr { expression } => {
-            format!("{{{}}}", expression)
+        Node::Table { align, rows } => decompile_table(align, rows),
+        // `---` could be read back as a frontmatter delimiter or a setext underline
+        Node::ThematicBreak => "***\n\n".to_string(),
+        Node::FootnoteDefinition { label, children } => {
+            let marker = format!("[^{}]: ", label);
+            format!("{}{}\n\n", marker, indent_continuation(&decompile_blocks(children), 4))
         }
-        Node::Each { from, as_name, children } => {
-            let mut output = String::new();
-            output.push_str(&format!("<each from={{{}}} as=\"{}\">\n", from, as_name));
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
-            }
-            output.push_str("</each>\n\n");
-            output
+        Node::Each { from, as_name, key, viewport, on_end, children, .. } => {
+            let mut attrs = format!(" from={{{}}}{}", from, attr_literal("as", as_name));
+            if let Some(key) = key { attrs.push_str(&format!(" key={{{}}}", key)); }
+            if let Some(height) = viewport { attrs.push_str(&format!(" virtual height=\"{}\"", height)); }
+            if let Some(action) = on_end { attrs.push_str(&format!(" on_end={{{}}}", action)); }
+            decompile_element("each", &attrs, children)
         }
-        Node::If { value, children, else_children } => {
-            let mut output = String::new();
-            output.push_str(&format!("<if value={{{}}}>\n", value));
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
-            }
+        Node::If { value, children, else_children, .. } => {
+            let mut body = decompile_blocks(children);
             if let Some(else_children) = else_children {
-                output.push_str("<else>\n");
-                for child in else_children {
-                    output.push_str(&decompile_node(child, indent + 2));
-                }
+                body.push_str(&decompile_element("else", "", else_children));
             }
-            output.push_str("</if>\n\n");
-            output
+            format!("<if value={{{}}}>\n{}</if>\n\n", value, body)
         }
-        Node::Button { on_click, children } => {
-            let mut output = String::new();
-            if let Some(action) = on_click {
-                output.push_str(&format!("<button on_click={{{}}}>\n", action));
+        Node::Button { on_click, children, .. } => {
+            let attrs = on_click
+                .as_ref()
+                .map(|action| format!(" on_click={{{}}}", action))
+                .unwrap_or_default();
+            if children.iter().all(is_inline) {
+                // A one-line label parses back to the same inline children
+                decompile_element("button", &attrs, &[Node::paragraph(children.clone())])
             } else {
-                output.push_str("<button>\n");
-            }
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
+                decompile_element("button", &attrs, children)
             }
-            output.push_str("</button>\n\n");
-            output
         }
-        Node::Input { name, placeholder } => {
+        Node::Input { name, placeholder, .. } => {
+            let mut attrs = attr_literal("name", name);
             if let Some(ph) = placeholder {
-                format!("<input name=\"{}\" placeholder=\"{}\" />\n\n", name, ph)
-            } else {
-                format!("<input name=\"{}\" />\n\n", name)
+                attrs.push_str(&attr_literal("placeholder", ph));
             }
+            format!("<input{} />\n\n", attrs)
         }
-        Node::VStack { children, width, height, flex, align } => {
-            let mut output = String::new();
-            output.push_str("<vstack");
-            if let Some(w) = width { output.push_str(&format!(" width=\"{}\"", w)); }
-            if let Some(h) = height { output.push_str(&format!(" height=\"{}\"", h)); }
-            if let Some(f) = flex { output.push_str(&format!(" flex=\"{}\"", f)); }
-            if let Some(a) = align { output.push_str(&format!(" align=\"{}\"", a)); }
-            output.push_str(">\n");
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
+        Node::VStack { children, layout } => decompile_element("vstack", &decompile_layout_attrs(layout), children),
+        Node::HStack { children, layout } => decompile_element("hstack", &decompile_layout_attrs(layout), children),
+        Node::TextBlock { children, style } => {
+            if children.iter().all(is_inline) {
+                // A one-line body parses back to the same inline children
+                decompile_element("text", &decompile_style_attrs(style), &[Node::paragraph(children.clone())])
+            } else {
+                decompile_element("text", &decompile_style_attrs(style), children)
             }
-            output.push_str("</vstack>\n\n");
-            output
         }
-        Node::HStack { children, width, height, flex, align } => {
-            let mut output = String::new();
-            output.push_str("<hstack");
-            if let Some(w) = width { output.push_str(&format!(" width=\"{}\"", w)); }
-            if let Some(h) = height { output.push_str(&format!(" height=\"{}\"", h)); }
-            if let Some(f) = flex { output.push_str(&format!(" flex=\"{}\"", f)); }
-            if let Some(a) = align { output.push_str(&format!(" align=\"{}\"", a)); }
-            output.push_str(">\n");
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
+        Node::Grid { columns, min_column_width, gap, row_gap, column_gap, children } => {
+            let mut attrs = columns.map(|cols| format!(" columns={{{}}}", cols)).unwrap_or_default();
+            let numbers = [
+                ("min_column_width", min_column_width),
+                ("gap", gap),
+                ("row_gap", row_gap),
+                ("column_gap", column_gap),
+            ];
+            for (name, value) in numbers {
+                if let Some(v) = value { attrs.push_str(&format!(" {}=\"{}\"", name, v)); }
             }
-            output.push_str("</hstack>\n\n");
-            output
+            decompile_element("grid", &attrs, children)
         }
-        Node::Grid { columns, children } => {
-            let mut output = String::new();
-            if let Some(cols) = columns {
-                output.push_str(&format!("<grid columns={{{}}}>\n", cols));
-            } else {
-                output.push_str("<grid>\n");
-            }
-            for child in children {
-                output.push_str(&decompile_node(child, indent + 2));
-            }
-            output.push_str("</grid>\n\n");
-            output
-        }
-        Node::Json { value } => {
+        Node::Json { value, .. } => {
             format!("<json value={{{}}} />\n\n", value)
         }
         Node::Spacer { size } => {
@@ -295,61 +696,51 @@ fn decompile_node(node: &Node, indent: usize) -> String {
                 "<spacer />\n\n".to_string()
             }
         }
-        Node::CustomComponent { name, props, children } => {
-            let mut output = String::new();
-            output.push('<');
-            output.push_str(name);
-
-            // Render props
-            for (key, value) in props {
-                output.push(' ');
-                output.push_str(key);
-                output.push('=');
-                match value {
-                    PropValue::Literal(s) => {
-                        output.push('"');
-                        output.push_str(s);
-                        output.push('"');
-                    }
-                    PropValue::Expression(expr) => {
-                        output.push('{');
-                        output.push_str(expr);
-                        output.push('}');
-                    }
-                }
-            }
-
+        Node::CustomComponent { name, props, children, .. } => {
+            let attrs = decompile_props_attrs(props);
             if children.is_empty() {
-                output.push_str(" />\n\n");
+                format!("<{}{} />\n\n", name, attrs)
             } else {
-                output.push_str(">\n");
-                for child in children {
-                    output.push_str(&decompile_node(child, indent + 2));
-                }
-                output.push_str("</");
-                output.push_str(name);
-                output.push_str(">\n\n");
+                decompile_element(name, &attrs, children)
             }
-
-            output
         }
+        inline => decompile_block(inline),
     }
 }
 
-/// Decompile children nodes
-fn decompile_children(children: &[Node], indent: usize) -> String {
-    children
-        .iter()
-        .map(|child| decompile_node(child, indent))
-        .collect::<Vec<_>>()
-        .join("")
-        .trim_end()
-        .to_string()
+/// Decompile list item, indenting continuation lines under the marker
+fn decompile_list_item(item: &ListItem, marker_width: usize) -> String {
+    let mut prefix = match item.checked {
+        Some(true) => "[x] ".to_string(),
+        Some(false) => "[ ] ".to_string(),
+        None => String::new(),
+    };
+    if let Some(bind) = &item.bind {
+        prefix.push_str(&format!("{{{}}}", bind));
+        if !item.children.is_empty() {
+            prefix.push(' ');
+        }
+    }
+    let content = indent_continuation(&decompile_blocks(&item.children), marker_width);
+    format!("{}{}", prefix, content).trim_end().to_string()
 }
 
-/// Decompile list item
-fn decompile_list_item(item: &ListItem, indent: usize) -> String {
-    decompile_children(&item.children, indent)
+/// Indent every line after the first by `width` spaces, leaving blank lines empty
+fn indent_continuation(content: &str, width: usize) -> String {
+    let indent = " ".repeat(width);
+    content
+        .trim_end()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
@@ -357,6 +748,11 @@ mod tests {
     use super::*;
     use crate::parser::ast::*;
 
+    /// Documents compared as JSON, which leaves out spans: parsed nodes have them, hand-built ones don't
+    fn assert_same(parsed: &Document, doc: &Document) {
+        assert_eq!(serde_json::to_value(parsed).unwrap(), serde_json::to_value(doc).unwrap());
+    }
+
     #[test]
     fn test_decompile_empty_document() {
         let doc = Document::new(Frontmatter::new(), vec![]);
@@ -455,12 +851,8 @@ mod tests {
             vec![Node::List {
                 ordered: false,
                 items: vec![
-                    ListItem {
-                        children: vec![Node::text("Item 1")],
-                    },
-                    ListItem {
-                        children: vec![Node::text("Item 2")],
-                    },
+                    ListItem::new(vec![Node::text("Item 1")]),
+                    ListItem::new(vec![Node::text("Item 2")]),
                 ],
             }],
         );
@@ -501,4 +893,571 @@ mod tests {
         // Verify body
         assert_eq!(body.len(), 2);
     }
+
+    #[test]
+    fn test_decompile_layout_and_props() {
+        let mut props = HashMap::new();
+        props.insert("pubkey".to_string(), PropValue::Expression("note.pubkey".to_string()));
+        props.insert("label".to_string(), PropValue::Literal("Author".to_string()));
+        let doc = Document::new(
+            Frontmatter::new(),
+            vec![Node::VStack {
+                children: vec![Node::CustomComponent {
+                    name: "Profile".to_string(),
+                    props,
+                    children: vec![],
+                    span: None,
+                }],
+                layout: StackLayout {
+                    width: Some(200.0),
+                    flex: Some(1.0),
+                    align: Some("center".to_string()),
+                    ..StackLayout::default()
+                },
+            }],
+        );
+        let output = decompile(&doc);
+        assert!(output.contains("<vstack width=\"200\" flex=\"1\" align=\"center\">"));
+        assert!(output.contains("<Profile label=\"Author\" pubkey={note.pubkey} />"));
+        assert_same(&crate::loader::parse_hnmd(&output).unwrap(), &doc);
+    }
+
+    #[test]
+    fn test_attr_literal_quotes() {
+        assert_eq!(attr_literal("label", "say \"hi\""), " label='say \"hi\"'");
+        assert_eq!(attr_literal("label", "it's \"x\" {y}"), " label={\"it's \\\"x\\\" \\u007by\\u007d\"}");
+
+        // Every kind of literal reads back as the same literal
+        let mut props = HashMap::new();
+        for (name, value) in [("a", "plain"), ("b", "say \"hi\""), ("c", "it's"), ("d", "it's \"quoted\" {braces}")] {
+            props.insert(name.to_string(), PropValue::Literal(value.to_string()));
+        }
+        let doc = Document::new(
+            Frontmatter::new(),
+            vec![Node::CustomComponent {
+                name: "Card".to_string(),
+                props,
+                children: vec![],
+                span: None,
+            }],
+        );
+        assert_same(&crate::loader::parse_hnmd(&decompile(&doc)).unwrap(), &doc);
+    }
+
+    #[test]
+    fn test_button_label_is_inline() {
+        // The parser keeps just the inline content of a one-paragraph button, so a button
+        // built around a paragraph comes back with the paragraph's children
+        let button = |children| Node::button(Some("actions.post".to_string()), children);
+        let label = vec![Node::text("Send "), Node::strong(vec![Node::text("now")])];
+        let doc = Document::new(Frontmatter::new(), vec![button(vec![Node::paragraph(label.clone())])]);
+        let parsed = crate::loader::parse_hnmd(&decompile(&doc)).unwrap();
+        assert_same(&parsed, &Document::new(Frontmatter::new(), vec![button(label)]));
+    }
+
+    #[test]
+    fn test_decompile_code_quote_and_table() {
+        let cell = |text: &str| TableCell { children: vec![Node::text(text)] };
+        let doc = Document::new(
+            Frontmatter::new(),
+            vec![
+                Node::paragraph(vec![Node::text("Use "), Node::inline_code("a`b")]),
+                Node::code_block(Some("rust".to_string()), "let x = 1;\n\nlet y = x;"),
+                Node::Blockquote { children: vec![Node::paragraph(vec![Node::text("Quote")])] },
+                Node::Table {
+                    align: vec![ColumnAlign::None, ColumnAlign::Center],
+                    rows: vec![
+                        TableRow { cells: vec![cell("a"), cell("b")] },
+                        TableRow { cells: vec![cell("1"), cell("x|y")] },
+                    ],
+                },
+            ],
+        );
+        let output = decompile(&doc);
+        assert_eq!(
+            output,
+            "Use ``a`b``\n\n```rust\nlet x = 1;\n\nlet y = x;\n```\n\n> Quote\n\n| a | b |\n| --- | :---: |\n| 1 | x\\|y |\n\n"
+        );
+        assert_same(&crate::loader::parse_hnmd(&output).unwrap(), &doc);
+    }
+
+    #[test]
+    fn test_decompile_gfm_extensions() {
+        let link = |url: &str, text: &str| Node::Link { url: url.to_string(), children: vec![Node::text(text)] };
+        let doc = Document::new(
+            Frontmatter::new(),
+            vec![
+                Node::paragraph(vec![
+                    Node::strikethrough(vec![Node::text("old")]),
+                    Node::text(" see "),
+                    link("https://example.com/a", "https://example.com/a"),
+                    Node::text(" or "),
+                    link("http://www.example.org", "www.example.org"),
+                    Node::text(" ~x"),
+                    Node::FootnoteReference { label: "note".to_string() },
+                ]),
+                Node::List {
+                    ordered: false,
+                    items: vec![
+                        ListItem::task(true, vec![Node::paragraph(vec![Node::text("Done")])]),
+                        ListItem::task(false, vec![Node::paragraph(vec![Node::text("Milk")])]).with_bind("state.milk"),
+                    ],
+                },
+                Node::FootnoteDefinition {
+                    label: "note".to_string(),
+                    children: vec![
+                        Node::paragraph(vec![Node::text("First")]),
+                        Node::paragraph(vec![Node::text("Second")]),
+                    ],
+                },
+            ],
+        );
+        let output = decompile(&doc);
+        assert_eq!(
+            output,
+            "~~old~~ see https://example.com/a or www.example.org \\~x[^note]\n\n- [x] Done\n- [ ] {state.milk} Milk\n\n[^note]: First\n\n    Second\n\n"
+        );
+        assert_same(&crate::loader::parse_hnmd(&output).unwrap(), &doc);
+    }
+
+    #[test]
+    fn test_roundtrip_apps() {
+        for entry in std::fs::read_dir("apps").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("hnmd") {
+                continue;
+            }
+            let doc = crate::loader::parse_hnmd(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let reparsed = crate::loader::parse_hnmd(&decompile(&doc)).unwrap();
+            assert_eq!(reparsed, doc, "{} did not roundtrip", path.display());
+        }
+
+        for entry in std::fs::read_dir("apps/components").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            let def = crate::parser::component_def::parse_component(&source).unwrap();
+            let reparsed = crate::parser::component_def::parse_component(&decompile_component(&def)).unwrap();
+            assert_eq!(reparsed, def, "{} did not roundtrip", path.display());
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_bundled_components() {
+        let mut doc = crate::loader::parse_hnmd(&std::fs::read_to_string("apps/feed.hnmd").unwrap()).unwrap();
+        let source = std::fs::read_to_string("apps/components/Profile.hnmc").unwrap();
+        let profile = crate::parser::component_def::parse_component(&source).unwrap();
+        doc.components.insert("Profile".to_string(), profile);
+
+        let output = decompile(&doc);
+        assert!(output.contains("imports:"));
+        assert!(output.contains("components:"));
+        assert_same(&crate::loader::parse_hnmd(&output).unwrap(), &doc);
+    }
+
+    /// Strategies for ASTs in the shape the parser produces
+    mod generated {
+        use super::*;
+        use proptest::collection::{hash_map, vec};
+        use proptest::option;
+        use proptest::prelude::*;
+
+        fn word() -> impl Strategy<Value = String> {
+            "[a-zA-Z][a-zA-Z0-9]{0,7}"
+        }
+
+        fn path() -> impl Strategy<Value = String> {
+            "[a-z]{1,6}(\\.[a-z]{1,6}){0,2}"
+        }
+
+        fn size() -> impl Strategy<Value = f64> {
+            (1u32..400).prop_map(|n| n as f64 / 2.0)
+        }
+
+        /// Join atoms with spaces, merging neighbouring text like the parser does
+        fn run(atoms: Vec<Node>) -> Vec<Node> {
+            let mut nodes: Vec<Node> = Vec::new();
+            for (i, atom) in atoms.into_iter().enumerate() {
+                let pieces = if i == 0 { vec![atom] } else { vec![Node::text(" "), atom] };
+                for piece in pieces {
+                    match (nodes.last_mut(), piece) {
+                        (Some(Node::Text { value }), Node::Text { value: next }) => value.push_str(&next),
+                        (_, piece) => nodes.push(piece),
+                    }
+                }
+            }
+            nodes
+        }
+
+        /// Words and expressions, starting and ending with a word
+        fn text_run() -> impl Strategy<Value = Vec<Node>> {
+            (word(), vec((path(), word()), 0..2)).prop_map(|(first, rest)| {
+                let mut atoms = vec![Node::text(first)];
+                for (expression, word) in rest {
+                    atoms.push(Node::expr(expression));
+                    atoms.push(Node::text(word));
+                }
+                run(atoms)
+            })
+        }
+
+        /// Phrasing content that starts with a word, so it never reads as a block
+        fn inlines() -> impl Strategy<Value = Vec<Node>> {
+            let atom = prop_oneof![
+                word().prop_map(Node::text),
+                path().prop_map(Node::expr),
+                text_run().prop_map(Node::strong),
+                text_run().prop_map(Node::emphasis),
+                text_run().prop_map(Node::strikethrough),
+                word().prop_map(|page| {
+                    let url = format!("https://example.com/{}", page);
+                    Node::Link { url: url.clone(), children: vec![Node::text(url)] }
+                }),
+                (word(), word()).prop_map(|(text, page)| Node::Link {
+                    url: format!("https://example.com/{}", page),
+                    children: vec![Node::text(text)],
+                }),
+                (word(), word()).prop_map(|(alt, name)| Node::image(format!("images/{}.png", name), alt)),
+                "[a-z{}<*][a-z {}<*]{0,6}".prop_map(Node::inline_code),
+            ];
+            (word(), vec(atom, 0..4)).prop_map(|(first, rest)| {
+                let mut atoms = vec![Node::text(first)];
+                atoms.extend(rest);
+                run(atoms)
+            })
+        }
+
+        fn list_item() -> impl Strategy<Value = ListItem> {
+            (option::of((any::<bool>(), option::of(path()))), inlines()).prop_map(|(task, children)| {
+                let children = vec![Node::paragraph(children)];
+                match task {
+                    Some((checked, Some(bind))) => ListItem::task(checked, children).with_bind(format!("state.{}", bind)),
+                    Some((checked, None)) => ListItem::task(checked, children),
+                    None => ListItem::new(children),
+                }
+            })
+        }
+
+        fn list() -> impl Strategy<Value = Node> {
+            (any::<bool>(), vec(list_item(), 1..4)).prop_map(|(ordered, items)| Node::List { ordered, items })
+        }
+
+        fn code_block() -> impl Strategy<Value = Node> {
+            (option::of("[a-z]{1,6}"), vec("[a-z {}<>*#]{0,10}", 0..3))
+                .prop_map(|(lang, lines)| Node::code_block(lang, lines.join("\n")))
+        }
+
+        fn blockquote() -> impl Strategy<Value = Node> {
+            let quoted = prop_oneof![
+                (1u8..=6, inlines()).prop_map(|(level, children)| Node::heading(level, children)),
+                inlines().prop_map(Node::paragraph),
+                list(),
+                code_block(),
+            ];
+            blocks(quoted, 1..3).prop_map(|children| Node::Blockquote { children })
+        }
+
+        fn table() -> impl Strategy<Value = Node> {
+            let align = prop_oneof![
+                Just(ColumnAlign::None),
+                Just(ColumnAlign::Left),
+                Just(ColumnAlign::Center),
+                Just(ColumnAlign::Right),
+            ];
+            (1usize..4)
+                .prop_flat_map(move |columns| (vec(align.clone(), columns), vec(vec(inlines(), columns), 1..4)))
+                .prop_map(|(align, rows)| Node::Table {
+                    align,
+                    rows: rows
+                        .into_iter()
+                        .map(|cells| TableRow {
+                            cells: cells.into_iter().map(|children| TableCell { children }).collect(),
+                        })
+                        .collect(),
+                })
+        }
+
+        fn leaf_block() -> impl Strategy<Value = Node> {
+            prop_oneof![
+                (1u8..=6, inlines()).prop_map(|(level, children)| Node::heading(level, children)),
+                inlines().prop_map(Node::paragraph),
+                list(),
+                code_block(),
+                blockquote(),
+                table(),
+                (word(), word(), option::of((1u8..=200, prop_oneof![Just("cover"), Just("contain")])))
+                    .prop_map(|(alt, name, layout)| match layout {
+                        Some((size, fit)) => Node::Image {
+                            src: format!("{{{}.picture}}", name),
+                            alt,
+                            width: Some(size as f64),
+                            height: Some(size as f64),
+                            fit: Some(fit.to_string()),
+                            span: None,
+                        },
+                        None => Node::image(format!("images/{}.png", name), alt),
+                    }),
+                path().prop_map(Node::expr),
+                (word(), option::of(word())).prop_map(|(name, placeholder)| Node::Input {
+                    name,
+                    placeholder,
+                    span: None,
+                }),
+                path().prop_map(|value| Node::Json { value, span: None }),
+                option::of(size()).prop_map(|size| Node::Spacer { size }),
+                Just(Node::ThematicBreak),
+            ]
+        }
+
+        /// Consecutive lists would merge into one when reparsed
+        fn blocks(block: impl Strategy<Value = Node>, len: std::ops::Range<usize>) -> impl Strategy<Value = Vec<Node>> {
+            vec(block, len).prop_map(|nodes| {
+                let mut blocks: Vec<Node> = Vec::new();
+                for node in nodes {
+                    let after_list = matches!(blocks.last(), Some(Node::List { .. }));
+                    if !(after_list && matches!(node, Node::List { .. })) {
+                        blocks.push(node);
+                    }
+                }
+                blocks
+            })
+        }
+
+        fn props() -> impl Strategy<Value = HashMap<String, PropValue>> {
+            hash_map(
+                "[a-z][a-zA-Z]{0,5}",
+                prop_oneof![word().prop_map(PropValue::Literal), path().prop_map(PropValue::Expression)],
+                0..3,
+            )
+        }
+
+        fn node_style() -> impl Strategy<Value = NodeStyle> {
+            let property = prop_oneof![Just("color"), Just("background"), Just("font_size"), Just("radius")].prop_map(String::from);
+            let value = prop_oneof!["[a-z0-9#]{1,7}".prop_map(PropValue::Literal), path().prop_map(PropValue::Expression)];
+            (option::of("[a-z]{1,6}( [a-z]{1,6})?"), hash_map(property, value, 0..3))
+                .prop_map(|(class, properties)| NodeStyle { class, properties })
+        }
+
+        fn style_rules() -> impl Strategy<Value = HashMap<String, StyleRules>> {
+            hash_map(word(), hash_map("[a-z_]{1,10}", "[a-z0-9# ]{1,8}", 0..3), 0..3)
+        }
+
+        pub fn block() -> impl Strategy<Value = Node> {
+            leaf_block().prop_recursive(3, 24, 3, |inner| {
+                let children = || blocks(inner.clone(), 0..3);
+                let layout = || {
+                    let sizes = (
+                        option::of(size()),
+                        option::of(size()),
+                        option::of(size()),
+                        option::of(size()),
+                        option::of(size()),
+                        option::of(size()),
+                    );
+                    let spacing = (
+                        option::of(size()),
+                        option::of(prop_oneof![Just("start"), Just("center"), Just("end"), Just("stretch")].prop_map(String::from)),
+                        option::of(prop_oneof![Just("center"), Just("space-between")].prop_map(String::from)),
+                        option::of(size()),
+                        option::of(size()),
+                        option::of((1usize..4, 1usize..3)),
+                    );
+                    (children(), sizes, spacing, node_style()).prop_map(
+                        |(children, (width, height, min_width, max_width, min_height, max_height), (flex, align, justify, gap, padding, spans), style)| {
+                            let layout = StackLayout {
+                                width,
+                                height,
+                                min_width,
+                                max_width,
+                                min_height,
+                                max_height,
+                                flex,
+                                align,
+                                justify,
+                                gap,
+                                padding,
+                                span: spans.map(|(span, _)| span),
+                                row_span: spans.map(|(_, row_span)| row_span),
+                                style,
+                            };
+                            (children, layout)
+                        },
+                    )
+                };
+                prop_oneof![
+                    (path(), word(), children()).prop_map(|(from, as_name, children)| Node::each(from, as_name, children)),
+                    (path(), word(), option::of(path()), 1u16..2000, option::of(word()), children()).prop_map(
+                        |(from, as_name, key, height, on_end, children)| {
+                            let each = Node::virtual_each(from, as_name, key, height as f64, children);
+                            match on_end {
+                                Some(query) => each.with_on_end(format!("loadMore({})", query)),
+                                None => each,
+                            }
+                        }
+                    ),
+                    (path(), children(), option::of(children())).prop_map(|(value, children, else_children)| Node::If {
+                        value,
+                        children,
+                        else_children,
+                        span: None,
+                    }),
+                    (option::of(path()), inlines()).prop_map(|(on_click, children)| Node::button(on_click, children)),
+                    (option::of(path()), blocks(inner.clone(), 2..4))
+                        .prop_filter("a lone paragraph reads back as a label", |(_, children)| {
+                            !matches!(children.as_slice(), [] | [Node::Paragraph { .. }])
+                        })
+                        .prop_map(|(on_click, children)| Node::button(on_click, children)),
+                    layout().prop_map(|(children, layout)| Node::VStack { children, layout }),
+                    layout().prop_map(|(children, layout)| Node::HStack { children, layout }),
+                    (node_style(), inlines()).prop_map(|(style, children)| Node::text_block(children, style)),
+                    (option::of(1usize..6), option::of(size()), option::of(size()), children()).prop_map(
+                        |(columns, min_column_width, gap, children)| Node::Grid {
+                            columns,
+                            min_column_width,
+                            gap,
+                            row_gap: None,
+                            column_gap: None,
+                            children,
+                        },
+                    ),
+                    ("[A-Z][a-zA-Z]{0,6}", props(), children()).prop_map(|(name, props, children)| Node::CustomComponent {
+                        name,
+                        props,
+                        children,
+                        span: None,
+                    }),
+                ]
+            })
+        }
+
+        pub fn body() -> impl Strategy<Value = Vec<Node>> {
+            blocks(block(), 0..6)
+        }
+
+        fn filter() -> impl Strategy<Value = Filter> {
+            (
+                option::of(vec(0u64..40000, 1..3)),
+                option::of(vec("[0-9a-f]{8}", 1..3)),
+                option::of(vec(word(), 1..3)),
+                option::of(0u64..2_000_000_000),
+                option::of(1usize..500),
+            )
+                .prop_map(|(kinds, authors, topics, since, limit)| {
+                    let mut filter = Filter::new();
+                    filter.kinds = kinds;
+                    filter.authors = authors;
+                    if let Some(topics) = topics {
+                        filter.custom_tags.insert("#t".to_string(), topics);
+                    }
+                    filter.since = since;
+                    filter.limit = limit;
+                    filter
+                })
+        }
+
+        fn state_value() -> impl Strategy<Value = serde_json::Value> {
+            prop_oneof![
+                any::<i32>().prop_map(serde_json::Value::from),
+                any::<bool>().prop_map(serde_json::Value::from),
+                "[a-zA-Z0-9 ]{0,12}".prop_map(serde_json::Value::from),
+            ]
+        }
+
+        pub fn frontmatter() -> impl Strategy<Value = Frontmatter> {
+            (
+                hash_map(word(), filter(), 0..3),
+                hash_map(word(), (word(), "\\.[a-z]{1,6}( \\| length)?"), 0..3),
+                hash_map(word(), (1u64..40000, "[a-zA-Z {}.\"]{0,16}", vec(vec(word(), 1..3), 0..2)), 0..3),
+                hash_map(word(), state_value(), 0..3),
+                hash_map("(/[a-z]{1,6}){1,2}(/:[a-z]{1,6})?", "[a-z]{1,6}\\.hnmd", 0..3),
+                (hash_map(word(), "#[0-9a-f]{6}", 0..3), hash_map(word(), size(), 0..3), hash_map(word(), size(), 0..3)),
+                style_rules(),
+            )
+                .prop_map(|(filters, pipes, actions, state, routes, (palette, font_sizes, spacing), styles)| Frontmatter {
+                    filters,
+                    pipes: pipes.into_iter().map(|(id, (from, jq))| (id, Pipe::new(from, jq))).collect(),
+                    actions: actions
+                        .into_iter()
+                        .map(|(id, (kind, content, tags))| {
+                            let mut action = Action::new(kind, content);
+                            action.tags = tags;
+                            (id, action)
+                        })
+                        .collect(),
+                    state,
+                    routes,
+                    theme: Theme { palette, font_sizes, spacing },
+                    styles,
+                })
+        }
+
+        pub fn component() -> impl Strategy<Value = ComponentDef> {
+            (
+                hash_map("[A-Z][a-z]{0,6}", "\\./[a-z]{1,6}\\.hnmc", 0..2),
+                hash_map(word(), filter(), 0..2),
+                hash_map(word(), (prop_oneof![Just("string"), Just("number")], any::<bool>(), option::of(0i64..100)), 0..3),
+                style_rules(),
+                blocks(leaf_block(), 0..3),
+            )
+                .prop_map(|(imports, queries, props, styles, body)| ComponentDef {
+                    imports,
+                    queries,
+                    props: props
+                        .into_iter()
+                        .map(|(name, (type_name, required, default))| {
+                            let schema = PropSchema {
+                                type_name: type_name.to_string(),
+                                required,
+                                default: default.map(serde_json::Value::from),
+                            };
+                            (name, schema)
+                        })
+                        .collect(),
+                    styles,
+                    body,
+                })
+        }
+
+        pub fn document() -> impl Strategy<Value = Document> {
+            (
+                hash_map("[A-Z][a-z]{0,6}", "\\./components/[a-z]{1,6}\\.hnmc", 0..3),
+                hash_map("[A-Z][a-z]{0,6}", component(), 0..2),
+                frontmatter(),
+                body(),
+            )
+                .prop_map(|(imports, components, frontmatter, body)| {
+                    let mut doc = Document::new(frontmatter, body);
+                    doc.imports = imports;
+                    doc.components = components;
+                    doc
+                })
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_roundtrip_document(doc in generated::document()) {
+            let source = decompile(&doc);
+            let reparsed = crate::loader::parse_hnmd(&source);
+            proptest::prop_assert!(reparsed.is_ok(), "failed to parse:\n{}\n{:?}", source, reparsed.err());
+            // As JSON, which leaves out spans (see `assert_same`)
+            let (reparsed, doc) = (serde_json::to_value(reparsed.unwrap()).unwrap(), serde_json::to_value(&doc).unwrap());
+            proptest::prop_assert_eq!(reparsed, doc, "source:\n{}", source);
+        }
+
+        #[test]
+        fn prop_decompile_is_idempotent(doc in generated::document()) {
+            let source = decompile(&doc);
+            let reparsed = crate::loader::parse_hnmd(&source).unwrap();
+            proptest::prop_assert_eq!(decompile(&reparsed), source);
+        }
+
+        #[test]
+        fn prop_roundtrip_component(def in generated::component()) {
+            let source = decompile_component(&def);
+            let reparsed = crate::parser::component_def::parse_component(&source);
+            proptest::prop_assert!(reparsed.is_ok(), "failed to parse:\n{}\n{:?}", source, reparsed.err());
+            let (reparsed, def) = (serde_json::to_value(reparsed.unwrap()).unwrap(), serde_json::to_value(&def).unwrap());
+            proptest::prop_assert_eq!(reparsed, def, "source:\n{}", source);
+        }
+    }
 }
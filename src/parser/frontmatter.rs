@@ -1,26 +1,158 @@
-use crate::parser::ast::{Action, Filter, Frontmatter, Pipe};
+use crate::parser::ast::{Action, Filter, Frontmatter, ParseError, Pipe, Span, StyleRules, Theme};
 use anyhow::{Context, Result};
 use serde_yaml_ng::Value;
 use std::collections::HashMap;
 
+/// Where frontmatter entries start, keyed by (section, key), e.g. ("filters", "feed")
+pub type EntrySpans = HashMap<(String, String), Span>;
+
 /// Parse YAML frontmatter into Frontmatter struct
 /// Returns (frontmatter, imports)
 pub fn parse_frontmatter(yaml: &str) -> Result<Frontmatter> {
-    let value: Value = serde_yaml_ng::from_str(yaml)
-        .context("Failed to parse YAML frontmatter")?;
+    parse_frontmatter_at(yaml, Span::new(1, 1, 0), &EntrySpans::new())
+}
+
+/// Parse YAML frontmatter that starts at `origin` in its file
+/// Entries get their spans from `spans` (see `entry_spans`), and errors are located
+pub fn parse_frontmatter_at(yaml: &str, origin: Span, spans: &EntrySpans) -> Result<Frontmatter> {
+    let value: Value = serde_yaml_ng::from_str(yaml).map_err(|e| {
+        match e.location() {
+            Some(location) => {
+                let span = origin.offset_by(Span::new(location.line(), location.column(), location.index()));
+                anyhow::Error::new(ParseError::new(span, format!("Failed to parse YAML frontmatter: {}", e)))
+            }
+            None => anyhow::Error::new(e).context("Failed to parse YAML frontmatter"),
+        }
+    })?;
 
     let obj = value
         .as_mapping()
         .context("Frontmatter must be a YAML mapping")?;
 
     Ok(Frontmatter {
-        filters: parse_filters(obj.get(&Value::String("filters".to_string())))?,
-        pipes: parse_pipes(obj.get(&Value::String("pipes".to_string())))?,
-        actions: parse_actions(obj.get(&Value::String("actions".to_string())))?,
+        filters: parse_filters(obj.get(&Value::String("filters".to_string())), spans)?,
+        pipes: parse_pipes(obj.get(&Value::String("pipes".to_string())), spans)?,
+        actions: parse_actions(obj.get(&Value::String("actions".to_string())), spans)?,
         state: parse_state(obj.get(&Value::String("state".to_string())))?,
+        routes: parse_routes(obj.get(&Value::String("routes".to_string())))?,
+        theme: parse_theme(obj.get(&Value::String("theme".to_string())))?,
+        styles: parse_styles(obj.get(&Value::String("styles".to_string())))?,
     })
 }
 
+/// Find where each `section:` / `key:` entry of the frontmatter starts in a whole source file
+pub fn entry_spans(source: &str) -> EntrySpans {
+    let mut spans = EntrySpans::new();
+    let Some((fm, _)) = split_source(source) else {
+        return spans;
+    };
+
+    let mut offset = source.find("---").unwrap_or(0) + 3;
+    let mut section: Option<String> = None;
+    let mut entry_indent: Option<usize> = None;
+
+    for line in fm.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let content = line.trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some(key) = yaml_key(trimmed) else {
+            continue;
+        };
+
+        let indent = content.len() - trimmed.len();
+        if indent == 0 {
+            section = Some(key);
+            entry_indent = None;
+            continue;
+        }
+
+        // Entries are the first indentation level under a section
+        let Some(section) = &section else { continue };
+        if indent == *entry_indent.get_or_insert(indent) {
+            spans.insert((section.clone(), key), Span::at(source, line_start + indent));
+        }
+    }
+
+    spans
+}
+
+/// Key of a `key: value` YAML line (quotes removed)
+fn yaml_key(line: &str) -> Option<String> {
+    if line.starts_with('-') {
+        return None;
+    }
+    for quote in ['"', '\''] {
+        if let Some(rest) = line.strip_prefix(quote) {
+            let end = rest.find(quote)?;
+            return rest[end + 1..].trim_start().starts_with(':').then(|| rest[..end].to_string());
+        }
+    }
+
+    let colon = line.find(": ").or_else(|| line.ends_with(':').then_some(line.len() - 1))?;
+    Some(line[..colon].trim_end().to_string())
+}
+
+/// Name (and locate, when known) an error in a frontmatter entry
+fn entry_error(error: anyhow::Error, section: &str, key: &str, spans: &EntrySpans) -> anyhow::Error {
+    let message = format!("{}.{}: {:#}", section, key, error);
+    match spans.get(&(section.to_string(), key.to_string())) {
+        Some(span) => anyhow::Error::new(ParseError::new(*span, message)),
+        None => anyhow::anyhow!(message),
+    }
+}
+
+fn entry_span(spans: &EntrySpans, section: &str, key: &str) -> Option<Span> {
+    spans.get(&(section.to_string(), key.to_string())).copied()
+}
+
+/// Split a source file into raw (frontmatter, body), without the `---` delimiters
+/// The frontmatter ends at the first line that is exactly `---`, so indented `---`
+/// inside YAML block strings (e.g. bundled components) don't end it.
+/// Returns None when the file has no (complete) frontmatter
+pub fn split_source(source: &str) -> Option<(&str, &str)> {
+    let start = source.len() - source.trim_start().len();
+    if !source[start..].starts_with("---") {
+        return None;
+    }
+
+    let fm_start = start + 3;
+    let mut offset = fm_start;
+    for line in source[fm_start..].split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        // The first item is the remainder of the opening delimiter line
+        if line_start == fm_start {
+            continue;
+        }
+
+        if line.trim_end() == "---" {
+            return Some((&source[fm_start..line_start], &source[line_start + 3..]));
+        }
+    }
+
+    None
+}
+
+/// Read the imports of a whole .hnmd/.hnmc source, sorted by name
+pub fn parse_source_imports(source: &str) -> Result<Vec<(String, String)>> {
+    let Some((fm, _)) = split_source(source) else {
+        return Ok(Vec::new());
+    };
+    if fm.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut imports: Vec<_> = parse_imports(fm)?.into_iter().collect();
+    imports.sort();
+    Ok(imports)
+}
+
 /// Parse imports from frontmatter YAML
 pub fn parse_imports(yaml: &str) -> Result<HashMap<String, String>> {
     let value: Value = serde_yaml_ng::from_str(yaml)
@@ -55,8 +187,41 @@ pub fn parse_imports(yaml: &str) -> Result<HashMap<String, String>> {
     Ok(imports)
 }
 
+/// Parse bundled component sources from frontmatter YAML (name → .hnmc source)
+pub fn parse_components(yaml: &str) -> Result<HashMap<String, String>> {
+    let value: Value = serde_yaml_ng::from_str(yaml)
+        .context("Failed to parse YAML frontmatter")?;
+
+    let obj = value
+        .as_mapping()
+        .context("Frontmatter must be a YAML mapping")?;
+
+    let Some(components_val) = obj.get(&Value::String("components".to_string())) else {
+        return Ok(HashMap::new());
+    };
+
+    let mapping = components_val
+        .as_mapping()
+        .context("components must be a mapping")?;
+
+    let mut components = HashMap::new();
+    for (key, value) in mapping {
+        let key_str = key
+            .as_str()
+            .context("component name must be a string")?
+            .to_string();
+        let source = value
+            .as_str()
+            .context("component source must be a string")?
+            .to_string();
+        components.insert(key_str, source);
+    }
+
+    Ok(components)
+}
+
 /// Parse filters section
-fn parse_filters(value: Option<&Value>) -> Result<HashMap<String, Filter>> {
+fn parse_filters(value: Option<&Value>, spans: &EntrySpans) -> Result<HashMap<String, Filter>> {
     let Some(value) = value else {
         return Ok(HashMap::new());
     };
@@ -73,7 +238,8 @@ fn parse_filters(value: Option<&Value>) -> Result<HashMap<String, Filter>> {
             .context("filter key must be a string")?
             .to_string();
 
-        let filter = parse_filter(val)?;
+        let mut filter = parse_filter(val).map_err(|e| entry_error(e, "filters", &key_str, spans))?;
+        filter.span = entry_span(spans, "filters", &key_str);
         filters.insert(key_str, filter);
     }
 
@@ -190,13 +356,30 @@ fn parse_filter(value: &Value) -> Result<Filter> {
         );
     }
 
-    // TODO: Parse custom tags (#a, #t, etc.)
+    // Parse custom tags (#a, #t, etc.)
+    for (key, val) in obj {
+        let Some(tag) = key.as_str() else { continue };
+        if !tag.starts_with('#') || tag == "#e" || tag == "#p" {
+            continue;
+        }
+        let values = val
+            .as_sequence()
+            .with_context(|| format!("{} must be an array", tag))?
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .with_context(|| format!("{} tag must be a string", tag))
+                    .map(|s| s.to_string())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        filter.custom_tags.insert(tag.to_string(), values);
+    }
 
     Ok(filter)
 }
 
 /// Parse pipes section
-fn parse_pipes(value: Option<&Value>) -> Result<HashMap<String, Pipe>> {
+fn parse_pipes(value: Option<&Value>, spans: &EntrySpans) -> Result<HashMap<String, Pipe>> {
     let Some(value) = value else {
         return Ok(HashMap::new());
     };
@@ -211,7 +394,8 @@ fn parse_pipes(value: Option<&Value>) -> Result<HashMap<String, Pipe>> {
             .context("pipe key must be a string")?
             .to_string();
 
-        let pipe = parse_pipe(val)?;
+        let mut pipe = parse_pipe(val).map_err(|e| entry_error(e, "pipes", &key_str, spans))?;
+        pipe.span = entry_span(spans, "pipes", &key_str);
         pipes.insert(key_str, pipe);
     }
 
@@ -240,7 +424,7 @@ fn parse_pipe(value: &Value) -> Result<Pipe> {
 }
 
 /// Parse actions section
-fn parse_actions(value: Option<&Value>) -> Result<HashMap<String, Action>> {
+fn parse_actions(value: Option<&Value>, spans: &EntrySpans) -> Result<HashMap<String, Action>> {
     let Some(value) = value else {
         return Ok(HashMap::new());
     };
@@ -257,7 +441,8 @@ fn parse_actions(value: Option<&Value>) -> Result<HashMap<String, Action>> {
             .context("action key must be a string")?
             .to_string();
 
-        let action = parse_action(val)?;
+        let mut action = parse_action(val).map_err(|e| entry_error(e, "actions", &key_str, spans))?;
+        action.span = entry_span(spans, "actions", &key_str);
         actions.insert(key_str, action);
     }
 
@@ -330,6 +515,76 @@ fn parse_state(value: Option<&Value>) -> Result<HashMap<String, serde_json::Valu
     Ok(obj.clone().into_iter().collect())
 }
 
+/// Parse routes section: `/pattern/:param` → file
+fn parse_routes(value: Option<&Value>) -> Result<HashMap<String, String>> {
+    let Some(value) = value else {
+        return Ok(HashMap::new());
+    };
+
+    let mapping = value
+        .as_mapping()
+        .context("routes must be a mapping")?;
+
+    let mut routes = HashMap::new();
+    for (key, val) in mapping {
+        let pattern = key.as_str().context("Route pattern must be a string")?;
+        if !pattern.starts_with('/') {
+            anyhow::bail!("Route pattern '{}' must start with '/'", pattern);
+        }
+        let file = val
+            .as_str()
+            .with_context(|| format!("Route '{}' must name a .hnmd file", pattern))?;
+        routes.insert(pattern.to_string(), file.to_string());
+    }
+
+    Ok(routes)
+}
+
+/// Parse theme section: `palette`, `font_sizes` and `spacing` mappings
+fn parse_theme(value: Option<&Value>) -> Result<Theme> {
+    let Some(value) = value else {
+        return Ok(Theme::default());
+    };
+
+    serde_yaml_ng::from_value(value.clone()).context("theme must have palette, font_sizes and spacing mappings")
+}
+
+/// Parse a styles section: class → property → value (numbers are kept as written)
+/// Shared with component files, whose styles only apply inside the component
+pub fn parse_styles(value: Option<&Value>) -> Result<HashMap<String, StyleRules>> {
+    let Some(value) = value else {
+        return Ok(HashMap::new());
+    };
+
+    let mapping = value
+        .as_mapping()
+        .context("styles must be a mapping")?;
+
+    let mut styles = HashMap::new();
+    for (key, val) in mapping {
+        let class = key.as_str().context("Style class name must be a string")?;
+        let properties = val
+            .as_mapping()
+            .with_context(|| format!("Style '{}' must be a mapping of properties", class))?;
+
+        let mut rules = StyleRules::new();
+        for (name, value) in properties {
+            let name = name
+                .as_str()
+                .with_context(|| format!("Style '{}' has a property name that is not a string", class))?;
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => anyhow::bail!("Style '{}' property '{}' must be a string or number", class, name),
+            };
+            rules.insert(name.replace('-', "_"), value);
+        }
+        styles.insert(class.to_string(), rules);
+    }
+
+    Ok(styles)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -435,6 +690,51 @@ state:
         );
     }
 
+    #[test]
+    fn test_parse_routes() {
+        let yaml = r#"
+routes:
+  /: feed.hnmd
+  /profile/:pubkey: profile.hnmd
+"#;
+        let fm = parse_frontmatter(yaml).unwrap();
+        assert_eq!(fm.routes.len(), 2);
+        assert_eq!(fm.routes.get("/profile/:pubkey").unwrap(), "profile.hnmd");
+
+        assert!(parse_frontmatter("routes:\n  profile: profile.hnmd").is_err());
+        assert!(parse_frontmatter("routes:\n  /a: [1]").is_err());
+    }
+
+    #[test]
+    fn test_parse_theme_and_styles() {
+        let yaml = r##"
+theme:
+  palette:
+    primary: "#7c3aed"
+  font_sizes:
+    body: 16
+  spacing:
+    md: 10
+styles:
+  card:
+    background: surface
+    padding: md
+  title:
+    font-size: 24
+    font_weight: bold
+"##;
+        let fm = parse_frontmatter(yaml).unwrap();
+        assert_eq!(fm.theme.palette.get("primary").unwrap(), "#7c3aed");
+        assert_eq!(fm.theme.font_sizes.get("body"), Some(&16.0));
+        assert_eq!(fm.theme.spacing.get("md"), Some(&10.0));
+        assert_eq!(fm.styles["card"].get("padding").unwrap(), "md");
+        // Numbers are kept as written and property names use underscores
+        assert_eq!(fm.styles["title"].get("font_size").unwrap(), "24");
+
+        assert!(parse_frontmatter("styles:\n  card: red").is_err());
+        assert!(parse_frontmatter("theme:\n  spacing:\n    md: big").is_err());
+    }
+
     #[test]
     fn test_parse_complete_frontmatter() {
         let yaml = r#"
@@ -466,6 +766,66 @@ state:
         assert_eq!(fm.state.len(), 1);
     }
 
+    #[test]
+    fn test_parse_components() {
+        let yaml = r#"
+components:
+  Greeting: |
+    ---
+    props:
+      name: string
+    ---
+
+    Hello {props.name}
+"#;
+        let components = parse_components(yaml).unwrap();
+        assert_eq!(components.len(), 1);
+        assert!(components["Greeting"].contains("Hello {props.name}"));
+
+        assert!(parse_components("state:\n  x: 1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_entry_spans() {
+        let source = "---\nfilters:\n  feed:\n    kinds: [1]\n\npipes:\n  \"names\":\n    from: feed\n    jq: \".\"\n---\n# Hi\n";
+        let spans = entry_spans(source);
+
+        let feed = spans[&("filters".to_string(), "feed".to_string())];
+        assert_eq!((feed.line, feed.column), (3, 3));
+        assert_eq!(&source[feed.offset..feed.offset + 5], "feed:");
+
+        let names = spans[&("pipes".to_string(), "names".to_string())];
+        assert_eq!((names.line, names.column), (7, 3));
+        assert!(!spans.contains_key(&("filters".to_string(), "kinds".to_string())));
+    }
+
+    #[test]
+    fn test_entry_errors_are_located() {
+        let source = "---\npipes:\n  broken:\n    jq: \".\"\n---\n";
+        let (fm, _) = split_source(source).unwrap();
+        let err = parse_frontmatter_at(fm, Span::new(1, 4, 3), &entry_spans(source)).unwrap_err();
+        let parse_error = err.downcast_ref::<ParseError>().unwrap();
+        assert_eq!(parse_error.span.line, 3);
+        assert!(parse_error.message.contains("pipes.broken"));
+    }
+
+    #[test]
+    fn test_split_source() {
+        let source = "---\nstate:\n  x: 1\n---\n\n# Body\n\n---\n\nMore";
+        let (fm, body) = split_source(source).unwrap();
+        assert_eq!(fm, "\nstate:\n  x: 1\n");
+        assert_eq!(body, "\n\n# Body\n\n---\n\nMore");
+
+        // Indented delimiters inside block strings don't close the frontmatter
+        let nested = "---\ncomponents:\n  A: |\n    ---\n    props: {}\n    ---\n    Hi\n---\nBody";
+        let (fm, body) = split_source(nested).unwrap();
+        assert!(fm.contains("props: {}"));
+        assert_eq!(body, "\nBody");
+
+        assert!(split_source("# No frontmatter").is_none());
+        assert!(split_source("---\nstate: {}\n").is_none());
+    }
+
     #[test]
     fn test_invalid_yaml() {
         let yaml = "this is not valid yaml: [[[";
@@ -1,10 +1,46 @@
-use crate::parser::ast::{ListItem, Node};
+use crate::parser::ast::{
+    parse_style_attr, ColumnAlign, ListItem, Node, NodeStyle, ParseError, PropValue, Span, StackLayout, TableCell,
+    TableRow, STYLE_PROPERTIES,
+};
 use anyhow::Result;
 use markdown::mdast;
+use markdown::unist::Position;
 use regex::Regex;
 
+/// Height of a `virtual` list's scrolling box when it doesn't set one
+const DEFAULT_VIRTUAL_HEIGHT: f64 = 600.0;
+
 /// Parse markdown body with MDX JSX support
 pub fn parse_body(source: &str) -> Result<Vec<Node>> {
+    parse_body_at(source, Span::new(1, 1, 0))
+}
+
+/// Parse markdown body that starts at `origin` in its file, so node spans are file positions
+pub fn parse_body_at(source: &str, origin: Span) -> Result<Vec<Node>> {
+    let ast = markdown::to_mdast(source, &parse_options()).map_err(|e| {
+        let point = match e.place.as_deref() {
+            Some(markdown::message::Place::Point(point)) => Some(point.clone()),
+            Some(markdown::message::Place::Position(position)) => Some(position.start.clone()),
+            None => None,
+        };
+        match point {
+            Some(point) => {
+                let span = origin.offset_by(Span::new(point.line, point.column, point.offset));
+                anyhow::Error::new(ParseError::new(span, format!("Failed to parse markdown: {}", e.reason)))
+            }
+            None => anyhow::anyhow!("Failed to parse markdown: {}", e),
+        }
+    })?;
+
+    // The root is always a Root node containing children
+    match ast {
+        mdast::Node::Root(root) => transform_children(root.children, origin),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Markdown options: MDX plus the GFM extensions
+fn parse_options() -> markdown::ParseOptions {
     let mut options = markdown::ParseOptions::default();
 
     // Enable MDX JSX parsing (disable HTML parsing as it conflicts)
@@ -14,44 +50,92 @@ pub fn parse_body(source: &str) -> Result<Vec<Node>> {
     options.constructs.mdx_expression_text = true;  // Inline expressions {expr}
     options.constructs.html_flow = false;    // Must disable when using MDX JSX
     options.constructs.html_text = false;    // Must disable when using MDX JSX
+    options.constructs.gfm_table = true;     // GitHub-style tables
+    options.constructs.gfm_strikethrough = true;
+    options.constructs.gfm_task_list_item = true;
+    options.constructs.gfm_autolink_literal = true;  // Bare URLs become links
+    options.constructs.gfm_footnote_definition = true;
+    options.constructs.gfm_label_start_footnote = true;
 
-    let ast = markdown::to_mdast(source, &options)
-        .map_err(|e| anyhow::anyhow!("Failed to parse markdown: {}", e))?;
 
-    // The root is always a Root node containing children
-    match ast {
-        mdast::Node::Root(root) => transform_children(root.children),
-        _ => Ok(vec![]),
+    options
+}
+
+/// Markdown constructs in `source` that the AST doesn't keep, by name (each named once)
+/// Decompiling a body that has them would drop or change them
+pub fn unkept_constructs(source: &str) -> Vec<&'static str> {
+    let mut found = Vec::new();
+    if let Ok(ast) = markdown::to_mdast(source, &parse_options()) {
+        collect_unkept(&ast, &mut found);
+    }
+    found
+}
+
+fn collect_unkept(node: &mdast::Node, found: &mut Vec<&'static str>) {
+    let name = match node {
+        mdast::Node::Definition(_) => Some("link reference definitions"),
+        mdast::Node::LinkReference(_) => Some("reference links"),
+        mdast::Node::ImageReference(_) => Some("reference images"),
+        mdast::Node::Break(_) => Some("hard line breaks"),
+        mdast::Node::Html(_) => Some("HTML"),
+        mdast::Node::MdxjsEsm(_) => Some("import/export statements"),
+        mdast::Node::Link(link) if link.title.is_some() => Some("link titles"),
+        mdast::Node::Image(image) if image.title.is_some() => Some("image titles"),
+        mdast::Node::Code(code) if code.meta.is_some() => Some("code fence info after the language"),
+        _ => None,
+    };
+    if let Some(name) = name {
+        if !found.contains(&name) {
+            found.push(name);
+        }
+    }
+    for child in node.children().into_iter().flatten() {
+        collect_unkept(child, found);
+    }
+}
+
+/// File span of an mdast node
+fn node_span(position: Option<&Position>, origin: Span) -> Option<Span> {
+    position.map(|p| origin.offset_by(Span::new(p.start.line, p.start.column, p.start.offset)))
+}
+
+/// Attach a location to an error raised while building a node
+fn locate_error(error: anyhow::Error, span: Option<Span>) -> anyhow::Error {
+    match span {
+        Some(span) if error.downcast_ref::<ParseError>().is_none() => {
+            anyhow::Error::new(ParseError::new(span, error))
+        }
+        _ => error,
     }
 }
 
 /// Transform a list of markdown AST children
-fn transform_children(children: Vec<mdast::Node>) -> Result<Vec<Node>> {
+fn transform_children(children: Vec<mdast::Node>, origin: Span) -> Result<Vec<Node>> {
     children
         .into_iter()
-        .flat_map(|child| transform_node(child).transpose())
+        .flat_map(|child| transform_node(child, origin).transpose())
         .collect()
 }
 
 /// Transform a single markdown node to our AST
-fn transform_node(node: mdast::Node) -> Result<Option<Node>> {
+fn transform_node(node: mdast::Node, origin: Span) -> Result<Option<Node>> {
     Ok(Some(match node {
         // Markdown nodes
         mdast::Node::Heading(h) => Node::Heading {
             level: h.depth,
-            children: transform_children(h.children)?,
+            children: transform_children(h.children, origin)?,
         },
 
         mdast::Node::Paragraph(p) => {
             // Check if paragraph contains only an image - render directly
             if p.children.len() == 1 {
                 if let mdast::Node::Image(_) = &p.children[0] {
-                    return transform_node(p.children[0].clone());
+                    return transform_node(p.children[0].clone(), origin);
                 }
             }
 
             Node::Paragraph {
-                children: transform_children(p.children)?,
+                children: transform_children(p.children, origin)?,
             }
         },
 
@@ -62,11 +146,15 @@ fn transform_node(node: mdast::Node) -> Result<Option<Node>> {
         }
 
         mdast::Node::Strong(s) => Node::Strong {
-            children: transform_children(s.children)?,
+            children: transform_children(s.children, origin)?,
         },
 
         mdast::Node::Emphasis(e) => Node::Emphasis {
-            children: transform_children(e.children)?,
+            children: transform_children(e.children, origin)?,
+        },
+
+        mdast::Node::Delete(d) => Node::Strikethrough {
+            children: transform_children(d.children, origin)?,
         },
 
         mdast::Node::List(l) => Node::List {
@@ -76,8 +164,10 @@ fn transform_node(node: mdast::Node) -> Result<Option<Node>> {
                 .into_iter()
                 .map(|item| {
                     if let mdast::Node::ListItem(li) = item {
-                        Ok(ListItem {
-                            children: transform_children(li.children)?,
+                        let children = transform_children(li.children, origin)?;
+                        Ok(match li.checked {
+                            Some(checked) => task_item(checked, children),
+                            None => ListItem::new(children),
                         })
                     } else {
                         Err(anyhow::anyhow!("Expected ListItem in List"))
@@ -88,41 +178,94 @@ fn transform_node(node: mdast::Node) -> Result<Option<Node>> {
 
         mdast::Node::Link(link) => Node::Link {
             url: link.url,
-            children: transform_children(link.children)?,
+            children: transform_children(link.children, origin)?,
         },
 
         mdast::Node::Image(img) => Node::Image {
+            span: node_span(img.position.as_ref(), origin),
             src: img.url,
             alt: img.alt,
+            width: None,
+            height: None,
+            fit: None,
         },
 
-        mdast::Node::ThematicBreak(_) => Node::Spacer { size: Some(20.0) },
+        mdast::Node::ThematicBreak(_) => Node::ThematicBreak,
 
         // MDX JSX Components - this is the good stuff!
         mdast::Node::MdxJsxFlowElement(jsx) => {
-            return transform_jsx_element(jsx);
+            return transform_jsx_element(jsx, origin);
         }
 
         mdast::Node::MdxJsxTextElement(jsx) => {
-            return transform_jsx_element_text(jsx);
+            return transform_jsx_element_text(jsx, origin);
         }
 
         // MDX Expressions
         mdast::Node::MdxFlowExpression(expr) => Node::Expr {
+            span: node_span(expr.position.as_ref(), origin),
             expression: expr.value,
         },
 
         mdast::Node::MdxTextExpression(expr) => Node::Expr {
+            span: node_span(expr.position.as_ref(), origin),
             expression: expr.value,
         },
 
-        // Unsupported nodes - skip
-        mdast::Node::Code(_) => return Ok(None),
-        mdast::Node::InlineCode(code) => Node::Text {
-            value: format!("`{}`", code.value),
+        mdast::Node::Code(code) => Node::CodeBlock {
+            lang: code.lang,
+            value: code.value,
+        },
+
+        mdast::Node::InlineCode(code) => Node::InlineCode { value: code.value },
+
+        mdast::Node::FootnoteReference(note) => Node::FootnoteReference {
+            label: note.label.unwrap_or(note.identifier),
+        },
+
+        mdast::Node::FootnoteDefinition(note) => Node::FootnoteDefinition {
+            label: note.label.unwrap_or(note.identifier),
+            children: transform_children(note.children, origin)?,
+        },
+
+        mdast::Node::Blockquote(quote) => Node::Blockquote {
+            children: transform_children(quote.children, origin)?,
         },
-        mdast::Node::Blockquote(_) => return Ok(None),
-        mdast::Node::Table(_) => return Ok(None),
+
+        mdast::Node::Table(table) => Node::Table {
+            align: table
+                .align
+                .iter()
+                .map(|kind| match kind {
+                    mdast::AlignKind::Left => ColumnAlign::Left,
+                    mdast::AlignKind::Center => ColumnAlign::Center,
+                    mdast::AlignKind::Right => ColumnAlign::Right,
+                    mdast::AlignKind::None => ColumnAlign::None,
+                })
+                .collect(),
+            rows: table
+                .children
+                .into_iter()
+                .map(|row| {
+                    let mdast::Node::TableRow(row) = row else {
+                        return Err(anyhow::anyhow!("Expected TableRow in Table"));
+                    };
+                    let cells = row
+                        .children
+                        .into_iter()
+                        .map(|cell| match cell {
+                            mdast::Node::TableCell(cell) => Ok(TableCell {
+                                children: transform_children(cell.children, origin)?,
+                            }),
+                            _ => Err(anyhow::anyhow!("Expected TableCell in TableRow")),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(TableRow { cells })
+                })
+                .collect::<Result<Vec<_>>>()?,
+        },
+
+        // Unsupported nodes - skip
         mdast::Node::Html(_) => return Ok(None), // Shouldn't happen with MDX mode
         mdast::Node::MdxjsEsm(_) => return Ok(None), // ESM imports - not supported yet
 
@@ -131,6 +274,32 @@ fn transform_node(node: mdast::Node) -> Result<Option<Node>> {
     }))
 }
 
+/// Build a task list item; a leading `{state.*}` expression binds the checkbox to that path
+fn task_item(checked: bool, mut children: Vec<Node>) -> ListItem {
+    let mut item = ListItem::task(checked, Vec::new());
+
+    if let Some(Node::Paragraph { children: inlines }) = children.first_mut() {
+        if let Some(Node::Expr { expression, .. }) = inlines.first() {
+            if expression.starts_with("state.") {
+                item.bind = Some(expression.clone());
+                inlines.remove(0);
+                if let Some(Node::Text { value }) = inlines.first_mut() {
+                    *value = value.trim_start().to_string();
+                    if value.is_empty() {
+                        inlines.remove(0);
+                    }
+                }
+                if inlines.is_empty() {
+                    children.remove(0);
+                }
+            }
+        }
+    }
+
+    item.children = children;
+    item
+}
+
 /// Parse text that might contain {expr} interpolations
 fn parse_text_with_expressions(text: &str) -> Result<Option<Node>> {
     let expr_re = Regex::new(r"\{([^}]+)\}").unwrap();
@@ -151,8 +320,9 @@ fn parse_text_with_expressions(text: &str) -> Result<Option<Node>> {
 }
 
 /// Transform an MDX JSX flow element (block-level component)
-fn transform_jsx_element(jsx: mdast::MdxJsxFlowElement) -> Result<Option<Node>> {
+fn transform_jsx_element(jsx: mdast::MdxJsxFlowElement, origin: Span) -> Result<Option<Node>> {
     let tag_name = jsx.name.as_deref().unwrap_or("fragment");
+    let span = node_span(jsx.position.as_ref(), origin);
 
     // Extract attributes into our format
     let mut attrs = std::collections::HashMap::new();
@@ -160,25 +330,49 @@ fn transform_jsx_element(jsx: mdast::MdxJsxFlowElement) -> Result<Option<Node>>
         if let mdast::AttributeContent::Property(prop) = attr {
             let value = match prop.value {
                 Some(mdast::AttributeValue::Literal(lit)) => crate::parser::component::AttrValue::Literal(lit),
-                Some(mdast::AttributeValue::Expression(expr)) => {
-                    crate::parser::component::AttrValue::Expression(expr.value)
-                }
+                Some(mdast::AttributeValue::Expression(expr)) => attr_expression(expr.value),
                 None => crate::parser::component::AttrValue::Literal(String::new()),
             };
             attrs.insert(prop.name, value);
         }
     }
 
-    // Transform children
-    let children = transform_children(jsx.children)?;
+    // Transform children, keeping an `<if>`'s `<else>` branch separate
+    let (children, else_children) = split_else(tag_name, jsx.children);
+    let children = transform_children(children, origin)?;
+    let else_children = else_children
+        .map(|else_children| transform_children(else_children, origin))
+        .transpose()?;
 
     // Build component node based on tag name
-    build_component_from_jsx(tag_name, attrs, children)
+    build_component_from_jsx(tag_name, attrs, children, else_children, span)
+        .map_err(|e| locate_error(e, span))
+}
+
+/// Pull the `<else>` branch out of an `<if>` element's children
+fn split_else(tag: &str, children: Vec<mdast::Node>) -> (Vec<mdast::Node>, Option<Vec<mdast::Node>>) {
+    if tag != "if" {
+        return (children, None);
+    }
+
+    let mut else_children = None;
+    let children = children
+        .into_iter()
+        .filter_map(|child| match child {
+            mdast::Node::MdxJsxFlowElement(el) if el.name.as_deref() == Some("else") => {
+                else_children = Some(el.children);
+                None
+            }
+            child => Some(child),
+        })
+        .collect();
+    (children, else_children)
 }
 
 /// Transform an MDX JSX text element (inline component)
-fn transform_jsx_element_text(jsx: mdast::MdxJsxTextElement) -> Result<Option<Node>> {
+fn transform_jsx_element_text(jsx: mdast::MdxJsxTextElement, origin: Span) -> Result<Option<Node>> {
     let tag_name = jsx.name.as_deref().unwrap_or("fragment");
+    let span = node_span(jsx.position.as_ref(), origin);
 
     // Extract attributes
     let mut attrs = std::collections::HashMap::new();
@@ -186,9 +380,7 @@ fn transform_jsx_element_text(jsx: mdast::MdxJsxTextElement) -> Result<Option<No
         if let mdast::AttributeContent::Property(prop) = attr {
             let value = match prop.value {
                 Some(mdast::AttributeValue::Literal(lit)) => crate::parser::component::AttrValue::Literal(lit),
-                Some(mdast::AttributeValue::Expression(expr)) => {
-                    crate::parser::component::AttrValue::Expression(expr.value)
-                }
+                Some(mdast::AttributeValue::Expression(expr)) => attr_expression(expr.value),
                 None => crate::parser::component::AttrValue::Literal(String::new()),
             };
             attrs.insert(prop.name, value);
@@ -196,10 +388,10 @@ fn transform_jsx_element_text(jsx: mdast::MdxJsxTextElement) -> Result<Option<No
     }
 
     // Transform children
-    let children = transform_children(jsx.children)?;
+    let children = transform_children(jsx.children, origin)?;
 
     // Build component node
-    build_component_from_jsx(tag_name, attrs, children)
+    build_component_from_jsx(tag_name, attrs, children, None, span).map_err(|e| locate_error(e, span))
 }
 
 /// Build our AST node from JSX component info
@@ -207,6 +399,8 @@ fn build_component_from_jsx(
     tag: &str,
     attrs: std::collections::HashMap<String, crate::parser::component::AttrValue>,
     children: Vec<Node>,
+    else_children: Option<Vec<Node>>,
+    span: Option<Span>,
 ) -> Result<Option<Node>> {
     use crate::parser::component::AttrValue;
 
@@ -214,16 +408,21 @@ fn build_component_from_jsx(
         "each" => {
             let from = get_attr_expr(&attrs, "from")?;
             let as_name = get_attr_literal(&attrs, "as")?;
-            Node::Each { from, as_name, children }
+            let key = attrs.contains_key("key").then(|| get_attr_expr(&attrs, "key")).transpose()?;
+            let viewport = attrs
+                .contains_key("virtual")
+                .then(|| get_attr_number(&attrs, "height").unwrap_or(DEFAULT_VIRTUAL_HEIGHT));
+            let on_end = attrs.contains_key("on_end").then(|| get_attr_expr(&attrs, "on_end")).transpose()?;
+            Node::Each { from, as_name, key, viewport, on_end, children, span }
         }
 
         "if" => {
             let value = get_attr_expr(&attrs, "value")?;
-            // TODO: Handle <else> tag in children
             Node::If {
                 value,
                 children,
-                else_children: None,
+                else_children,
+                span,
             }
         }
 
@@ -244,6 +443,9 @@ fn build_component_from_jsx(
                         ));
                     }
                 }
+            } else if let [Node::Paragraph { children: inline }] = children.as_slice() {
+                // A one-line label parses as a paragraph; keep just its inline content
+                inline.clone()
             } else if !children.is_empty() {
                 children
             } else {
@@ -253,6 +455,7 @@ fn build_component_from_jsx(
             Node::Button {
                 on_click,
                 children: label_children,
+                span,
             }
         }
 
@@ -263,41 +466,43 @@ fn build_component_from_jsx(
                 AttrValue::Expression(expr) => expr.clone(),
             });
 
-            Node::Input { name, placeholder }
+            Node::Input { name, placeholder, span }
         }
 
-        "vstack" => {
-            let flex = attrs.get("flex").and_then(|av| {
-                match av {
-                    AttrValue::Literal(s) => s.parse::<f64>().ok(),
-                    _ => None,
-                }
-            });
-
-            Node::VStack {
-                children,
-                width: None,  // TODO: Parse width/height/align
-                height: None,
-                flex,
-                align: None,
+        "img" => {
+            // `src={expr}` is the template "{expr}"; literal values can hold templates too
+            let template = |av: &AttrValue| match av {
+                AttrValue::Literal(s) => s.clone(),
+                AttrValue::Expression(e) => format!("{{{}}}", e),
+            };
+            let src = attrs
+                .get("src")
+                .map(template)
+                .ok_or_else(|| anyhow::anyhow!("Missing required attribute 'src'"))?;
+
+            Node::Image {
+                src,
+                alt: attrs.get("alt").map(template).unwrap_or_default(),
+                width: get_attr_number(&attrs, "width"),
+                height: get_attr_number(&attrs, "height"),
+                fit: attrs.get("fit").map(|av| match av {
+                    AttrValue::Literal(s) | AttrValue::Expression(s) => s.clone(),
+                }),
+                span,
             }
         }
 
-        "hstack" => {
-            let flex = attrs.get("flex").and_then(|av| {
-                match av {
-                    AttrValue::Literal(s) => s.parse::<f64>().ok(),
-                    _ => None,
-                }
-            });
+        "vstack" => Node::VStack { children, layout: stack_layout(&attrs)? },
 
-            Node::HStack {
-                children,
-                width: None,
-                height: None,
-                flex,
-                align: None,
-            }
+        "hstack" => Node::HStack { children, layout: stack_layout(&attrs)? },
+
+        "text" => {
+            // A one-line `<text>` parses as a paragraph; keep just its inline content
+            let children = match children.as_slice() {
+                [Node::Paragraph { children: inline }] => inline.clone(),
+                _ => children,
+            };
+            Node::TextBlock { children, style: node_style(&attrs)? }
         }
 
         "grid" => {
@@ -308,23 +513,23 @@ fn build_component_from_jsx(
                 }
             });
 
-            Node::Grid { children, columns }
+            Node::Grid {
+                columns,
+                min_column_width: get_attr_number(&attrs, "min_column_width"),
+                gap: get_attr_number(&attrs, "gap"),
+                row_gap: get_attr_number(&attrs, "row_gap"),
+                column_gap: get_attr_number(&attrs, "column_gap"),
+                children,
+            }
         }
 
-        "spacer" => {
-            let size = attrs.get("size").and_then(|av| {
-                match av {
-                    AttrValue::Literal(s) => s.parse::<f64>().ok(),
-                    _ => None,
-                }
-            });
-
-            Node::Spacer { size }
-        }
+        "spacer" => Node::Spacer {
+            size: get_attr_number(&attrs, "size"),
+        },
 
         "json" => {
             let value = get_attr_expr(&attrs, "value")?;
-            Node::Json { value }
+            Node::Json { value, span }
         }
 
         _ => {
@@ -344,6 +549,7 @@ fn build_component_from_jsx(
                     name: tag.to_string(),
                     props,
                     children,
+                    span,
                 }
             } else {
                 return Err(anyhow::anyhow!("Unknown component: <{}>", tag));
@@ -354,6 +560,88 @@ fn build_component_from_jsx(
     Ok(Some(node))
 }
 
+/// Helper: Get the sizing, alignment and style attributes of a stack
+fn stack_layout(attrs: &std::collections::HashMap<String, crate::parser::component::AttrValue>) -> Result<StackLayout> {
+    use crate::parser::component::AttrValue;
+
+    let word = |name: &str| {
+        attrs.get(name).map(|av| match av {
+            AttrValue::Literal(s) | AttrValue::Expression(s) => s.clone(),
+        })
+    };
+
+    // Numeric padding is layout; a spacing name (`padding="md"`) is a style property
+    let padding = get_attr_number(attrs, "padding");
+    let mut style = node_style(attrs)?;
+    if padding.is_some() {
+        style.properties.remove("padding");
+    }
+
+    Ok(StackLayout {
+        width: get_attr_number(attrs, "width"),
+        height: get_attr_number(attrs, "height"),
+        min_width: get_attr_number(attrs, "min_width"),
+        max_width: get_attr_number(attrs, "max_width"),
+        min_height: get_attr_number(attrs, "min_height"),
+        max_height: get_attr_number(attrs, "max_height"),
+        flex: get_attr_number(attrs, "flex"),
+        align: word("align"),
+        justify: word("justify"),
+        gap: get_attr_number(attrs, "gap"),
+        padding,
+        span: get_attr_number(attrs, "span").map(|n| n as usize),
+        row_span: get_attr_number(attrs, "row_span").map(|n| n as usize),
+        style,
+    })
+}
+
+/// Helper: Get `class` and style properties; attributes named after a property
+/// (e.g. `color={expr}`) win over the same property in `style="..."`
+fn node_style(attrs: &std::collections::HashMap<String, crate::parser::component::AttrValue>) -> Result<NodeStyle> {
+    use crate::parser::component::AttrValue;
+
+    let mut properties = std::collections::HashMap::new();
+    match attrs.get("style") {
+        Some(AttrValue::Literal(style)) => {
+            for (name, value) in parse_style_attr(style) {
+                properties.insert(name, PropValue::Literal(value));
+            }
+        }
+        Some(AttrValue::Expression(_)) => {
+            return Err(anyhow::anyhow!("Attribute 'style' must be a literal string; use e.g. color={{...}} for dynamic styles"));
+        }
+        None => {}
+    }
+    for name in STYLE_PROPERTIES {
+        match attrs.get(*name) {
+            Some(AttrValue::Literal(value)) => { properties.insert(name.to_string(), PropValue::Literal(value.clone())); }
+            Some(AttrValue::Expression(expr)) => { properties.insert(name.to_string(), PropValue::Expression(expr.clone())); }
+            None => {}
+        }
+    }
+
+    // `class={expr}` is the template "{expr}", like image sources
+    let class = attrs.get("class").map(|av| match av {
+        AttrValue::Literal(s) => s.clone(),
+        AttrValue::Expression(e) => format!("{{{}}}", e),
+    });
+
+    Ok(NodeStyle { class, properties })
+}
+
+/// An attribute written as `{...}`; as in JSX, a lone string literal (`title={"..."}`) is the same as `title="..."`
+fn attr_expression(expr: String) -> crate::parser::component::AttrValue {
+    use crate::parser::component::AttrValue;
+
+    let trimmed = expr.trim();
+    if trimmed.starts_with('"') {
+        if let Ok(literal) = serde_json::from_str::<String>(trimmed) {
+            return AttrValue::Literal(literal);
+        }
+    }
+    AttrValue::Expression(expr)
+}
+
 /// Helper: Get required attribute as expression/literal string
 fn get_attr_expr(attrs: &std::collections::HashMap<String, crate::parser::component::AttrValue>, name: &str) -> Result<String> {
     use crate::parser::component::AttrValue;
@@ -366,6 +654,15 @@ fn get_attr_expr(attrs: &std::collections::HashMap<String, crate::parser::compon
         })
 }
 
+/// Helper: Get optional numeric attribute, written as `"12"` or `{12}`
+fn get_attr_number(attrs: &std::collections::HashMap<String, crate::parser::component::AttrValue>, name: &str) -> Option<f64> {
+    use crate::parser::component::AttrValue;
+
+    attrs.get(name).and_then(|av| match av {
+        AttrValue::Literal(s) | AttrValue::Expression(s) => s.trim().parse::<f64>().ok(),
+    })
+}
+
 /// Helper: Get required attribute as literal string only
 fn get_attr_literal(attrs: &std::collections::HashMap<String, crate::parser::component::AttrValue>, name: &str) -> Result<String> {
     use crate::parser::component::AttrValue;
@@ -446,12 +743,12 @@ Test
         let nodes = parse_body(md).unwrap();
 
         match &nodes[0] {
-            Node::VStack { flex, children, .. } => {
-                assert_eq!(*flex, Some(0.5));
+            Node::VStack { layout, children } => {
+                assert_eq!(layout.flex, Some(0.5));
                 assert_eq!(children.len(), 1);
 
                 match &children[0] {
-                    Node::Input { name, placeholder } => {
+                    Node::Input { name, placeholder, .. } => {
                         assert_eq!(name, "test");
                         assert_eq!(placeholder.as_deref(), Some("Type here"));
                     }
@@ -470,7 +767,7 @@ Test
         let nodes = parse_body(md).unwrap();
 
         match &nodes[0] {
-            Node::Each { from, as_name, children } => {
+            Node::Each { from, as_name, children, .. } => {
                 assert_eq!(from, "queries.feed");
                 assert_eq!(as_name, "note");
                 assert_eq!(children.len(), 1);
@@ -511,7 +808,7 @@ Hello {user.name}!"#;
         match &nodes[0] {
             Node::Heading { children, .. } => {
                 assert_eq!(children.len(), 1);
-                assert!(matches!(&children[0], Node::Expr { expression } if expression == "state.title"));
+                assert!(matches!(&children[0], Node::Expr { expression, .. } if expression == "state.title"));
             }
             _ => panic!("Expected heading"),
         }
@@ -522,10 +819,225 @@ Hello {user.name}!"#;
                 assert!(children.len() >= 2);
                 // Should have both text and expr nodes
                 let has_text = children.iter().any(|c| matches!(c, Node::Text { .. }));
-                let has_expr = children.iter().any(|c| matches!(c, Node::Expr { expression } if expression == "user.name"));
+                let has_expr = children.iter().any(|c| matches!(c, Node::Expr { expression, .. } if expression == "user.name"));
                 assert!(has_text && has_expr);
             }
             _ => panic!("Expected paragraph"),
         }
     }
+
+    #[test]
+    fn test_node_spans() {
+        let md = "# Title\n\nHello {user.name}!";
+        let nodes = parse_body_at(md, Span::new(5, 1, 40)).unwrap();
+
+        let Node::Paragraph { children } = &nodes[1] else { panic!("Expected paragraph") };
+        let span = children.iter().find_map(|c| c.span()).unwrap();
+        assert_eq!((span.line, span.column, span.offset), (7, 7, 55));
+    }
+
+    #[test]
+    fn test_errors_carry_location() {
+        let md = "# Title\n\n<button />";
+        let err = parse_body(md).unwrap_err();
+        let parse_error = err.downcast_ref::<ParseError>().unwrap();
+
+        assert_eq!((parse_error.span.line, parse_error.span.column), (3, 1));
+        assert!(err.to_string().starts_with("3:1: Button must have"));
+    }
+
+    #[test]
+    fn test_parse_layout_attributes() {
+        let md = "<vstack width=\"200\" height={40} flex=\"1\" align=\"center\">\n</vstack>\n\n<spacer size={8} />\n\n<hstack min_width=\"120\" max_height=\"300\" justify=\"space-between\" gap={8} padding=\"12\">\n</hstack>";
+        let nodes = parse_body(md).unwrap();
+
+        match &nodes[0] {
+            Node::VStack { layout, .. } => {
+                assert_eq!(layout.width, Some(200.0));
+                assert_eq!(layout.height, Some(40.0));
+                assert_eq!(layout.flex, Some(1.0));
+                assert_eq!(layout.align.as_deref(), Some("center"));
+            }
+            _ => panic!("Expected vstack"),
+        }
+        assert_eq!(
+            nodes[2],
+            Node::HStack {
+                children: vec![],
+                layout: StackLayout {
+                    min_width: Some(120.0),
+                    max_height: Some(300.0),
+                    justify: Some("space-between".to_string()),
+                    gap: Some(8.0),
+                    padding: Some(12.0),
+                    ..StackLayout::default()
+                },
+            }
+        );
+        assert_eq!(nodes[1], Node::Spacer { size: Some(8.0) });
+    }
+
+    #[test]
+    fn test_parse_if_else() {
+        let md = "<if value={state.open}>\nOpen\n\n<else>\nClosed\n\n</else>\n</if>";
+        let nodes = parse_body(md).unwrap();
+
+        match &nodes[0] {
+            Node::If { children, else_children, .. } => {
+                assert_eq!(children, &vec![Node::paragraph(vec![Node::text("Open")])]);
+                assert_eq!(else_children, &Some(vec![Node::paragraph(vec![Node::text("Closed")])]));
+            }
+            _ => panic!("Expected if"),
+        }
+    }
+
+    #[test]
+    fn test_parse_code_quote_and_table() {
+        let md = "Run `cargo test` now\n\n```rust\nfn main() {}\n```\n\n> Quoted **text**\n\n| Name | Count |\n| :--- | ----: |\n| a | {state.count} |";
+        let nodes = parse_body(md).unwrap();
+
+        assert_eq!(
+            nodes[0],
+            Node::paragraph(vec![Node::text("Run "), Node::inline_code("cargo test"), Node::text(" now")])
+        );
+        assert_eq!(nodes[1], Node::code_block(Some("rust".to_string()), "fn main() {}"));
+        assert!(matches!(&nodes[2], Node::Blockquote { children } if matches!(children[0], Node::Paragraph { .. })));
+
+        match &nodes[3] {
+            Node::Table { align, rows } => {
+                assert_eq!(align, &vec![ColumnAlign::Left, ColumnAlign::Right]);
+                assert_eq!(rows.len(), 2);
+                assert_eq!(rows[0].cells[0].children, vec![Node::text("Name")]);
+                assert!(matches!(&rows[1].cells[1].children[0], Node::Expr { expression, .. } if expression == "state.count"));
+            }
+            _ => panic!("Expected table"),
+        }
+    }
+
+    #[test]
+    fn test_parse_gfm_extensions() {
+        let md = "Was ~~wrong~~ see https://example.com and www.example.org too[^src]\n\n- [x] Done\n- [ ] {state.milk} Buy milk\n- Plain\n\n[^src]: The *source*\n\n<vstack>\n    Indented text\n</vstack>";
+        let nodes = parse_body(md).unwrap();
+
+        assert_eq!(
+            nodes[0],
+            Node::paragraph(vec![
+                Node::text("Was "),
+                Node::strikethrough(vec![Node::text("wrong")]),
+                Node::text(" see "),
+                Node::Link { url: "https://example.com".to_string(), children: vec![Node::text("https://example.com")] },
+                Node::text(" and "),
+                Node::Link { url: "http://www.example.org".to_string(), children: vec![Node::text("www.example.org")] },
+                Node::text(" too"),
+                Node::FootnoteReference { label: "src".to_string() },
+            ])
+        );
+
+        match &nodes[1] {
+            Node::List { items, .. } => {
+                assert_eq!(items[0], ListItem::task(true, vec![Node::paragraph(vec![Node::text("Done")])]));
+                assert_eq!(
+                    items[1],
+                    ListItem::task(false, vec![Node::paragraph(vec![Node::text("Buy milk")])]).with_bind("state.milk")
+                );
+                assert_eq!(items[2], ListItem::new(vec![Node::paragraph(vec![Node::text("Plain")])]));
+            }
+            _ => panic!("Expected list"),
+        }
+
+        assert!(matches!(&nodes[2], Node::FootnoteDefinition { label, children } if label == "src" && children.len() == 1));
+
+        // Four spaces of indentation is an indented code block, as in plain markdown
+        assert_eq!(nodes[3], Node::vstack(vec![Node::code_block(None, "Indented text")]));
+    }
+
+    #[test]
+    fn test_parse_image_templates() {
+        let md = "![Avatar]({props.picture})\n\n<img src={note.picture} alt=\"Avatar\" width=\"48\" height={48} fit=\"cover\" />";
+        let nodes = parse_body(md).unwrap();
+
+        assert_eq!(nodes[0], Node::image("{props.picture}", "Avatar"));
+        match &nodes[1] {
+            Node::Image { src, alt, width, height, fit, .. } => {
+                assert_eq!(src, "{note.picture}");
+                assert_eq!(alt, "Avatar");
+                assert_eq!(*width, Some(48.0));
+                assert_eq!(*height, Some(48.0));
+                assert_eq!(fit.as_deref(), Some("cover"));
+            }
+            _ => panic!("Expected image"),
+        }
+
+        assert!(parse_body("<img alt=\"x\" />").is_err());
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let md = "<grid min_column_width=\"160\" gap={8} row_gap=\"12\">\n<vstack span=\"2\">\nWide\n</vstack>\n</grid>";
+        let nodes = parse_body(md).unwrap();
+
+        match &nodes[0] {
+            Node::Grid { columns, min_column_width, gap, row_gap, column_gap, children } => {
+                assert_eq!(*columns, None);
+                assert_eq!(*min_column_width, Some(160.0));
+                assert_eq!(*gap, Some(8.0));
+                assert_eq!(*row_gap, Some(12.0));
+                assert_eq!(*column_gap, None);
+                assert!(matches!(&children[0], Node::VStack { layout, .. } if layout.span == Some(2)));
+            }
+            _ => panic!("Expected grid"),
+        }
+    }
+
+    #[test]
+    fn test_parse_styles() {
+        let md = "<vstack class=\"card\" style=\"background: surface; color: red\" color={state.accent} padding=\"md\">\n  <text class={state.kind} font_size=\"h2\">Hello **there**</text>\n</vstack>";
+        let nodes = parse_body(md).unwrap();
+
+        match &nodes[0] {
+            Node::VStack { layout, children } => {
+                assert_eq!(layout.style.class.as_deref(), Some("card"));
+                assert_eq!(layout.style.properties["background"], PropValue::Literal("surface".to_string()));
+                // The attribute wins over `style`
+                assert_eq!(layout.style.properties["color"], PropValue::Expression("state.accent".to_string()));
+                // A spacing name is a style, not numeric layout
+                assert_eq!(layout.padding, None);
+                assert_eq!(layout.style.properties["padding"], PropValue::Literal("md".to_string()));
+
+                match &children[0] {
+                    Node::TextBlock { children, style } => {
+                        assert_eq!(style.class.as_deref(), Some("{state.kind}"));
+                        assert_eq!(children.len(), 2);
+                        assert!(matches!(&children[1], Node::Strong { .. }));
+                    }
+                    other => panic!("Expected text, got {:?}", other),
+                }
+            }
+            _ => panic!("Expected VStack"),
+        }
+
+        assert!(parse_body("<vstack style={state.style}>\n  x\n</vstack>").is_err());
+    }
+
+    #[test]
+    fn test_parse_virtual_each() {
+        let md = "<each from={queries.feed} as=\"note\" key={note.id} virtual height=\"400\" on_end={loadMore(feed)}>\n{note.content}\n</each>\n\n<each from={queries.feed} as=\"note\" virtual>\n{note.content}\n</each>";
+        let nodes = parse_body(md).unwrap();
+
+        match &nodes[0] {
+            Node::Each { key, viewport, on_end, .. } => {
+                assert_eq!(key.as_deref(), Some("note.id"));
+                assert_eq!(*viewport, Some(400.0));
+                assert_eq!(on_end.as_deref(), Some("loadMore(feed)"));
+            }
+            other => panic!("Expected each, got {:?}", other),
+        }
+        match &nodes[1] {
+            Node::Each { key, viewport, .. } => {
+                assert_eq!(*key, None);
+                assert_eq!(*viewport, Some(DEFAULT_VIRTUAL_HEIGHT));
+            }
+            other => panic!("Expected each, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,274 @@
+use crate::loader;
+use crate::parser::frontmatter;
+use crate::runtime::address::{
+    content_sha256, source_event_builder, AppAddress, ImportRef, APP_KIND, COMPONENT_TAG,
+    DOCUMENT_TAG,
+};
+use crate::runtime::NostrClient;
+use anyhow::{Context, Result};
+use nostr_sdk::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A source file prepared for publishing
+#[derive(Debug, Clone)]
+pub struct PublishedSource {
+    /// File the source was read from
+    pub path: PathBuf,
+    /// Address the event will be reachable at
+    pub address: AppAddress,
+    /// Source with relative imports rewritten to Nostr addresses
+    pub content: String,
+    /// Signed event
+    pub event: Event,
+}
+
+/// Everything `html6 publish` will send, in dependency order (components first, document last)
+#[derive(Debug, Clone)]
+pub struct PublishPlan {
+    pub sources: Vec<PublishedSource>,
+}
+
+impl PublishPlan {
+    /// The root document
+    pub fn root(&self) -> &PublishedSource {
+        self.sources.last().expect("plan always contains the root document")
+    }
+
+    /// All signed events
+    pub fn events(&self) -> Vec<Event> {
+        self.sources.iter().map(|s| s.event.clone()).collect()
+    }
+}
+
+/// Walk the import graph of a document and sign every file as an addressable event
+/// Relative imports are rewritten to `nostr:<kind>:<pubkey>:<d>#sha256=<hash>` references
+pub fn build_plan(path: &str, keys: &Keys) -> Result<PublishPlan> {
+    // Validate the whole graph loads before signing anything
+    loader::load_hnmd_unresolved(path, None)?;
+
+    let mut builder = PlanBuilder {
+        keys,
+        sources: Vec::new(),
+        visited: HashMap::new(),
+        in_progress: Vec::new(),
+        identifiers: HashMap::new(),
+    };
+    builder.visit(Path::new(path), DOCUMENT_TAG)?;
+
+    Ok(PublishPlan {
+        sources: builder.sources,
+    })
+}
+
+/// Send every event of a plan to the given relays
+pub async fn send_plan(plan: &PublishPlan, relays: Vec<String>) -> Result<()> {
+    let client = NostrClient::new(relays).await?;
+
+    for source in &plan.sources {
+        let output = client
+            .publish(source.event.clone())
+            .await
+            .with_context(|| format!("Failed to publish {}", source.path.display()))?;
+        println!(
+            "  ✓ {} → {} ({} ok, {} failed)",
+            source.path.display(),
+            source.address.coordinate(),
+            output.success.len(),
+            output.failed.len()
+        );
+    }
+
+    Ok(())
+}
+
+struct PlanBuilder<'a> {
+    keys: &'a Keys,
+    sources: Vec<PublishedSource>,
+    /// Canonical file path → address it was published at
+    visited: HashMap<PathBuf, AppAddress>,
+    /// Files currently being visited (for cycle detection)
+    in_progress: Vec<PathBuf>,
+    /// `d` identifier → file using it (identifiers must be unique per author)
+    identifiers: HashMap<String, PathBuf>,
+}
+
+impl PlanBuilder<'_> {
+    fn visit(&mut self, path: &Path, file_tag: &str) -> Result<AppAddress> {
+        let canonical = fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+
+        if let Some(address) = self.visited.get(&canonical) {
+            return Ok(address.clone());
+        }
+        if self.in_progress.contains(&canonical) {
+            return Err(anyhow::anyhow!("Import cycle through {}", path.display()));
+        }
+        self.in_progress.push(canonical.clone());
+
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+
+        // Publish dependencies first so their hashes can be pinned
+        let mut rewrites = Vec::new();
+        for (name, import) in frontmatter::parse_source_imports(&source)? {
+            if let ImportRef::Path(relative) = ImportRef::parse(&import)? {
+                let dependency = resolve_import(base, &relative);
+                let address = self.visit(&dependency, COMPONENT_TAG)
+                    .with_context(|| format!("Failed to publish import '{}' of {}", name, path.display()))?;
+                rewrites.push((name, import, address.to_reference()));
+            }
+        }
+        let content = rewrite_imports(&source, &rewrites)?;
+
+        let identifier = identifier_for(path);
+        if let Some(other) = self.identifiers.insert(identifier.clone(), canonical.clone()) {
+            if other != canonical {
+                return Err(anyhow::anyhow!(
+                    "{} and {} would both be published as '{}'",
+                    other.display(),
+                    path.display(),
+                    identifier
+                ));
+            }
+        }
+
+        let event = source_event_builder(&identifier, &content, file_tag)
+            .sign_with_keys(self.keys)
+            .with_context(|| format!("Failed to sign {}", path.display()))?;
+        let address = AppAddress::new(APP_KIND, self.keys.public_key(), identifier)
+            .with_sha256(content_sha256(&content));
+
+        self.in_progress.pop();
+        self.visited.insert(canonical, address.clone());
+        self.sources.push(PublishedSource {
+            path: path.to_path_buf(),
+            address: address.clone(),
+            content,
+            event,
+        });
+
+        Ok(address)
+    }
+}
+
+/// `d` identifier for a file: its lowercased file name (Profile.hnmc → profile.hnmc)
+fn identifier_for(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "app.hnmd".to_string())
+}
+
+/// Resolve an import path the same way ComponentRegistry does
+fn resolve_import(base: &Path, import: &str) -> PathBuf {
+    if import.starts_with("./") || import.starts_with("../") {
+        base.join(import)
+    } else {
+        PathBuf::from(import)
+    }
+}
+
+/// Replace import values inside the frontmatter, leaving the rest of the file untouched
+fn rewrite_imports(source: &str, rewrites: &[(String, String, String)]) -> Result<String> {
+    if rewrites.is_empty() {
+        return Ok(source.to_string());
+    }
+    let Some((fm, body)) = frontmatter::split_source(source) else {
+        return Ok(source.to_string());
+    };
+
+    let mut fm = fm.to_string();
+    for (name, old, new) in rewrites {
+        let line_re = Regex::new(&format!(
+            r#"(?m)^([ \t]*{}[ \t]*:[ \t]*)(?:"{}"|'{}'|{})[ \t]*$"#,
+            regex::escape(name),
+            regex::escape(old),
+            regex::escape(old),
+            regex::escape(old)
+        ))
+        .unwrap();
+
+        if !line_re.is_match(&fm) {
+            return Err(anyhow::anyhow!("Could not find import '{}: {}' to rewrite", name, old));
+        }
+        fm = line_re
+            .replace(&fm, |caps: &regex::Captures| format!("{}\"{}\"", &caps[1], new))
+            .into_owned();
+    }
+
+    // Keep whatever preceded the opening delimiter (normally nothing)
+    let prefix_len = source.find("---").unwrap_or(0);
+    Ok(format!("{}---{}---{}", &source[..prefix_len], fm, body))
+}
+
+/// Pretty JSON array of all signed events, for `--dry-run`
+pub fn plan_to_json(plan: &PublishPlan) -> String {
+    let events: Vec<serde_json::Value> = plan
+        .sources
+        .iter()
+        .map(|s| serde_json::from_str(&s.event.as_json()).unwrap_or_default())
+        .collect();
+    serde_json::to_string_pretty(&events).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::address::verify_source_event;
+
+    #[test]
+    fn test_rewrite_imports() {
+        let source = "---\nimports:\n  Feed: \"./components/Feed.hnmc\"\n\nstate:\n  x: 1\n---\n\n# Hi\n";
+        let rewritten = rewrite_imports(
+            source,
+            &[("Feed".to_string(), "./components/Feed.hnmc".to_string(), "nostr:30078:abc:feed".to_string())],
+        )
+        .unwrap();
+        assert!(rewritten.contains("  Feed: \"nostr:30078:abc:feed\"\n"));
+        assert!(rewritten.ends_with("---\n\n# Hi\n"));
+        assert!(rewritten.contains("state:\n  x: 1"));
+    }
+
+    #[test]
+    fn test_build_plan_for_feed() {
+        let keys = Keys::generate();
+        let plan = build_plan("apps/feed.hnmd", &keys).unwrap();
+
+        // Profile, Feed, then the document itself
+        assert_eq!(plan.sources.len(), 3);
+        assert_eq!(plan.sources[0].address.identifier, "profile.hnmc");
+        assert_eq!(plan.sources[1].address.identifier, "feed.hnmc");
+        assert_eq!(plan.root().address.identifier, "feed.hnmd");
+        assert_eq!(plan.root().path, PathBuf::from("apps/feed.hnmd"));
+
+        // Imports point at the published components, pinned by hash
+        assert!(plan.sources[1].content.contains(&plan.sources[0].address.to_reference()));
+        assert!(plan.root().content.contains(&plan.sources[1].address.to_reference()));
+
+        for source in &plan.sources {
+            verify_source_event(&source.event, &source.address).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_plan_to_json() {
+        let keys = Keys::generate();
+        let plan = build_plan("apps/test_component.hnmd", &keys).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&plan_to_json(&plan)).unwrap();
+        let events = json.as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["kind"], APP_KIND);
+        assert_eq!(events[1]["pubkey"], keys.public_key().to_hex());
+    }
+
+    #[test]
+    fn test_published_document_parses() {
+        let keys = Keys::generate();
+        let plan = build_plan("apps/feed.hnmd", &keys).unwrap();
+        let doc = loader::parse_hnmd(&plan.root().content).unwrap();
+        assert!(ImportRef::parse(&doc.imports["Feed"]).unwrap().is_address());
+    }
+}
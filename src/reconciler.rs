@@ -6,7 +6,7 @@
 /// - Reuses unchanged widgets (preserves input focus!)
 /// - Only rebuilds changed subtrees
 
-use crate::parser::ast::Node;
+use crate::parser::ast::{is_template, Node};
 use crate::renderer::RenderContext;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
@@ -153,7 +153,7 @@ fn build_state_with_expr_hash(
     ctx: &mut Option<RenderContext>,
     generation: u32,
 ) -> WidgetState {
-    let expr_hash = if let Node::Expr { expression } = node {
+    let expr_hash = if let Node::Expr { expression, .. } = node {
         // Evaluate expression and hash the result
         if let Some(context) = ctx {
             match context.eval(expression) {
@@ -193,21 +193,35 @@ fn nodes_equal_with_state(a: &WidgetState, b: &WidgetState) -> bool {
 fn node_contains_expr(node: &Node) -> bool {
     match node {
         Node::Expr { .. } => true,
+        Node::Image { src, alt, .. } => is_template(src) || is_template(alt),
         Node::Heading { children, .. } => children.iter().any(node_contains_expr),
         Node::Paragraph { children } => children.iter().any(node_contains_expr),
         Node::Strong { children } => children.iter().any(node_contains_expr),
         Node::Emphasis { children } => children.iter().any(node_contains_expr),
+        Node::Strikethrough { children } => children.iter().any(node_contains_expr),
         Node::Link { children, .. } => children.iter().any(node_contains_expr),
-        Node::List { items, .. } => items.iter().any(|item| item.children.iter().any(node_contains_expr)),
-        Node::VStack { children, .. } => children.iter().any(node_contains_expr),
-        Node::HStack { children, .. } => children.iter().any(node_contains_expr),
+        Node::Blockquote { children } => children.iter().any(node_contains_expr),
+        Node::FootnoteDefinition { children, .. } => children.iter().any(node_contains_expr),
+        Node::Table { rows, .. } => rows
+            .iter()
+            .any(|row| row.cells.iter().any(|cell| cell.children.iter().any(node_contains_expr))),
+        Node::List { items, .. } => items
+            .iter()
+            .any(|item| item.bind.is_some() || item.children.iter().any(node_contains_expr)),
+        Node::VStack { children, layout } | Node::HStack { children, layout } => {
+            layout.style.is_dynamic() || children.iter().any(node_contains_expr)
+        }
+        Node::TextBlock { children, style } => style.is_dynamic() || children.iter().any(node_contains_expr),
         Node::Grid { children, .. } => children.iter().any(node_contains_expr),
         Node::Each { children, .. } => children.iter().any(node_contains_expr),
         Node::If { children, else_children, .. } => {
             children.iter().any(node_contains_expr) ||
             else_children.as_ref().map(|ec| ec.iter().any(node_contains_expr)).unwrap_or(false)
         }
-        Node::Button { children, .. } => children.iter().any(node_contains_expr),
+        Node::Button { on_click, children, .. } => {
+            on_click.as_deref().and_then(crate::navigation::navigate_target).is_some()
+                || children.iter().any(node_contains_expr)
+        }
         _ => false,
     }
 }
@@ -230,45 +244,82 @@ fn nodes_equal(a: &Node, b: &Node) -> bool {
 
         (Emphasis { children: c1 }, Emphasis { children: c2 }) => children_equal(c1, c2),
 
+        (Strikethrough { children: c1 }, Strikethrough { children: c2 }) => children_equal(c1, c2),
+
         (Link { url: u1, children: c1 }, Link { url: u2, children: c2 }) => {
             u1 == u2 && children_equal(c1, c2)
         }
 
-        (Image { src: s1, alt: a1 }, Image { src: s2, alt: a2 }) => s1 == s2 && a1 == a2,
+        (
+            Image { src: s1, alt: a1, width: w1, height: h1, fit: f1, .. },
+            Image { src: s2, alt: a2, width: w2, height: h2, fit: f2, .. },
+        ) => s1 == s2 && a1 == a2 && w1 == w2 && h1 == h2 && f1 == f2,
+
+        (InlineCode { value: v1 }, InlineCode { value: v2 }) => v1 == v2,
+
+        (FootnoteReference { label: l1 }, FootnoteReference { label: l2 }) => l1 == l2,
+
+        (FootnoteDefinition { label: l1, children: c1 }, FootnoteDefinition { label: l2, children: c2 }) => {
+            l1 == l2 && children_equal(c1, c2)
+        }
+
+        (CodeBlock { lang: l1, value: v1 }, CodeBlock { lang: l2, value: v2 }) => l1 == l2 && v1 == v2,
+
+        (Blockquote { children: c1 }, Blockquote { children: c2 }) => children_equal(c1, c2),
+
+        (Table { align: a1, rows: r1 }, Table { align: a2, rows: r2 }) => {
+            a1 == a2
+                && r1.len() == r2.len()
+                && r1.iter().zip(r2).all(|(a, b)| {
+                    a.cells.len() == b.cells.len()
+                        && a.cells.iter().zip(&b.cells).all(|(a, b)| children_equal(&a.children, &b.children))
+                })
+        }
 
         (List { ordered: o1, items: i1 }, List { ordered: o2, items: i2 }) => {
-            o1 == o2 && i1.len() == i2.len() && i1.iter().zip(i2).all(|(a, b)| children_equal(&a.children, &b.children))
+            o1 == o2
+                && i1.len() == i2.len()
+                && i1.iter().zip(i2).all(|(a, b)| {
+                    a.checked == b.checked && a.bind == b.bind && children_equal(&a.children, &b.children)
+                })
         }
 
-        (Expr { expression: e1 }, Expr { expression: e2 }) => e1 == e2,
+        (Expr { expression: e1, .. }, Expr { expression: e2, .. }) => e1 == e2,
 
-        (Button { on_click: oc1, children: c1 }, Button { on_click: oc2, children: c2 }) => {
+        (Button { on_click: oc1, children: c1, .. }, Button { on_click: oc2, children: c2, .. }) => {
             oc1 == oc2 && children_equal(c1, c2)
         }
 
-        (Input { name: n1, placeholder: p1 }, Input { name: n2, placeholder: p2 }) => {
+        (Input { name: n1, placeholder: p1, .. }, Input { name: n2, placeholder: p2, .. }) => {
             n1 == n2 && p1 == p2
         }
 
-        (VStack { children: c1, flex: f1, .. }, VStack { children: c2, flex: f2, .. }) => {
-            f1 == f2 && children_equal(c1, c2)
+        (VStack { children: c1, layout: l1 }, VStack { children: c2, layout: l2 }) => {
+            l1 == l2 && children_equal(c1, c2)
         }
 
-        (HStack { children: c1, flex: f1, .. }, HStack { children: c2, flex: f2, .. }) => {
-            f1 == f2 && children_equal(c1, c2)
+        (HStack { children: c1, layout: l1 }, HStack { children: c2, layout: l2 }) => {
+            l1 == l2 && children_equal(c1, c2)
         }
 
-        (Grid { children: c1, columns: col1 }, Grid { children: c2, columns: col2 }) => {
-            col1 == col2 && children_equal(c1, c2)
+        (TextBlock { children: c1, style: s1 }, TextBlock { children: c2, style: s2 }) => {
+            s1 == s2 && children_equal(c1, c2)
         }
 
+        (
+            Grid { columns: col1, min_column_width: m1, gap: g1, row_gap: r1, column_gap: cg1, children: c1 },
+            Grid { columns: col2, min_column_width: m2, gap: g2, row_gap: r2, column_gap: cg2, children: c2 },
+        ) => col1 == col2 && m1 == m2 && g1 == g2 && r1 == r2 && cg1 == cg2 && children_equal(c1, c2),
+
         (Spacer { size: s1 }, Spacer { size: s2 }) => s1 == s2,
+        (ThematicBreak, ThematicBreak) => true,
 
-        (Each { from: f1, as_name: a1, children: c1 }, Each { from: f2, as_name: a2, children: c2 }) => {
-            f1 == f2 && a1 == a2 && children_equal(c1, c2)
-        }
+        (
+            Each { from: f1, as_name: a1, key: k1, viewport: v1, on_end: o1, children: c1, .. },
+            Each { from: f2, as_name: a2, key: k2, viewport: v2, on_end: o2, children: c2, .. },
+        ) => f1 == f2 && a1 == a2 && k1 == k2 && v1 == v2 && o1 == o2 && children_equal(c1, c2),
 
-        (If { value: v1, children: c1, else_children: e1 }, If { value: v2, children: c2, else_children: e2 }) => {
+        (If { value: v1, children: c1, else_children: e1, .. }, If { value: v2, children: c2, else_children: e2, .. }) => {
             v1 == v2 && children_equal(c1, c2) &&
             match (e1, e2) {
                 (Some(ec1), Some(ec2)) => children_equal(ec1, ec2),
@@ -335,6 +386,15 @@ mod tests {
 
         assert!(nodes_equal(&heading1, &heading2));
         assert!(!nodes_equal(&heading1, &heading3));
+
+        // Any layout attribute change rebuilds the stack
+        let stack1 = Node::vstack(vec![Node::text("A")]);
+        let stack2 = Node::VStack {
+            children: vec![Node::text("A")],
+            layout: crate::parser::ast::StackLayout { gap: Some(8.0), ..Default::default() },
+        };
+        assert!(nodes_equal(&stack1, &stack1.clone()));
+        assert!(!nodes_equal(&stack1, &stack2));
     }
 
     #[test]
@@ -356,4 +416,33 @@ mod tests {
         // Position 3: nothing → "C" = Add
         assert!(matches!(ops[3], ReconcileOp::Add));
     }
+
+    #[test]
+    fn test_image_templates_rebuild() {
+        let nodes = vec![Node::image("apps/waffle_dog.jpeg", "Dog"), Node::image("{props.picture}", "Avatar")];
+        let arena = WidgetArena::from_nodes(&nodes);
+
+        // A literal image is kept; one whose source is evaluated is re-rendered
+        let (_, ops) = reconcile_arena(&arena, &nodes, &mut None);
+        assert!(matches!(ops[0], ReconcileOp::Keep));
+        assert!(matches!(ops[1], ReconcileOp::Rebuild));
+    }
+
+    #[test]
+    fn test_dynamic_styles_rebuild() {
+        use crate::parser::ast::{NodeStyle, PropValue};
+
+        let literal = NodeStyle { class: Some("card".to_string()), ..Default::default() };
+        let mut dynamic = NodeStyle::default();
+        dynamic.properties.insert("color".to_string(), PropValue::Expression("state.accent".to_string()));
+        let nodes = vec![
+            Node::text_block(vec![Node::text("Fixed")], literal),
+            Node::text_block(vec![Node::text("Tinted")], dynamic),
+        ];
+        let arena = WidgetArena::from_nodes(&nodes);
+
+        let (_, ops) = reconcile_arena(&arena, &nodes, &mut None);
+        assert!(matches!(ops[0], ReconcileOp::Keep));
+        assert!(matches!(ops[1], ReconcileOp::Rebuild));
+    }
 }
@@ -1,7 +1,9 @@
+pub mod style;
 pub mod vendored;
 pub mod widgets;
 
 pub use widgets::{
     build_document_widget, build_document_widget_tagged, build_document_widget_with_context,
-    build_widget, build_widget_with_context, RenderContext,
+    build_widget, build_widget_with_context, image_brush, RemoteImages, RenderContext,
 };
+pub use style::{Appearance, StyleSheet};
@@ -0,0 +1,414 @@
+use crate::parser::ast::{StyleRules, Theme};
+use masonry::parley::style::FontWeight;
+use masonry::peniko::Color;
+use masonry::peniko::color::{parse_color, Srgb};
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+use std::sync::{LazyLock, Mutex};
+
+/// Style warnings already printed; pages are rebuilt on every change, and the `check` command lists them all anyway
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Mutex::default);
+
+/// Print a warning the first time it comes up; false if it was printed before
+fn warn_once(message: String) -> bool {
+    let first = WARNED.lock().unwrap().insert(message.clone());
+    if first {
+        eprintln!("⚠️  {}", message);
+    }
+    first
+}
+
+/// Which built-in theme documents are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Appearance {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    /// "light" or "dark"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "light" => Some(Appearance::Light),
+            "dark" => Some(Appearance::Dark),
+            _ => None,
+        }
+    }
+
+    /// Name documents see as `theme`
+    pub fn name(self) -> &'static str {
+        match self {
+            Appearance::Light => "light",
+            Appearance::Dark => "dark",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Appearance::Light => Appearance::Dark,
+            Appearance::Dark => Appearance::Light,
+        }
+    }
+
+    /// The built-in theme for this appearance
+    pub fn theme(self) -> Theme {
+        match self {
+            Appearance::Light => light_theme(),
+            Appearance::Dark => dark_theme(),
+        }
+    }
+
+    /// The desktop's light/dark setting; None where it can't be read
+    pub fn system() -> Option<Self> {
+        let output = |program: &str, args: &[&str]| {
+            Command::new(program)
+                .args(args)
+                .output()
+                .ok()
+                .map(|out| (out.status.success(), String::from_utf8_lossy(&out.stdout).to_lowercase()))
+        };
+
+        if cfg!(target_os = "macos") {
+            // The key only exists while dark mode is on
+            let (found, value) = output("defaults", &["read", "-g", "AppleInterfaceStyle"])?;
+            Some(if found && value.contains("dark") { Appearance::Dark } else { Appearance::Light })
+        } else if cfg!(target_os = "windows") {
+            let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+            let (found, value) = output("reg", &["query", key, "/v", "AppsUseLightTheme"])?;
+            let light = found.then(|| reg_dword(&value, "appsuselighttheme")).flatten()?;
+            Some(if light == 0 { Appearance::Dark } else { Appearance::Light })
+        } else {
+            let (found, value) = output("gsettings", &["get", "org.gnome.desktop.interface", "color-scheme"])?;
+            found.then(|| if value.contains("dark") { Appearance::Dark } else { Appearance::Light })
+        }
+    }
+}
+
+/// A `REG_DWORD` value from `reg query` output, e.g. `    AppsUseLightTheme    REG_DWORD    0x1`
+fn reg_dword(output: &str, name: &str) -> Option<u32> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if !fields.next()?.eq_ignore_ascii_case(name) || !fields.next()?.eq_ignore_ascii_case("REG_DWORD") {
+            return None;
+        }
+        let value = fields.next()?.to_ascii_lowercase();
+        u32::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+    })
+}
+
+/// Built-in light theme; a document's `theme:` adds to or overrides these entries
+pub fn light_theme() -> Theme {
+    theme_with_palette(&[
+        ("text", "#000000"),
+        ("muted", "#646464"),
+        ("background", "#ececec"),
+        ("surface", "#f5f5f5"),
+        ("border", "#c8c8c8"),
+        ("button", "#c8c8c8"),
+        ("control_border", "#808080"),
+        ("input", "#ffffff"),
+        ("primary", "#007aff"),
+        ("selection", "#add6ff"),
+        ("selection_inactive", "#c8c8c8"),
+        ("hr", "#b4b4b4"),
+    ])
+}
+
+/// Built-in dark theme; same names, sizes and spacing as the light one
+pub fn dark_theme() -> Theme {
+    theme_with_palette(&[
+        ("text", "#e6e6e6"),
+        ("muted", "#9a9a9a"),
+        ("background", "#1e1e1e"),
+        ("surface", "#2a2a2a"),
+        ("border", "#3c3c3c"),
+        ("button", "#3a3a3a"),
+        ("control_border", "#6a6a6a"),
+        ("input", "#262626"),
+        ("primary", "#0a84ff"),
+        ("selection", "#264f78"),
+        ("selection_inactive", "#3a3a3a"),
+        ("hr", "#4a4a4a"),
+    ])
+}
+
+fn theme_with_palette(palette: &[(&str, &str)]) -> Theme {
+    let font_sizes = [
+        ("body", 18.0),
+        ("h1", 40.0),
+        ("h2", 30.0),
+        ("h3", 24.0),
+        ("h4", 20.0),
+        ("h5", 18.0),
+        ("h6", 16.0),
+        ("code", 15.0),
+        ("table", 16.0),
+        ("small", 14.0),
+    ];
+    let spacing = [("xs", 4.0), ("sm", 8.0), ("md", 12.0), ("lg", 16.0), ("xl", 24.0)];
+
+    Theme {
+        palette: palette.iter().map(|(name, color)| (name.to_string(), color.to_string())).collect(),
+        font_sizes: font_sizes.iter().map(|(name, size)| (name.to_string(), *size)).collect(),
+        spacing: spacing.iter().map(|(name, px)| (name.to_string(), *px)).collect(),
+    }
+}
+
+/// Resolved properties of a styled node; unset ones fall back to the parent or the theme
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComputedStyle {
+    pub color: Option<Color>,
+    pub font_size: Option<f32>,
+    pub font_weight: Option<FontWeight>,
+    pub background: Option<Color>,
+    pub border_color: Option<Color>,
+    pub border_width: Option<f64>,
+    pub radius: Option<f64>,
+    pub padding: Option<f64>,
+}
+
+impl ComputedStyle {
+    /// The text properties children inherit, with ours winning over the parent's
+    pub fn inherit(&self, parent: &ComputedStyle) -> ComputedStyle {
+        ComputedStyle {
+            color: self.color.or(parent.color),
+            font_size: self.font_size.or(parent.font_size),
+            font_weight: self.font_weight.or(parent.font_weight),
+            ..ComputedStyle::default()
+        }
+    }
+}
+
+/// Theme and style classes that `class`/`style` attributes resolve against
+#[derive(Debug, Clone)]
+pub struct StyleSheet {
+    pub appearance: Appearance,
+    pub theme: Theme,
+    /// Classes from the document's `styles:`
+    pub classes: HashMap<String, StyleRules>,
+    /// Classes of the component being rendered; they win over the document's
+    pub component_classes: HashMap<String, StyleRules>,
+}
+
+impl Default for StyleSheet {
+    fn default() -> Self {
+        Self::new(&Theme::default(), HashMap::new())
+    }
+}
+
+impl StyleSheet {
+    /// The built-in light theme with `theme` over it
+    pub fn new(theme: &Theme, classes: HashMap<String, StyleRules>) -> Self {
+        Self::with_appearance(Appearance::Light, theme, classes)
+    }
+
+    /// The light or dark built-in theme with `theme` over it
+    pub fn with_appearance(appearance: Appearance, theme: &Theme, classes: HashMap<String, StyleRules>) -> Self {
+        let mut resolved = appearance.theme();
+        resolved.palette.extend(theme.palette.clone());
+        resolved.font_sizes.extend(theme.font_sizes.clone());
+        resolved.spacing.extend(theme.spacing.clone());
+        Self {
+            appearance,
+            theme: resolved,
+            classes,
+            component_classes: HashMap::new(),
+        }
+    }
+
+    /// This sheet for rendering a component with its own classes
+    pub fn for_component(&self, classes: &HashMap<String, StyleRules>) -> Self {
+        Self {
+            component_classes: classes.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Palette name or CSS color (`#rrggbb`, `rgb(...)`, `red`, ...)
+    pub fn parse_color(&self, value: &str) -> Option<Color> {
+        let value = self.theme.palette.get(value).map(String::as_str).unwrap_or(value);
+        parse_color(value).ok().map(|color| color.to_alpha_color::<Srgb>())
+    }
+
+    /// A palette color; black if a `theme:` set it to something unreadable
+    pub fn color(&self, name: &str) -> Color {
+        self.parse_color(name).unwrap_or(Color::BLACK)
+    }
+
+    /// Font size name or pixels
+    pub fn parse_font_size(&self, value: &str) -> Option<f32> {
+        self.theme.font_sizes.get(value).copied().or_else(|| value.parse().ok()).map(|size| size as f32)
+    }
+
+    /// A named font size
+    pub fn font_size(&self, name: &str) -> f32 {
+        self.parse_font_size(name).unwrap_or(18.0)
+    }
+
+    /// Spacing name or pixels
+    pub fn parse_spacing(&self, value: &str) -> Option<f64> {
+        self.theme.spacing.get(value).copied().or_else(|| value.parse().ok())
+    }
+
+    /// Whether a class is defined for the document or the current component
+    pub fn has_class(&self, class: &str) -> bool {
+        self.component_classes.contains_key(class) || self.classes.contains_key(class)
+    }
+
+    /// Style of a node: its classes in order, then its own properties
+    /// Values that don't resolve are reported and skipped
+    pub fn compute(&self, class: &str, properties: &[(String, String)]) -> ComputedStyle {
+        let mut style = ComputedStyle::default();
+        for name in class.split_whitespace() {
+            let rules = self.component_classes.get(name).or_else(|| self.classes.get(name));
+            let Some(rules) = rules else {
+                warn_once(format!("Unknown style class '{}'", name));
+                continue;
+            };
+            let mut rules: Vec<(&String, &String)> = rules.iter().collect();
+            rules.sort();
+            for (property, value) in rules {
+                self.apply(&mut style, property, value);
+            }
+        }
+        for (property, value) in properties {
+            self.apply(&mut style, property, value);
+        }
+        style
+    }
+
+    fn apply(&self, style: &mut ComputedStyle, property: &str, value: &str) {
+        let applied = match property {
+            "color" => self.parse_color(value).map(|c| style.color = Some(c)),
+            "background" => self.parse_color(value).map(|c| style.background = Some(c)),
+            "border_color" => self.parse_color(value).map(|c| style.border_color = Some(c)),
+            "font_size" => self.parse_font_size(value).map(|s| style.font_size = Some(s)),
+            "font_weight" => font_weight(value).map(|w| style.font_weight = Some(w)),
+            "border_width" => value.parse().ok().map(|w| style.border_width = Some(w)),
+            "radius" => value.parse().ok().map(|r| style.radius = Some(r)),
+            "padding" => self.parse_spacing(value).map(|p| style.padding = Some(p)),
+            _ => {
+                warn_once(format!("Unknown style property '{}'", property));
+                return;
+            }
+        };
+        if applied.is_none() {
+            warn_once(format!("Invalid {} '{}'", property, value));
+        }
+    }
+}
+
+/// `font_weight` values: "normal", "bold" or a number from 1 to 1000
+pub fn font_weight(value: &str) -> Option<FontWeight> {
+    match value {
+        "normal" => Some(FontWeight::NORMAL),
+        "bold" => Some(FontWeight::BOLD),
+        _ => value
+            .parse::<f32>()
+            .ok()
+            .filter(|weight| (1.0..=1000.0).contains(weight))
+            .map(FontWeight::new),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_overrides() {
+        let mut theme = Theme::default();
+        theme.palette.insert("primary".to_string(), "#7c3aed".to_string());
+        theme.spacing.insert("md".to_string(), 10.0);
+        let sheet = StyleSheet::new(&theme, HashMap::new());
+
+        assert_eq!(sheet.color("primary"), Color::from_rgb8(0x7c, 0x3a, 0xed));
+        // Built-in entries the document didn't touch are kept
+        assert_eq!(sheet.color("text"), Color::from_rgb8(0, 0, 0));
+        assert_eq!(sheet.parse_spacing("md"), Some(10.0));
+        assert_eq!(sheet.parse_spacing("7"), Some(7.0));
+        assert_eq!(sheet.parse_font_size("h1"), Some(40.0));
+        assert_eq!(sheet.parse_color("rgb(255, 0, 0)"), Some(Color::from_rgb8(255, 0, 0)));
+        assert_eq!(sheet.parse_color("not a color"), None);
+    }
+
+    #[test]
+    fn test_warnings_print_once() {
+        assert!(warn_once("Unknown style class 'test-warn-once'".to_string()));
+        assert!(!warn_once("Unknown style class 'test-warn-once'".to_string()));
+
+        // Rendering the same page again doesn't repeat its warnings
+        let sheet = StyleSheet::default();
+        sheet.compute("test-warn-twice", &[]);
+        assert!(!warn_once("Unknown style class 'test-warn-twice'".to_string()));
+    }
+
+    #[test]
+    fn test_reg_dword() {
+        let output = "\nHKEY_CURRENT_USER\\Software\\Microsoft\n    AppsUseLightTheme    REG_DWORD    0x0\n";
+        assert_eq!(reg_dword(output, "AppsUseLightTheme"), Some(0));
+        // Values that merely contain "0x0" aren't dark
+        assert_eq!(reg_dword("    AppsUseLightTheme    REG_DWORD    0x10\n", "appsuselighttheme"), Some(16));
+        assert_eq!(reg_dword("    SystemUsesLightTheme    REG_DWORD    0x0\n", "AppsUseLightTheme"), None);
+        assert_eq!(reg_dword("ERROR: The system was unable to find the specified registry key", "AppsUseLightTheme"), None);
+    }
+
+    #[test]
+    fn test_dark_theme() {
+        let mut theme = Theme::default();
+        theme.palette.insert("primary".to_string(), "#7c3aed".to_string());
+        let light = StyleSheet::new(&theme, HashMap::new());
+        let dark = StyleSheet::with_appearance(Appearance::Dark, &theme, HashMap::new());
+
+        assert_eq!(dark.appearance, Appearance::Dark);
+        assert_ne!(dark.color("text"), light.color("text"));
+        assert_ne!(dark.color("background"), light.color("background"));
+        // The document's entries apply to either theme
+        assert_eq!(dark.color("primary"), Color::from_rgb8(0x7c, 0x3a, 0xed));
+        // Both themes name the same entries
+        let names = |theme: Theme| {
+            let mut names: Vec<String> = theme.palette.into_keys().collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(light_theme()), names(dark_theme()));
+
+        assert_eq!(Appearance::parse("dark"), Some(Appearance::Dark));
+        assert_eq!(Appearance::parse("system"), None);
+        assert_eq!(Appearance::Light.toggled().name(), "dark");
+    }
+
+    #[test]
+    fn test_compute_cascade() {
+        let rule = |pairs: &[(&str, &str)]| -> StyleRules {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let mut classes = HashMap::new();
+        classes.insert("card".to_string(), rule(&[("background", "surface"), ("padding", "md")]));
+        classes.insert("title".to_string(), rule(&[("font_size", "h2"), ("color", "muted")]));
+        let sheet = StyleSheet::new(&Theme::default(), classes);
+
+        // Later classes and then the node's own properties win
+        let style = sheet.compute("card title", &[("color".to_string(), "primary".to_string())]);
+        assert_eq!(style.background, Some(sheet.color("surface")));
+        assert_eq!(style.padding, Some(12.0));
+        assert_eq!(style.font_size, Some(30.0));
+        assert_eq!(style.color, Some(sheet.color("primary")));
+
+        // A component's class shadows the document's only inside that component
+        let mut own = HashMap::new();
+        own.insert("title".to_string(), rule(&[("font_weight", "bold")]));
+        let component = sheet.for_component(&own);
+        let style = component.compute("title", &[]);
+        assert_eq!(style.font_weight, Some(FontWeight::BOLD));
+        assert_eq!(style.font_size, None);
+        assert_eq!(sheet.compute("title", &[]).font_size, Some(30.0));
+
+        // Only text properties are inherited
+        let child = ComputedStyle { font_size: Some(12.0), ..Default::default() }.inherit(&sheet.compute("card title", &[]));
+        assert_eq!(child.font_size, Some(12.0));
+        assert_eq!(child.color, Some(sheet.color("muted")));
+        assert_eq!(child.background, None);
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2025 HNMD Authors
+// SPDX-License-Identifier: Apache-2.0
+//
+// Grid that flows its cells into rows (Masonry's Grid has fixed dimensions and equal rows)
+
+use masonry::accesskit::{Node, Role};
+use masonry::core::{
+    AccessCtx, BoxConstraints, ChildrenIds, LayoutCtx, NewWidget, NoAction, PaintCtx, PropertiesMut,
+    PropertiesRef, RegisterCtx, Update, UpdateCtx, Widget, WidgetId, WidgetPod,
+};
+use masonry::vello::kurbo::{Point, Size};
+use masonry::vello::Scene;
+use tracing::{Span, trace_span};
+
+/// Width of each fixed column when the grid is given unbounded width
+const FALLBACK_COLUMN_WIDTH: f64 = 150.0;
+
+/// How many columns a grid has
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridColumns {
+    /// Exactly this many
+    Fixed(usize),
+    /// As many as fit while each is at least this wide
+    MinWidth(f64),
+}
+
+impl GridColumns {
+    /// Number of columns that fit in `width`
+    pub fn count(self, width: f64, gap: f64) -> usize {
+        match self {
+            GridColumns::Fixed(n) => n.max(1),
+            GridColumns::MinWidth(min) => (((width + gap) / (min + gap)).floor() as usize).max(1),
+        }
+    }
+
+    /// Width to take when any width is allowed (e.g. inside a horizontal stack): fixed columns
+    /// are 150px each, and an auto-fill grid has nothing to fill, so it is one column at its minimum
+    pub fn natural_width(self, gap: f64) -> f64 {
+        match self {
+            GridColumns::Fixed(n) => {
+                let n = n.max(1) as f64;
+                n * FALLBACK_COLUMN_WIDTH + (n - 1.0) * gap
+            }
+            GridColumns::MinWidth(min) => min,
+        }
+    }
+}
+
+struct Cell {
+    widget: WidgetPod<dyn Widget>,
+    span: usize,
+    row_span: usize,
+}
+
+/// Lays cells out left to right in rows; rows are as tall as their tallest cell
+pub struct AutoGrid {
+    cells: Vec<Cell>,
+    columns: GridColumns,
+    row_gap: f64,
+    column_gap: f64,
+}
+
+impl AutoGrid {
+    pub fn new(columns: GridColumns) -> Self {
+        Self {
+            cells: Vec::new(),
+            columns,
+            row_gap: 0.0,
+            column_gap: 0.0,
+        }
+    }
+
+    pub fn with_gaps(mut self, row_gap: f64, column_gap: f64) -> Self {
+        self.row_gap = row_gap;
+        self.column_gap = column_gap;
+        self
+    }
+
+    /// Add a cell covering `span` columns and `row_span` rows
+    pub fn with_cell(mut self, widget: NewWidget<impl Widget + ?Sized>, span: usize, row_span: usize) -> Self {
+        self.cells.push(Cell {
+            widget: widget.erased().to_pod(),
+            span: span.max(1),
+            row_span: row_span.max(1),
+        });
+        self
+    }
+}
+
+/// (row, column) of each cell, filling the first free slot that fits it in reading order
+pub fn place_cells(spans: &[(usize, usize)], columns: usize) -> Vec<(usize, usize)> {
+    let mut occupied: Vec<Vec<bool>> = Vec::new();
+    let mut cursor = (0, 0);
+    let mut positions = Vec::with_capacity(spans.len());
+
+    for &(span, row_span) in spans {
+        let span = span.min(columns);
+        let (mut row, mut col) = cursor;
+        loop {
+            if col + span > columns {
+                row += 1;
+                col = 0;
+                continue;
+            }
+            let free = (row..row + row_span)
+                .all(|r| (col..col + span).all(|c| !occupied.get(r).is_some_and(|cells| cells[c])));
+            if free {
+                break;
+            }
+            col += 1;
+        }
+
+        while occupied.len() < row + row_span {
+            occupied.push(vec![false; columns]);
+        }
+        for cells in &mut occupied[row..row + row_span] {
+            cells[col..col + span].fill(true);
+        }
+        positions.push((row, col));
+        cursor = (row, col + span);
+    }
+    positions
+}
+
+impl Widget for AutoGrid {
+    type Action = NoAction;
+
+    fn register_children(&mut self, ctx: &mut RegisterCtx<'_>) {
+        for cell in &mut self.cells {
+            ctx.register_child(&mut cell.widget);
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx<'_>, _props: &mut PropertiesMut<'_>, _event: &Update) {}
+
+    fn layout(&mut self, ctx: &mut LayoutCtx<'_>, _props: &mut PropertiesMut<'_>, bc: &BoxConstraints) -> Size {
+        let gap = self.column_gap;
+        let width = if bc.max().width.is_finite() {
+            bc.max().width
+        } else {
+            self.columns.natural_width(gap).max(bc.min().width)
+        };
+        let columns = self.columns.count(width, gap);
+        let column_width = ((width - gap * (columns - 1) as f64) / columns as f64).max(0.0);
+
+        let spans: Vec<(usize, usize)> = self.cells.iter().map(|cell| (cell.span, cell.row_span)).collect();
+        let positions = place_cells(&spans, columns);
+        let rows = positions.iter().zip(&spans).map(|(&(row, _), &(_, rs))| row + rs).max().unwrap_or(0);
+
+        // Cells get their columns' width and whatever height they need
+        let mut heights = Vec::with_capacity(self.cells.len());
+        for cell in &mut self.cells {
+            let span = cell.span.min(columns) as f64;
+            let cell_width = span * column_width + (span - 1.0) * gap;
+            let cell_bc = BoxConstraints::new(Size::new(cell_width, 0.0), Size::new(cell_width, f64::INFINITY));
+            heights.push(ctx.run_layout(&mut cell.widget, &cell_bc).height);
+        }
+
+        // Rows fit their tallest one-row cell, then grow to fit cells spanning several rows
+        let mut row_heights = vec![0.0f64; rows];
+        for ((&(row, _), &(_, row_span)), &height) in positions.iter().zip(&spans).zip(&heights) {
+            if row_span == 1 {
+                row_heights[row] = row_heights[row].max(height);
+            }
+        }
+        for ((&(row, _), &(_, row_span)), &height) in positions.iter().zip(&spans).zip(&heights) {
+            if row_span > 1 {
+                let covered: f64 = row_heights[row..row + row_span].iter().sum::<f64>()
+                    + self.row_gap * (row_span - 1) as f64;
+                if height > covered {
+                    row_heights[row + row_span - 1] += height - covered;
+                }
+            }
+        }
+
+        let mut row_tops = Vec::with_capacity(rows);
+        let mut y = 0.0;
+        for height in &row_heights {
+            row_tops.push(y);
+            y += height + self.row_gap;
+        }
+        let total_height = if rows == 0 { 0.0 } else { y - self.row_gap };
+
+        for (cell, &(row, col)) in self.cells.iter_mut().zip(&positions) {
+            let x = col as f64 * (column_width + gap);
+            ctx.place_child(&mut cell.widget, Point::new(x, row_tops[row]));
+        }
+
+        bc.constrain(Size::new(width, total_height))
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx<'_>, _props: &PropertiesRef<'_>, _scene: &mut Scene) {}
+
+    fn accessibility_role(&self) -> Role {
+        Role::GenericContainer
+    }
+
+    fn accessibility(&mut self, _ctx: &mut AccessCtx<'_>, _props: &PropertiesRef<'_>, _node: &mut Node) {}
+
+    fn children_ids(&self) -> ChildrenIds {
+        self.cells.iter().map(|cell| cell.widget.id()).collect()
+    }
+
+    fn make_trace_span(&self, id: WidgetId) -> Span {
+        trace_span!("AutoGrid", id = id.trace())
+    }
+
+    fn get_debug_text(&self) -> Option<String> {
+        Some(format!("{:?}, {} cells", self.columns, self.cells.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use masonry::properties::types::Length;
+    use masonry::testing::TestHarness;
+    use masonry::theme::default_property_set;
+    use masonry::vello::kurbo::Rect;
+    use masonry::widgets::SizedBox;
+
+    fn cell(height: f64) -> NewWidget<SizedBox> {
+        NewWidget::new(SizedBox::empty().height(Length::px(height)))
+    }
+
+    #[test]
+    fn test_column_count() {
+        assert_eq!(GridColumns::Fixed(3).count(100.0, 8.0), 3);
+        assert_eq!(GridColumns::Fixed(0).count(100.0, 8.0), 1);
+        // 3 * 150 + 2 * 10 = 470 fits three, 469 only two
+        assert_eq!(GridColumns::MinWidth(150.0).count(470.0, 10.0), 3);
+        assert_eq!(GridColumns::MinWidth(150.0).count(469.0, 10.0), 2);
+        assert_eq!(GridColumns::MinWidth(150.0).count(50.0, 10.0), 1);
+
+        assert_eq!(GridColumns::Fixed(2).natural_width(10.0), 310.0);
+        assert_eq!(GridColumns::MinWidth(120.0).natural_width(10.0), 120.0);
+    }
+
+    #[test]
+    fn test_place_cells() {
+        // A wide cell that doesn't fit moves to the next row
+        let positions = place_cells(&[(1, 1), (1, 1), (2, 1), (1, 1)], 3);
+        assert_eq!(positions, vec![(0, 0), (0, 1), (1, 0), (1, 2)]);
+
+        // Cells flow around one spanning two rows
+        let positions = place_cells(&[(1, 2), (1, 1), (1, 1), (1, 1)], 2);
+        assert_eq!(positions, vec![(0, 0), (0, 1), (1, 1), (2, 0)]);
+
+        // Spans wider than the grid are clamped
+        assert_eq!(place_cells(&[(5, 1), (1, 1)], 2), vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_cells_laid_out() {
+        let grid = AutoGrid::new(GridColumns::MinWidth(120.0))
+            .with_gaps(8.0, 10.0)
+            .with_cell(cell(30.0), 1, 1)
+            .with_cell(cell(50.0), 1, 1)
+            .with_cell(cell(20.0), 1, 1)
+            .with_cell(cell(40.0), 2, 1)
+            .with_cell(cell(10.0), 1, 1);
+        let harness = TestHarness::create(default_property_set(), NewWidget::new(grid));
+        let root = harness.root_widget();
+        let width = root.ctx().size().width;
+        let origin = root.ctx().window_origin();
+
+        // As many 120px columns as fit, sharing the width left after the gaps
+        let columns = GridColumns::MinWidth(120.0).count(width, 10.0);
+        assert_eq!(columns, 3);
+        let column_width = (width - 20.0) / 3.0;
+
+        let cells: Vec<Rect> = root
+            .children()
+            .into_iter()
+            .map(|cell| Rect::from_origin_size(cell.ctx().window_origin() - origin.to_vec2(), cell.ctx().size()))
+            .collect();
+        // Within a pixel, in case positions are snapped
+        let at = |index: usize, x: f64, y: f64, width: f64, height: f64| {
+            let expected = Rect::new(x, y, x + width, y + height);
+            let cell = cells[index];
+            let near = [(cell.x0, expected.x0), (cell.y0, expected.y0), (cell.x1, expected.x1), (cell.y1, expected.y1)]
+                .iter()
+                .all(|(a, b)| (a - b).abs() < 1.0);
+            assert!(near, "cell {} at {:?}, expected {:?}", index, cell, expected);
+        };
+        at(0, 0.0, 0.0, column_width, 30.0);
+        at(1, column_width + 10.0, 0.0, column_width, 50.0);
+        at(2, 2.0 * (column_width + 10.0), 0.0, column_width, 20.0);
+        // The first row is as tall as its tallest cell and the next starts a row gap below it;
+        // a cell spanning two columns covers the gap between them
+        at(3, 0.0, 58.0, 2.0 * column_width + 10.0, 40.0);
+        at(4, 2.0 * (column_width + 10.0), 58.0, column_width, 10.0);
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2025 HNMD Authors
+// SPDX-License-Identifier: Apache-2.0
+//
+// Min/max size constraints around a child (SizedBox only sets exact sizes)
+
+use masonry::accesskit::{Node, Role};
+use masonry::core::{
+    AccessCtx, BoxConstraints, ChildrenIds, LayoutCtx, NewWidget, NoAction, PaintCtx, PropertiesMut,
+    PropertiesRef, RegisterCtx, Update, UpdateCtx, Widget, WidgetId, WidgetPod,
+};
+use masonry::vello::kurbo::{Point, Size};
+use masonry::vello::Scene;
+use tracing::{Span, trace_span};
+
+/// Keeps its child between a minimum and a maximum size
+pub struct Constrained {
+    child: WidgetPod<dyn Widget>,
+    min: Size,
+    max: Size,
+}
+
+impl Constrained {
+    /// No constraints until `with_*` narrows them
+    pub fn new(child: NewWidget<impl Widget + ?Sized>) -> Self {
+        Self {
+            child: child.erased().to_pod(),
+            min: Size::ZERO,
+            max: Size::new(f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn with_min_width(mut self, width: f64) -> Self {
+        self.min.width = width;
+        self
+    }
+
+    pub fn with_max_width(mut self, width: f64) -> Self {
+        self.max.width = width;
+        self
+    }
+
+    pub fn with_min_height(mut self, height: f64) -> Self {
+        self.min.height = height;
+        self
+    }
+
+    pub fn with_max_height(mut self, height: f64) -> Self {
+        self.max.height = height;
+        self
+    }
+
+    /// The parent's constraints narrowed to ours; the parent wins where they conflict
+    fn constraints(&self, bc: &BoxConstraints) -> BoxConstraints {
+        let clamp = |value: f64, min: f64, max: f64| value.max(min).min(max);
+        let max = Size::new(
+            clamp(self.max.width, bc.min().width, bc.max().width),
+            clamp(self.max.height, bc.min().height, bc.max().height),
+        );
+        let min = Size::new(
+            clamp(self.min.width, bc.min().width, max.width),
+            clamp(self.min.height, bc.min().height, max.height),
+        );
+        BoxConstraints::new(min, max)
+    }
+}
+
+impl Widget for Constrained {
+    type Action = NoAction;
+
+    fn register_children(&mut self, ctx: &mut RegisterCtx<'_>) {
+        ctx.register_child(&mut self.child);
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx<'_>, _props: &mut PropertiesMut<'_>, _event: &Update) {}
+
+    fn layout(&mut self, ctx: &mut LayoutCtx<'_>, _props: &mut PropertiesMut<'_>, bc: &BoxConstraints) -> Size {
+        let bc = self.constraints(bc);
+        let size = bc.constrain(ctx.run_layout(&mut self.child, &bc));
+        ctx.place_child(&mut self.child, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx<'_>, _props: &PropertiesRef<'_>, _scene: &mut Scene) {}
+
+    fn accessibility_role(&self) -> Role {
+        Role::GenericContainer
+    }
+
+    fn accessibility(&mut self, _ctx: &mut AccessCtx<'_>, _props: &PropertiesRef<'_>, _node: &mut Node) {}
+
+    fn children_ids(&self) -> ChildrenIds {
+        ChildrenIds::from_slice(&[self.child.id()])
+    }
+
+    fn make_trace_span(&self, id: WidgetId) -> Span {
+        trace_span!("Constrained", id = id.trace())
+    }
+
+    fn get_debug_text(&self) -> Option<String> {
+        Some(format!("min={:?} max={:?}", self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use masonry::widgets::Label;
+
+    #[test]
+    fn test_constraints() {
+        let constrained = Constrained::new(NewWidget::new(Label::new("x")))
+            .with_min_width(100.0)
+            .with_max_width(300.0)
+            .with_max_height(50.0);
+
+        let bc = constrained.constraints(&BoxConstraints::new(Size::ZERO, Size::new(500.0, f64::INFINITY)));
+        assert_eq!(bc.min(), Size::new(100.0, 0.0));
+        assert_eq!(bc.max(), Size::new(300.0, 50.0));
+
+        // A parent narrower than the minimum wins
+        let bc = constrained.constraints(&BoxConstraints::new(Size::ZERO, Size::new(80.0, 40.0)));
+        assert_eq!(bc.min(), Size::new(80.0, 0.0));
+        assert_eq!(bc.max(), Size::new(80.0, 40.0));
+    }
+}
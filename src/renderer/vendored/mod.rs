@@ -1,8 +1,16 @@
 // Vendored Masonry widgets with fixes
 // TODO: Upstream PRs to Masonry
 
+pub mod auto_grid;
+pub mod constrained;
 pub mod hr;
+pub mod rich_text;
 pub mod text_input;
+pub mod virtual_list;
 
+pub use auto_grid::{AutoGrid, GridColumns};
+pub use constrained::Constrained;
 pub use hr::{Hr, HrColor};
+pub use rich_text::{LinkClicked, RichText, RunStyle};
 pub use text_input::{TextInput, FocusedBorderColor};
+pub use virtual_list::{EndReached, ListMemory, RowHeights, SharedListMemory, VirtualList};
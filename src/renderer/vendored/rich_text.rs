@@ -0,0 +1,366 @@
+// Copyright 2025 HNMD Authors
+// SPDX-License-Identifier: Apache-2.0
+//
+// Custom rich text widget: one wrapping, selectable text layout with styled runs
+
+use std::ops::Range;
+
+use masonry::accesskit::{Node, Role};
+use masonry::core::keyboard::{Key, KeyState};
+use masonry::core::{
+    render_text, AccessCtx, BoxConstraints, BrushIndex, ChildrenIds, CursorIcon, EventCtx, HasProperty,
+    LayoutCtx, PaintCtx, PointerButtonEvent, PointerEvent, PointerUpdate, PropertiesMut,
+    PropertiesRef, QueryCtx, RegisterCtx, StyleProperty, TextEvent, Update, UpdateCtx, Widget, WidgetId,
+};
+use masonry::parley::style::{FontFamily, FontStack, FontStyle, FontWeight, GenericFamily};
+use masonry::parley::{Affinity, Cursor, Layout, Selection};
+use masonry::peniko::{Brush, Color};
+use masonry::properties::{ContentColor, SelectionColor};
+use masonry::util::fill;
+use masonry::vello::kurbo::{Affine, Point, Rect, Size};
+use masonry::vello::Scene;
+use tracing::{Span, trace_span};
+
+/// Styling of one run of text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    /// Monospace on a grey background
+    pub code: bool,
+    /// Link target; the run is drawn in the link colour
+    pub link: Option<String>,
+}
+
+/// Brush slots in the layout
+const TEXT_BRUSH: BrushIndex = BrushIndex(0);
+const LINK_BRUSH: BrushIndex = BrushIndex(1);
+
+const LINK_COLOR: Color = Color::from_rgb8(0, 102, 204);
+const CODE_BACKGROUND: Color = Color::from_rgb8(240, 240, 240);
+
+/// A link in the text was clicked (pressed and released without dragging)
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkClicked(pub String);
+
+/// A paragraph of styled text laid out as one unit, so it wraps across style boundaries
+/// and can be selected (drag, double-click) and copied (Ctrl/Cmd+C)
+pub struct RichText {
+    text: String,
+    runs: Vec<(Range<usize>, RunStyle)>,
+    font_size: f32,
+    weight: FontWeight,
+
+    layout: Layout<BrushIndex>,
+    selection: Selection,
+    /// Link under the pointer when it was pressed
+    pressed_link: Option<String>,
+}
+
+impl RichText {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            runs: Vec::new(),
+            font_size: 18.0,
+            weight: FontWeight::NORMAL,
+            layout: Layout::new(),
+            selection: Selection::default(),
+            pressed_link: None,
+        }
+    }
+
+    /// Append text with a style
+    pub fn push(&mut self, text: &str, style: RunStyle) {
+        if text.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(text);
+        match self.runs.last_mut() {
+            // Neighbouring runs with the same style are one run
+            Some((range, last)) if *last == style && range.end == start => range.end = self.text.len(),
+            _ => self.runs.push((start..self.text.len(), style)),
+        }
+    }
+
+    pub fn with_run(mut self, text: &str, style: RunStyle) -> Self {
+        self.push(text, style);
+        self
+    }
+
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        self.font_size = size;
+        self
+    }
+
+    /// Base weight for the whole text (e.g. bold for headings)
+    pub fn with_weight(mut self, weight: FontWeight) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// The plain text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Styled runs as byte ranges into [`text`](Self::text)
+    pub fn runs(&self) -> &[(Range<usize>, RunStyle)] {
+        &self.runs
+    }
+
+    /// Currently selected text
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection.text_range()]
+    }
+
+    /// Link target of the run containing a byte index
+    pub fn link_at(&self, index: usize) -> Option<&str> {
+        self.runs
+            .iter()
+            .find(|(range, _)| range.contains(&index))
+            .and_then(|(_, style)| style.link.as_deref())
+    }
+
+    /// Whether any run is a link
+    pub fn has_links(&self) -> bool {
+        self.runs.iter().any(|(_, style)| style.link.is_some())
+    }
+
+    /// Link target under a point in layout coordinates
+    fn link_at_point(&self, pos: Point) -> Option<&str> {
+        if pos.x < 0.0 || pos.y < 0.0 || pos.x > self.layout.width() as f64 || pos.y > self.layout.height() as f64 {
+            return None;
+        }
+        let cursor = Cursor::from_point(&self.layout, pos.x as f32, pos.y as f32);
+        // The cursor sits between characters; look at the one it is attached to
+        let index = match cursor.affinity() {
+            Affinity::Upstream => cursor.index().saturating_sub(1),
+            Affinity::Downstream => cursor.index(),
+        };
+        self.link_at(index)
+    }
+
+    /// Call `f` with the layout rectangles covering a byte range
+    fn range_rects(&self, range: Range<usize>, mut f: impl FnMut(Rect)) {
+        let selection = Selection::new(
+            Cursor::from_byte_index(&self.layout, range.start, Affinity::Downstream),
+            Cursor::from_byte_index(&self.layout, range.end, Affinity::Upstream),
+        );
+        selection.geometry_with(&self.layout, |rect, _| f(Rect::new(rect.x0, rect.y0, rect.x1, rect.y1)));
+    }
+}
+
+impl Default for RichText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HasProperty<ContentColor> for RichText {}
+impl HasProperty<SelectionColor> for RichText {}
+
+impl Widget for RichText {
+    type Action = LinkClicked;
+
+    fn on_pointer_event(&mut self, ctx: &mut EventCtx<'_>, _props: &mut PropertiesMut<'_>, event: &PointerEvent) {
+        match event {
+            PointerEvent::Down(PointerButtonEvent { state, .. }) => {
+                let pos = ctx.local_position(state.position);
+                self.selection = if state.count >= 2 {
+                    Selection::word_from_point(&self.layout, pos.x as f32, pos.y as f32)
+                } else {
+                    Selection::from_point(&self.layout, pos.x as f32, pos.y as f32)
+                };
+                self.pressed_link = self.link_at_point(pos).map(str::to_string);
+                ctx.request_focus();
+                ctx.capture_pointer();
+                ctx.request_paint_only();
+            }
+            PointerEvent::Move(PointerUpdate { current, .. }) if ctx.is_pointer_capture_target() => {
+                let pos = ctx.local_position(current.position);
+                self.selection = self.selection.extend_to_point(&self.layout, pos.x as f32, pos.y as f32);
+                ctx.request_paint_only();
+            }
+            PointerEvent::Up(PointerButtonEvent { state, .. }) => {
+                let pos = ctx.local_position(state.position);
+                let released_link = self.link_at_point(pos).map(str::to_string);
+                let pressed_link = self.pressed_link.take();
+                // A drag selects text instead of following the link
+                if pressed_link.is_some() && pressed_link == released_link && self.selection.is_collapsed() {
+                    ctx.submit_action::<Self::Action>(LinkClicked(pressed_link.unwrap()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_text_event(&mut self, ctx: &mut EventCtx<'_>, _props: &mut PropertiesMut<'_>, event: &TextEvent) {
+        let TextEvent::Keyboard(key_event) = event else { return };
+        if key_event.state != KeyState::Down {
+            return;
+        }
+
+        let action_mod = if cfg!(target_os = "macos") {
+            key_event.modifiers.meta()
+        } else {
+            key_event.modifiers.ctrl()
+        };
+        match &key_event.key {
+            Key::Character(c) if action_mod && c.eq_ignore_ascii_case("c") => {
+                if !self.selection.is_collapsed() {
+                    ctx.set_clipboard(self.selected_text().to_string());
+                }
+                ctx.set_handled();
+            }
+            Key::Character(c) if action_mod && c.eq_ignore_ascii_case("a") => {
+                self.selection = Selection::new(
+                    Cursor::from_byte_index(&self.layout, 0, Affinity::Downstream),
+                    Cursor::from_byte_index(&self.layout, self.text.len(), Affinity::Upstream),
+                );
+                ctx.request_paint_only();
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        // Tab stops only at text with links; clicking still focuses any text for copying
+        self.has_links()
+    }
+
+    fn register_children(&mut self, _ctx: &mut RegisterCtx<'_>) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx<'_>, _props: &mut PropertiesMut<'_>, event: &Update) {
+        if let Update::FocusChanged(false) = event {
+            self.selection = self.selection.collapse();
+            ctx.request_paint_only();
+        }
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx<'_>, _props: &mut PropertiesMut<'_>, bc: &BoxConstraints) -> Size {
+        let (font_ctx, layout_ctx) = ctx.text_contexts();
+        let mut builder = layout_ctx.ranged_builder(font_ctx, &self.text, 1.0, true);
+        builder.push_default(StyleProperty::FontSize(self.font_size));
+        builder.push_default(StyleProperty::FontWeight(self.weight));
+        builder.push_default(StyleProperty::Brush(TEXT_BRUSH));
+        for (range, style) in &self.runs {
+            if style.bold {
+                builder.push(StyleProperty::FontWeight(FontWeight::BOLD), range.clone());
+            }
+            if style.italic {
+                builder.push(StyleProperty::FontStyle(FontStyle::Italic), range.clone());
+            }
+            if style.strikethrough {
+                builder.push(StyleProperty::Strikethrough(true), range.clone());
+            }
+            if style.code {
+                let monospace = FontStack::Single(FontFamily::Generic(GenericFamily::Monospace));
+                builder.push(StyleProperty::FontStack(monospace), range.clone());
+                builder.push(StyleProperty::FontSize(self.font_size * 0.9), range.clone());
+            }
+            if style.link.is_some() {
+                builder.push(StyleProperty::Brush(LINK_BRUSH), range.clone());
+                builder.push(StyleProperty::Underline(true), range.clone());
+            }
+        }
+        builder.build_into(&mut self.layout, &self.text);
+
+        let max_width = bc.max().width;
+        self.layout.break_all_lines(max_width.is_finite().then_some(max_width as f32));
+        self.selection = self.selection.refresh(&self.layout);
+
+        bc.constrain(Size::new(self.layout.width() as f64, self.layout.height() as f64))
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx<'_>, props: &PropertiesRef<'_>, scene: &mut Scene) {
+        for (range, style) in &self.runs {
+            if style.code {
+                self.range_rects(range.clone(), |rect| fill(scene, &rect.inflate(2.0, 0.0), CODE_BACKGROUND));
+            }
+        }
+
+        if !self.selection.is_collapsed() {
+            let color = props.get::<SelectionColor>().color;
+            self.selection.geometry_with(&self.layout, |rect, _| {
+                fill(scene, &Rect::new(rect.x0, rect.y0, rect.x1, rect.y1), color);
+            });
+        }
+
+        let text_color = props.get::<ContentColor>().color;
+        let brushes = [Brush::Solid(text_color), Brush::Solid(LINK_COLOR)];
+        render_text(scene, Affine::IDENTITY, &self.layout, &brushes, true);
+    }
+
+    fn get_cursor(&self, ctx: &QueryCtx<'_>, pos: Point) -> CursorIcon {
+        if self.link_at_point(ctx.local_position(pos)).is_some() {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Text
+        }
+    }
+
+    fn accessibility_role(&self) -> Role {
+        Role::Paragraph
+    }
+
+    fn accessibility(&mut self, _ctx: &mut AccessCtx<'_>, _props: &PropertiesRef<'_>, node: &mut Node) {
+        node.set_value(self.text.clone());
+    }
+
+    fn children_ids(&self) -> ChildrenIds {
+        ChildrenIds::default()
+    }
+
+    fn make_trace_span(&self, id: WidgetId) -> Span {
+        trace_span!("RichText", id = id.trace())
+    }
+
+    fn get_debug_text(&self) -> Option<String> {
+        Some(self.text.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_merges_runs() {
+        let bold = RunStyle { bold: true, ..Default::default() };
+        let text = RichText::new()
+            .with_run("Hello ", RunStyle::default())
+            .with_run("big ", bold.clone())
+            .with_run("world", bold.clone())
+            .with_run("", RunStyle::default())
+            .with_run("!", RunStyle::default());
+
+        assert_eq!(text.text(), "Hello big world!");
+        assert_eq!(
+            text.runs(),
+            &[(0..6, RunStyle::default()), (6..15, bold), (15..16, RunStyle::default())]
+        );
+    }
+
+    #[test]
+    fn test_link_at() {
+        let link = RunStyle { link: Some("other.hnmd".to_string()), ..Default::default() };
+        let text = RichText::new()
+            .with_run("See ", RunStyle::default())
+            .with_run("the other page", link)
+            .with_run(".", RunStyle::default());
+
+        assert_eq!(text.link_at(0), None);
+        assert_eq!(text.link_at(4), Some("other.hnmd"));
+        assert_eq!(text.link_at(17), Some("other.hnmd"));
+        assert_eq!(text.link_at(18), None);
+
+        // Only text with a link is a tab stop
+        assert!(text.has_links() && text.accepts_focus());
+        let plain = RichText::new().with_run("No links here", RunStyle::default());
+        assert!(!plain.has_links() && !plain.accepts_focus());
+    }
+}
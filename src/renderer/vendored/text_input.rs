@@ -102,6 +102,11 @@ impl TextInput {
         this.ctx.get_mut(&mut this.widget.text)
     }
 
+    /// The text currently in the input.
+    pub fn text(this: &mut WidgetMut<'_, Self>) -> String {
+        Self::text_mut(this).widget.text().to_string()
+    }
+
     /// Edit the child label representing the placeholder text.
     pub fn placeholder_mut<'t>(this: &'t mut WidgetMut<'_, Self>) -> WidgetMut<'t, Label> {
         this.ctx.get_mut(&mut this.widget.placeholder)
@@ -155,6 +160,7 @@ impl FocusedBorderColor {
 
 impl HasProperty<Background> for TextInput {}
 impl HasProperty<CaretColor> for TextInput {}
+impl HasProperty<ContentColor> for TextInput {}
 impl HasProperty<DisabledBackground> for TextInput {}
 impl HasProperty<BorderColor> for TextInput {}
 impl HasProperty<FocusedBorderColor> for TextInput {}
@@ -213,6 +219,13 @@ impl Widget for TextInput {
                 let mut text_area = Self::text_mut(&mut input);
                 text_area.insert_prop(color);
             });
+        } else if property_type == TypeId::of::<ContentColor>() {
+            ctx.mutate_self_later(|mut input| {
+                let mut input = input.downcast::<Self>();
+                let color = *input.get_prop::<ContentColor>();
+                let mut text_area = Self::text_mut(&mut input);
+                text_area.insert_prop(color);
+            });
         } else if property_type == TypeId::of::<PlaceholderColor>() {
             ctx.mutate_self_later(|mut input| {
                 let mut input = input.downcast::<Self>();
@@ -245,6 +258,12 @@ impl Widget for TextInput {
                     let mut text_area = Self::text_mut(&mut input);
                     text_area.insert_prop(color);
                 });
+                ctx.mutate_self_later(|mut input| {
+                    let mut input = input.downcast::<Self>();
+                    let color = *input.get_prop::<ContentColor>();
+                    let mut text_area = Self::text_mut(&mut input);
+                    text_area.insert_prop(color);
+                });
                 ctx.mutate_self_later(|mut input| {
                     let mut input = input.downcast::<Self>();
                     let color = input.get_prop::<PlaceholderColor>().color;
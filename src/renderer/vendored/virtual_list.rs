@@ -0,0 +1,511 @@
+// Copyright 2025 HNMD Authors
+// SPDX-License-Identifier: Apache-2.0
+//
+// Scrolling list that only builds the rows in view (Flex inside a Portal builds every row up front)
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use masonry::accesskit::{Node, Role};
+use masonry::core::{
+    AccessCtx, BoxConstraints, ChildrenIds, EventCtx, LayoutCtx, NewWidget, PaintCtx, PointerEvent,
+    PointerScrollEvent, PropertiesMut, PropertiesRef, RegisterCtx, ScrollDelta, Update, UpdateCtx, Widget, WidgetId,
+    WidgetMut, WidgetPod,
+};
+use masonry::peniko::Color;
+use masonry::util::fill;
+use masonry::vello::kurbo::{Point, Rect, RoundedRect, Size};
+use masonry::vello::Scene;
+use tracing::{Span, trace_span};
+
+/// Height assumed for rows until some have been measured
+const ESTIMATED_ROW_HEIGHT: f64 = 48.0;
+
+/// Pixels of rows kept built above and below the visible ones
+const OVERSCAN: f64 = 400.0;
+
+/// Pixels scrolled per wheel line
+const LINE_HEIGHT: f64 = 40.0;
+
+/// Heights of the rows measured so far; the others are estimated from them
+#[derive(Debug, Clone, Default)]
+pub struct RowHeights {
+    heights: Vec<Option<f64>>,
+    measured_total: f64,
+    measured_count: usize,
+}
+
+impl RowHeights {
+    pub fn new(len: usize) -> Self {
+        Self {
+            heights: vec![None; len],
+            measured_total: 0.0,
+            measured_count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heights.is_empty()
+    }
+
+    pub fn set(&mut self, index: usize, height: f64) {
+        match self.heights[index].replace(height) {
+            Some(old) => self.measured_total -= old,
+            None => self.measured_count += 1,
+        }
+        self.measured_total += height;
+    }
+
+    /// Average of the measured rows
+    pub fn estimate(&self) -> f64 {
+        if self.measured_count == 0 {
+            ESTIMATED_ROW_HEIGHT
+        } else {
+            self.measured_total / self.measured_count as f64
+        }
+    }
+
+    /// Measured or estimated height of a row
+    pub fn height(&self, index: usize) -> f64 {
+        self.heights[index].unwrap_or_else(|| self.estimate())
+    }
+
+    /// Distance from the top of the list to the top of a row
+    pub fn offset_of(&self, index: usize) -> f64 {
+        let estimate = self.estimate();
+        self.heights[..index].iter().map(|height| height.unwrap_or(estimate)).sum()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.offset_of(self.len())
+    }
+
+    /// The row at `y` and how far into it `y` is (the last row past the end)
+    pub fn index_at(&self, y: f64) -> (usize, f64) {
+        let estimate = self.estimate();
+        let mut top = 0.0;
+        for (index, height) in self.heights.iter().enumerate() {
+            let height = height.unwrap_or(estimate);
+            if y < top + height || index + 1 == self.len() {
+                return (index, (y - top).max(0.0));
+            }
+            top += height;
+        }
+        (0, 0.0)
+    }
+
+    /// Rows overlapping `top..top + viewport`, widened by `overscan` pixels on both sides
+    pub fn visible(&self, top: f64, viewport: f64, overscan: f64) -> Range<usize> {
+        if self.is_empty() {
+            return 0..0;
+        }
+        let (first, _) = self.index_at((top - overscan).max(0.0));
+        let bottom = top + viewport + overscan;
+        let mut end = first;
+        let mut y = self.offset_of(first);
+        while end < self.len() && y < bottom {
+            y += self.height(end);
+            end += 1;
+        }
+        first..end.max(first + 1)
+    }
+}
+
+/// The list was scrolled to within the overscan of its last row
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndReached;
+
+/// What a list remembers across rebuilds, by item key
+#[derive(Debug, Clone, Default)]
+pub struct ListMemory {
+    pub heights: HashMap<String, f64>,
+    /// Key of the top row in view and how far it is scrolled past
+    pub anchor: Option<(String, f64)>,
+}
+
+/// Shared between a list and the one that replaces it when the page is rebuilt
+pub type SharedListMemory = Arc<Mutex<ListMemory>>;
+
+/// Vertical list in a scrolling box that builds rows as they come into view and drops them after
+pub struct VirtualList {
+    keys: Vec<String>,
+    build_row: Box<dyn FnMut(usize) -> NewWidget<dyn Widget>>,
+    rows: BTreeMap<usize, WidgetPod<dyn Widget>>,
+    heights: RowHeights,
+    /// Top row in view and how far it is scrolled past
+    anchor: (usize, f64),
+    /// Height of the scrolling box
+    viewport: f64,
+    /// Size from the last layout
+    size: Size,
+    memory: Option<SharedListMemory>,
+}
+
+impl VirtualList {
+    /// A list of rows identified by `keys`, built on demand by `build_row(index)`
+    pub fn new(
+        keys: Vec<String>,
+        viewport: f64,
+        build_row: impl FnMut(usize) -> NewWidget<dyn Widget> + 'static,
+    ) -> Self {
+        Self {
+            heights: RowHeights::new(keys.len()),
+            keys,
+            build_row: Box::new(build_row),
+            rows: BTreeMap::new(),
+            anchor: (0, 0.0),
+            viewport,
+            size: Size::ZERO,
+            memory: None,
+        }
+    }
+
+    /// Reuse the heights and scroll position a previous list saved for the same keys
+    pub fn with_memory(mut self, memory: SharedListMemory) -> Self {
+        {
+            let saved = memory.lock().unwrap();
+            for (index, key) in self.keys.iter().enumerate() {
+                if let Some(height) = saved.heights.get(key) {
+                    self.heights.set(index, *height);
+                }
+            }
+            if let Some((key, within)) = &saved.anchor {
+                if let Some(index) = self.keys.iter().position(|k| k == key) {
+                    self.anchor = (index, *within);
+                }
+            }
+        }
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Start scrolled to a row
+    pub fn with_scroll_index(mut self, index: usize) -> Self {
+        self.anchor = (index.min(self.keys.len().saturating_sub(1)), 0.0);
+        self
+    }
+
+    /// Number of rows, built or not
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Indexes of the rows currently built
+    pub fn built_rows(&self) -> Vec<usize> {
+        self.rows.keys().copied().collect()
+    }
+
+    /// Distance scrolled from the top of the list
+    fn top(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        self.heights.offset_of(self.anchor.0) + self.anchor.1
+    }
+
+    fn viewport_height(&self) -> f64 {
+        if self.size.height > 0.0 { self.size.height } else { self.viewport }
+    }
+
+    fn max_top(&self) -> f64 {
+        (self.heights.total() - self.viewport_height()).max(0.0)
+    }
+
+    /// Scroll so the top of the view is at `top`; false if it was already there
+    fn scroll_to_offset(&mut self, top: f64) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let top = top.clamp(0.0, self.max_top());
+        if (top - self.top()).abs() < f64::EPSILON {
+            return false;
+        }
+        self.anchor = self.heights.index_at(top);
+        true
+    }
+
+    /// Rows that should be built for the current scroll position
+    fn wanted_rows(&self) -> Range<usize> {
+        self.heights.visible(self.top(), self.viewport_height(), OVERSCAN)
+    }
+
+    /// Whether the last row is among the wanted ones
+    fn near_end(&self) -> bool {
+        !self.is_empty() && self.wanted_rows().end == self.len()
+    }
+
+    /// Build the wanted rows that aren't built yet
+    fn fill_rows(&mut self) {
+        for index in self.wanted_rows() {
+            if !self.rows.contains_key(&index) {
+                let row = (self.build_row)(index);
+                self.rows.insert(index, row.to_pod());
+            }
+        }
+    }
+
+    /// Built rows that are no longer wanted
+    fn stale_rows(&self) -> Vec<usize> {
+        let wanted = self.wanted_rows();
+        self.rows.keys().filter(|index| !wanted.contains(index)).copied().collect()
+    }
+
+    /// Whether some wanted row isn't built, e.g. after measured rows came out shorter than estimated
+    fn missing_rows(&self) -> bool {
+        self.wanted_rows().any(|index| !self.rows.contains_key(&index))
+    }
+
+    /// Save heights and the scroll position for the list that replaces this one
+    fn remember(&self) {
+        let Some(memory) = &self.memory else { return };
+        let mut memory = memory.lock().unwrap();
+        for (index, key) in self.keys.iter().enumerate() {
+            if let Some(height) = self.heights.heights[index] {
+                memory.heights.insert(key.clone(), height);
+            }
+        }
+        memory.anchor = self.keys.get(self.anchor.0).map(|key| (key.clone(), self.anchor.1));
+    }
+}
+
+// --- MARK: WIDGETMUT
+impl VirtualList {
+    /// Scroll so a row is at the top of the view (or as close as the end of the list allows)
+    pub fn scroll_to_index(this: &mut WidgetMut<'_, Self>, index: usize) {
+        if this.widget.is_empty() {
+            return;
+        }
+        let index = index.min(this.widget.len() - 1);
+        let top = this.widget.heights.offset_of(index);
+        this.widget.anchor = (index, 0.0);
+        this.widget.scroll_to_offset(top);
+        Self::sync_rows(this);
+    }
+
+    /// Build rows that came into view and drop the ones far out of it
+    pub fn sync_rows(this: &mut WidgetMut<'_, Self>) {
+        for index in this.widget.stale_rows() {
+            let row = this.widget.rows.remove(&index).expect("row is built");
+            this.ctx.remove_child(row);
+        }
+        this.widget.fill_rows();
+        this.widget.remember();
+        this.ctx.children_changed();
+        this.ctx.request_layout();
+    }
+}
+
+impl Widget for VirtualList {
+    type Action = EndReached;
+
+    fn on_pointer_event(&mut self, ctx: &mut EventCtx<'_>, _props: &mut PropertiesMut<'_>, event: &PointerEvent) {
+        let PointerEvent::Scroll(PointerScrollEvent { delta, .. }) = event else { return };
+        let dy = match delta {
+            ScrollDelta::PixelDelta(position) => -position.y,
+            ScrollDelta::LineDelta(_, lines) => -*lines as f64 * LINE_HEIGHT,
+            ScrollDelta::PageDelta(_, pages) => -*pages as f64 * self.viewport_height(),
+        };
+        // At either end the enclosing page scrolls instead
+        let was_near_end = self.near_end();
+        if self.scroll_to_offset(self.top() + dy) {
+            ctx.set_handled();
+            if self.near_end() && !was_near_end {
+                ctx.submit_action::<Self::Action>(EndReached);
+            }
+            ctx.mutate_self_later(|mut list| {
+                let mut list = list.downcast::<Self>();
+                Self::sync_rows(&mut list);
+            });
+        }
+    }
+
+    fn register_children(&mut self, ctx: &mut RegisterCtx<'_>) {
+        // Rows are first built when the list is added, so `with_memory` can move the start
+        self.fill_rows();
+        for row in self.rows.values_mut() {
+            ctx.register_child(row);
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx<'_>, _props: &mut PropertiesMut<'_>, _event: &Update) {}
+
+    fn layout(&mut self, ctx: &mut LayoutCtx<'_>, _props: &mut PropertiesMut<'_>, bc: &BoxConstraints) -> Size {
+        let width = if bc.max().width.is_finite() { bc.max().width } else { bc.min().width };
+        let size = bc.constrain(Size::new(width, self.viewport));
+        self.size = size;
+
+        let row_bc = BoxConstraints::new(Size::new(size.width, 0.0), Size::new(size.width, f64::INFINITY));
+        for (index, row) in &mut self.rows {
+            let height = ctx.run_layout(row, &row_bc).height;
+            self.heights.set(*index, height);
+        }
+
+        // Measuring may have changed how far the list can scroll
+        let top = self.top().min(self.max_top());
+        self.anchor = if self.is_empty() { (0, 0.0) } else { self.heights.index_at(top) };
+
+        let mut next: Option<(usize, f64)> = None;
+        for (index, row) in &mut self.rows {
+            let y = match next {
+                Some((expected, y)) if expected == *index => y,
+                _ => self.heights.offset_of(*index),
+            };
+            ctx.place_child(row, Point::new(0.0, y - top));
+            next = Some((index + 1, y + self.heights.height(*index)));
+        }
+
+        // Rows can only be added outside of layout
+        if self.missing_rows() {
+            ctx.mutate_self_later(|mut list| {
+                let mut list = list.downcast::<Self>();
+                Self::sync_rows(&mut list);
+            });
+        }
+
+        ctx.set_clip_path(size.to_rect());
+        self.remember();
+        size
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx<'_>, _props: &PropertiesRef<'_>, scene: &mut Scene) {
+        // Scrollbar thumb when the rows don't fit
+        let total = self.heights.total();
+        if total <= self.size.height || self.size.height <= 0.0 {
+            return;
+        }
+        let thumb_height = (self.size.height * self.size.height / total).max(20.0);
+        let thumb_top = self.top() / (total - self.size.height) * (self.size.height - thumb_height);
+        let x = self.size.width - 6.0;
+        let thumb = RoundedRect::from_rect(Rect::new(x, thumb_top, x + 4.0, thumb_top + thumb_height), 2.0);
+        fill(scene, &thumb, Color::from_rgba8(128, 128, 128, 160));
+    }
+
+    fn accessibility_role(&self) -> Role {
+        Role::List
+    }
+
+    fn accessibility(&mut self, _ctx: &mut AccessCtx<'_>, _props: &PropertiesRef<'_>, _node: &mut Node) {}
+
+    fn children_ids(&self) -> ChildrenIds {
+        self.rows.values().map(|row| row.id()).collect()
+    }
+
+    fn make_trace_span(&self, id: WidgetId) -> Span {
+        trace_span!("VirtualList", id = id.trace())
+    }
+
+    fn get_debug_text(&self) -> Option<String> {
+        Some(format!("{} rows, {} built", self.keys.len(), self.rows.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use masonry::widgets::Label;
+
+    fn label_rows(count: usize) -> VirtualList {
+        let keys = (0..count).map(|i| i.to_string()).collect();
+        VirtualList::new(keys, 400.0, |index| NewWidget::new(Label::new(format!("Row {}", index))).erased())
+    }
+
+    #[test]
+    fn test_row_heights() {
+        let mut heights = RowHeights::new(5);
+        assert_eq!(heights.total(), 5.0 * ESTIMATED_ROW_HEIGHT);
+
+        // Unmeasured rows take the average of the measured ones
+        heights.set(0, 10.0);
+        heights.set(1, 30.0);
+        assert_eq!(heights.estimate(), 20.0);
+        assert_eq!(heights.offset_of(3), 60.0);
+        assert_eq!(heights.total(), 100.0);
+
+        assert_eq!(heights.index_at(5.0), (0, 5.0));
+        assert_eq!(heights.index_at(45.0), (2, 5.0));
+        assert_eq!(heights.index_at(1000.0).0, 4);
+
+        // Rows 1..=3 overlap 15..75; overscan widens that
+        assert_eq!(heights.visible(15.0, 60.0, 0.0), 1..4);
+        assert_eq!(heights.visible(15.0, 60.0, 20.0), 0..5);
+        assert_eq!(RowHeights::new(0).visible(0.0, 100.0, 0.0), 0..0);
+    }
+
+    #[test]
+    fn test_builds_only_rows_in_view() {
+        let mut list = label_rows(10_000);
+        list.fill_rows();
+        let built = list.built_rows();
+        assert_eq!(built.first(), Some(&0));
+        assert!(built.len() < 50, "built {} rows", built.len());
+
+        // Scrolling far down wants rows around the new position only
+        list.size = Size::new(300.0, 400.0);
+        assert!(list.scroll_to_offset(100_000.0));
+        let wanted = list.wanted_rows();
+        assert!(wanted.start > 1000 && wanted.len() < 50);
+
+        assert!(!list.near_end());
+
+        // Scrolling past either end stops at it
+        assert!(list.scroll_to_offset(f64::INFINITY));
+        assert!(list.near_end());
+        assert!(!list.scroll_to_offset(f64::INFINITY));
+        assert_eq!(list.top(), list.max_top());
+        assert!(list.scroll_to_offset(-10.0));
+        assert_eq!(list.top(), 0.0);
+    }
+
+    #[test]
+    fn test_short_rows_fill_the_view() {
+        let mut list = label_rows(1_000);
+        list.size = Size::new(300.0, 400.0);
+        list.fill_rows();
+        assert!(!list.missing_rows());
+
+        // Rows measured shorter than the estimate leave room for more
+        for index in list.built_rows() {
+            list.heights.set(index, 10.0);
+        }
+        assert!(list.missing_rows());
+        list.fill_rows();
+        let built = list.built_rows();
+        assert!(list.heights.offset_of(*built.last().unwrap() + 1) >= 400.0 + OVERSCAN);
+
+        // After scrolling, the built rows are exactly the ones around the view
+        assert!(list.scroll_to_offset(5_000.0));
+        for index in list.stale_rows() {
+            list.rows.remove(&index);
+        }
+        list.fill_rows();
+        let built = list.built_rows();
+        let wanted = list.wanted_rows();
+        assert_eq!(built, wanted.clone().collect::<Vec<_>>());
+        assert!(list.heights.offset_of(wanted.start) <= list.top());
+        assert!(list.heights.offset_of(wanted.end) >= list.top() + 400.0);
+    }
+
+    #[test]
+    fn test_memory_by_key() {
+        let memory = SharedListMemory::default();
+        let mut list = label_rows(100).with_memory(memory.clone());
+        list.heights.set(10, 75.0);
+        list.anchor = (10, 5.0);
+        list.remember();
+
+        // New items above keep the same row at the top, with its measured height
+        let keys = ["new".to_string()].into_iter().chain((0..100).map(|i| i.to_string())).collect();
+        let list = VirtualList::new(keys, 400.0, |_| NewWidget::new(Label::new("")).erased()).with_memory(memory);
+        assert_eq!(list.anchor, (11, 5.0));
+        assert_eq!(list.heights.height(11), 75.0);
+    }
+}
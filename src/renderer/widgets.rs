@@ -1,25 +1,78 @@
-use crate::parser::ast::Node;
-use crate::renderer::vendored::{TextInput, FocusedBorderColor, Hr, HrColor};
-use crate::runtime::{ComponentRegistry, JaqEvaluator, RuntimeContext};
-use masonry::core::{NewWidget, Properties, StyleProperty};
-use masonry::parley::style::{FontStyle, FontWeight};
+use crate::parser::ast::{
+    is_template, template_parts, with_location, ColumnAlign, ListItem, Node, NodeStyle, PropValue, Span, StackLayout,
+    TableRow, TemplatePart,
+};
+use crate::renderer::style::{ComputedStyle, StyleSheet};
+use crate::renderer::vendored::{
+    AutoGrid, Constrained, GridColumns, TextInput, FocusedBorderColor, Hr, HrColor, RichText, RunStyle, SharedListMemory,
+    VirtualList,
+};
+use crate::runtime::query::load_more_target;
+use crate::runtime::{ComponentRegistry, DecodedImage, ImageLoader, JaqEvaluator, RuntimeContext};
+use masonry::core::{NewWidget, Properties, StyleProperty, WidgetId};
+use masonry::parley::style::{FontFamily, FontStack, FontWeight, GenericFamily};
 use masonry::peniko::Color;
-use masonry::peniko::color::AlphaColor;
-use masonry::properties::{Background, BorderColor, BorderWidth, CornerRadius, ObjectFit, Padding, CaretColor, SelectionColor, UnfocusedSelectionColor};
+use masonry::properties::{Background, BorderColor, BorderWidth, ContentColor, CornerRadius, ObjectFit, Padding, CaretColor, PlaceholderColor, SelectionColor, UnfocusedSelectionColor};
 use masonry::properties::types::{CrossAxisAlignment, Length, MainAxisAlignment};
-use masonry::widgets::{Button, Flex, Image, Label};
+use masonry::widgets::{Button, Checkbox, Flex, Grid, GridParams, Image, Label, SizedBox};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::fs;
+use std::sync::{Arc, LazyLock, Mutex};
 
 /// Debug flag to show borders around layout containers
 const DEBUG_LAYOUT: bool = false;
 
+/// Built-in theme, for rendering without a context
+static DEFAULT_STYLES: LazyLock<Arc<StyleSheet>> = LazyLock::new(|| Arc::new(StyleSheet::default()));
+
+/// State paths written by interactive widgets, keyed by widget id
+pub type Bindings = Arc<Mutex<HashMap<WidgetId, String>>>;
+
+/// Heights and scroll positions of `virtual` lists, by where they are (see `RenderContext::scope`) and their `from` expression
+pub type ListMemories = Arc<Mutex<HashMap<String, SharedListMemory>>>;
+
+/// Loader for remote images, and who to tell when one arrives for a placeholder
+#[derive(Clone)]
+pub struct RemoteImages {
+    pub loader: ImageLoader,
+    /// Called (on a background task) with the placeholder `Image` widget's id and its image
+    pub on_ready: Arc<dyn Fn(WidgetId, Arc<DecodedImage>) + Send + Sync>,
+}
+
+impl RemoteImages {
+    pub fn new(loader: ImageLoader, on_ready: impl Fn(WidgetId, Arc<DecodedImage>) + Send + Sync + 'static) -> Self {
+        Self {
+            loader,
+            on_ready: Arc::new(on_ready),
+        }
+    }
+}
+
 /// Context for rendering widgets with runtime data
 #[derive(Clone)]
 pub struct RenderContext {
     pub runtime_ctx: RuntimeContext,
     pub evaluator: JaqEvaluator,
     pub registry: Option<ComponentRegistry>,
+    /// Shared by every clone, so the driver can find which path a widget's action writes
+    pub bindings: Bindings,
+    /// Where `navigate(...)` buttons go, keyed by widget id (shared like `bindings`)
+    pub navigations: Bindings,
+    /// Queries that `loadMore(...)` buttons and lists page, keyed by widget id (shared like `bindings`)
+    pub load_mores: Bindings,
+    /// Names of text inputs, keyed by widget id (shared like `bindings`)
+    pub fields: Bindings,
+    /// Kept across reloads so rebuilt `virtual` lists stay where they were scrolled
+    pub lists: ListMemories,
+    /// Component call sites and `<each>` rows the node is inside, so each instance of a list keeps its own memory
+    pub scope: String,
+    /// Remote images are shown as placeholders without this
+    pub images: Option<RemoteImages>,
+    /// Theme and style classes (the component's inside a component)
+    pub styles: Arc<StyleSheet>,
+    /// Text color, size and weight set by enclosing styled nodes
+    pub inherited: ComputedStyle,
 }
 
 impl RenderContext {
@@ -28,6 +81,62 @@ impl RenderContext {
             runtime_ctx,
             evaluator: JaqEvaluator::new(),
             registry: None,
+            bindings: Bindings::default(),
+            navigations: Bindings::default(),
+            load_mores: Bindings::default(),
+            fields: Bindings::default(),
+            lists: ListMemories::default(),
+            scope: String::new(),
+            images: None,
+            styles: Arc::clone(&DEFAULT_STYLES),
+            inherited: ComputedStyle::default(),
+        }
+    }
+
+    /// Record that the widget writes to a state path
+    pub fn bind(&self, id: WidgetId, path: &str) {
+        self.bindings.lock().unwrap().insert(id, path.to_string());
+    }
+
+    /// State path the widget writes to, if it is bound
+    pub fn binding(&self, id: WidgetId) -> Option<String> {
+        self.bindings.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Record that pressing the widget navigates to a path or link
+    pub fn navigate_on(&self, id: WidgetId, target: &str) {
+        self.navigations.lock().unwrap().insert(id, target.to_string());
+    }
+
+    /// Where pressing the widget navigates to, if anywhere
+    pub fn navigation(&self, id: WidgetId) -> Option<String> {
+        self.navigations.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Record that pressing the widget (or scrolling it to the end) loads more of a query
+    pub fn load_more_on(&self, id: WidgetId, query: &str) {
+        self.load_mores.lock().unwrap().insert(id, query.to_string());
+    }
+
+    /// Query the widget loads more of, if any
+    pub fn load_more(&self, id: WidgetId) -> Option<String> {
+        self.load_mores.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Record that the widget is the text input for a form field
+    pub fn field_on(&self, id: WidgetId, name: &str) {
+        self.fields.lock().unwrap().insert(id, name.to_string());
+    }
+
+    /// Text inputs on the page and the form fields they edit
+    pub fn fields(&self) -> Vec<(WidgetId, String)> {
+        self.fields.lock().unwrap().iter().map(|(id, name)| (*id, name.clone())).collect()
+    }
+
+    /// Forget the actions of widgets that are no longer in the tree
+    pub fn prune(&self, in_tree: impl Fn(WidgetId) -> bool) {
+        for map in [&self.bindings, &self.navigations, &self.load_mores, &self.fields] {
+            map.lock().unwrap().retain(|id, _| in_tree(*id));
         }
     }
 
@@ -36,6 +145,16 @@ impl RenderContext {
         self
     }
 
+    pub fn with_images(mut self, images: RemoteImages) -> Self {
+        self.images = Some(images);
+        self
+    }
+
+    pub fn with_styles(mut self, styles: StyleSheet) -> Self {
+        self.styles = Arc::new(styles);
+        self
+    }
+
     /// Evaluate an expression using this context
     pub fn eval(&mut self, expression: &str) -> Result<Value, String> {
         self.runtime_ctx
@@ -52,11 +171,223 @@ fn wrap_in_flex<W: masonry::core::Widget + 'static>(widget: NewWidget<W>) -> New
 /// Extract flex value from a node if it has one
 fn get_child_flex(node: &Node) -> Option<f64> {
     match node {
-        Node::VStack { flex, .. } | Node::HStack { flex, .. } => *flex,
+        Node::VStack { layout, .. } | Node::HStack { layout, .. } => layout.flex,
         _ => None,
     }
 }
 
+/// Lay out a stack's children in `flex`, then apply its sizes and constraints
+fn build_stack(
+    flex: Flex,
+    children: &[Node],
+    layout: &StackLayout,
+    debug_color: Color,
+    ctx: &Option<RenderContext>,
+) -> NewWidget<Flex> {
+    let mut style_ctx = ctx.clone();
+    let style = compute_style(&layout.style, &mut style_ctx);
+    let child_ctx = styled_context(ctx, &style);
+
+    let main = layout.justify.as_deref().and_then(main_axis_alignment).unwrap_or(MainAxisAlignment::Start);
+    let cross = layout.align.as_deref().and_then(cross_axis_alignment).unwrap_or(CrossAxisAlignment::Start);
+    let mut flex_widget = flex.main_axis_alignment(main).cross_axis_alignment(cross);
+    if let Some(gap) = layout.gap {
+        flex_widget = flex_widget.with_gap(Length::px(gap));
+    }
+
+    // Add children - use flex attribute if specified
+    for child in children {
+        if let Some(child_flex) = get_child_flex(child) {
+            flex_widget = flex_widget.with_flex_child(build_widget_with_context(child, child_ctx.clone()), child_flex);
+        } else {
+            flex_widget = flex_widget.with_child(build_widget_with_context(child, child_ctx.clone()));
+        }
+    }
+
+    let props = if DEBUG_LAYOUT {
+        Properties::new()
+            .with(BorderColor { color: debug_color })
+            .with(BorderWidth { width: 1.0 })
+            .with(Padding::from_vh(4.0, 4.0))
+    } else {
+        Properties::new()
+    };
+    let mut props = box_props(&style, props);
+    if let Some(padding) = layout.padding {
+        props = props.with(Padding::all(padding));
+    }
+    let stack = NewWidget::new_with_props(flex_widget, props);
+
+    let bounded = [layout.min_width, layout.max_width, layout.min_height, layout.max_height]
+        .iter()
+        .any(Option::is_some);
+    if !bounded && layout.width.is_none() && layout.height.is_none() {
+        return stack;
+    }
+
+    // Exact sizes go on a SizedBox; min/max bounds on a Constrained inside it
+    let mut sized = if bounded {
+        let mut constrained = Constrained::new(stack);
+        if let Some(w) = layout.min_width { constrained = constrained.with_min_width(w); }
+        if let Some(w) = layout.max_width { constrained = constrained.with_max_width(w); }
+        if let Some(h) = layout.min_height { constrained = constrained.with_min_height(h); }
+        if let Some(h) = layout.max_height { constrained = constrained.with_max_height(h); }
+        SizedBox::new(NewWidget::new(constrained))
+    } else {
+        SizedBox::new(stack)
+    };
+    if let Some(w) = layout.width { sized = sized.width(Length::px(w)); }
+    if let Some(h) = layout.height { sized = sized.height(Length::px(h)); }
+    wrap_in_flex(NewWidget::new(sized))
+}
+
+/// `justify` attribute values
+pub fn main_axis_alignment(name: &str) -> Option<MainAxisAlignment> {
+    match name {
+        "start" => Some(MainAxisAlignment::Start),
+        "center" => Some(MainAxisAlignment::Center),
+        "end" => Some(MainAxisAlignment::End),
+        "space-between" => Some(MainAxisAlignment::SpaceBetween),
+        "space-around" => Some(MainAxisAlignment::SpaceAround),
+        "space-evenly" => Some(MainAxisAlignment::SpaceEvenly),
+        _ => None,
+    }
+}
+
+/// `align` attribute values
+pub fn cross_axis_alignment(name: &str) -> Option<CrossAxisAlignment> {
+    match name {
+        "start" => Some(CrossAxisAlignment::Start),
+        "center" => Some(CrossAxisAlignment::Center),
+        "end" => Some(CrossAxisAlignment::End),
+        "stretch" => Some(CrossAxisAlignment::Fill),
+        "baseline" => Some(CrossAxisAlignment::Baseline),
+        _ => None,
+    }
+}
+
+/// Theme and classes in effect (the built-in theme without a context)
+fn styles(ctx: &Option<RenderContext>) -> Arc<StyleSheet> {
+    ctx.as_ref().map_or_else(|| Arc::clone(&DEFAULT_STYLES), |ctx| Arc::clone(&ctx.styles))
+}
+
+/// Text properties set by enclosing styled nodes
+fn inherited(ctx: &Option<RenderContext>) -> ComputedStyle {
+    ctx.as_ref().map(|ctx| ctx.inherited.clone()).unwrap_or_default()
+}
+
+/// Size and weight of body text
+fn body_font(ctx: &Option<RenderContext>) -> (f32, FontWeight) {
+    let inherited = inherited(ctx);
+    (
+        inherited.font_size.unwrap_or_else(|| styles(ctx).font_size("body")),
+        inherited.font_weight.unwrap_or(FontWeight::NORMAL),
+    )
+}
+
+/// Text color: the inherited one, or the theme's
+fn text_props(ctx: &Option<RenderContext>) -> Properties {
+    let color = inherited(ctx).color.unwrap_or_else(|| styles(ctx).color("text"));
+    Properties::new().with(ContentColor::new(color))
+}
+
+/// Resolve a node's classes and style properties, evaluating dynamic ones
+fn compute_style(style: &NodeStyle, ctx: &mut Option<RenderContext>) -> ComputedStyle {
+    if style.is_empty() {
+        return ComputedStyle::default();
+    }
+
+    let class = match &style.class {
+        // Without a context a `{expr}` class has nothing to evaluate against
+        Some(class) if ctx.is_some() || !is_template(class) => interpolate(class, None, ctx),
+        _ => String::new(),
+    };
+
+    let mut names: Vec<&String> = style.properties.keys().collect();
+    names.sort();
+    let mut properties = Vec::new();
+    for name in names {
+        let value = match &style.properties[name] {
+            PropValue::Literal(value) => value.clone(),
+            PropValue::Expression(expr) => match ctx.as_mut().map(|context| context.eval(expr)) {
+                Some(Ok(value)) => value_to_string(&value),
+                Some(Err(e)) => {
+                    report_eval_error(None, &format!("style '{}'", name), expr, e);
+                    continue;
+                }
+                None => continue,
+            },
+        };
+        // `null` leaves the property to the classes
+        if !value.is_empty() {
+            properties.push((name.clone(), value));
+        }
+    }
+    styles(ctx).compute(&class, &properties)
+}
+
+/// Context for the children of a styled node, whose text inherits its style
+fn styled_context(ctx: &Option<RenderContext>, style: &ComputedStyle) -> Option<RenderContext> {
+    ctx.clone().map(|mut ctx| {
+        ctx.inherited = style.inherit(&ctx.inherited);
+        ctx
+    })
+}
+
+/// Background, border, corner radius and padding of a styled box
+fn box_props(style: &ComputedStyle, mut props: Properties) -> Properties {
+    if let Some(color) = style.background {
+        props = props.with(Background::Color(color));
+    }
+    if let Some(color) = style.border_color {
+        props = props
+            .with(BorderColor { color })
+            .with(BorderWidth { width: style.border_width.unwrap_or(1.0) });
+    } else if let Some(width) = style.border_width {
+        props = props.with(BorderWidth { width });
+    }
+    if let Some(radius) = style.radius {
+        props = props.with(CornerRadius { radius });
+    }
+    if let Some(padding) = style.padding {
+        props = props.with(Padding::all(padding));
+    }
+    props
+}
+
+/// Monospace font for code
+fn monospace() -> StyleProperty {
+    StyleProperty::FontStack(FontStack::Single(FontFamily::Generic(GenericFamily::Monospace)))
+}
+
+/// Checkbox for a task list item; a bound item reads its state and is registered for writes
+fn task_checkbox(item: &ListItem, ctx: &mut Option<RenderContext>) -> NewWidget<Checkbox> {
+    let text = item
+        .children
+        .iter()
+        .map(|child| node_to_text_with_context(child, ctx))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut checked = item.checked.unwrap_or(false);
+    if let (Some(path), Some(context)) = (&item.bind, ctx.as_mut()) {
+        match context.eval(path) {
+            // Unset state keeps the checkbox from the source
+            Ok(Value::Null) => {}
+            Ok(Value::Bool(value)) => checked = value,
+            Ok(other) => report_eval_error(None, "task binding", path, format!("expected a boolean, got {}", other)),
+            Err(e) => report_eval_error(None, "task binding", path, e),
+        }
+    }
+
+    let label = NewWidget::new_with_props(Label::new(text), text_props(ctx));
+    let widget = NewWidget::new(Checkbox::from_label(checked, label));
+    if let (Some(path), Some(context)) = (&item.bind, ctx.as_ref()) {
+        context.bind(widget.id(), path);
+    }
+    widget
+}
+
 /// Convert a JSON value to a displayable string
 fn value_to_string(value: &Value) -> String {
     match value {
@@ -68,104 +399,74 @@ fn value_to_string(value: &Value) -> String {
     }
 }
 
-/// Build an inline widget (for use in paragraphs/lists with mixed formatting)
-/// The `add_space` parameter adds a trailing space for word separation
-fn build_inline_widget(node: &Node, add_space: bool) -> NewWidget<Label> {
-    let props = Properties::new().with(Padding::ZERO);
-    let space = if add_space { " " } else { "" };
+/// Whether an inline node can be drawn as a run of styled text
+fn is_text_run(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Text { .. }
+            | Node::Strong { .. }
+            | Node::Emphasis { .. }
+            | Node::Strikethrough { .. }
+            | Node::Link { .. }
+            | Node::InlineCode { .. }
+            | Node::FootnoteReference { .. }
+            | Node::Expr { .. }
+    )
+}
 
-    match node {
-        Node::Text { value } => NewWidget::new_with_props(
-            Label::new(format!("{}{}", value, space)).with_style(StyleProperty::FontSize(18.0)),
-            props,
-        ),
-        Node::Strong { children } => {
-            let text = render_children_to_text(children);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space))
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontWeight(FontWeight::BOLD)),
-                props,
-            )
-        }
-        Node::Emphasis { children } => {
-            let text = render_children_to_text(children);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space))
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontStyle(FontStyle::Italic)),
-                props,
-            )
-        }
-        Node::Link { children, .. } => {
-            let text = render_children_to_text(children);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space)).with_style(StyleProperty::FontSize(18.0)),
-                props,
-            )
+/// Append inline nodes to a rich text, nested formatting adding to the inherited style
+fn push_runs(nodes: &[Node], style: &RunStyle, text: &mut RichText, ctx: &mut Option<RenderContext>) {
+    for node in nodes {
+        match node {
+            Node::Text { value } => text.push(value, style.clone()),
+            Node::Strong { children } => push_runs(children, &RunStyle { bold: true, ..style.clone() }, text, ctx),
+            Node::Emphasis { children } => push_runs(children, &RunStyle { italic: true, ..style.clone() }, text, ctx),
+            Node::Strikethrough { children } => {
+                push_runs(children, &RunStyle { strikethrough: true, ..style.clone() }, text, ctx)
+            }
+            Node::Link { url, children } => {
+                push_runs(children, &RunStyle { link: Some(url.clone()), ..style.clone() }, text, ctx)
+            }
+            Node::InlineCode { value } => text.push(value, RunStyle { code: true, ..style.clone() }),
+            other => text.push(&node_to_text_with_context(other, ctx), style.clone()),
         }
-        _ => NewWidget::new_with_props(
-            Label::new(format!("{}{}", render_children_to_text(&[node.clone()]), space))
-                .with_style(StyleProperty::FontSize(18.0)),
-            props,
-        ),
     }
 }
 
-fn build_inline_widget_with_context(node: &Node, add_space: bool, ctx: &mut Option<RenderContext>) -> NewWidget<Label> {
-    let props = Properties::new().with(Padding::ZERO);
-    let space = if add_space { " " } else { "" };
+/// Rich text for a run of inline nodes
+fn rich_text(nodes: &[Node], font_size: f32, weight: FontWeight, ctx: &mut Option<RenderContext>) -> NewWidget<RichText> {
+    let mut text = RichText::new().with_font_size(font_size).with_weight(weight);
+    push_runs(nodes, &RunStyle::default(), &mut text, ctx);
+    let selection = SelectionColor { color: styles(ctx).color("selection") };
+    NewWidget::new_with_props(text, text_props(ctx).with(selection))
+}
 
-    match node {
-        Node::Text { value } => NewWidget::new_with_props(
-            Label::new(format!("{}{}", value, space)).with_style(StyleProperty::FontSize(18.0)),
-            props,
-        ),
-        Node::Strong { children } => {
-            let text = render_children_to_text_with_context(children, ctx);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space))
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontWeight(FontWeight::BOLD)),
-                props,
-            )
-        }
-        Node::Emphasis { children } => {
-            let text = render_children_to_text_with_context(children, ctx);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space))
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontStyle(FontStyle::Italic)),
-                props,
-            )
-        }
-        Node::Link { children, .. } => {
-            let text = render_children_to_text_with_context(children, ctx);
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space)).with_style(StyleProperty::FontSize(18.0)),
-                props,
-            )
-        }
-        Node::Expr { expression } => {
-            let text = if let Some(context) = ctx {
-                match context.eval(expression) {
-                    Ok(value) => value_to_string(&value),
-                    Err(_) => format!("{{{}}} [error]", expression),
-                }
-            } else {
-                format!("{{{}}}", expression)
-            };
-            NewWidget::new_with_props(
-                Label::new(format!("{}{}", text, space)).with_style(StyleProperty::FontSize(18.0)),
-                props,
-            )
+/// Build phrasing content (paragraph, heading or list item text) as wrapping rich text
+/// Inline images and components sit between text runs in a row
+fn build_inline_content(
+    children: &[Node],
+    font_size: f32,
+    weight: FontWeight,
+    ctx: &mut Option<RenderContext>,
+) -> NewWidget<Flex> {
+    if children.iter().all(is_text_run) {
+        return wrap_in_flex(rich_text(children, font_size, weight, ctx));
+    }
+
+    let mut row = Flex::row()
+        .with_gap(Length::ZERO)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center);
+    for group in children.chunk_by(|a, b| is_text_run(a) == is_text_run(b)) {
+        if is_text_run(&group[0]) {
+            row = row.with_child(rich_text(group, font_size, weight, ctx));
+        } else {
+            for child in group {
+                row = row.with_child(build_widget_with_context(child, ctx.clone()));
+            }
         }
-        _ => NewWidget::new_with_props(
-            Label::new(format!("{}{}", node_to_text_with_context(node, ctx), space))
-                .with_style(StyleProperty::FontSize(18.0)),
-            props,
-        ),
     }
+    NewWidget::new(row)
 }
 
 /// Build a Masonry widget from an AST node (static rendering, no data)
@@ -182,21 +483,8 @@ pub fn build_widget_with_context(
 
     match node {
         Node::Heading { level, children } => {
-            let text = render_children_to_text_with_context(children, &mut ctx_mut);
-            let size = match level {
-                1 => 40.0,
-                2 => 30.0,
-                3 => 24.0,
-                4 => 20.0,
-                5 => 18.0,
-                _ => 16.0,
-            };
-
-            wrap_in_flex(NewWidget::new(
-                Label::new(text)
-                    .with_style(StyleProperty::FontSize(size))
-                    .with_style(StyleProperty::FontWeight(FontWeight::BOLD)),
-            ))
+            let size = styles(&ctx).font_size(&format!("h{}", (*level).clamp(1, 6)));
+            build_inline_content(children, size, FontWeight::BOLD, &mut ctx_mut)
         }
 
         Node::Paragraph { children } => {
@@ -205,76 +493,125 @@ pub fn build_widget_with_context(
                 return build_widget_with_context(&children[0], ctx.clone());
             }
 
-            // Handle inline formatting by building widgets for each child
-            if children.iter().any(|c| matches!(c, Node::Strong { .. } | Node::Emphasis { .. } | Node::Link { .. } | Node::Expr { .. })) {
-                let mut flex = Flex::row()
-                    .with_gap(Length::ZERO)
-                    .main_axis_alignment(MainAxisAlignment::Start);
-                let len = children.len();
-                for (i, child) in children.iter().enumerate() {
-                    let add_space = i < len - 1;
-                    flex = flex.with_child(build_inline_widget_with_context(child, add_space, &mut ctx_mut));
-                }
-                NewWidget::new(flex)
+            let (size, weight) = body_font(&ctx);
+            build_inline_content(children, size, weight, &mut ctx_mut)
+        }
+
+        Node::Text { value } => {
+            let (size, weight) = body_font(&ctx);
+            wrap_in_flex(NewWidget::new_with_props(
+                Label::new(value.clone())
+                    .with_style(StyleProperty::FontSize(size))
+                    .with_style(StyleProperty::FontWeight(weight)),
+                text_props(&ctx),
+            ))
+        }
+
+        Node::Strong { .. }
+        | Node::Emphasis { .. }
+        | Node::Strikethrough { .. }
+        | Node::Link { .. }
+        | Node::InlineCode { .. }
+        | Node::FootnoteReference { .. } => {
+            let (size, weight) = body_font(&ctx);
+            build_inline_content(std::slice::from_ref(node), size, weight, &mut ctx_mut)
+        }
+
+        Node::TextBlock { children, style } => {
+            let style = compute_style(style, &mut ctx_mut);
+            let mut text_ctx = styled_context(&ctx, &style);
+            let content = if children.iter().all(|child| is_text_run(child) || matches!(child, Node::Image { .. })) {
+                let (size, weight) = body_font(&text_ctx);
+                build_inline_content(children, size, weight, &mut text_ctx)
             } else {
-                let text = render_children_to_text_with_context(children, &mut ctx_mut);
-                wrap_in_flex(NewWidget::new(
-                    Label::new(text).with_style(StyleProperty::FontSize(18.0))
-                ))
+                let mut column = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+                for child in children {
+                    column = column.with_child(build_widget_with_context(child, text_ctx.clone()));
+                }
+                NewWidget::new(column)
+            };
+            if style == ComputedStyle::default() {
+                return content;
             }
+            let boxed = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start).with_child(content);
+            NewWidget::new_with_props(boxed, box_props(&style, Properties::new()))
         }
 
-        Node::Text { value } => wrap_in_flex(NewWidget::new(
-            Label::new(value.clone()).with_style(StyleProperty::FontSize(18.0))
-        )),
-
-        Node::Strong { children } => {
-            let text = render_children_to_text_with_context(children, &mut ctx_mut);
-            wrap_in_flex(NewWidget::new(
-                Label::new(text)
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontWeight(FontWeight::BOLD)),
-            ))
+        Node::FootnoteDefinition { label, children } => {
+            let mut content = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+            for child in children {
+                content = content.with_child(build_widget_with_context(child, ctx.clone()));
+            }
+            let row = Flex::row()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_gap(Length::px(6.0))
+                .with_child(NewWidget::new_with_props(
+                    Label::new(format!("[{}]", label)).with_style(StyleProperty::FontSize(styles(&ctx).font_size("small"))),
+                    text_props(&ctx),
+                ))
+                .with_flex_child(NewWidget::new(content), 1.0);
+            NewWidget::new(row)
         }
 
-        Node::Emphasis { children } => {
-            let text = render_children_to_text_with_context(children, &mut ctx_mut);
-            wrap_in_flex(NewWidget::new(
-                Label::new(text)
-                    .with_style(StyleProperty::FontSize(18.0))
-                    .with_style(StyleProperty::FontStyle(FontStyle::Italic)),
+        Node::CodeBlock { value, .. } => {
+            let theme = styles(&ctx);
+            let code_props = text_props(&ctx)
+                .with(Background::Color(theme.color("surface")))
+                .with(Padding::from_vh(12., 12.))
+                .with(BorderColor { color: theme.color("border") })
+                .with(BorderWidth { width: 1.0 })
+                .with(CornerRadius { radius: 4.0 });
+
+            wrap_in_flex(NewWidget::new_with_props(
+                Label::new(value.clone())
+                    .with_style(StyleProperty::FontSize(theme.font_size("code")))
+                    .with_style(monospace()),
+                code_props,
             ))
         }
 
-        Node::Link { url: _, children } => {
-            let text = render_children_to_text_with_context(children, &mut ctx_mut);
-            wrap_in_flex(NewWidget::new(Label::new(text)))
+        Node::Blockquote { children } => {
+            let mut content = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+            for child in children {
+                content = content.with_child(build_widget_with_context(child, ctx.clone()));
+            }
+
+            // Quote bar down the left edge, stretched to the content's height
+            let bar = NewWidget::new_with_props(
+                SizedBox::empty().width(Length::px(4.0)),
+                Properties::new().with(Background::Color(styles(&ctx).color("border"))),
+            );
+            let row = Flex::row()
+                .cross_axis_alignment(CrossAxisAlignment::Fill)
+                .with_gap(Length::px(12.0))
+                .with_child(bar)
+                .with_flex_child(NewWidget::new(content), 1.0);
+
+            NewWidget::new_with_props(row, Properties::new().with(Padding::from_vh(4.0, 0.0)))
         }
 
-        Node::Image { src, alt } => {
+        Node::Table { align, rows } => build_table(align, rows, &mut ctx_mut),
+
+        Node::Image { src, alt, width, height, fit, span } => {
+            let src = interpolate(src, *span, &mut ctx_mut);
+            let alt = interpolate(alt, *span, &mut ctx_mut);
+            let layout = ImageLayout::new(*width, *height, fit.as_deref());
+
+            // Nothing to show yet (e.g. a profile without a picture)
+            if src.is_empty() {
+                return wrap_in_flex(NewWidget::new_with_props(Label::new(format!("[Image: {}]", alt)), text_props(&ctx)));
+            }
+            if ImageLoader::is_remote(&src) {
+                return remote_image(&src, &alt, layout, &ctx);
+            }
+
             // Try to load image from file
-            match load_image(src) {
-                Ok(image_brush) => {
-                    use masonry::widgets::SizedBox;
-
-                    // Wrap image in SizedBox to constrain its size
-                    // This prevents images from expanding beyond their container
-                    let image = Image::new(image_brush);
-                    let props = Properties::new()
-                        .with(ObjectFit::Contain);  // Maintain aspect ratio
-
-                    // Constrain to max 100x100 for avatar-style images
-                    // The container's flex will further constrain if needed
-                    let sized_image = SizedBox::new(NewWidget::new_with_props(image, props))
-                        .width(Length::px(100.0))
-                        .height(Length::px(100.0));
-
-                    wrap_in_flex(NewWidget::new(sized_image))
-                }
+            match load_image(&src) {
+                Ok(brush) => layout.apply(brush),
                 Err(e) => {
                     // Fallback to showing alt text if image fails to load
-                    eprintln!("Failed to load image {}: {}", src, e);
-                    wrap_in_flex(NewWidget::new(Label::new(format!("[Image: {}]", alt))))
+                    eprintln!("{}", with_location(*span, format!("Failed to load image {}: {}", src, e)));
+                    wrap_in_flex(NewWidget::new_with_props(Label::new(format!("[Image: {}]", alt)), text_props(&ctx)))
                 }
             }
         }
@@ -282,131 +619,113 @@ pub fn build_widget_with_context(
         Node::List { ordered, items } => {
             let mut flex = Flex::column();
             for (i, item) in items.iter().enumerate() {
+                if item.checked.is_some() {
+                    flex = flex.with_child(task_checkbox(item, &mut ctx_mut));
+                    continue;
+                }
+
                 let marker = if *ordered {
                     format!("{}. ", i + 1)
                 } else {
                     "• ".to_string()
                 };
 
-                // Build a row with marker + inline content
-                let mut row = Flex::row().with_gap(Length::ZERO);
-                row = row.with_child(NewWidget::new_with_props(
-                    Label::new(marker).with_style(StyleProperty::FontSize(18.0)),
-                    Properties::new().with(Padding::ZERO),
-                ));
-
+                // Marker, then the item's content wrapping in the remaining width
+                let mut content = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
                 for child in &item.children {
-                    // Check if child has inline formatting
-                    if let Node::Paragraph { children } = child {
-                        let len = children.len();
-                        for (i, inline_child) in children.iter().enumerate() {
-                            let add_space = i < len - 1;
-                            row = row.with_child(build_inline_widget(inline_child, add_space));
+                    content = content.with_child(match child {
+                        Node::Paragraph { children } => {
+                            let (size, weight) = body_font(&ctx);
+                            build_inline_content(children, size, weight, &mut ctx_mut)
                         }
-                    } else {
-                        row = row.with_child(build_inline_widget(child, false));
-                    }
+                        block => build_widget_with_context(block, ctx_mut.clone()),
+                    });
                 }
+                let row = Flex::row()
+                    .with_gap(Length::ZERO)
+                    .cross_axis_alignment(CrossAxisAlignment::Start)
+                    .with_child(NewWidget::new_with_props(
+                        Label::new(marker).with_style(StyleProperty::FontSize(body_font(&ctx).0)),
+                        text_props(&ctx).with(Padding::ZERO),
+                    ))
+                    .with_flex_child(NewWidget::new(content), 1.0);
 
                 flex = flex.with_child(NewWidget::new(row));
             }
             NewWidget::new(flex)
         }
 
-        Node::VStack { children, width, height, flex: flex_val, align } => {
-            let mut flex_widget = Flex::column()
-                .main_axis_alignment(MainAxisAlignment::Start)
-                .cross_axis_alignment(CrossAxisAlignment::Start);
-
-            // Add children - use flex attribute if specified
-            for child in children {
-                if let Some(child_flex) = get_child_flex(child) {
-                    flex_widget = flex_widget.with_flex_child(build_widget_with_context(child, ctx.clone()), child_flex);
-                } else {
-                    flex_widget = flex_widget.with_child(build_widget_with_context(child, ctx.clone()));
-                }
-            }
-
-            // Apply width/height constraints if specified
-            let mut props = if DEBUG_LAYOUT {
-                Properties::new()
-                    .with(BorderColor { color: Color::from_rgb8(255, 0, 0) })
-                    .with(BorderWidth { width: 1.0 })
-                    .with(Padding::from_vh(4.0, 4.0))
-            } else {
-                Properties::new()
-            };
-
-            // TODO: Apply width/height/align properties when Masonry supports them
-
-            NewWidget::new_with_props(flex_widget, props)
+        Node::VStack { children, layout } => {
+            build_stack(Flex::column(), children, layout, Color::from_rgb8(255, 0, 0), &ctx)
         }
 
-        Node::HStack { children, width, height, flex: flex_val, align } => {
-            let mut flex_widget = Flex::row()
-                .main_axis_alignment(MainAxisAlignment::Start)
-                .cross_axis_alignment(CrossAxisAlignment::Start);
-
-            // Add children - use flex attribute if specified
-            for child in children {
-                if let Some(child_flex) = get_child_flex(child) {
-                    flex_widget = flex_widget.with_flex_child(build_widget_with_context(child, ctx.clone()), child_flex);
-                } else {
-                    flex_widget = flex_widget.with_child(build_widget_with_context(child, ctx.clone()));
-                }
-            }
-
-            // Apply width/height constraints if specified
-            let mut props = if DEBUG_LAYOUT {
-                Properties::new()
-                    .with(BorderColor { color: Color::from_rgb8(0, 0, 255) })
-                    .with(BorderWidth { width: 1.0 })
-                    .with(Padding::from_vh(4.0, 4.0))
-            } else {
-                Properties::new()
-            };
-
-            // TODO: Apply width/height/align properties when Masonry supports them
-
-            NewWidget::new_with_props(flex_widget, props)
+        Node::HStack { children, layout } => {
+            build_stack(Flex::row(), children, layout, Color::from_rgb8(0, 0, 255), &ctx)
         }
 
-        Node::Button { on_click: _, children } => {
+        Node::Button { on_click, children, span } => {
             let text = render_children_to_text(children);
+            let theme = styles(&ctx);
             let button_props = Properties::new()
-                .with(Background::Color(Color::from_rgb8(200, 200, 200)))
-                .with(BorderColor { color: Color::from_rgb8(128, 128, 128) })
+                .with(Background::Color(theme.color("button")))
+                .with(BorderColor { color: theme.color("control_border") })
                 .with(BorderWidth { width: 1.0 })
                 .with(CornerRadius { radius: 4.0 })
                 .with(Padding::from_vh(8., 16.));
-            wrap_in_flex(NewWidget::new_with_props(Button::with_text(text), button_props))
+            let label = NewWidget::new_with_props(Label::new(text), text_props(&ctx));
+            let widget = NewWidget::new_with_props(Button::new(label), button_props);
+
+            // `loadMore(<query>)` buttons are registered with the query they page
+            let query = on_click.as_deref().and_then(load_more_target);
+            if let (Some(query), Some(ctx)) = (query, &ctx) {
+                ctx.load_more_on(widget.id(), query);
+            }
+
+            // `navigate(<expr>)` buttons are registered with where they lead
+            let target = on_click.as_deref().and_then(crate::navigation::navigate_target);
+            if let (Some(target), Some(ctx)) = (target, ctx_mut.as_mut()) {
+                match ctx.eval(target) {
+                    Ok(Value::String(path)) => ctx.navigate_on(widget.id(), &path),
+                    Ok(other) => report_eval_error(*span, "navigate target", target, format!("expected a string, got {}", other)),
+                    Err(e) => report_eval_error(*span, "navigate target", target, e),
+                }
+            }
+            wrap_in_flex(widget)
         }
 
-        Node::Input { name, placeholder } => {
-            // Create TextInput with placeholder
-            let mut input = TextInput::new("");
+        Node::Input { name, placeholder, .. } => {
+            // Create TextInput with placeholder, keeping text typed before a rebuild
+            let text = ctx.as_ref().and_then(|ctx| ctx.runtime_ctx.get_form_field(name).cloned()).unwrap_or_default();
+            let mut input = TextInput::new(&text);
             if let Some(ph) = placeholder {
                 input = input.with_placeholder(ph.as_str());
             } else {
                 input = input.with_placeholder(name.as_str());
             }
 
-            // Style the input with our light mode theme
+            // Style the input from the theme
+            let theme = styles(&ctx);
             let input_props = Properties::new()
-                .with(BorderColor { color: Color::from_rgb8(128, 128, 128) })
-                .with(FocusedBorderColor::new(Color::from_rgb8(0, 122, 255)))  // Blue focus border
+                .with(BorderColor { color: theme.color("control_border") })
+                .with(FocusedBorderColor::new(theme.color("primary")))
                 .with(BorderWidth { width: 1.0 })
                 .with(CornerRadius { radius: 4.0 })
                 .with(Padding::from_vh(8., 12.))
-                .with(Background::Color(Color::from_rgb8(255, 255, 255)))
-                .with(CaretColor { color: AlphaColor::from_rgb8(0, 0, 0) })
-                .with(SelectionColor { color: AlphaColor::from_rgb8(173, 214, 255) })
-                .with(UnfocusedSelectionColor(SelectionColor { color: AlphaColor::from_rgb8(200, 200, 200) }));
-            wrap_in_flex(NewWidget::new_with_props(input, input_props))
+                .with(Background::Color(theme.color("input")))
+                .with(ContentColor::new(theme.color("text")))
+                .with(PlaceholderColor { color: theme.color("muted") })
+                .with(CaretColor { color: theme.color("text") })
+                .with(SelectionColor { color: theme.color("selection") })
+                .with(UnfocusedSelectionColor(SelectionColor { color: theme.color("selection_inactive") }));
+            let widget = NewWidget::new_with_props(input, input_props);
+            if let Some(ctx) = &ctx {
+                ctx.field_on(widget.id(), name);
+            }
+            wrap_in_flex(widget)
         }
 
         // JSON debug viewer - renders JSON with pretty formatting
-        Node::Json { value } => {
+        Node::Json { value, span } => {
             let text = if let Some(mut ctx) = ctx.clone() {
                 // Try to evaluate the expression and pretty-print JSON
                 match ctx.eval(value) {
@@ -415,7 +734,10 @@ pub fn build_widget_with_context(
                         serde_json::to_string_pretty(&json_value)
                             .unwrap_or_else(|_| format!("{{{}}} [json error]", value))
                     }
-                    Err(_) => format!("{{{}}} [eval error]", value),
+                    Err(e) => {
+                        report_eval_error(*span, "json value", value, e);
+                        format!("{{{}}} [eval error]", value)
+                    }
                 }
             } else {
                 // No context, show placeholder
@@ -423,10 +745,11 @@ pub fn build_widget_with_context(
             };
 
             // Use a monospace-style label for JSON display
-            let json_props = Properties::new()
-                .with(Background::Color(Color::from_rgb8(245, 245, 245)))
+            let theme = styles(&ctx);
+            let json_props = text_props(&ctx)
+                .with(Background::Color(theme.color("surface")))
                 .with(Padding::from_vh(12., 12.))
-                .with(BorderColor { color: Color::from_rgb8(200, 200, 200) })
+                .with(BorderColor { color: theme.color("border") })
                 .with(BorderWidth { width: 1.0 })
                 .with(CornerRadius { radius: 4.0 });
 
@@ -434,53 +757,50 @@ pub fn build_widget_with_context(
         }
 
         // Expression evaluation - render the evaluated value or placeholder
-        Node::Expr { expression } => {
+        Node::Expr { expression, span } => {
             let text = if let Some(mut ctx) = ctx.clone() {
                 // Try to evaluate the expression
                 match ctx.eval(expression) {
                     Ok(value) => value_to_string(&value),
-                    Err(_) => format!("{{{}}} [error]", expression),
+                    Err(e) => {
+                        report_eval_error(*span, "expression", expression, e);
+                        format!("{{{}}} [error]", expression)
+                    }
                 }
             } else {
                 // No context, show placeholder
                 format!("{{{}}}", expression)
             };
-            wrap_in_flex(NewWidget::new(Label::new(text)))
+            let (size, weight) = body_font(&ctx);
+            wrap_in_flex(NewWidget::new_with_props(
+                Label::new(text)
+                    .with_style(StyleProperty::FontSize(size))
+                    .with_style(StyleProperty::FontWeight(weight)),
+                text_props(&ctx),
+            ))
         }
 
-        Node::Each { from, as_name, children } => {
-            // If we have a context, evaluate the `from` expression to get an array
-            if let Some(mut render_ctx) = ctx_mut.clone() {
-                let items = match render_ctx.runtime_ctx.eval(from, &mut render_ctx.evaluator) {
-                    Ok(value) => {
-                        if let Value::Array(arr) = value {
-                            arr
-                        } else {
-                            // If it's not an array, treat it as a single-item array
-                            vec![value]
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error evaluating each expression '{}': {}", from, e);
-                        vec![]
-                    }
-                };
+        Node::Each { from, as_name, key, viewport: Some(height), on_end, children, span } => match &ctx {
+            Some(render_ctx) => {
+                let list = build_virtual_list(from, as_name, key.as_deref(), *height, children, *span, render_ctx);
+                if let Some(query) = on_end.as_deref().and_then(load_more_target) {
+                    render_ctx.load_more_on(list.id(), query);
+                }
+                wrap_in_flex(list)
+            }
+            None => wrap_in_flex(NewWidget::new_with_props(
+                Label::new(format!("[Each: {} as {} - no context]", from, as_name)),
+                text_props(&ctx),
+            )),
+        },
 
+        Node::Each { from, as_name, children, span, .. } => {
+            // If we have a context, evaluate the `from` expression to get an array
+            if let Some(render_ctx) = &ctx {
                 // Build a vstack containing all the items
                 let mut flex = Flex::column();
 
-                for (index, item) in items.iter().enumerate() {
-                    // Create scoped context with item binding
-                    let scoped_runtime_ctx = render_ctx.runtime_ctx
-                        .with_local(as_name, item.clone())
-                        .with_local("itemIndex", json!(index));
-
-                    let scoped_ctx = RenderContext {
-                        runtime_ctx: scoped_runtime_ctx,
-                        evaluator: render_ctx.evaluator.clone(),
-                        registry: render_ctx.registry.clone(),
-                    };
-
+                for scoped_ctx in each_scopes(from, as_name, *span, render_ctx) {
                     // Render children with scoped context
                     for child in children {
                         flex = flex.with_child(build_widget_with_context(child, Some(scoped_ctx.clone())));
@@ -490,37 +810,191 @@ pub fn build_widget_with_context(
                 NewWidget::new(flex)
             } else {
                 // No context available, show placeholder
-                wrap_in_flex(NewWidget::new(Label::new(format!(
-                    "[Each: {} as {} - no context]",
-                    from, as_name
-                ))))
+                wrap_in_flex(NewWidget::new_with_props(
+                    Label::new(format!("[Each: {} as {} - no context]", from, as_name)),
+                    text_props(&ctx),
+                ))
             }
         }
 
-        Node::If { value, .. } => wrap_in_flex(NewWidget::new(Label::new(format!("[If: {}]", value)))),
+        Node::If { value, .. } => {
+            wrap_in_flex(NewWidget::new_with_props(Label::new(format!("[If: {}]", value)), text_props(&ctx)))
+        }
+
+        Node::Grid { columns, min_column_width, gap, row_gap, column_gap, children } => {
+            let layout = match (columns, min_column_width) {
+                (None, Some(min)) => GridColumns::MinWidth(*min),
+                (columns, _) => GridColumns::Fixed(columns.unwrap_or(1)),
+            };
+            let gap = gap.unwrap_or(0.0);
+            let mut grid = AutoGrid::new(layout).with_gaps(row_gap.unwrap_or(gap), column_gap.unwrap_or(gap));
 
-        Node::Grid { children, .. } => {
-            let mut flex = Flex::column();
             for child in children {
-                flex = flex.with_child(build_widget_with_context(child, ctx.clone()));
+                // Each item of an `<each>` is its own cell (or cells)
+                let scopes = match (child, &ctx) {
+                    (Node::Each { from, as_name, children, span, .. }, Some(render_ctx)) => {
+                        each_scopes(from, as_name, *span, render_ctx)
+                            .into_iter()
+                            .flat_map(|scope| children.iter().map(move |child| (child, Some(scope.clone()))))
+                            .collect()
+                    }
+                    _ => vec![(child, ctx.clone())],
+                };
+                for (cell, cell_ctx) in scopes {
+                    let (span, row_span) = grid_span(cell);
+                    grid = grid.with_cell(build_widget_with_context(cell, cell_ctx), span, row_span);
+                }
             }
-            NewWidget::new(flex)
+
+            wrap_in_flex(NewWidget::new(grid))
         }
 
-        Node::Spacer { size } => {
-            let height = size.unwrap_or(20.0);
+        Node::Spacer { .. } | Node::ThematicBreak => {
+            let height = match node {
+                Node::Spacer { size: Some(size) } => *size,
+                _ => 20.0,
+            };
             // Use our custom Hr widget for horizontal rules
             let hr = Hr::new().with_height(height);
             let props = Properties::new()
-                .with(HrColor::new(Color::from_rgb8(180, 180, 180)))
+                .with(HrColor::new(styles(&ctx).color("hr")))
                 .with(Padding::from_vh(8.0, 0.0));
             wrap_in_flex(NewWidget::new_with_props(hr, props))
         }
 
-        Node::CustomComponent { name, props, children } => {
-            render_custom_component(name, props, children, &ctx)
+        Node::CustomComponent { name, props, children, span } => {
+            render_custom_component(name, props, children, *span, &ctx)
+        }
+    }
+}
+
+/// Items of an `<each>`; a value that isn't an array is a single item
+fn each_items(from: &str, span: Option<Span>, ctx: &RenderContext) -> Vec<Value> {
+    let mut render_ctx = ctx.clone();
+    match render_ctx.eval(from) {
+        Ok(Value::Array(arr)) => arr,
+        Ok(value) => vec![value],
+        Err(e) => {
+            report_eval_error(span, "each expression", from, e);
+            vec![]
+        }
+    }
+}
+
+/// Context for one item of an `<each>`, binding it to `as_name` and its index to `itemIndex`
+fn each_scope(ctx: &RenderContext, as_name: &str, index: usize, item: Value) -> RenderContext {
+    let mut scoped_ctx = ctx.clone();
+    scoped_ctx.runtime_ctx = ctx.runtime_ctx.with_local(as_name, item).with_local("itemIndex", json!(index));
+    scoped_ctx.scope = format!("{}[{}]", ctx.scope, index);
+    scoped_ctx
+}
+
+/// A `virtual` `<each>`: rows are built from their item only while they are near the view
+fn build_virtual_list(
+    from: &str,
+    as_name: &str,
+    key: Option<&str>,
+    height: f64,
+    children: &[Node],
+    span: Option<Span>,
+    ctx: &RenderContext,
+) -> NewWidget<VirtualList> {
+    let items = each_items(from, span, ctx);
+
+    // Keys follow items when the list changes: the `key` expression, the item's `id`, or its position
+    let keys = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| match key {
+            Some(key) => match each_scope(ctx, as_name, index, item.clone()).eval(key) {
+                Ok(Value::String(s)) => s,
+                Ok(other) => other.to_string(),
+                Err(e) => {
+                    report_eval_error(span, "each key", key, e);
+                    index.to_string()
+                }
+            },
+            None => match item.get("id") {
+                Some(Value::String(id)) => id.clone(),
+                _ => index.to_string(),
+            },
+        })
+        .collect();
+
+    let memory = Arc::clone(ctx.lists.lock().unwrap().entry(list_key(from, span, ctx)).or_default());
+    let (ctx, as_name, children) = (ctx.clone(), as_name.to_string(), children.to_vec());
+    let list = VirtualList::new(keys, height, move |index| {
+        let scoped_ctx = each_scope(&ctx, &as_name, index, items[index].clone());
+        let mut row = Flex::column();
+        for child in &children {
+            row = row.with_child(build_widget_with_context(child, Some(scoped_ctx.clone())));
+        }
+        NewWidget::new(row).erased()
+    })
+    .with_memory(memory);
+
+    NewWidget::new(list)
+}
+
+/// Key of a `virtual` list's memory: the list's place in the page and its `from` expression
+fn list_key(from: &str, span: Option<Span>, ctx: &RenderContext) -> String {
+    match span {
+        Some(span) => format!("{}@{}:{} {}", ctx.scope, span.line, span.column, from),
+        None => format!("{}{}", ctx.scope, from),
+    }
+}
+
+/// Scoped contexts for the items of an `<each>`, binding each item to `as_name` and its index to `itemIndex`
+fn each_scopes(from: &str, as_name: &str, span: Option<Span>, ctx: &RenderContext) -> Vec<RenderContext> {
+    each_items(from, span, ctx)
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| each_scope(ctx, as_name, index, item))
+        .collect()
+}
+
+/// Columns and rows a grid child covers (`span`/`row_span` on a stack)
+fn grid_span(node: &Node) -> (usize, usize) {
+    match node {
+        Node::VStack { layout, .. } | Node::HStack { layout, .. } => {
+            (layout.span.unwrap_or(1), layout.row_span.unwrap_or(1))
+        }
+        _ => (1, 1),
+    }
+}
+
+/// Build a table as a grid of bordered cells, aligned per column; the first row is bold
+fn build_table(align: &[ColumnAlign], rows: &[TableRow], ctx: &mut Option<RenderContext>) -> NewWidget<Flex> {
+    let columns = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0).max(align.len());
+    let mut grid = Grid::with_dimensions(columns as i32, rows.len() as i32);
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.cells.iter().enumerate() {
+            let text = render_children_to_text_with_context(&cell.children, ctx);
+            let mut label = Label::new(text).with_style(StyleProperty::FontSize(styles(ctx).font_size("table")));
+            if y == 0 {
+                label = label.with_style(StyleProperty::FontWeight(FontWeight::BOLD));
+            }
+
+            let cross = match align.get(x).copied().unwrap_or_default() {
+                ColumnAlign::Center => CrossAxisAlignment::Center,
+                ColumnAlign::Right => CrossAxisAlignment::End,
+                ColumnAlign::Left | ColumnAlign::None => CrossAxisAlignment::Start,
+            };
+            let cell_props = Properties::new()
+                .with(Padding::from_vh(6., 10.))
+                .with(BorderColor { color: styles(ctx).color("border") })
+                .with(BorderWidth { width: 1.0 });
+            let cell_widget = Flex::column().cross_axis_alignment(cross).with_child(NewWidget::new_with_props(label, text_props(ctx)));
+
+            grid = grid.with_child(
+                NewWidget::new_with_props(cell_widget, cell_props),
+                GridParams::new(x as i32, y as i32, 1, 1),
+            );
         }
     }
+
+    wrap_in_flex(NewWidget::new(grid))
 }
 
 /// Render a custom component by looking it up in the registry
@@ -528,14 +1002,16 @@ fn render_custom_component(
     name: &str,
     props: &std::collections::HashMap<String, crate::parser::ast::PropValue>,
     _children: &[Node],
+    span: Option<Span>,
     parent_ctx: &Option<RenderContext>,
 ) -> NewWidget<Flex> {
     // Get component registry from context
     let ctx = match parent_ctx {
         Some(c) => c,
         None => {
-            return wrap_in_flex(NewWidget::new(
-                Label::new(format!("[Component: {} (no context)]", name))
+            return wrap_in_flex(NewWidget::new_with_props(
+                Label::new(format!("[Component: {} (no context)]", name)),
+                text_props(parent_ctx),
             ));
         }
     };
@@ -543,17 +1019,20 @@ fn render_custom_component(
     let registry = match &ctx.registry {
         Some(r) => r,
         None => {
-            return wrap_in_flex(NewWidget::new(
-                Label::new(format!("[Component: {} (no registry)]", name))
+            return wrap_in_flex(NewWidget::new_with_props(
+                Label::new(format!("[Component: {} (no registry)]", name)),
+                text_props(parent_ctx),
             ));
         }
     };
 
     // Look up component definition
     let Some(component_def) = registry.get(name) else {
-        return wrap_in_flex(NewWidget::new(
+        eprintln!("⚠️  {}", with_location(span, format!("Component '{}' not found", name)));
+        return wrap_in_flex(NewWidget::new_with_props(
             Label::new(format!("[Component: {} not found]", name))
-                .with_style(StyleProperty::FontSize(14.0))
+                .with_style(StyleProperty::FontSize(ctx.styles.font_size("small"))),
+            text_props(parent_ctx),
         ));
     };
 
@@ -567,7 +1046,7 @@ fn render_custom_component(
                 // Evaluate expression in parent context
                 if let Some(ctx) = &mut parent_ctx_mut {
                     ctx.eval(expr).unwrap_or_else(|e| {
-                        eprintln!("⚠️  Failed to evaluate prop '{}' expression '{}': {}", prop_name, expr, e);
+                        report_eval_error(span, &format!("prop '{}'", prop_name), expr, &e);
                         json!(format!("[eval error: {}]", e))
                     })
                 } else {
@@ -584,12 +1063,26 @@ fn render_custom_component(
 
     // Inherit queries from parent context (components share the same query results)
     component_runtime_ctx.queries = ctx.runtime_ctx.queries.clone();
+    component_runtime_ctx.theme = ctx.runtime_ctx.theme.clone();
 
     // Create render context for component
     let mut component_ctx = RenderContext::new(component_runtime_ctx);
     if let Some(ref reg) = ctx.registry {
         component_ctx = component_ctx.with_registry(reg.clone());
     }
+    component_ctx.bindings = ctx.bindings.clone();
+    component_ctx.navigations = ctx.navigations.clone();
+    component_ctx.load_mores = ctx.load_mores.clone();
+    component_ctx.fields = ctx.fields.clone();
+    component_ctx.lists = ctx.lists.clone();
+    component_ctx.scope = match span {
+        Some(span) => format!("{}/{}@{}:{}/", ctx.scope, name, span.line, span.column),
+        None => format!("{}/{}/", ctx.scope, name),
+    };
+    component_ctx.images = ctx.images.clone();
+    // Text styles cascade into the component; its own classes apply only inside it
+    component_ctx.styles = Arc::new(ctx.styles.for_component(&component_def.styles));
+    component_ctx.inherited = ctx.inherited.clone();
 
     // Render component body
     let mut flex = Flex::column();
@@ -600,6 +1093,14 @@ fn render_custom_component(
     NewWidget::new(flex)
 }
 
+/// Log a failed runtime evaluation with the source location of its node
+fn report_eval_error(span: Option<Span>, what: &str, expression: &str, error: impl std::fmt::Display) {
+    eprintln!(
+        "⚠️  {}",
+        with_location(span, format!("Failed to evaluate {} '{}': {}", what, expression, error))
+    );
+}
+
 /// Render child nodes to text (for labels)
 fn render_children_to_text(children: &[Node]) -> String {
     children.iter().map(node_to_text).collect()
@@ -611,9 +1112,12 @@ fn node_to_text(node: &Node) -> String {
         Node::Text { value } => value.clone(),
         Node::Strong { children } => render_children_to_text(children),
         Node::Emphasis { children } => render_children_to_text(children),
+        Node::Strikethrough { children } => render_children_to_text(children),
+        Node::FootnoteReference { label } => format!("[{}]", label),
         Node::Link { children, .. } => render_children_to_text(children),
+        Node::InlineCode { value } => value.clone(),
         Node::Paragraph { children } => render_children_to_text(children),
-        Node::Expr { expression } => format!("{{{}}}", expression),
+        Node::Expr { expression, .. } => format!("{{{}}}", expression),
         _ => String::new(),
     }
 }
@@ -629,14 +1133,17 @@ fn node_to_text_with_context(node: &Node, ctx: &mut Option<RenderContext>) -> St
         Node::Text { value } => value.clone(),
         Node::Strong { children } => render_children_to_text_with_context(children, ctx),
         Node::Emphasis { children } => render_children_to_text_with_context(children, ctx),
+        Node::Strikethrough { children } => render_children_to_text_with_context(children, ctx),
+        Node::FootnoteReference { label } => format!("[{}]", label),
         Node::Link { children, .. } => render_children_to_text_with_context(children, ctx),
+        Node::InlineCode { value } => value.clone(),
         Node::Paragraph { children } => render_children_to_text_with_context(children, ctx),
-        Node::Expr { expression } => {
+        Node::Expr { expression, span } => {
             if let Some(context) = ctx {
                 match context.eval(expression) {
                     Ok(value) => value_to_string(&value),
                     Err(e) => {
-                        eprintln!("⚠️  Failed to evaluate expression '{}': {}", expression, e);
+                        report_eval_error(*span, "expression", expression, e);
                         format!("{{{}}} [error]", expression)
                     }
                 }
@@ -648,6 +1155,27 @@ fn node_to_text_with_context(node: &Node, ctx: &mut Option<RenderContext>) -> St
     }
 }
 
+/// Fill in a `{expr}` template; without a context it is left as written
+fn interpolate(template: &str, span: Option<Span>, ctx: &mut Option<RenderContext>) -> String {
+    let Some(context) = ctx else {
+        return template.to_string();
+    };
+
+    template_parts(template)
+        .into_iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => text.to_string(),
+            TemplatePart::Expr(expression) => match context.eval(expression) {
+                Ok(value) => value_to_string(&value),
+                Err(e) => {
+                    report_eval_error(span, "expression", expression, e);
+                    String::new()
+                }
+            },
+        })
+        .collect()
+}
+
 /// Build a complete document widget tree
 pub fn build_document_widget(nodes: &[Node]) -> NewWidget<Flex> {
     build_document_widget_tagged(nodes, None)
@@ -675,38 +1203,131 @@ pub fn build_document_widget_with_context(
     }
 }
 
-/// Load an image from a file path and convert to ImageBrush
-fn load_image(path: &str) -> Result<masonry::vello::peniko::ImageBrush, Box<dyn std::error::Error>> {
-    use masonry::vello::peniko::{ImageBrush, ImageFormat, ImageData, ImageAlphaType, Blob};
+/// Box and fit of an image
+#[derive(Clone, Copy)]
+struct ImageLayout {
+    width: f64,
+    height: f64,
+    fit: ObjectFit,
+}
 
-    // Read image file
-    let bytes = fs::read(path)?;
+impl ImageLayout {
+    /// Avatar-style 100x100 box that keeps the aspect ratio, unless the node says otherwise
+    fn new(width: Option<f64>, height: Option<f64>, fit: Option<&str>) -> Self {
+        Self {
+            width: width.unwrap_or(100.0),
+            height: height.unwrap_or(100.0),
+            fit: fit.and_then(object_fit).unwrap_or(ObjectFit::Contain),
+        }
+    }
+
+    /// Image widget for a brush (its id is where a remote image is delivered)
+    fn image(&self, brush: masonry::vello::peniko::ImageBrush) -> NewWidget<Image> {
+        NewWidget::new_with_props(Image::new(brush), Properties::new().with(self.fit))
+    }
+
+    /// Constrain an image to the box; the container's flex will further constrain if needed
+    fn sized(&self, image: NewWidget<Image>) -> NewWidget<Flex> {
+        let sized_image = SizedBox::new(image)
+            .width(Length::px(self.width))
+            .height(Length::px(self.height));
+        wrap_in_flex(NewWidget::new(sized_image))
+    }
 
-    // Decode image using image crate
-    let img = ::image::load_from_memory(&bytes)?;
-    let rgba = img.to_rgba8();
+    fn apply(&self, brush: masonry::vello::peniko::ImageBrush) -> NewWidget<Flex> {
+        self.sized(self.image(brush))
+    }
+}
 
-    let (width, height) = rgba.dimensions();
-    let data = rgba.into_raw();
+/// `fit` attribute values, named as in CSS `object-fit`
+pub fn object_fit(name: &str) -> Option<ObjectFit> {
+    match name {
+        "contain" => Some(ObjectFit::Contain),
+        "cover" => Some(ObjectFit::Cover),
+        "fill" => Some(ObjectFit::Fill),
+        "none" => Some(ObjectFit::None),
+        "scale-down" => Some(ObjectFit::ScaleDown),
+        _ => None,
+    }
+}
+
+/// Remote image: drawn straight from the memory cache, or a grey placeholder that is
+/// filled in (through `RemoteImages::on_ready`) when the download arrives
+fn remote_image(src: &str, alt: &str, layout: ImageLayout, ctx: &Option<RenderContext>) -> NewWidget<Flex> {
+    let Some(images) = ctx.as_ref().and_then(|ctx| ctx.images.clone()) else {
+        return wrap_in_flex(NewWidget::new_with_props(Label::new(format!("[Image: {}]", alt)), text_props(ctx)));
+    };
+    if let Some(image) = images.loader.cached(src) {
+        return layout.apply(image_brush(&image));
+    }
+
+    let placeholder = DecodedImage { width: 1, height: 1, rgba: Arc::new(vec![220, 220, 220, 255]) };
+    let widget = layout.image(image_brush(&placeholder));
+    let id = widget.id();
+    let on_ready = Arc::clone(&images.on_ready);
+    images.loader.request(src, move |image| {
+        if let Some(image) = image {
+            on_ready(id, image);
+        }
+    });
+    layout.sized(widget)
+}
+
+/// Convert a decoded image into a brush for `Image`
+pub fn image_brush(image: &DecodedImage) -> masonry::vello::peniko::ImageBrush {
+    use masonry::vello::peniko::{ImageBrush, ImageFormat, ImageData, ImageAlphaType, Blob};
 
-    // Create Peniko image
     let peniko_image = ImageData {
-        data: Blob::new(std::sync::Arc::new(data)),
+        data: Blob::new(image.rgba.clone()),
         format: ImageFormat::Rgba8,
         alpha_type: ImageAlphaType::Alpha,  // Unpremultiplied alpha
-        width,
-        height,
+        width: image.width,
+        height: image.height,
     };
 
-    Ok(ImageBrush::new(peniko_image))
+    ImageBrush::new(peniko_image)
+}
+
+/// Load an image from a file path and convert to ImageBrush
+fn load_image(path: &str) -> Result<masonry::vello::peniko::ImageBrush, Box<dyn std::error::Error>> {
+    // Read image file
+    let bytes = fs::read(path)?;
+
+    // Decode at full size
+    let image = crate::runtime::images::decode(&bytes, u32::MAX)?;
+    Ok(image_brush(&image))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::parser::ast::*;
+    use crate::renderer::style::Appearance;
+    use masonry::core::{Widget, WidgetRef};
     use masonry::testing::TestHarness;
     use masonry::theme::default_property_set;
+    use masonry::vello::kurbo::Rect;
+
+    /// Labels under `widget`, with their text and where they are in the window
+    fn labels(widget: WidgetRef<'_, dyn Widget>) -> HashMap<String, Rect> {
+        let mut found = HashMap::new();
+        if let Some(label) = widget.downcast::<Label>() {
+            let rect = Rect::from_origin_size(widget.ctx().window_origin(), widget.ctx().size());
+            found.insert(label.text().to_string(), rect);
+        }
+        for child in widget.children() {
+            found.extend(labels(child));
+        }
+        found
+    }
+
+    /// Where the first widget of type `W` under `widget` is in the window
+    fn rect_of<W: Widget>(widget: WidgetRef<'_, dyn Widget>) -> Option<Rect> {
+        if widget.downcast::<W>().is_some() {
+            return Some(Rect::from_origin_size(widget.ctx().window_origin(), widget.ctx().size()));
+        }
+        widget.children().into_iter().find_map(rect_of::<W>)
+    }
 
     #[test]
     fn test_render_heading() {
@@ -762,19 +1383,15 @@ mod tests {
         let node = Node::List {
             ordered: false,
             items: vec![
-                ListItem {
-                    children: vec![Node::paragraph(vec![
-                        Node::text("Static markdown rendering"),
-                    ])],
-                },
-                ListItem {
-                    children: vec![Node::paragraph(vec![
-                        Node::strong(vec![Node::text("Bold")]),
-                        Node::text(" and "),
-                        Node::emphasis(vec![Node::text("italic")]),
-                        Node::text(" text"),
-                    ])],
-                },
+                ListItem::new(vec![Node::paragraph(vec![
+                    Node::text("Static markdown rendering"),
+                ])]),
+                ListItem::new(vec![Node::paragraph(vec![
+                    Node::strong(vec![Node::text("Bold")]),
+                    Node::text(" and "),
+                    Node::emphasis(vec![Node::text("italic")]),
+                    Node::text(" text"),
+                ])]),
             ],
         };
 
@@ -827,12 +1444,8 @@ mod tests {
         let node = Node::List {
             ordered: true,
             items: vec![
-                ListItem {
-                    children: vec![Node::text("First")],
-                },
-                ListItem {
-                    children: vec![Node::text("Second")],
-                },
+                ListItem::new(vec![Node::text("First")]),
+                ListItem::new(vec![Node::text("Second")]),
             ],
         };
 
@@ -841,4 +1454,348 @@ mod tests {
 
         assert!(harness.root_widget().ctx().size().height > 0.0);
     }
+
+    #[test]
+    fn test_render_code_and_quote() {
+        let nodes = vec![
+            Node::paragraph(vec![Node::text("Run "), Node::inline_code("cargo test")]),
+            Node::code_block(Some("rust".to_string()), "fn main() {\n    println!(\"hi\");\n}"),
+            Node::Blockquote {
+                children: vec![Node::paragraph(vec![Node::text("Quoted")])],
+            },
+        ];
+
+        let widget = build_document_widget(&nodes);
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert!(harness.root_widget().ctx().size().height > 0.0);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let cell = |text: &str| TableCell { children: vec![Node::text(text)] };
+        let node = Node::Table {
+            align: vec![ColumnAlign::Left, ColumnAlign::Right],
+            rows: vec![
+                TableRow { cells: vec![cell("Name"), cell("Count")] },
+                TableRow { cells: vec![cell("notes"), cell("42")] },
+            ],
+        };
+
+        let widget = build_widget(&node);
+        let harness = TestHarness::create(default_property_set(), widget);
+        let labels = labels(harness.root_widget());
+
+        // Left-aligned cells share a left edge, right-aligned ones a right edge
+        assert_eq!(labels["Name"].x0, labels["notes"].x0);
+        assert_eq!(labels["Count"].x1, labels["42"].x1);
+        assert!(labels["42"].x0 > labels["Count"].x0);
+        // The second column starts after the first, and the body row is below the header
+        assert!(labels["Count"].x0 > labels["notes"].x1);
+        assert!(labels["notes"].y0 >= labels["Name"].y1);
+    }
+
+    #[test]
+    fn test_render_gfm_extensions() {
+        let mut state = HashMap::new();
+        state.insert("milk".to_string(), json!(true));
+        let ctx = RenderContext::new(RuntimeContext::with_state(state));
+
+        let nodes = vec![
+            Node::paragraph(vec![
+                Node::strikethrough(vec![Node::text("gone")]),
+                Node::text(" kept"),
+                Node::FootnoteReference { label: "1".to_string() },
+            ]),
+            Node::List {
+                ordered: false,
+                items: vec![
+                    ListItem::task(false, vec![Node::paragraph(vec![Node::text("Milk")])]).with_bind("state.milk"),
+                    ListItem::task(true, vec![Node::paragraph(vec![Node::text("Eggs")])]),
+                ],
+            },
+            Node::FootnoteDefinition {
+                label: "1".to_string(),
+                children: vec![Node::paragraph(vec![Node::text("Source")])],
+            },
+        ];
+
+        let widget = build_document_widget_with_context(&nodes, Some(ctx.clone()), None);
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert!(harness.root_widget().ctx().size().height > 0.0);
+
+        // Only the bound task is registered for state writes
+        let bindings = ctx.bindings.lock().unwrap();
+        assert_eq!(bindings.values().collect::<Vec<_>>(), vec!["state.milk"]);
+    }
+
+    #[test]
+    fn test_navigate_button() {
+        let ctx = RenderContext::new(RuntimeContext::new().with_route(json!({ "pubkey": "abc" })));
+        let nodes = vec![
+            Node::button(Some("navigate(\"/profile/\" + route.pubkey)".to_string()), vec![Node::text("Profile")]),
+            Node::button(Some("actions.post".to_string()), vec![Node::text("Post")]),
+            Node::button(Some("loadMore(feed)".to_string()), vec![Node::text("More")]),
+        ];
+
+        let widget = build_document_widget_with_context(&nodes, Some(ctx.clone()), None);
+        let _harness = TestHarness::create(default_property_set(), widget);
+
+        let navigations = ctx.navigations.lock().unwrap();
+        assert_eq!(navigations.values().collect::<Vec<_>>(), vec!["/profile/abc"]);
+        let load_mores = ctx.load_mores.lock().unwrap();
+        assert_eq!(load_mores.values().collect::<Vec<_>>(), vec!["feed"]);
+    }
+
+    #[test]
+    fn test_prune_forgets_removed_widgets() {
+        let ctx = RenderContext::new(RuntimeContext::new());
+        let (kept, removed) = (WidgetId::next(), WidgetId::next());
+        ctx.bind(kept, "state.a");
+        ctx.bind(removed, "state.b");
+        ctx.navigate_on(removed, "/profile/abc");
+        ctx.load_more_on(removed, "feed");
+        ctx.field_on(removed, "message");
+
+        ctx.prune(|id| id == kept);
+        assert_eq!(ctx.binding(kept).as_deref(), Some("state.a"));
+        assert_eq!(ctx.binding(removed), None);
+        assert_eq!(ctx.navigation(removed), None);
+        assert_eq!(ctx.load_more(removed), None);
+        assert!(ctx.fields().is_empty());
+    }
+
+    #[test]
+    fn test_render_stack_layout() {
+        let node = Node::HStack {
+            children: vec![Node::text("Left"), Node::text("Right")],
+            layout: StackLayout {
+                width: Some(240.0),
+                min_height: Some(60.0),
+                max_height: Some(80.0),
+                justify: Some("space-between".to_string()),
+                align: Some("center".to_string()),
+                gap: Some(8.0),
+                padding: Some(4.0),
+                ..StackLayout::default()
+            },
+        };
+        let widget = build_widget(&node);
+
+        let harness = TestHarness::create(default_property_set(), widget);
+        let stack = rect_of::<Constrained>(harness.root_widget()).unwrap();
+        let labels = labels(harness.root_widget());
+
+        // Exact width; the short content is stretched to the minimum height
+        assert_eq!(stack.width(), 240.0);
+        assert_eq!(stack.height(), 60.0);
+        // Padding keeps the children off the edges; space-between pushes them to either side
+        assert!(labels["Left"].x0 >= stack.x0 + 4.0 && labels["Left"].x0 < stack.x0 + 10.0);
+        assert!(labels["Right"].x1 <= stack.x1 - 4.0 && labels["Right"].x1 > stack.x1 - 10.0);
+        // Centered vertically
+        let middle = |rect: Rect| (rect.y0 + rect.y1) / 2.0;
+        assert!((middle(labels["Left"]) - middle(stack)).abs() < 1.0);
+
+        // Tall content is cut to the maximum height, and the gap separates children
+        let node = Node::VStack {
+            children: (0..10).map(|i| Node::text(format!("Line {}", i))).collect(),
+            layout: StackLayout {
+                max_height: Some(80.0),
+                gap: Some(8.0),
+                ..StackLayout::default()
+            },
+        };
+        let harness = TestHarness::create(default_property_set(), build_widget(&node));
+        let stack = rect_of::<Constrained>(harness.root_widget()).unwrap();
+        let labels = labels(harness.root_widget());
+        assert_eq!(stack.height(), 80.0);
+        assert!((labels["Line 1"].y0 - labels["Line 0"].y1 - 8.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_render_grid_with_each() {
+        let mut state = HashMap::new();
+        state.insert("names".to_string(), json!(["a", "b", "c"]));
+        let ctx = RenderContext::new(RuntimeContext::with_state(state));
+
+        let scopes = each_scopes("state.names", "name", None, &ctx);
+        assert_eq!(scopes.len(), 3);
+        assert_eq!(scopes[2].clone().eval("itemIndex").unwrap(), json!(2));
+
+        let wide = Node::VStack {
+            children: vec![Node::text("Header")],
+            layout: StackLayout { span: Some(2), ..StackLayout::default() },
+        };
+        assert_eq!(grid_span(&wide), (2, 1));
+
+        let node = Node::Grid {
+            columns: None,
+            min_column_width: Some(120.0),
+            gap: Some(8.0),
+            row_gap: None,
+            column_gap: None,
+            children: vec![wide, Node::each("state.names", "name", vec![Node::expr("name")])],
+        };
+        let widget = build_widget_with_context(&node, Some(ctx));
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert!(harness.root_widget().ctx().size().height > 0.0);
+    }
+
+    #[test]
+    fn test_render_styles() {
+        let mut state = HashMap::new();
+        state.insert("accent".to_string(), json!("primary"));
+        state.insert("kind".to_string(), json!("card"));
+        let mut classes = HashMap::new();
+        classes.insert(
+            "card".to_string(),
+            StyleRules::from([("background".to_string(), "surface".to_string()), ("padding".to_string(), "md".to_string())]),
+        );
+        let sheet = StyleSheet::new(&Theme::default(), classes);
+        let mut ctx = Some(RenderContext::new(RuntimeContext::with_state(state)).with_styles(sheet.clone()));
+
+        let mut style = NodeStyle { class: Some("{state.kind}".to_string()), ..NodeStyle::default() };
+        style.properties.insert("color".to_string(), PropValue::Expression("state.accent".to_string()));
+        style.properties.insert("font_size".to_string(), PropValue::Literal("h3".to_string()));
+        let computed = compute_style(&style, &mut ctx);
+        assert_eq!(computed.background, Some(sheet.color("surface")));
+        assert_eq!(computed.padding, Some(12.0));
+        assert_eq!(computed.color, Some(sheet.color("primary")));
+
+        // Text inside the stack inherits its color and size
+        let child_ctx = styled_context(&ctx, &computed);
+        assert_eq!(body_font(&child_ctx).0, 24.0);
+        assert_eq!(body_font(&ctx).0, 18.0);
+
+        let node = Node::VStack {
+            children: vec![Node::text_block(vec![Node::text("Styled")], NodeStyle::default())],
+            layout: StackLayout { style, ..StackLayout::default() },
+        };
+        let widget = build_widget_with_context(&node, ctx);
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert!(harness.root_widget().ctx().size().height > 0.0);
+    }
+
+    #[test]
+    fn test_virtual_each_builds_rows_in_view() {
+        let items: Vec<Value> = (0..5000).map(|i| json!({ "id": format!("n{}", i), "content": "hello" })).collect();
+        let mut state = HashMap::new();
+        state.insert("items".to_string(), json!(items));
+        let ctx = RenderContext::new(RuntimeContext::with_state(state));
+
+        let node = Node::virtual_each("state.items", "item", None, 300.0, vec![Node::expr("item.content")]);
+        let widget = build_widget_with_context(&node, Some(ctx.clone()));
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert_eq!(harness.root_widget().ctx().size().height, 300.0);
+
+        // Only rows near the view were built and measured, and they are known by their ids
+        let lists = ctx.lists.lock().unwrap();
+        let memory = lists["state.items"].lock().unwrap();
+        assert!(!memory.heights.is_empty() && memory.heights.len() < 100);
+        assert!(memory.heights.contains_key("n0"));
+        assert_eq!(memory.anchor, Some(("n0".to_string(), 0.0)));
+    }
+
+    #[test]
+    fn test_virtual_lists_remember_per_instance() {
+        let mut state = HashMap::new();
+        state.insert("groups".to_string(), json!(["a", "b"]));
+        state.insert("items".to_string(), json!([{ "id": "x" }, { "id": "y" }]));
+        let ctx = RenderContext::new(RuntimeContext::with_state(state));
+
+        // The same list inside each group row is a separate list with its own scroll position
+        let list = Node::virtual_each("state.items", "item", None, 100.0, vec![Node::expr("item.id")]);
+        let node = Node::each("state.groups", "group", vec![list]);
+        let widget = build_widget_with_context(&node, Some(ctx.clone()));
+        let _harness = TestHarness::create(default_property_set(), widget);
+
+        let lists = ctx.lists.lock().unwrap();
+        let mut keys: Vec<&String> = lists.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["[0]state.items", "[1]state.items"]);
+    }
+
+    #[test]
+    fn test_input_keeps_typed_text() {
+        let mut runtime_ctx = RuntimeContext::new().with_theme("dark");
+        runtime_ctx.set_form_field("message", "half typed".to_string());
+        let sheet = StyleSheet::with_appearance(Appearance::Dark, &Theme::default(), HashMap::new());
+        let ctx = RenderContext::new(runtime_ctx).with_styles(sheet);
+
+        let node = Node::Input { name: "message".to_string(), placeholder: None, span: None };
+        let widget = build_widget_with_context(&node, Some(ctx.clone()));
+        let _harness = TestHarness::create(default_property_set(), widget);
+
+        // The driver finds the input again to read its text before the next rebuild
+        let fields = ctx.fields();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].1, "message");
+    }
+
+    #[test]
+    fn test_image_templates() {
+        let mut state = HashMap::new();
+        state.insert("pic".to_string(), json!("apps/waffle_dog.jpeg"));
+        let mut ctx = Some(RenderContext::new(RuntimeContext::with_state(state)));
+
+        assert_eq!(interpolate("{state.pic}", None, &mut ctx), "apps/waffle_dog.jpeg");
+        assert_eq!(interpolate("https://x.test/{state.missing}.png", None, &mut ctx), "https://x.test/.png");
+        assert_eq!(interpolate("{state.pic}", None, &mut None), "{state.pic}");
+
+        let node = Node::Image {
+            src: "{state.pic}".to_string(),
+            alt: "Dog".to_string(),
+            width: Some(48.0),
+            height: Some(48.0),
+            fit: Some("cover".to_string()),
+            span: None,
+        };
+        let widget = build_widget_with_context(&node, ctx);
+        let harness = TestHarness::create(default_property_set(), widget);
+        assert!(harness.root_widget().ctx().size().width > 0.0);
+    }
+
+    #[test]
+    fn test_inline_runs() {
+        let mut state = HashMap::new();
+        state.insert("name".to_string(), json!("Alice"));
+        let mut ctx = Some(RenderContext::new(RuntimeContext::with_state(state)));
+
+        let mut text = RichText::new();
+        let nodes = vec![
+            Node::text("Hi "),
+            Node::strong(vec![Node::text("bold "), Node::emphasis(vec![Node::expr("state.name")])]),
+            Node::text(", see "),
+            Node::Link { url: "https://example.com".to_string(), children: vec![Node::text("docs")] },
+            Node::text(" and "),
+            Node::inline_code("x"),
+        ];
+        push_runs(&nodes, &RunStyle::default(), &mut text, &mut ctx);
+
+        assert_eq!(text.text(), "Hi bold Alice, see docs and x");
+        let bold = RunStyle { bold: true, ..Default::default() };
+        let link = RunStyle { link: Some("https://example.com".to_string()), ..Default::default() };
+        assert_eq!(
+            text.runs(),
+            &[
+                (0..3, RunStyle::default()),
+                (3..8, bold.clone()),
+                (8..13, RunStyle { italic: true, ..bold }),
+                (13..19, RunStyle::default()),
+                (19..23, link),
+                (23..28, RunStyle::default()),
+                (28..29, RunStyle { code: true, ..Default::default() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paragraph_wraps() {
+        let height = |words: usize| {
+            let text = vec!["wrapping"; words].join(" ");
+            let node = Node::paragraph(vec![Node::text(text), Node::strong(vec![Node::text(" across bold")])]);
+            let harness = TestHarness::create(default_property_set(), build_widget(&node));
+            harness.root_widget().ctx().size().height
+        };
+        assert!(height(60) > 2.0 * height(1));
+    }
 }
@@ -0,0 +1,316 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::hashes::sha256::Hash as Sha256Hash;
+use nostr_sdk::hashes::Hash;
+use nostr_sdk::prelude::*;
+
+/// Default kind for hnmd documents and components published to Nostr (NIP-78 app data)
+pub const APP_KIND: u16 = 30078;
+
+/// `t` tag value marking an event as an `.hnmd` document
+pub const DOCUMENT_TAG: &str = "hnmd";
+
+/// `t` tag value marking an event as an `.hnmc` component
+pub const COMPONENT_TAG: &str = "hnmc";
+
+/// Where an import points to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportRef {
+    /// Local file path (relative paths resolve against the importing file)
+    Path(String),
+    /// Addressable Nostr event holding the source
+    Address(AppAddress),
+}
+
+impl ImportRef {
+    /// Parse an import value
+    /// Examples:
+    /// - "./components/Profile.hnmc" → Path
+    /// - "naddr1..." → Address
+    /// - "nostr:30078:<pubkey>:profile-card" → Address
+    /// - "nostr:naddr1..." → Address
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if AppAddress::is_address(value) {
+            Ok(ImportRef::Address(AppAddress::parse(value)?))
+        } else {
+            Ok(ImportRef::Path(value.to_string()))
+        }
+    }
+
+    /// Check if this import must be fetched from relays
+    pub fn is_address(&self) -> bool {
+        matches!(self, ImportRef::Address(_))
+    }
+}
+
+/// Address of a replaceable/addressable event (kind:pubkey:identifier)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppAddress {
+    pub kind: u16,
+    pub pubkey: PublicKey,
+    /// The `d` tag of the event
+    pub identifier: String,
+    /// Relay hints (from naddr)
+    pub relays: Vec<String>,
+    /// Pinned sha256 of the event content: `...#sha256=<hex>`
+    pub sha256: Option<String>,
+}
+
+impl AppAddress {
+    pub fn new(kind: u16, pubkey: PublicKey, identifier: impl Into<String>) -> Self {
+        Self {
+            kind,
+            pubkey,
+            identifier: identifier.into(),
+            relays: Vec::new(),
+            sha256: None,
+        }
+    }
+
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+
+    /// Check if a string looks like a Nostr address rather than a file path
+    pub fn is_address(value: &str) -> bool {
+        let value = value.trim();
+        value.starts_with("naddr1") || value.starts_with("nostr:")
+    }
+
+    /// Parse `naddr1...`, `nostr:naddr1...` or `nostr:<kind>:<pubkey>:<identifier>`
+    /// with an optional `#sha256=<hex>` suffix
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+
+        // Split off the pinned content hash
+        let (value, sha256) = match value.rsplit_once("#sha256=") {
+            Some((addr, hash)) => {
+                let hash = hash.to_lowercase();
+                if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(anyhow!("Invalid sha256 pin in address: {}", hash));
+                }
+                (addr, Some(hash))
+            }
+            None => (value, None),
+        };
+
+        let body = value.strip_prefix("nostr:").unwrap_or(value);
+
+        let mut address = if body.starts_with("naddr1") {
+            let naddr = Nip19Coordinate::from_bech32(body)
+                .map_err(|e| anyhow!("Invalid naddr '{}': {}", body, e))?;
+            AppAddress {
+                kind: naddr.coordinate.kind.as_u16(),
+                pubkey: naddr.coordinate.public_key,
+                identifier: naddr.coordinate.identifier.clone(),
+                relays: naddr.relays.iter().map(|r| r.to_string()).collect(),
+                sha256: None,
+            }
+        } else {
+            // kind:pubkey:identifier (identifier may itself contain ':')
+            let parts: Vec<&str> = body.splitn(3, ':').collect();
+            if parts.len() != 3 {
+                return Err(anyhow!(
+                    "Invalid address '{}' (expected nostr:<kind>:<pubkey>:<identifier>)",
+                    value
+                ));
+            }
+            let kind = parts[0]
+                .parse::<u16>()
+                .map_err(|_| anyhow!("Invalid kind in address: {}", parts[0]))?;
+            let pubkey = PublicKey::parse(parts[1])
+                .map_err(|e| anyhow!("Invalid pubkey in address '{}': {}", parts[1], e))?;
+            AppAddress::new(kind, pubkey, parts[2])
+        };
+
+        address.sha256 = sha256;
+        Ok(address)
+    }
+
+    /// Coordinate string used as cache key: `<kind>:<pubkey hex>:<identifier>`
+    pub fn coordinate(&self) -> String {
+        format!("{}:{}:{}", self.kind, self.pubkey.to_hex(), self.identifier)
+    }
+
+    /// Canonical import reference: `nostr:<kind>:<pubkey>:<identifier>[#sha256=<hex>]`
+    pub fn to_reference(&self) -> String {
+        match &self.sha256 {
+            Some(hash) => format!("nostr:{}#sha256={}", self.coordinate(), hash),
+            None => format!("nostr:{}", self.coordinate()),
+        }
+    }
+
+    /// Encode as naddr (relay hints included)
+    pub fn to_naddr(&self) -> Result<String> {
+        let coordinate = Coordinate::new(Kind::from(self.kind), self.pubkey)
+            .identifier(self.identifier.clone());
+        let relays = self
+            .relays
+            .iter()
+            .filter_map(|r| RelayUrl::parse(r).ok())
+            .collect();
+        Nip19Coordinate::new(coordinate, relays)
+            .to_bech32()
+            .map_err(|e| anyhow!("Failed to encode naddr: {}", e))
+    }
+}
+
+/// Hex sha256 of source content
+pub fn content_sha256(content: &str) -> String {
+    Sha256Hash::hash(content.as_bytes()).to_string()
+}
+
+/// Get the first value of a tag by name
+pub fn tag_value(event: &Event, name: &str) -> Option<String> {
+    event.tags.iter().find_map(|t| {
+        let values = t.clone().to_vec();
+        if values.first().map(|s| s.as_str()) == Some(name) {
+            values.get(1).cloned()
+        } else {
+            None
+        }
+    })
+}
+
+/// Verify that an event is a valid, untampered source for an address
+/// Checks id/signature, that the event matches the address, and the content hash
+pub fn verify_source_event(event: &Event, address: &AppAddress) -> Result<()> {
+    event
+        .verify()
+        .map_err(|e| anyhow!("Invalid event for {}: {}", address.coordinate(), e))?;
+
+    if event.kind.as_u16() != address.kind || event.pubkey != address.pubkey {
+        return Err(anyhow!(
+            "Event {} does not match address {}",
+            event.id.to_hex(),
+            address.coordinate()
+        ));
+    }
+
+    if tag_value(event, "d").unwrap_or_default() != address.identifier {
+        return Err(anyhow!(
+            "Event {} has wrong identifier for {}",
+            event.id.to_hex(),
+            address.coordinate()
+        ));
+    }
+
+    let digest = content_sha256(&event.content);
+
+    if let Some(pinned) = &address.sha256 {
+        if &digest != pinned {
+            return Err(anyhow!(
+                "Content hash mismatch for {}: expected {}, got {}",
+                address.coordinate(),
+                pinned,
+                digest
+            ));
+        }
+    }
+
+    // Publishers add an `x` tag with the content hash; it must agree with the content
+    if let Some(declared) = tag_value(event, "x") {
+        if declared != digest {
+            return Err(anyhow!(
+                "Content hash tag mismatch for {}: tag says {}, content is {}",
+                address.coordinate(),
+                declared,
+                digest
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Build an addressable event carrying hnmd/hnmc source
+/// `file_tag` is DOCUMENT_TAG or COMPONENT_TAG
+pub fn source_event_builder(identifier: &str, content: &str, file_tag: &str) -> EventBuilder {
+    EventBuilder::new(Kind::from(APP_KIND), content).tags(vec![
+        Tag::identifier(identifier),
+        Tag::parse(&["x".to_string(), content_sha256(content)]).expect("valid x tag"),
+        Tag::hashtag(file_tag),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+
+    #[test]
+    fn test_parse_path_import() {
+        let import = ImportRef::parse("./components/Profile.hnmc").unwrap();
+        assert_eq!(import, ImportRef::Path("./components/Profile.hnmc".to_string()));
+        assert!(!import.is_address());
+    }
+
+    #[test]
+    fn test_parse_coordinate_import() {
+        let import = ImportRef::parse(&format!("nostr:30078:{}:profile-card", PUBKEY)).unwrap();
+        match import {
+            ImportRef::Address(addr) => {
+                assert_eq!(addr.kind, 30078);
+                assert_eq!(addr.pubkey.to_hex(), PUBKEY);
+                assert_eq!(addr.identifier, "profile-card");
+                assert_eq!(addr.sha256, None);
+            }
+            _ => panic!("Expected Address"),
+        }
+    }
+
+    #[test]
+    fn test_parse_sha256_pin() {
+        let hash = content_sha256("hello");
+        let addr = AppAddress::parse(&format!("nostr:30078:{}:card#sha256={}", PUBKEY, hash)).unwrap();
+        assert_eq!(addr.identifier, "card");
+        assert_eq!(addr.sha256, Some(hash));
+
+        assert!(AppAddress::parse(&format!("nostr:30078:{}:card#sha256=nothex", PUBKEY)).is_err());
+    }
+
+    #[test]
+    fn test_naddr_roundtrip() {
+        let pubkey = PublicKey::from_hex(PUBKEY).unwrap();
+        let addr = AppAddress::new(APP_KIND, pubkey, "profile-card");
+        let naddr = addr.to_naddr().unwrap();
+        assert!(naddr.starts_with("naddr1"));
+
+        let parsed = AppAddress::parse(&naddr).unwrap();
+        assert_eq!(parsed.coordinate(), addr.coordinate());
+
+        let prefixed = AppAddress::parse(&format!("nostr:{}", naddr)).unwrap();
+        assert_eq!(prefixed.coordinate(), addr.coordinate());
+    }
+
+    #[test]
+    fn test_reference_roundtrip() {
+        let pubkey = PublicKey::from_hex(PUBKEY).unwrap();
+        let addr = AppAddress::new(APP_KIND, pubkey, "feed").with_sha256(content_sha256("x"));
+        let parsed = AppAddress::parse(&addr.to_reference()).unwrap();
+        assert_eq!(parsed, addr);
+    }
+
+    #[test]
+    fn test_verify_source_event() {
+        let keys = Keys::generate();
+        let content = "# Hello";
+        let event = source_event_builder("hello", content, DOCUMENT_TAG)
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let addr = AppAddress::new(APP_KIND, keys.public_key(), "hello");
+        assert!(verify_source_event(&event, &addr).is_ok());
+
+        let pinned = addr.clone().with_sha256(content_sha256(content));
+        assert!(verify_source_event(&event, &pinned).is_ok());
+
+        let wrong_pin = addr.clone().with_sha256(content_sha256("other"));
+        assert!(verify_source_event(&event, &wrong_pin).is_err());
+
+        let wrong_id = AppAddress::new(APP_KIND, keys.public_key(), "other");
+        assert!(verify_source_event(&event, &wrong_id).is_err());
+    }
+}
@@ -1,4 +1,7 @@
 use crate::parser::component_def::{parse_component, ComponentDef};
+use crate::runtime::address::{AppAddress, ImportRef};
+use crate::runtime::source_cache::SourceCache;
+use crate::runtime::AddressLoader;
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::fs;
@@ -11,6 +14,12 @@ pub struct ComponentRegistry {
     components: HashMap<String, ComponentDef>,
     /// Base path for resolving relative component imports
     base_path: PathBuf,
+    /// Nested imports that point at Nostr addresses, waiting for `resolve_addresses`
+    unresolved: Vec<(String, AppAddress)>,
+    /// On-disk cache for components fetched from Nostr
+    cache: Option<SourceCache>,
+    /// Files that local components were read from (for diagnostics)
+    sources: HashMap<String, PathBuf>,
 }
 
 impl ComponentRegistry {
@@ -18,6 +27,31 @@ impl ComponentRegistry {
         Self {
             components: HashMap::new(),
             base_path: base_path.as_ref().to_path_buf(),
+            unresolved: Vec::new(),
+            cache: None,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Use an on-disk cache for components fetched from Nostr
+    pub fn with_cache(mut self, cache: SourceCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Register an import: files are loaded now, Nostr addresses are queued for `resolve_addresses`
+    pub fn load_import(&mut self, name: impl Into<String>, import: &str) -> Result<()> {
+        let name = name.into();
+        if self.components.contains_key(&name) {
+            // Already provided (e.g. bundled into the document)
+            return Ok(());
+        }
+        match ImportRef::parse(import)? {
+            ImportRef::Path(path) => self.load_component(name, path),
+            ImportRef::Address(address) => {
+                self.unresolved.push((name, address));
+                Ok(())
+            }
         }
     }
 
@@ -43,6 +77,14 @@ impl ComponentRegistry {
 
         // Recursively load nested component imports
         for (nested_name, nested_path) in &component_def.imports {
+            if let ImportRef::Address(address) = ImportRef::parse(nested_path)? {
+                // Fetched later by resolve_addresses (needs a Nostr connection)
+                if !self.components.contains_key(nested_name) {
+                    self.unresolved.push((nested_name.clone(), address));
+                }
+                continue;
+            }
+
             if !self.components.contains_key(nested_name) {
                 // Resolve nested import relative to current component's directory
                 let nested_base = full_path.parent().unwrap_or(&self.base_path);
@@ -57,19 +99,80 @@ impl ComponentRegistry {
                 let nested_def = parse_component(&nested_content)
                     .map_err(|e| anyhow::anyhow!("Failed to parse nested component: {}", e))?;
                 self.components.insert(nested_name.clone(), nested_def);
+                self.sources.insert(nested_name.clone(), resolved_nested);
             }
         }
 
         // Store component
+        self.sources.insert(name.clone(), full_path);
         self.components.insert(name, component_def);
         Ok(())
     }
 
+    /// Load a component (and its nested imports) from addressable Nostr events
+    pub async fn load_remote_component(
+        &mut self,
+        name: impl Into<String>,
+        address: &AppAddress,
+        loader: &AddressLoader,
+    ) -> Result<()> {
+        let mut pending = vec![(name.into(), address.clone())];
+
+        while let Some((name, address)) = pending.pop() {
+            if self.components.contains_key(&name) {
+                continue;
+            }
+
+            let content = loader.load_source(&address, self.cache.as_ref()).await?;
+            let component_def = parse_component(&content)
+                .map_err(|e| anyhow::anyhow!("Failed to parse component '{}' from {}: {}", name, address.coordinate(), e))?;
+
+            for (nested_name, nested_path) in &component_def.imports {
+                match ImportRef::parse(nested_path)? {
+                    ImportRef::Address(nested) => pending.push((nested_name.clone(), nested)),
+                    ImportRef::Path(path) => {
+                        return Err(anyhow::anyhow!(
+                            "Component '{}' loaded from Nostr cannot import local file '{}'",
+                            name, path
+                        ));
+                    }
+                }
+            }
+
+            self.components.insert(name, component_def);
+        }
+
+        Ok(())
+    }
+
+    /// Fetch nested imports of local components that point at Nostr addresses
+    pub async fn resolve_addresses(&mut self, loader: &AddressLoader) -> Result<()> {
+        for (name, address) in std::mem::take(&mut self.unresolved) {
+            self.load_remote_component(name, &address, loader).await?;
+        }
+        Ok(())
+    }
+
+    /// Nested imports that still need a Nostr connection to load
+    pub fn unresolved_addresses(&self) -> &[(String, AppAddress)] {
+        &self.unresolved
+    }
+
+    /// Register an already-parsed component
+    pub fn insert(&mut self, name: impl Into<String>, component_def: ComponentDef) {
+        self.components.insert(name.into(), component_def);
+    }
+
     /// Get a component by name
     pub fn get(&self, name: &str) -> Option<&ComponentDef> {
         self.components.get(name)
     }
 
+    /// File a component was loaded from (None for bundled or Nostr components)
+    pub fn source_path(&self, name: &str) -> Option<&Path> {
+        self.sources.get(name).map(|p| p.as_path())
+    }
+
     /// Check if a component exists
     pub fn contains(&self, name: &str) -> bool {
         self.components.contains_key(name)
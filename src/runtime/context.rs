@@ -9,6 +9,10 @@ pub struct RuntimeContext {
     pub queries: Value,
     pub state: Value,
     pub form: HashMap<String, String>,
+    /// Parameters of the route the page was opened with (e.g. `route.pubkey`)
+    pub route: Value,
+    /// Built-in theme the page is drawn with ("light" or "dark")
+    pub theme: String,
     pub locals: HashMap<String, Value>,  // For scoped variables like "note" in <each>
 }
 
@@ -19,6 +23,8 @@ impl RuntimeContext {
             queries: json!({}),
             state: json!({}),
             form: HashMap::new(),
+            route: json!({}),
+            theme: "light".to_string(),
             locals: HashMap::new(),
         }
     }
@@ -30,6 +36,8 @@ impl RuntimeContext {
             queries: json!({}),
             state: json!(state),
             form: HashMap::new(),
+            route: json!({}),
+            theme: "light".to_string(),
             locals: HashMap::new(),
         }
     }
@@ -41,6 +49,8 @@ impl RuntimeContext {
         obj.insert("queries".to_string(), self.queries.clone());
         obj.insert("state".to_string(), self.state.clone());
         obj.insert("form".to_string(), json!(self.form));
+        obj.insert("route".to_string(), self.route.clone());
+        obj.insert("theme".to_string(), json!(self.theme));
 
         // Add locals at the top level so they can be accessed directly (e.g., "note" not "locals.note")
         for (key, value) in &self.locals {
@@ -52,14 +62,7 @@ impl RuntimeContext {
 
     /// Evaluate a jq expression against this context
     pub fn eval(&self, expr: &str, evaluator: &mut JaqEvaluator) -> JaqResult<Value> {
-        // Prepend `.` if not present (for convenience)
-        let jq_expr = if expr.starts_with('.') {
-            expr.to_string()
-        } else {
-            format!(".{}", expr)
-        };
-
-        evaluator.eval(&jq_expr, &self.to_json())
+        evaluator.eval(&to_jq_expr(expr), &self.to_json())
     }
 
     /// Add a local binding to the context (for use in scoped contexts like <each>)
@@ -69,6 +72,39 @@ impl RuntimeContext {
         new_ctx
     }
 
+    /// Set the parameters of the route the page was opened with
+    pub fn with_route(mut self, params: Value) -> Self {
+        self.route = params;
+        self
+    }
+
+    /// Set the name of the theme the page is drawn with
+    pub fn with_theme(mut self, theme: &str) -> Self {
+        self.theme = theme.to_string();
+        self
+    }
+
+    /// Write a value at a `state.` path (e.g. `state.tasks.milk`), creating objects along the way
+    pub fn set_state(&mut self, path: &str, value: Value) -> anyhow::Result<()> {
+        let Some(rest) = path.strip_prefix("state.") else {
+            anyhow::bail!("Only state paths can be written, got '{}'", path);
+        };
+
+        let mut target = &mut self.state;
+        for key in rest.split('.') {
+            if !target.is_object() {
+                *target = json!({});
+            }
+            target = target
+                .as_object_mut()
+                .expect("just made an object")
+                .entry(key.to_string())
+                .or_insert(Value::Null);
+        }
+        *target = value;
+        Ok(())
+    }
+
     /// Update a form field value
     pub fn set_form_field(&mut self, name: &str, value: String) {
         self.form.insert(name.to_string(), value);
@@ -80,6 +116,52 @@ impl RuntimeContext {
     }
 }
 
+/// Words that start jq expressions which aren't paths
+const JQ_KEYWORDS: [&str; 9] = ["true", "false", "null", "if", "not", "try", "reduce", "foreach", "def"];
+
+/// jq program for an HNMD expression: one starting with a name is a path and gets a leading `.`
+/// (`state.count` is `.state.count`); anything else is jq as written: `.feed`, literals (`50`, `"x"`,
+/// `true`, `null`), variables (`$item`) and keywords (`if ... then ... end`)
+/// `.$meta` (query state beside the results) isn't a jq path, so it is quoted
+pub fn to_jq_expr(expr: &str) -> String {
+    let expr = quote_meta(expr);
+    let first_word = expr.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).next().unwrap_or("");
+    match expr.chars().next() {
+        Some(c) if (c.is_ascii_alphabetic() || c == '_') && !JQ_KEYWORDS.contains(&first_word) => format!(".{}", expr),
+        _ => expr,
+    }
+}
+
+/// Rewrite `.$meta` path segments to `["$meta"]`, leaving string literals alone
+fn quote_meta(expr: &str) -> String {
+    let mut quoted = String::with_capacity(expr.len());
+    let (mut in_string, mut escaped) = (false, false);
+    let mut rest = expr;
+    while let Some(c) = rest.chars().next() {
+        let segment_end = rest.get(6..).is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'));
+        if !in_string && rest.starts_with(".$meta") && segment_end {
+            quoted.push_str("[\"$meta\"]");
+            rest = &rest[6..];
+            continue;
+        }
+
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        }
+        quoted.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    quoted
+}
+
 impl Default for RuntimeContext {
     fn default() -> Self {
         Self::new()
@@ -141,6 +223,62 @@ mod tests {
         assert_eq!(result2, json!(123));
     }
 
+    #[test]
+    fn test_route_params() {
+        let ctx = RuntimeContext::new().with_route(json!({ "pubkey": "abc" }));
+        let mut evaluator = JaqEvaluator::new();
+
+        assert_eq!(ctx.eval("route.pubkey", &mut evaluator).unwrap(), json!("abc"));
+        assert_eq!(RuntimeContext::new().eval("route.pubkey", &mut evaluator).unwrap(), json!(null));
+    }
+
+    #[test]
+    fn test_jq_expr_forms() {
+        // Names are paths
+        assert_eq!(to_jq_expr("state.count"), ".state.count");
+        assert_eq!(to_jq_expr("_private"), "._private");
+        assert_eq!(to_jq_expr("nullable.x"), ".nullable.x");
+        assert_eq!(to_jq_expr(".feed"), ".feed");
+
+        // Literals, variables and keywords are jq as written (they used to become `."x"`, `.50`, `.true`...)
+        for expr in ["50", "\"x\"", "true", "false", "null", "$item.name", "[1, 2]", "if state.on then 1 else 2 end"] {
+            assert_eq!(to_jq_expr(expr), expr);
+        }
+
+        let mut state = HashMap::new();
+        state.insert("on".to_string(), json!(true));
+        let ctx = RuntimeContext::with_state(state);
+        let mut evaluator = JaqEvaluator::new();
+        assert_eq!(ctx.eval("\"x\"", &mut evaluator).unwrap(), json!("x"));
+        assert_eq!(ctx.eval("50", &mut evaluator).unwrap(), json!(50));
+        assert_eq!(ctx.eval("null", &mut evaluator).unwrap(), json!(null));
+        assert_eq!(ctx.eval("if .state.on then 1 else 2 end", &mut evaluator).unwrap(), json!(1));
+    }
+
+    #[test]
+    fn test_query_meta_path() {
+        let mut ctx = RuntimeContext::new();
+        ctx.queries = json!({ "feed": [], "$meta": { "feed": { "hasMore": true } } });
+        let mut evaluator = JaqEvaluator::new();
+
+        assert_eq!(to_jq_expr("queries.$meta.feed"), ".queries[\"$meta\"].feed");
+        assert_eq!(ctx.eval("queries.$meta.feed.hasMore", &mut evaluator).unwrap(), json!(true));
+
+        // Only path segments are rewritten
+        assert_eq!(to_jq_expr("\"costs.$meta\""), "\"costs.$meta\"");
+        assert_eq!(ctx.eval("\"a.$meta \\\" b.$meta\"", &mut evaluator).unwrap(), json!("a.$meta \" b.$meta"));
+        assert_eq!(to_jq_expr("queries.$metadata"), ".queries.$metadata");
+    }
+
+    #[test]
+    fn test_theme() {
+        let mut evaluator = JaqEvaluator::new();
+
+        assert_eq!(RuntimeContext::new().eval("theme", &mut evaluator).unwrap(), json!("light"));
+        let ctx = RuntimeContext::new().with_theme("dark");
+        assert_eq!(ctx.eval("(if .theme == \"dark\" then 1 else 0 end)", &mut evaluator).unwrap(), json!(1));
+    }
+
     #[test]
     fn test_form_fields() {
         let mut ctx = RuntimeContext::new();
@@ -161,4 +299,17 @@ mod tests {
         let result = ctx.eval("state.missing // \"default\"", &mut evaluator).unwrap();
         assert_eq!(result, json!("default"));
     }
+
+    #[test]
+    fn test_set_state() {
+        let mut state = HashMap::new();
+        state.insert("count".to_string(), json!(1));
+        let mut ctx = RuntimeContext::with_state(state);
+
+        ctx.set_state("state.count", json!(2)).unwrap();
+        ctx.set_state("state.tasks.milk", json!(true)).unwrap();
+
+        assert_eq!(ctx.state, json!({"count": 2, "tasks": {"milk": true}}));
+        assert!(ctx.set_state("queries.feed", json!([])).is_err());
+    }
 }
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::runtime::SourceCache;
+
+/// Most events kept on disk; the oldest are dropped when the file is compacted
+const MAX_STORED_EVENTS: usize = 50_000;
+
+/// Lines the file may grow to during a run before it is compacted again
+const COMPACT_AT_LINES: usize = MAX_STORED_EVENTS + MAX_STORED_EVENTS / 10;
+
+type StoredEvents = Arc<Mutex<HashMap<EventId, Event>>>;
+
+/// Work for the writer thread
+enum WriterMessage {
+    /// JSON lines to append, and how many events they hold
+    Append(String, usize),
+    /// Reply once everything sent before is written
+    Flush(mpsc::Sender<()>),
+}
+
+/// On-disk store of events received from relays, so queries can show them before relays answer (or offline)
+/// Events are appended as signed JSON lines by a writer thread, so saving never blocks on disk I/O
+#[derive(Debug, Clone)]
+pub struct EventStore {
+    events: StoredEvents,
+    writer: mpsc::Sender<WriterMessage>,
+}
+
+impl EventStore {
+    /// Open the store in `dir`, reading what earlier runs saved
+    /// Signatures were checked when the events arrived, so read-back events only have their id re-hashed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create event store dir {}", dir.display()))?;
+        let path = dir.join("events.jsonl");
+
+        let mut events = HashMap::new();
+        let mut lines = 0;
+        if let Ok(text) = fs::read_to_string(&path) {
+            for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                lines += 1;
+                // Lines cut short by a crash or edited by hand are skipped
+                if let Ok(event) = Event::from_json(line) {
+                    if event.verify_id() {
+                        events.insert(event.id, event);
+                    }
+                }
+            }
+        }
+        let events: StoredEvents = Arc::new(Mutex::new(events));
+
+        // Rewrite the file when it holds duplicates, bad lines or too many events
+        let len = events.lock().unwrap().len();
+        if lines > len || len > MAX_STORED_EVENTS {
+            lines = compact(&path, &events)?;
+        }
+
+        let (writer, messages) = mpsc::channel();
+        let thread_events = Arc::clone(&events);
+        std::thread::Builder::new()
+            .name("event-store".to_string())
+            .spawn(move || write_loop(&path, &thread_events, lines, messages))
+            .context("Failed to start the event store writer")?;
+
+        Ok(Self { events, writer })
+    }
+
+    /// Default location: `events` in the cache dir (see `SourceCache::default_dir`)
+    pub fn default_dir() -> PathBuf {
+        SourceCache::default_dir().join("events")
+    }
+
+    /// Store at the default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_dir())
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stored events matching a filter, newest first (at most the filter's `limit`)
+    pub fn query(&self, filter: &Filter) -> Vec<Event> {
+        let mut matched: Vec<Event> = self
+            .events
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|event| matches(filter, event))
+            .cloned()
+            .collect();
+        matched.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        if let Some(limit) = filter.limit {
+            matched.truncate(limit);
+        }
+        matched
+    }
+
+    /// Save the events that aren't stored yet; returns how many were new
+    /// They can be queried right away; the writer thread puts them on disk
+    pub fn save(&self, events: &[Event]) -> Result<usize> {
+        let mut lines = String::new();
+        let mut added = 0;
+        {
+            let mut stored = self.events.lock().unwrap();
+            for event in events {
+                if !stored.contains_key(&event.id) {
+                    lines.push_str(&event.as_json());
+                    lines.push('\n');
+                    stored.insert(event.id, event.clone());
+                    added += 1;
+                }
+            }
+        }
+        if added > 0 {
+            self.writer
+                .send(WriterMessage::Append(lines, added))
+                .map_err(|_| anyhow!("Event store writer has stopped"))?;
+        }
+        Ok(added)
+    }
+
+    /// Wait until everything saved so far is on disk
+    pub fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if self.writer.send(WriterMessage::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+/// Append saved events to the file, compacting it when it grows past `COMPACT_AT_LINES`
+fn write_loop(path: &Path, events: &StoredEvents, mut lines: usize, messages: mpsc::Receiver<WriterMessage>) {
+    for message in messages {
+        match message {
+            WriterMessage::Append(text, count) => {
+                let written = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(text.as_bytes()));
+                if let Err(e) = written {
+                    eprintln!("⚠️  Failed to write event store {}: {}", path.display(), e);
+                    continue;
+                }
+
+                lines += count;
+                if lines > COMPACT_AT_LINES {
+                    match compact(path, events) {
+                        Ok(len) => lines = len,
+                        Err(e) => eprintln!("⚠️  {:#}", e),
+                    }
+                }
+            }
+            WriterMessage::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Keep the newest `MAX_STORED_EVENTS` and rewrite the file with one line each; returns how many were kept
+fn compact(path: &Path, events: &StoredEvents) -> Result<usize> {
+    let kept: Vec<Event> = {
+        let mut events = events.lock().unwrap();
+        if events.len() > MAX_STORED_EVENTS {
+            let mut newest: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+            newest.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            newest.truncate(MAX_STORED_EVENTS);
+            *events = newest.into_iter().map(|event| (event.id, event)).collect();
+        }
+        events.values().cloned().collect()
+    };
+
+    let text: String = kept.iter().map(|event| event.as_json() + "\n").collect();
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, text).with_context(|| format!("Failed to write event store {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace event store {}", path.display()))?;
+    Ok(kept.len())
+}
+
+/// Whether an event passes a filter (see `Filter::match_event`), with `search` as a case-insensitive substring
+pub fn matches(filter: &Filter, event: &Event) -> bool {
+    filter.match_event(event, MatchEventOptions::default())
+        && filter
+            .search
+            .as_ref()
+            .is_none_or(|search| event.content.to_lowercase().contains(&search.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_at(keys: &Keys, content: &str, created_at: u64) -> Event {
+        EventBuilder::text_note(content)
+            .tag(Tag::hashtag("html6"))
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("html6-event-store-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let keys = Keys::generate();
+        let (old, new) = (note_at(&keys, "old", 100), note_at(&keys, "new", 200));
+
+        let store = EventStore::open(&dir).unwrap();
+        assert!(store.is_empty());
+        assert_eq!(store.save(&[old.clone(), new.clone()]).unwrap(), 2);
+        assert_eq!(store.save(&[new.clone()]).unwrap(), 0);
+        store.flush();
+
+        // A later run sees what was saved, even after a torn write
+        fs::OpenOptions::new().append(true).open(dir.join("events.jsonl")).unwrap().write_all(b"{\"id\":").unwrap();
+        let reopened = EventStore::open(&dir).unwrap();
+        assert_eq!(reopened.len(), 2);
+
+        let filter = Filter::new().kind(Kind::TextNote).author(keys.public_key()).limit(1);
+        assert_eq!(reopened.query(&filter), vec![new.clone()]);
+        assert_eq!(reopened.query(&Filter::new().until(Timestamp::from(150))), vec![old]);
+        assert!(reopened.query(&Filter::new().author(Keys::generate().public_key())).is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_matches_tags_and_search() {
+        let keys = Keys::generate();
+        let event = note_at(&keys, "Hello Nostr", 100);
+
+        assert!(matches(&Filter::new().hashtag("html6"), &event));
+        assert!(!matches(&Filter::new().hashtag("other"), &event));
+        assert!(matches(&Filter::new().search("nostr"), &event));
+        assert!(!matches(&Filter::new().search("other"), &event));
+        assert!(!matches(&Filter::new().since(Timestamp::from(101)), &event));
+    }
+
+    #[test]
+    fn test_open_rehashes_ids() {
+        let dir = std::env::temp_dir().join(format!("html6-event-store-ids-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let keys = Keys::generate();
+        let kept = note_at(&keys, "kept", 100);
+        let mut edited = note_at(&keys, "original", 100);
+        edited.content = "edited".to_string();
+        fs::write(dir.join("events.jsonl"), format!("{}\n{}\n", kept.as_json(), edited.as_json())).unwrap();
+
+        let store = EventStore::open(&dir).unwrap();
+        assert_eq!(store.query(&Filter::new()), vec![kept]);
+
+        // The bad line is compacted away
+        assert_eq!(fs::read_to_string(dir.join("events.jsonl")).unwrap().lines().count(), 1);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
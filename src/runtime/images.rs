@@ -0,0 +1,514 @@
+use anyhow::{anyhow, Result};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::runtime::address::content_sha256;
+use crate::runtime::SourceCache;
+
+/// A decoded image as unpremultiplied RGBA8
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// Shared so widgets can draw it without copying
+    pub rgba: Arc<Vec<u8>>,
+}
+
+impl fmt::Debug for DecodedImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DecodedImage({}x{})", self.width, self.height)
+    }
+}
+
+/// Size limits for remote images
+#[derive(Debug, Clone, Copy)]
+pub struct ImageLimits {
+    /// Largest download accepted
+    pub max_download_bytes: usize,
+    /// Images are downscaled so neither side is larger than this
+    pub max_dimension: u32,
+    /// Decoded bytes kept in memory before the least recently used images are dropped
+    pub memory_budget_bytes: usize,
+    /// Downloads kept on disk before the least recently used ones are deleted
+    pub disk_budget_bytes: usize,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            max_download_bytes: 10 * 1024 * 1024,
+            max_dimension: 512,
+            memory_budget_bytes: 64 * 1024 * 1024,
+            disk_budget_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+type Waiter = Box<dyn FnOnce(Option<Arc<DecodedImage>>) + Send>;
+
+/// How long a URL that failed to load is not tried again
+const DEFAULT_FAILURE_TTL: Duration = Duration::from_secs(30);
+
+/// Decoded images in least-recently-used order, with their total size
+struct MemoryCache {
+    images: LruCache<String, Arc<DecodedImage>>,
+    bytes: usize,
+}
+
+/// Loads images over HTTP(S), keeping downloads on disk and decoded images in memory
+#[derive(Clone)]
+pub struct ImageLoader {
+    client: reqwest::Client,
+    /// Where downloads run (the UI thread asks for images outside of any runtime)
+    runtime: tokio::runtime::Handle,
+    disk_dir: Option<PathBuf>,
+    limits: ImageLimits,
+    memory: Arc<Mutex<MemoryCache>>,
+    /// Callers waiting on each download in progress, so each URL is fetched once
+    in_flight: Arc<Mutex<HashMap<String, Vec<Waiter>>>>,
+    /// When each URL last failed, so broken images aren't fetched on every render
+    failures: Arc<Mutex<HashMap<String, Instant>>>,
+    failure_ttl: Duration,
+}
+
+impl ImageLoader {
+    pub fn new(runtime: tokio::runtime::Handle) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(30))
+                .build()
+                .expect("Failed to create HTTP client"),
+            runtime,
+            disk_dir: None,
+            limits: ImageLimits::default(),
+            memory: Arc::new(Mutex::new(MemoryCache {
+                images: LruCache::unbounded(),
+                bytes: 0,
+            })),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            failures: Arc::new(Mutex::new(HashMap::new())),
+            failure_ttl: DEFAULT_FAILURE_TTL,
+        }
+    }
+
+    /// Keep downloads in a directory so they survive restarts
+    pub fn with_disk_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ImageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// How long to wait before trying a URL that failed again
+    pub fn with_failure_ttl(mut self, ttl: Duration) -> Self {
+        self.failure_ttl = ttl;
+        self
+    }
+
+    /// Default disk cache location, next to the source cache
+    pub fn default_dir() -> PathBuf {
+        SourceCache::default_dir().join("images")
+    }
+
+    /// Whether an image source has to be fetched over HTTP(S)
+    pub fn is_remote(src: &str) -> bool {
+        src.starts_with("https://") || src.starts_with("http://")
+    }
+
+    /// The decoded image, if it is in memory
+    pub fn cached(&self, url: &str) -> Option<Arc<DecodedImage>> {
+        self.memory.lock().unwrap().images.get(url).cloned()
+    }
+
+    /// Load an image in the background; `on_ready` gets it (None if it failed) on a background task
+    pub fn request(&self, url: &str, on_ready: impl FnOnce(Option<Arc<DecodedImage>>) + Send + 'static) {
+        if let Some(image) = self.cached(url) {
+            on_ready(Some(image));
+            return;
+        }
+
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if let Some(waiters) = in_flight.get_mut(url) {
+                waiters.push(Box::new(on_ready));
+                return;
+            }
+            in_flight.insert(url.to_string(), vec![Box::new(on_ready)]);
+        }
+
+        let loader = self.clone();
+        let url = url.to_string();
+        self.runtime.spawn(async move {
+            let image = match loader.load(&url).await {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    eprintln!("⚠️  Failed to load image {}: {:#}", url, e);
+                    None
+                }
+            };
+
+            let waiters = loader.in_flight.lock().unwrap().remove(&url).unwrap_or_default();
+            for waiter in waiters {
+                waiter(image.clone());
+            }
+        });
+    }
+
+    /// Load an image from memory, then disk, then the network
+    /// A URL that failed is not tried again for a while (see `with_failure_ttl`)
+    pub async fn load(&self, url: &str) -> Result<Arc<DecodedImage>> {
+        if let Some(image) = self.cached(url) {
+            return Ok(image);
+        }
+        if let Some(failed_at) = self.failures.lock().unwrap().get(url) {
+            if failed_at.elapsed() < self.failure_ttl {
+                return Err(anyhow!("failed to load less than {}s ago", self.failure_ttl.as_secs()));
+            }
+        }
+
+        match self.load_uncached(url).await {
+            Ok(image) => {
+                self.failures.lock().unwrap().remove(url);
+                self.remember(url, Arc::clone(&image));
+                Ok(image)
+            }
+            Err(e) => {
+                self.failures.lock().unwrap().insert(url.to_string(), Instant::now());
+                Err(e)
+            }
+        }
+    }
+
+    /// Decode the download kept on disk, or fetch and keep it
+    async fn load_uncached(&self, url: &str) -> Result<Arc<DecodedImage>> {
+        let name = content_sha256(url);
+        if let Some(dir) = &self.disk_dir {
+            let path = dir.join(&name);
+            if let Ok(bytes) = fs::read(&path) {
+                match self.decode_in_background(bytes).await {
+                    Ok(image) => {
+                        touch(&path);
+                        return Ok(image);
+                    }
+                    // A truncated or corrupt entry is deleted and downloaded again
+                    Err(e) => {
+                        eprintln!("⚠️  Cached image {} is unreadable, fetching it again: {:#}", url, e);
+                        let _ = fs::remove_file(&path);
+                    }
+                }
+            }
+        }
+
+        let bytes = self.download(url).await?;
+        let image = self.decode_in_background(bytes.clone()).await?;
+        if let Some(dir) = self.disk_dir.clone() {
+            let budget = self.limits.disk_budget_bytes as u64;
+            let stored = tokio::task::spawn_blocking(move || store_on_disk(&dir, &name, &bytes, budget)).await?;
+            if let Err(e) = stored {
+                eprintln!("⚠️  Failed to cache image {}: {}", url, e);
+            }
+        }
+        Ok(image)
+    }
+
+    async fn decode_in_background(&self, bytes: Vec<u8>) -> Result<Arc<DecodedImage>> {
+        let max_dimension = self.limits.max_dimension;
+        let image = tokio::task::spawn_blocking(move || decode(&bytes, max_dimension)).await??;
+        Ok(Arc::new(image))
+    }
+
+    /// Fetch the raw bytes, refusing anything over the download limit
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let max = self.limits.max_download_bytes;
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        if response.content_length().is_some_and(|len| len as usize > max) {
+            return Err(anyhow!("image is larger than {} bytes", max));
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > max {
+                return Err(anyhow!("image is larger than {} bytes", max));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes)
+    }
+
+    /// Add to the memory cache, dropping the least recently used images over budget
+    fn remember(&self, url: &str, image: Arc<DecodedImage>) {
+        let mut memory = self.memory.lock().unwrap();
+        memory.bytes += image.rgba.len();
+        if let Some(old) = memory.images.put(url.to_string(), image) {
+            memory.bytes -= old.rgba.len();
+        }
+        while memory.bytes > self.limits.memory_budget_bytes && memory.images.len() > 1 {
+            if let Some((_, evicted)) = memory.images.pop_lru() {
+                memory.bytes -= evicted.rgba.len();
+            }
+        }
+    }
+}
+
+/// Write a download to the disk cache through a temp file, so a crash never leaves half of it,
+/// then delete the least recently used downloads over the budget
+fn store_on_disk(dir: &Path, name: &str, bytes: &[u8], budget: u64) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let tmp = dir.join(format!("{}.{}.tmp", name, std::process::id()));
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, dir.join(name))?;
+    evict_over_budget(dir, budget)
+}
+
+/// Delete files, least recently used first (by modification time, see `touch`), until the dir fits the budget
+fn evict_over_budget(dir: &Path, budget: u64) -> io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+    entries.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in entries {
+        if total <= budget {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+    Ok(())
+}
+
+/// Mark a disk cache entry as just used
+fn touch(path: &Path) {
+    let _ = fs::File::options().write(true).open(path).and_then(|file| file.set_modified(SystemTime::now()));
+}
+
+/// Decode image bytes, downscaling so neither side is larger than `max_dimension`
+pub fn decode(bytes: &[u8], max_dimension: u32) -> Result<DecodedImage> {
+    let mut reader = ::image::ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+
+    // Refuse absurd sizes before allocating for them
+    let mut limits = ::image::Limits::default();
+    limits.max_image_width = Some(16384);
+    limits.max_image_height = Some(16384);
+    reader.limits(limits);
+
+    let mut image = reader.decode()?;
+    if image.width() > max_dimension || image.height() > max_dimension {
+        image = image.thumbnail(max_dimension, max_dimension);
+    }
+
+    let rgba = image.to_rgba8();
+    Ok(DecodedImage {
+        width: rgba.width(),
+        height: rgba.height(),
+        rgba: Arc::new(rgba.into_raw()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ::image::DynamicImage::ImageRgba8(::image::RgbaImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut bytes), ::image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    /// Minimal HTTP server: `/image.png` is the body, anything else is a 404.
+    /// Returns the base URL and a counter of requests served.
+    fn serve(body: Vec<u8>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&hits);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let request = String::from_utf8_lossy(&request);
+                let (status, body) = if request.starts_with("GET /image.png ") {
+                    ("200 OK", body.as_slice())
+                } else {
+                    ("404 Not Found", &b""[..])
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+
+        (base, hits)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("html6-images-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_load_downscales_and_caches() {
+        let (base, hits) = serve(png(1000, 500));
+        let url = format!("{}/image.png", base);
+        let dir = temp_dir("cache");
+        let limits = ImageLimits { max_dimension: 100, ..Default::default() };
+
+        let loader = ImageLoader::new(tokio::runtime::Handle::current())
+            .with_disk_cache(&dir)
+            .with_limits(limits);
+        let image = loader.load(&url).await.unwrap();
+        assert_eq!((image.width, image.height), (100, 50));
+        assert_eq!(image.rgba.len(), 100 * 50 * 4);
+
+        // Memory hit
+        assert!(Arc::ptr_eq(&image, &loader.load(&url).await.unwrap()));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // A fresh loader reads the download back from disk
+        let reloaded = ImageLoader::new(tokio::runtime::Handle::current())
+            .with_disk_cache(&dir)
+            .with_limits(limits);
+        assert!(reloaded.cached(&url).is_none());
+        assert_eq!(reloaded.load(&url).await.unwrap().width, 100);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_disk_entry_is_fetched_again() {
+        let (base, hits) = serve(png(8, 8));
+        let url = format!("{}/image.png", base);
+        let dir = temp_dir("corrupt");
+
+        let loader = ImageLoader::new(tokio::runtime::Handle::current()).with_disk_cache(&dir);
+        loader.load(&url).await.unwrap();
+        let entry = dir.join(content_sha256(&url));
+        fs::write(&entry, b"not a png").unwrap();
+
+        let reloaded = ImageLoader::new(tokio::runtime::Handle::current()).with_disk_cache(&dir);
+        assert_eq!(reloaded.load(&url).await.unwrap().width, 8);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert_eq!(fs::read(&entry).unwrap(), png(8, 8));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_disk_budget_evicts_least_recently_used() {
+        let dir = temp_dir("budget");
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("old", 30), ("used", 20), ("new", 10)] {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            file.set_len(100).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        touch(&dir.join("used"));
+
+        evict_over_budget(&dir, 200).unwrap();
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("used").exists() && dir.join("new").exists());
+
+        store_on_disk(&dir, "newest", &[0; 100], 200).unwrap();
+        assert!(!dir.join("new").exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_not_retried_right_away() {
+        let (base, hits) = serve(png(4, 4));
+        let missing = format!("{}/missing.png", base);
+
+        let loader = ImageLoader::new(tokio::runtime::Handle::current());
+        assert!(loader.load(&missing).await.is_err());
+        assert!(loader.load(&missing).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let retrying = ImageLoader::new(tokio::runtime::Handle::current()).with_failure_ttl(Duration::ZERO);
+        assert!(retrying.load(&missing).await.is_err());
+        assert!(retrying.load(&missing).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_request_shares_downloads() {
+        let (base, hits) = serve(png(4, 4));
+        let url = format!("{}/image.png", base);
+        let loader = ImageLoader::new(tokio::runtime::Handle::current());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for _ in 0..3 {
+            let tx = tx.clone();
+            loader.request(&url, move |image| {
+                let _ = tx.send(image.map(|image| image.width));
+            });
+        }
+        for _ in 0..3 {
+            assert_eq!(rx.recv().await.unwrap(), Some(4));
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Failures reach the callback too
+        loader.request(&format!("{}/missing.png", base), move |image| {
+            let _ = tx.send(image.map(|image| image.width));
+        });
+        assert_eq!(rx.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let (base, _hits) = serve(png(64, 64));
+        let url = format!("{}/image.png", base);
+
+        let tiny = ImageLimits { max_download_bytes: 16, ..Default::default() };
+        let loader = ImageLoader::new(tokio::runtime::Handle::current()).with_limits(tiny);
+        let error = loader.load(&url).await.unwrap_err();
+        assert!(error.to_string().contains("larger than 16 bytes"), "{}", error);
+
+        // Over the memory budget, the least recently used image goes first
+        let budget = ImageLimits { memory_budget_bytes: 64 * 64 * 4, ..Default::default() };
+        let loader = ImageLoader::new(tokio::runtime::Handle::current()).with_limits(budget);
+        loader.load(&url).await.unwrap();
+        loader.remember("other", Arc::new(decode(&png(64, 64), 512).unwrap()));
+        assert!(loader.cached(&url).is_none());
+        assert!(loader.cached("other").is_some());
+    }
+}
@@ -64,6 +64,11 @@ impl JaqEvaluator {
         Ok(val_to_json(&result))
     }
 
+    /// Check that a jq expression parses, without running it
+    pub fn check(&mut self, expr: &str) -> Result<()> {
+        self.compile(expr).map(|_| ())
+    }
+
     fn compile(&mut self, expr: &str) -> Result<jaq_interpret::Filter> {
         // Check cache
         if let Some(cached) = self.cache.get(expr) {
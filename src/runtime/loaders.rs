@@ -1,31 +1,159 @@
 use anyhow::Result;
 use nostr_sdk::prelude::*;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, RwLock};
 
-use crate::runtime::NostrClient;
+use crate::runtime::address::{tag_value, verify_source_event, AppAddress};
+use crate::runtime::event_store::EventStore;
+use crate::runtime::normalize::{coordinate, normalize};
+use crate::runtime::source_cache::SourceCache;
+use crate::runtime::{EventPolicy, NostrClient};
+
+/// How long `load` calls are collected before they go out as one request
+const BATCH_WINDOW: Duration = Duration::from_millis(10);
+
+/// Attempts per request before a load fails
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; doubles with each one
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// How long each attempt waits for relays to send all stored events
+const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long an address relays didn't have is answered with None without asking again
+const DEFAULT_NOT_FOUND_TTL: Duration = Duration::from_secs(60);
+
+/// What a load resolves to; errors are strings so every waiter gets a copy
+type Loaded = std::result::Result<Option<Event>, String>;
+
+/// An address waiting for the next batch
+#[derive(Debug, Clone)]
+struct Request {
+    kind: u16,
+    pubkey: PublicKey,
+    identifier: Option<String>,
+}
+
+impl Request {
+    fn coordinate(&self) -> String {
+        coordinate(self.kind, &self.pubkey, self.identifier.as_deref().unwrap_or(""))
+    }
+
+    fn filter(&self) -> Filter {
+        let filter = Filter::new().kind(Kind::from(self.kind)).author(self.pubkey);
+        match &self.identifier {
+            Some(d) => filter.custom_tag(SingleLetterTag::lowercase(Alphabet::D), d),
+            None => filter,
+        }
+    }
+}
 
 /// Loads replaceable/addressable events by kind:pubkey:identifier
-/// Automatically deduplicates requests and caches results
+/// Loads made close together go out as one request per kind, each address is fetched once
+/// at a time, and results (including "not found", for a while) are cached
 #[derive(Clone)]
 pub struct AddressLoader {
     client: Arc<NostrClient>,
-    /// Track requested addresses to avoid duplicates
-    requested: Arc<RwLock<HashSet<String>>>,
+    /// Callers waiting on each address being fetched, so concurrent loads share one request
+    in_flight: Arc<Mutex<HashMap<String, Vec<oneshot::Sender<Loaded>>>>>,
+    /// Addresses collected for the next batch
+    queue: Arc<Mutex<Vec<Request>>>,
     /// Cache of loaded events by address
     cache: Arc<RwLock<HashMap<String, Event>>>,
+    /// Addresses relays didn't have, and when they said so
+    not_found: Arc<Mutex<HashMap<String, Instant>>>,
+    not_found_ttl: Duration,
+    fetch_timeout: Duration,
+    /// Events saved by earlier runs, used when relays don't answer
+    store: Option<EventStore>,
+    /// Never ask relays; answer from the store only
+    offline: bool,
+    /// Rules fetched and stored events must pass
+    policy: EventPolicy,
 }
 
 impl AddressLoader {
     pub fn new(client: Arc<NostrClient>) -> Self {
         Self {
             client,
-            requested: Arc::new(RwLock::new(HashSet::new())),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            queue: Arc::new(Mutex::new(Vec::new())),
             cache: Arc::new(RwLock::new(HashMap::new())),
+            not_found: Arc::new(Mutex::new(HashMap::new())),
+            not_found_ttl: DEFAULT_NOT_FOUND_TTL,
+            fetch_timeout: DEFAULT_FETCH_TIMEOUT,
+            store: None,
+            offline: false,
+            policy: EventPolicy::default(),
+        }
+    }
+
+    /// Save fetched events to a store, and fall back to it when relays don't have them
+    pub fn with_store(mut self, store: EventStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Answer from the store only, without connecting to relays
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Drop fetched and stored events that fail these rules (by default: invalid, oversized or from the future)
+    pub fn with_policy(mut self, policy: EventPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// How long "not found" is remembered before an address is asked for again
+    pub fn with_not_found_ttl(mut self, ttl: Duration) -> Self {
+        self.not_found_ttl = ttl;
+        self
+    }
+
+    /// How long each attempt waits for a relay's EOSE before it counts as failed
+    pub fn with_fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.fetch_timeout = timeout;
+        self
+    }
+
+    /// Drop events that fail the policy
+    fn screen(&self, events: Vec<Event>) -> Vec<Event> {
+        let now = Timestamp::now();
+        let count = events.len();
+        let kept: Vec<Event> = events.into_iter().filter(|event| self.policy.check(event, now).is_ok()).collect();
+        if kept.len() < count {
+            eprintln!("  ⚠️  Rejected {} loaded events", count - kept.len());
+        }
+        kept
+    }
+
+    /// Newest stored event matching a filter
+    fn stored(&self, filter: &Filter) -> Option<Event> {
+        newest(self.screen(self.store.as_ref()?.query(filter)))
+    }
+
+    /// Save fetched events for later runs
+    fn save(&self, events: &[Event]) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(events) {
+                eprintln!("⚠️  Failed to store events: {}", e);
+            }
         }
     }
 
+    /// Stored profiles for some pubkeys (newest per author), without fetching
+    pub fn stored_profiles(&self, pubkeys: &[PublicKey]) -> Vec<Event> {
+        let Some(store) = &self.store else { return vec![] };
+        let mut profiles = self.screen(store.query(&Filter::new().kind(Kind::Metadata).authors(pubkeys.to_vec())));
+        normalize(&mut profiles, &[], false);
+        profiles
+    }
+
     /// Load a single addressable event
     pub async fn load(
         &self,
@@ -33,59 +161,229 @@ impl AddressLoader {
         pubkey: PublicKey,
         identifier: Option<String>,
     ) -> Result<Option<Event>> {
-        let addr = format!(
-            "{}:{}:{}",
-            kind,
-            pubkey.to_hex(),
-            identifier.as_deref().unwrap_or("")
-        );
+        let request = Request { kind, pubkey, identifier };
+        let addr = request.coordinate();
 
         // Check cache first
         if let Some(event) = self.cache.read().await.get(&addr) {
             return Ok(Some(event.clone()));
         }
 
-        // Deduplicate concurrent requests
-        if !self.requested.write().await.insert(addr.clone()) {
-            // Already being fetched, wait a bit and check cache
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            return Ok(self.cache.read().await.get(&addr).cloned());
+        if self.offline {
+            let stored = self.stored(&request.filter());
+            if let Some(event) = &stored {
+                self.cache.write().await.insert(addr, event.clone());
+            }
+            return Ok(stored);
+        }
+
+        // Relays recently said they don't have it
+        {
+            let mut not_found = self.not_found.lock().unwrap();
+            match not_found.get(&addr) {
+                Some(at) if at.elapsed() < self.not_found_ttl => return Ok(None),
+                Some(_) => {
+                    not_found.remove(&addr);
+                }
+                None => {}
+            }
+        }
+
+        // Wait on the fetch in progress, or queue one for the next batch
+        let (tx, rx) = oneshot::channel();
+        let first = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get_mut(&addr) {
+                Some(waiters) => {
+                    waiters.push(tx);
+                    false
+                }
+                None => {
+                    in_flight.insert(addr, vec![tx]);
+                    true
+                }
+            }
+        };
+        if first {
+            let start_batch = {
+                let mut queue = self.queue.lock().unwrap();
+                queue.push(request);
+                queue.len() == 1
+            };
+            if start_batch {
+                let loader = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(BATCH_WINDOW).await;
+                    loader.flush().await;
+                });
+            }
+        }
+
+        rx.await
+            .map_err(|_| anyhow::anyhow!("Address loader stopped"))?
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// Fetch every queued address, one filter per kind, and hand the results to their callers
+    async fn flush(&self) {
+        let requests = std::mem::take(&mut *self.queue.lock().unwrap());
+
+        let mut results: HashMap<String, Loaded> = HashMap::new();
+        for ((kind, with_identifier), group) in group_requests(&requests) {
+            let mut filter = Filter::new().kind(Kind::from(kind)).authors(group.iter().map(|r| r.pubkey));
+            if with_identifier {
+                let identifiers: BTreeSet<&str> = group.iter().filter_map(|r| r.identifier.as_deref()).collect();
+                filter = filter.custom_tags(SingleLetterTag::lowercase(Alphabet::D), identifiers);
+            }
+
+            match self.fetch_with_retry(filter).await {
+                Ok(events) => {
+                    let mut events = self.screen(events);
+                    self.save(&events);
+
+                    // Newest first, so the first event per address is the one to keep
+                    normalize(&mut events, &[], false);
+                    let mut found: HashMap<String, Event> = HashMap::new();
+                    for event in events {
+                        let identifier = if with_identifier { tag_value(&event, "d").unwrap_or_default() } else { String::new() };
+                        let addr = coordinate(event.kind.as_u16(), &event.pubkey, &identifier);
+                        found.entry(addr).or_insert(event);
+                    }
+
+                    // Fall back to what earlier runs stored when relays don't have it
+                    for request in group {
+                        let event = found.get(&request.coordinate()).cloned().or_else(|| self.stored(&request.filter()));
+                        results.insert(request.coordinate(), Ok(event));
+                    }
+                }
+                Err(e) => {
+                    for request in group {
+                        let result = match self.stored(&request.filter()) {
+                            Some(event) => Ok(Some(event)),
+                            None => Err(e.to_string()),
+                        };
+                        results.insert(request.coordinate(), result);
+                    }
+                }
+            }
         }
 
-        // Build filter
-        let mut filter = Filter::new().kind(Kind::from(kind)).author(pubkey);
+        // Cache what was found and remember what wasn't; failures are asked for again next time
+        {
+            let mut cache = self.cache.write().await;
+            let mut not_found = self.not_found.lock().unwrap();
+            for (addr, result) in &results {
+                match result {
+                    Ok(Some(event)) => {
+                        cache.insert(addr.clone(), event.clone());
+                    }
+                    Ok(None) => {
+                        not_found.insert(addr.clone(), Instant::now());
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        for (addr, result) in results {
+            let waiters = self.in_flight.lock().unwrap().remove(&addr).unwrap_or_default();
+            for waiter in waiters {
+                let _ = waiter.send(result.clone());
+            }
+        }
+    }
+
+    /// Fetch from relays, retrying with a growing delay when the request fails
+    /// A timeout without EOSE is a failure too, so a slow relay isn't taken to mean "not found"
+    async fn fetch_with_retry(&self, filter: Filter) -> Result<Vec<Event>> {
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.client.fetch_events(filter.clone(), Some(self.fetch_timeout)).await {
+                Ok(events) => return Ok(events),
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    eprintln!("⚠️  Fetch failed (attempt {} of {}), retrying: {}", attempt, MAX_ATTEMPTS, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Load the source text (hnmd/hnmc) stored at an address
+    /// A cached copy is used directly when the address pins its content hash,
+    /// and as a fallback when relays don't return the event
+    pub async fn load_source(
+        &self,
+        address: &AppAddress,
+        cache: Option<&SourceCache>,
+    ) -> Result<String> {
+        let cached = cache.and_then(|c| c.get(address));
 
-        if let Some(d) = identifier {
-            filter = filter.custom_tag(
-                SingleLetterTag::lowercase(Alphabet::D),
-                d,
-            );
+        // Pinned content never changes, so a verified cached copy is authoritative
+        if address.sha256.is_some() {
+            if let Some(event) = &cached {
+                if verify_source_event(event, address).is_ok() {
+                    return Ok(event.content.clone());
+                }
+            }
         }
 
-        // Fetch from relays
-        let events = self.client.fetch_events(filter, None).await?;
+        self.add_relay_hints(&address.relays).await;
+
+        let fetched = self
+            .load(address.kind, address.pubkey, Some(address.identifier.clone()))
+            .await;
+
+        match fetched {
+            Ok(Some(event)) => {
+                verify_source_event(&event, address)?;
+                if let Some(cache) = cache {
+                    if let Err(e) = cache.put(address, &event) {
+                        eprintln!("⚠️  Failed to cache {}: {}", address.coordinate(), e);
+                    }
+                }
+                Ok(event.content)
+            }
+            result => {
+                // Offline or not found - fall back to the cached copy
+                if let Some(event) = cached {
+                    verify_source_event(&event, address)?;
+                    println!("  📦 Using cached source for {}", address.coordinate());
+                    return Ok(event.content);
+                }
+                match result {
+                    Err(e) => Err(anyhow::anyhow!("Failed to fetch {}: {}", address.coordinate(), e)),
+                    _ => Err(anyhow::anyhow!("No event found for {}", address.coordinate())),
+                }
+            }
+        }
+    }
 
-        // For replaceable events, keep the newest
-        if let Some(event) = events.into_iter().max_by_key(|e| e.created_at) {
-            self.cache.write().await.insert(addr, event.clone());
-            Ok(Some(event))
-        } else {
-            Ok(None)
+    /// Add relay hints (e.g. from an naddr) to the client
+    pub async fn add_relay_hints(&self, relays: &[String]) {
+        if relays.is_empty() || self.offline {
+            return;
+        }
+        for relay in relays {
+            if let Err(e) = self.client.client().add_relay(relay).await {
+                eprintln!("⚠️  Ignoring relay hint {}: {}", relay, e);
+            }
         }
+        self.client.client().connect().await;
     }
 
-    /// Batch load profiles for multiple pubkeys
-    /// Automatically deduplicates - won't re-fetch already-loaded profiles
+    /// Load profiles for multiple pubkeys; they go out together as one batch
+    /// Returns only the profiles that weren't loaded before (already-loaded ones aren't re-fetched)
     pub async fn load_profiles(&self, pubkeys: Vec<PublicKey>) -> Result<Vec<Event>> {
         // Filter out already-cached profiles
         let to_fetch: Vec<PublicKey> = {
             let cache = self.cache.read().await;
             pubkeys
                 .into_iter()
-                .filter(|pk| {
-                    let addr = format!("0:{}:", pk.to_hex());
-                    !cache.contains_key(&addr)
-                })
+                .filter(|pk| !cache.contains_key(&coordinate(0, pk, "")))
                 .collect()
         };
 
@@ -95,27 +393,20 @@ impl AddressLoader {
 
         println!("  👤 Fetching {} new profiles...", to_fetch.len());
 
-        // Mark as requested
-        {
-            let mut requested = self.requested.write().await;
-            for pk in &to_fetch {
-                let addr = format!("0:{}:", pk.to_hex());
-                requested.insert(addr);
+        let loads = to_fetch.into_iter().map(|pk| self.load(0, pk, None));
+        let mut events = Vec::new();
+        let mut error = None;
+        for result in futures::future::join_all(loads).await {
+            match result {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => {}
+                Err(e) => error = Some(e),
             }
         }
 
-        // Batch request for all profiles
-        let filter = Filter::new().kind(Kind::Metadata).authors(to_fetch);
-
-        let events = self.client.fetch_events(filter, Some(10)).await?;
-
-        // Store in cache
-        {
-            let mut cache = self.cache.write().await;
-            for event in &events {
-                let addr = format!("0:{}:", event.pubkey.to_hex());
-                cache.insert(addr, event.clone());
-            }
+        // Only fail when nothing came back
+        if let (true, Some(e)) = (events.is_empty(), error) {
+            return Err(e);
         }
 
         println!("  ✓ Loaded {} profiles", events.len());
@@ -129,6 +420,21 @@ impl AddressLoader {
     }
 }
 
+/// Queued requests by kind, split by whether they name a `d` identifier
+fn group_requests(requests: &[Request]) -> BTreeMap<(u16, bool), Vec<&Request>> {
+    let mut groups: BTreeMap<(u16, bool), Vec<&Request>> = BTreeMap::new();
+    for request in requests {
+        groups.entry((request.kind, request.identifier.is_some())).or_default().push(request);
+    }
+    groups
+}
+
+/// Newest of some versions of a replaceable event (deletions by its author honored)
+fn newest(mut events: Vec<Event>) -> Option<Event> {
+    normalize(&mut events, &[], false);
+    events.into_iter().next()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,7 +445,24 @@ mod tests {
             "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
         )
         .unwrap();
-        let addr = format!("0:{}:", pk.to_hex());
+        let addr = coordinate(0, &pk, "");
         assert!(addr.starts_with("0:3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d:"));
     }
+
+    #[test]
+    fn test_group_requests() {
+        let (a, b) = (Keys::generate().public_key(), Keys::generate().public_key());
+        let request = |kind, pubkey, identifier: Option<&str>| Request { kind, pubkey, identifier: identifier.map(String::from) };
+        let requests = vec![
+            request(0, a, None),
+            request(30078, a, Some("app")),
+            request(0, b, None),
+            request(30078, b, None),
+        ];
+
+        let groups = group_requests(&requests);
+        let sizes: Vec<_> = groups.iter().map(|(key, group)| (*key, group.len())).collect();
+        assert_eq!(sizes, vec![((0, false), 2), ((30078, false), 1), ((30078, true), 1)]);
+        assert_eq!(requests[1].coordinate(), format!("30078:{}:app", a.to_hex()));
+    }
 }
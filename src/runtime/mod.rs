@@ -1,17 +1,28 @@
+pub mod address;
 pub mod components;
 pub mod context;
+pub mod event_store;
 pub mod filters;
+pub mod images;
 pub mod jaq;
 pub mod loaders;
+pub mod normalize;
 pub mod nostr;
 pub mod pipes;
 pub mod query;
+pub mod source_cache;
+pub mod verify;
 
+pub use address::{AppAddress, ImportRef};
 pub use components::ComponentRegistry;
 pub use context::RuntimeContext;
+pub use event_store::EventStore;
 pub use filters::compile_filter;
+pub use images::{DecodedImage, ImageLimits, ImageLoader};
 pub use jaq::JaqEvaluator;
 pub use loaders::AddressLoader;
 pub use nostr::NostrClient;
 pub use pipes::execute_all_pipes;
 pub use query::QueryRuntime;
+pub use source_cache::SourceCache;
+pub use verify::EventPolicy;
@@ -0,0 +1,162 @@
+use crate::runtime::address::tag_value;
+use nostr_sdk::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Address of a replaceable or addressable event: `kind:pubkey:identifier` (empty identifier for replaceable kinds)
+pub fn coordinate(kind: u16, pubkey: &PublicKey, identifier: &str) -> String {
+    format!("{}:{}:{}", kind, pubkey.to_hex(), identifier)
+}
+
+/// Kinds where only the newest event per author is kept: 0, 3 and 10000-19999
+pub fn is_replaceable(kind: u16) -> bool {
+    kind == 0 || kind == 3 || (10_000..20_000).contains(&kind)
+}
+
+/// Kinds where only the newest event per author and `d` tag is kept: 30000-39999
+pub fn is_addressable(kind: u16) -> bool {
+    (30_000..40_000).contains(&kind)
+}
+
+/// Key shared by every version of a replaceable or addressable event; None for regular events
+pub fn replaceable_key(event: &Event) -> Option<String> {
+    let kind = event.kind.as_u16();
+    if is_replaceable(kind) {
+        Some(coordinate(kind, &event.pubkey, ""))
+    } else if is_addressable(kind) {
+        Some(coordinate(kind, &event.pubkey, &tag_value(event, "d").unwrap_or_default()))
+    } else {
+        None
+    }
+}
+
+/// What a set of deletion requests removes, honoring only requests by the events' own authors
+#[derive(Debug, Default)]
+struct Deleted {
+    /// Deleted event ids, with who asked
+    ids: HashMap<EventId, HashSet<PublicKey>>,
+    /// Deleted coordinates, with the newest deletion time (versions up to it are gone)
+    coordinates: HashMap<String, Timestamp>,
+}
+
+impl Deleted {
+    fn from_requests<'a>(requests: impl Iterator<Item = &'a Event>) -> Self {
+        let mut deleted = Self::default();
+        for request in requests.filter(|e| e.kind == Kind::EventDeletion) {
+            for tag in request.tags.iter() {
+                match tag.as_slice() {
+                    [name, id, ..] if name == "e" => {
+                        if let Ok(id) = EventId::from_hex(id) {
+                            deleted.ids.entry(id).or_default().insert(request.pubkey);
+                        }
+                    }
+                    // `kind:pubkey:d`, which can only name the requester's own events
+                    [name, coordinate, ..] if name == "a" => {
+                        if coordinate.split(':').nth(1) == Some(request.pubkey.to_hex().as_str()) {
+                            let until = deleted.coordinates.entry(coordinate.clone()).or_insert(request.created_at);
+                            *until = (*until).max(request.created_at);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        deleted
+    }
+
+    fn removes(&self, event: &Event) -> bool {
+        if self.ids.get(&event.id).is_some_and(|authors| authors.contains(&event.pubkey)) {
+            return true;
+        }
+        replaceable_key(event)
+            .and_then(|key| self.coordinates.get(&key))
+            .is_some_and(|until| event.created_at <= *until)
+    }
+}
+
+/// Put events in query-result shape: newest first, one per id, only the newest version of each
+/// replaceable/addressable event, and without events their author deleted
+/// Deletion requests can be in `events` itself or in `deletions`; once applied they are dropped too,
+/// unless `keep_requests` (for queries that ask for kind 5)
+pub fn normalize(events: &mut Vec<Event>, deletions: &[Event], keep_requests: bool) {
+    let mut ids = HashSet::new();
+    events.retain(|event| ids.insert(event.id));
+
+    let deleted = Deleted::from_requests(events.iter().chain(deletions));
+    events.retain(|event| !deleted.removes(event) && (keep_requests || event.kind != Kind::EventDeletion));
+
+    // Newest first; on a tie the lowest id wins (NIP-01)
+    events.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+    let mut keys = HashSet::new();
+    events.retain(|event| replaceable_key(event).is_none_or(|key| keys.insert(key)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(keys: &Keys, builder: EventBuilder, created_at: u64) -> Event {
+        builder.custom_created_at(Timestamp::from(created_at)).sign_with_keys(keys).unwrap()
+    }
+
+    fn contents(events: &[Event]) -> Vec<&str> {
+        events.iter().map(|e| e.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_newest_version_wins() {
+        let (alice, bob) = (Keys::generate(), Keys::generate());
+        let profile = |keys: &Keys, name: &str, at| signed(keys, EventBuilder::new(Kind::Metadata, name), at);
+        let article = |d: &str, content: &str, at| {
+            signed(&alice, EventBuilder::new(Kind::from(30023), content).tag(Tag::identifier(d)), at)
+        };
+
+        let old = profile(&alice, "alice v1", 100);
+        let mut events = vec![
+            old.clone(),
+            profile(&alice, "alice v2", 200),
+            old,
+            profile(&bob, "bob", 50),
+            article("post", "draft", 100),
+            article("post", "final", 300),
+            article("other", "other", 10),
+        ];
+        normalize(&mut events, &[], false);
+
+        assert_eq!(contents(&events), vec!["final", "alice v2", "bob", "other"]);
+        assert_eq!(replaceable_key(&events[1]), Some(format!("0:{}:", alice.public_key().to_hex())));
+    }
+
+    #[test]
+    fn test_author_deletions() {
+        let (alice, mallory) = (Keys::generate(), Keys::generate());
+        let note = signed(&alice, EventBuilder::text_note("note"), 100);
+        let kept = signed(&alice, EventBuilder::text_note("kept"), 100);
+        let article = signed(&alice, EventBuilder::new(Kind::from(30023), "article").tag(Tag::identifier("a")), 100);
+        let coordinate = coordinate(30023, &alice.public_key(), "a");
+
+        // Someone else can't delete alice's note
+        let forged = signed(&mallory, EventBuilder::new(Kind::EventDeletion, "").tag(Tag::event(kept.id)), 200);
+        let request = signed(
+            &alice,
+            EventBuilder::new(Kind::EventDeletion, "")
+                .tag(Tag::event(note.id))
+                .tag(Tag::parse(["a", coordinate.as_str()]).unwrap()),
+            200,
+        );
+
+        let mut events = vec![note.clone(), kept.clone(), article.clone()];
+        normalize(&mut events, &[forged, request.clone()], false);
+        assert_eq!(contents(&events), vec!["kept"]);
+
+        // An edit made after the deletion comes back
+        let edited = signed(&alice, EventBuilder::new(Kind::from(30023), "edited").tag(Tag::identifier("a")), 300);
+        let mut events = vec![article.clone(), edited.clone(), request.clone()];
+        normalize(&mut events, &[], false);
+        assert_eq!(contents(&events), vec!["edited"]);
+
+        // A query for kind 5 keeps the request itself
+        let mut events = vec![article, edited, request];
+        normalize(&mut events, &[], true);
+        assert_eq!(contents(&events), vec!["edited", ""]);
+    }
+}
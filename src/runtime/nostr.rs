@@ -65,22 +65,24 @@ impl NostrClient {
 
     /// Fetch events once (one-time subscription: subscribe, wait for EOSE, unsubscribe)
     /// This is for loading specific events/profiles without keeping a long-lived subscription
+    /// Fails if no relay sends EOSE within the timeout (5 seconds by default), since what arrived may be partial
     pub async fn fetch_events(
         &self,
         filter: Filter,
-        timeout_secs: Option<u64>,
+        timeout: Option<std::time::Duration>,
     ) -> Result<Vec<Event>> {
+        // Get notifications channel before subscribing so fast relays can't race us
+        let mut notifications = self.client.notifications();
+
         // Subscribe
         let output = self.subscribe(filter).await?;
         let sub_id = output.val;
 
-        // Get notifications channel
-        let mut notifications = self.client.notifications();
-
         // Collect events until EOSE
         let mut events = Vec::new();
-        let timeout_duration = std::time::Duration::from_secs(timeout_secs.unwrap_or(5));
+        let timeout_duration = timeout.unwrap_or(std::time::Duration::from_secs(5));
         let timeout = tokio::time::sleep(timeout_duration);
+        let mut complete = false;
         tokio::pin!(timeout);
 
         loop {
@@ -97,6 +99,7 @@ impl NostrClient {
                             if let RelayMessage::EndOfStoredEvents(id) = message {
                                 if id.as_ref() == &sub_id {
                                     // Got EOSE, we're done
+                                    complete = true;
                                     break;
                                 }
                             }
@@ -114,6 +117,9 @@ impl NostrClient {
         // Unsubscribe
         self.client.unsubscribe(&sub_id).await;
 
+        if !complete {
+            return Err(anyhow::anyhow!("No relay sent all stored events within {:?}", timeout_duration));
+        }
         Ok(events)
     }
 }
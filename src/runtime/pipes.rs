@@ -2,7 +2,7 @@ use anyhow::Result;
 use serde_json::Value;
 use std::collections::HashMap;
 
-use crate::parser::ast::Pipe;
+use crate::parser::ast::{with_location, Pipe};
 use crate::runtime::jaq::JaqEvaluator;
 
 /// Executor for jq pipes - transforms/enriches query data
@@ -41,7 +41,9 @@ pub fn execute_all_pipes(
     for (pipe_id, pipe_def) in pipes {
         // Execute pipe against the current full context
         let context = Value::Object(result_map.clone());
-        let pipe_result = executor.execute(&pipe_def.jq, &context)?;
+        let pipe_result = executor.execute(&pipe_def.jq, &context).map_err(|e| {
+            anyhow::anyhow!(with_location(pipe_def.span, format!("pipe '{}': {}", pipe_id, e)))
+        })?;
 
         // Debug output
         let result_type = if pipe_result.is_array() {
@@ -93,10 +95,7 @@ mod tests {
         let mut pipes = HashMap::new();
         pipes.insert(
             "contentOnly".to_string(),
-            Pipe {
-                from: "feed".to_string(),
-                jq: ".feed | map(.content)".to_string(),
-            },
+            Pipe::new("feed", ".feed | map(.content)"),
         );
 
         let queries = json!({
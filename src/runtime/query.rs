@@ -1,26 +1,122 @@
 use anyhow::Result;
 use nostr_sdk::prelude::*;
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use tokio::sync::{broadcast, RwLock};
 
-use crate::runtime::{AddressLoader, NostrClient, RuntimeContext};
+use crate::runtime::{AddressLoader, EventPolicy, EventStore, NostrClient, RuntimeContext};
 use crate::parser::ast;
 use crate::runtime::filters::compile_filter;
 use crate::runtime::jaq::JaqEvaluator;
+use crate::runtime::normalize::normalize;
+
+/// Events fetched per `loadMore` page when the query has no `limit`
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Loading and paging state of a query, shown to documents as `queries.$meta.<id>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMeta {
+    /// Stored events are still arriving (some relay hasn't sent EOSE yet)
+    pub loading: bool,
+    /// Relays that have sent all their stored events
+    pub eose: BTreeSet<String>,
+    /// Relays that closed the subscription, with their reason
+    pub closed: BTreeMap<String, String>,
+    /// How many relays accepted the subscription
+    pub relays: usize,
+    /// Why the subscription failed or a relay closed it
+    pub error: Option<String>,
+    /// When events were last stored (unix seconds)
+    pub updated_at: Option<u64>,
+    /// Events dropped by the `EventPolicy`, by reason
+    pub rejected: BTreeMap<&'static str, usize>,
+    /// Older events may exist (false once a page comes back empty)
+    pub has_more: bool,
+    /// A `loadMore` page is being fetched
+    pub loading_more: bool,
+    /// How many events are kept: the query's limit plus the pages loaded since
+    pub keep: usize,
+}
+
+impl QueryMeta {
+    fn new(page_size: usize) -> Self {
+        Self {
+            loading: true,
+            eose: BTreeSet::new(),
+            closed: BTreeMap::new(),
+            relays: 0,
+            error: None,
+            updated_at: None,
+            rejected: BTreeMap::new(),
+            has_more: true,
+            loading_more: false,
+            keep: page_size,
+        }
+    }
+
+    /// A relay finished sending stored events; loading ends once every relay has
+    fn relay_done(&mut self, relay: String) {
+        self.eose.insert(relay);
+        self.update_loading();
+    }
+
+    /// A relay closed the subscription; it won't send anything more either
+    fn relay_closed(&mut self, relay: String, message: String) {
+        self.error = Some(format!("{}: {}", relay, message));
+        self.closed.insert(relay, message);
+        self.update_loading();
+    }
+
+    fn update_loading(&mut self) {
+        let finished = self.eose.len() + self.closed.keys().filter(|relay| !self.eose.contains(*relay)).count();
+        self.loading = finished < self.relays;
+    }
+
+    /// The subscription can't deliver anything more
+    fn fail(&mut self, error: String) {
+        self.error = Some(error);
+        self.loading = false;
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "loading": self.loading,
+            "eose": self.eose,
+            "closed": self.closed,
+            "error": self.error,
+            "updatedAt": self.updated_at,
+            "rejected": self.rejected,
+            "hasMore": self.has_more,
+            "loadingMore": self.loading_more,
+        })
+    }
+}
 
 /// Query runtime for managing Nostr subscriptions and event streams
 pub struct QueryRuntime {
     client: Arc<NostrClient>,
     /// Map of query IDs to their event collections
     queries: Arc<RwLock<HashMap<String, Vec<Event>>>>,
+    /// Filters the queries were subscribed with, for fetching older pages
+    /// (shared with subscription tasks, which check them as queries are added)
+    filters: Arc<Mutex<HashMap<String, Filter>>>,
+    /// Paging state of each subscribed query
+    meta: Arc<RwLock<HashMap<String, QueryMeta>>>,
+    /// Deletion requests seen by any query, applied to all of them
+    deletions: Arc<RwLock<Vec<Event>>>,
     /// Broadcast channels for each query (send updates when new events arrive)
     broadcasters: HashMap<String, broadcast::Sender<Vec<Event>>>,
     /// Version counter that increments on each update (for detecting changes)
     version: Arc<RwLock<u64>>,
     /// Loader for addressable events (profiles, etc.)
     pub address_loader: AddressLoader,
+    /// Events saved by earlier runs, shown before relays answer
+    store: Option<EventStore>,
+    /// Never subscribe; queries show stored events only
+    offline: bool,
+    /// Rules events must pass before they are stored or shown
+    policy: EventPolicy,
 }
 
 impl QueryRuntime {
@@ -32,12 +128,39 @@ impl QueryRuntime {
         Ok(Self {
             client: Arc::clone(&client_arc),
             queries: Arc::new(RwLock::new(HashMap::new())),
+            filters: Arc::default(),
+            meta: Arc::new(RwLock::new(HashMap::new())),
+            deletions: Arc::new(RwLock::new(Vec::new())),
             broadcasters: HashMap::new(),
             version: Arc::new(RwLock::new(0)),
             address_loader: AddressLoader::new(Arc::clone(&client_arc)),
+            store: None,
+            offline: false,
+            policy: EventPolicy::default(),
         })
     }
 
+    /// Rules events must pass; the rest are dropped and counted in `$meta.<id>.rejected`
+    pub fn with_policy(mut self, policy: EventPolicy) -> Self {
+        self.address_loader = self.address_loader.with_policy(policy.clone());
+        self.policy = policy;
+        self
+    }
+
+    /// Show stored events as soon as a query subscribes, and save what relays send
+    pub fn with_store(mut self, store: EventStore) -> Self {
+        self.address_loader = self.address_loader.with_store(store.clone());
+        self.store = Some(store);
+        self
+    }
+
+    /// Answer queries from the store only, without subscribing to relays
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.address_loader = self.address_loader.with_offline(offline);
+        self.offline = offline;
+        self
+    }
+
     /// Add a relay to the client
     pub async fn add_relay(&self, url: &str) -> Result<()> {
         self.client.client().add_relay(url).await?;
@@ -46,6 +169,7 @@ impl QueryRuntime {
     }
 
     /// Subscribe to an AST filter (compiles it first)
+    /// Failures are also recorded in the query's `$meta.error`
     pub async fn subscribe_ast_filter(
         &mut self,
         id: &str,
@@ -54,7 +178,13 @@ impl QueryRuntime {
     ) -> Result<broadcast::Receiver<Vec<Event>>> {
         // Compile the AST filter
         let mut evaluator = JaqEvaluator::new();
-        let filter = compile_filter(ast_filter, ctx, &mut evaluator)?;
+        let filter = match compile_filter(ast_filter, ctx, &mut evaluator) {
+            Ok(filter) => filter,
+            Err(e) => {
+                self.record_error(id, e.to_string()).await;
+                return Err(e);
+            }
+        };
 
         // Subscribe to the compiled filter
         self.subscribe_filter(id.to_string(), filter).await
@@ -69,26 +199,72 @@ impl QueryRuntime {
         // Create broadcast channel for this query
         let (tx, rx) = broadcast::channel(100);
 
+        let page_size = page_size(&filter);
+        self.filters.lock().unwrap().insert(id.clone(), filter.clone());
+
+        // Show what earlier runs stored right away; relays fill in the rest
+        let stored = self.store.as_ref().map(|store| store.query(&filter)).unwrap_or_default();
+        if !stored.is_empty() || self.offline {
+            self.seed(&id, &filter, stored).await;
+        }
+
+        if self.offline {
+            let mut query_meta = QueryMeta::new(page_size);
+            query_meta.loading = false;
+            self.meta.write().await.insert(id.clone(), query_meta);
+            *self.version.write().await += 1;
+            self.broadcasters.insert(id, tx);
+            return Ok(rx);
+        }
+
+        // Get notifications before subscribing so a fast relay's EOSE can't race us
+        let mut notifications = self.client.client().notifications();
+
         // Subscribe to the filter and get subscription ID
-        let output = self.client.subscribe(filter.clone()).await?;
+        let output = match self.client.subscribe(filter.clone()).await {
+            Ok(output) => output,
+            Err(e) => {
+                self.record_error(&id, e.to_string()).await;
+                return Err(e);
+            }
+        };
         let sub_id = output.val;
 
+        // Deletions by the query's authors, so what they delete drops out of the results
+        let deletion_sub = match deletion_filter(&filter) {
+            Some(deletions) => match self.client.subscribe(deletions).await {
+                Ok(output) => Some(output.val),
+                Err(e) => {
+                    eprintln!("⚠️  Failed to subscribe to deletions for query '{}': {}", id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let mut query_meta = QueryMeta::new(page_size);
+        query_meta.relays = output.success.len();
+        query_meta.loading = query_meta.relays > 0;
+        if !output.failed.is_empty() {
+            let mut failed: Vec<String> = output.failed.iter().map(|(url, e)| format!("{}: {}", url, e)).collect();
+            failed.sort();
+            query_meta.error = Some(failed.join("; "));
+        }
+        self.meta.write().await.insert(id.clone(), query_meta);
+        *self.version.write().await += 1;
+
         // Clone references for the background task
-        let client = Arc::clone(&self.client);
-        let queries = Arc::clone(&self.queries);
-        let version = Arc::clone(&self.version);
+        let shared = self.shared();
         let address_loader = self.address_loader.clone();
         let query_id = id.clone();
         let tx_clone = tx.clone();
+        let limited = filter.limit.is_some();
 
         // Spawn background task to listen for events from the subscription
         tokio::spawn(async move {
             println!("  👂 Listening for events on subscription '{}'...", query_id);
 
-            // Handle notifications for this subscription
-            let mut notifications = client.client().notifications();
-
-            let mut collected_events = Vec::new();
+            let mut pending = Vec::new();
             let mut last_update = std::time::Instant::now();
 
             while let Ok(notification) = notifications.recv().await {
@@ -97,84 +273,22 @@ impl QueryRuntime {
                 match notification {
                     RelayPoolNotification::Event { subscription_id, event, .. } => {
                         // Only handle events for our subscription
-                        if subscription_id == sub_id {
-                            collected_events.push(*event);
+                        if deletion_sub.as_ref() == Some(&subscription_id) {
+                            shared.add_deletion(&query_id, *event).await;
+                        } else if subscription_id == sub_id {
+                            pending.push(*event);
 
                             // Update every 500ms or when we hit the limit
-                            if last_update.elapsed().as_millis() > 500 || collected_events.len() >= filter.limit.unwrap_or(100) as usize {
-                                println!("  📥 Received {} events for query '{}'", collected_events.len(), query_id);
-
-                                // Sort by created_at (newest first)
-                                collected_events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-
-                                // Apply limit (keep only the most recent N events)
-                                if let Some(limit) = filter.limit {
-                                    collected_events.truncate(limit as usize);
-                                }
-
-                                // Store in queries map and increment version
-                                {
-                                    let mut queries_lock = queries.write().await;
-                                    queries_lock.insert(query_id.clone(), collected_events.clone());
-
-                                    let mut version_lock = version.write().await;
-                                    *version_lock += 1;
-
-                                    println!("  ✓ Stored {} events in queries map (version: {})", collected_events.len(), *version_lock);
-                                }
+                            if last_update.elapsed().as_millis() > 500 || pending.len() >= page_size {
+                                println!("  📥 Received {} events for query '{}'", pending.len(), query_id);
+                                let stored = shared.store_batch(&query_id, std::mem::take(&mut pending), limited).await;
 
                                 // Broadcast updated events
-                                let _ = tx_clone.send(collected_events.clone());
+                                let _ = tx_clone.send(stored);
 
                                 // If this is the feed query, fetch profiles for new authors
                                 if query_id == "feed" {
-                                    let address_loader_clone = address_loader.clone();
-                                    let queries_clone = Arc::clone(&queries);
-                                    let version_clone = Arc::clone(&version);
-
-                                    tokio::spawn(async move {
-                                        // Extract unique pubkeys from feed
-                                        let pubkeys: Vec<PublicKey> = {
-                                            let queries_lock = queries_clone.read().await;
-                                            if let Some(feed_events) = queries_lock.get("feed") {
-                                                feed_events
-                                                    .iter()
-                                                    .map(|e| e.pubkey)
-                                                    .collect::<std::collections::HashSet<_>>()
-                                                    .into_iter()
-                                                    .collect()
-                                            } else {
-                                                vec![]
-                                            }
-                                        };
-
-                                        if !pubkeys.is_empty() {
-                                            // Load profiles (AddressLoader handles deduplication)
-                                            if let Ok(new_profiles) = address_loader_clone.load_profiles(pubkeys).await {
-                                                if !new_profiles.is_empty() {
-                                                    // Merge with existing profiles
-                                                    let mut queries_lock = queries_clone.write().await;
-                                                    let existing = queries_lock.get("profiles").cloned().unwrap_or_default();
-
-                                                    let mut all_profiles_map: HashMap<String, Event> = HashMap::new();
-                                                    for event in existing {
-                                                        all_profiles_map.insert(event.pubkey.to_hex(), event);
-                                                    }
-                                                    for event in new_profiles {
-                                                        all_profiles_map.insert(event.pubkey.to_hex(), event);
-                                                    }
-
-                                                    let all_profiles: Vec<Event> = all_profiles_map.into_values().collect();
-                                                    queries_lock.insert("profiles".to_string(), all_profiles);
-
-                                                    // Increment version
-                                                    let mut version_lock = version_clone.write().await;
-                                                    *version_lock += 1;
-                                                    println!("  ✓ Profiles updated (version: {})", *version_lock);
-                                                }
-                                            }
-                                        }
-                                    });
+                                    spawn_profile_fetch(address_loader.clone(), shared.clone());
                                 }
 
                                 last_update = std::time::Instant::now();
@@ -184,10 +298,33 @@ impl QueryRuntime {
                             }
                         }
                     }
-                    RelayPoolNotification::Message { .. } => {
+                    RelayPoolNotification::Message { relay_url, message } => match message {
+                        RelayMessage::EndOfStoredEvents(id) if id.as_ref() == &sub_id => {
+                            println!("  ✓ {} sent all stored events for query '{}'", relay_url, query_id);
+                            if let Some(m) = shared.meta.write().await.get_mut(&query_id) {
+                                m.relay_done(relay_url.to_string());
+                            }
+
+                            // Store what's buffered now rather than waiting for the next live event
+                            let stored = shared.store_batch(&query_id, std::mem::take(&mut pending), limited).await;
+                            let _ = tx_clone.send(stored);
+                            if query_id == "feed" {
+                                spawn_profile_fetch(address_loader.clone(), shared.clone());
+                            }
+                            last_update = std::time::Instant::now();
+                        }
+                        RelayMessage::Closed { subscription_id, message } if subscription_id.as_ref() == &sub_id => {
+                            eprintln!("  ⚠️  {} closed query '{}': {}", relay_url, query_id, message);
+                            if let Some(m) = shared.meta.write().await.get_mut(&query_id) {
+                                m.relay_closed(relay_url.to_string(), message.to_string());
+                            }
+                            *shared.version.write().await += 1;
+                        }
                         // Ignore other messages
-                    }
-                    _ => {}
+                        _ => {}
+                    },
+                    // The runtime was closed
+                    RelayPoolNotification::Shutdown => break,
                 }
             }
         });
@@ -198,6 +335,162 @@ impl QueryRuntime {
         Ok(rx)
     }
 
+    /// Handles the subscription tasks write through
+    fn shared(&self) -> Shared {
+        Shared {
+            queries: Arc::clone(&self.queries),
+            meta: Arc::clone(&self.meta),
+            version: Arc::clone(&self.version),
+            deletions: Arc::clone(&self.deletions),
+            store: self.store.clone(),
+            policy: self.policy.clone(),
+            filters: Arc::clone(&self.filters),
+        }
+    }
+
+    /// Put stored events (and, for the feed, its authors' stored profiles) into a query
+    /// Stored deletions by the filter's authors apply, as they would online
+    async fn seed(&self, id: &str, filter: &Filter, stored: Vec<Event>) {
+        // The policy may have changed since they were stored (e.g. a newly blocked author)
+        let stored = self.shared().screen(id, stored).await;
+        println!("  📦 {} stored events for query '{}'", stored.len(), id);
+        if let (Some(store), Some(authors)) = (&self.store, &filter.authors) {
+            let requests = store.query(&Filter::new().kind(Kind::EventDeletion).authors(authors.clone()));
+            let requests = self.shared().screen(id, requests).await;
+            let mut deletions = self.deletions.write().await;
+            for request in requests {
+                if !deletions.contains(&request) {
+                    deletions.push(request);
+                }
+            }
+        }
+
+        let profiles = if id == "feed" {
+            let authors: Vec<PublicKey> = stored.iter().map(|e| e.pubkey).collect::<std::collections::HashSet<_>>().into_iter().collect();
+            self.address_loader.stored_profiles(&authors)
+        } else {
+            vec![]
+        };
+
+        let deletions = self.deletions.read().await.clone();
+        let mut queries = self.queries.write().await;
+        merge_events(queries.entry(id.to_string()).or_default(), stored, &deletions, shows_requests(filter));
+        if !profiles.is_empty() {
+            merge_events(queries.entry("profiles".to_string()).or_default(), profiles, &[], false);
+        }
+    }
+
+    /// Mark a query as failed (it stops loading and shows the message in `$meta.error`)
+    pub async fn record_error(&self, id: &str, error: String) {
+        self.meta
+            .write()
+            .await
+            .entry(id.to_string())
+            .or_insert_with(|| QueryMeta::new(DEFAULT_PAGE_SIZE))
+            .fail(error);
+        *self.version.write().await += 1;
+    }
+
+    /// Whether any query is still waiting for stored events
+    pub async fn is_loading(&self) -> bool {
+        self.meta.read().await.values().any(|m| m.loading)
+    }
+
+    /// End every subscription and disconnect from the relays (e.g. when leaving the page)
+    pub async fn close(&self) {
+        self.client.client().shutdown().await;
+    }
+
+    /// Fetch the page of events older than the oldest one a query has, and add them to it
+    /// Returns how many new events arrived (0 when there's nothing to page from or a page is already loading)
+    pub async fn load_more(&self, id: &str) -> Result<usize> {
+        let Some(page) = self.start_load_more(id).await? else { return Ok(0) };
+        self.fetch_more(id, page).await
+    }
+
+    /// Fetch a page returned by `start_load_more` and add it to the query
+    pub async fn fetch_more(&self, id: &str, page: Filter) -> Result<usize> {
+        let result = match (&self.store, self.offline) {
+            (Some(store), true) => Ok(store.query(&page)),
+            (None, true) => Ok(vec![]),
+            _ => self.client.fetch_events(page, Some(std::time::Duration::from_secs(5))).await,
+        };
+        self.finish_load_more(id, result).await
+    }
+
+    /// Mark a query as loading its next page and return the filter for it
+    /// None if a page is already loading, the end was reached, or there are no events yet
+    pub async fn start_load_more(&self, id: &str) -> Result<Option<Filter>> {
+        let filter = self
+            .filters
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("loadMore: unknown query '{}'", id))?;
+
+        let oldest = {
+            let queries = self.queries.read().await;
+            queries.get(id).and_then(|events| events.iter().map(|e| e.created_at).min())
+        };
+        let Some(oldest) = oldest else { return Ok(None) };
+
+        let mut meta = self.meta.write().await;
+        let meta = meta.entry(id.to_string()).or_insert_with(|| QueryMeta::new(page_size(&filter)));
+        if meta.loading_more || !meta.has_more {
+            return Ok(None);
+        }
+        meta.loading_more = true;
+        *self.version.write().await += 1;
+
+        // `until` is inclusive; starting a second earlier keeps the page from being the same events again
+        let until = Timestamp::from(oldest.as_u64().saturating_sub(1));
+        let page_size = page_size(&filter);
+        Ok(Some(filter.until(until).limit(page_size)))
+    }
+
+    /// Add a fetched page to a query (and the store) and clear its loading flag
+    /// A page shorter than the limit means there's nothing older; how many were new doesn't matter,
+    /// since a full page can be all duplicates or rejected events
+    pub async fn finish_load_more(&self, id: &str, page: Result<Vec<Event>>) -> Result<usize> {
+        let full = page.as_ref().is_ok_and(|events| {
+            events.len() >= self.filters.lock().unwrap().get(id).map(page_size).unwrap_or(DEFAULT_PAGE_SIZE)
+        });
+        let page = match page {
+            Ok(events) => Ok(self.shared().screen(id, events).await),
+            Err(e) => Err(e),
+        };
+        let added = match &page {
+            Ok(events) => {
+                save_to(self.store.as_ref(), events);
+                let deletions = self.deletions.read().await.clone();
+                let mut queries = self.queries.write().await;
+                let keep_requests = self.filters.lock().unwrap().get(id).is_some_and(shows_requests);
+                merge_events(queries.entry(id.to_string()).or_default(), events.clone(), &deletions, keep_requests)
+            }
+            Err(_) => 0,
+        };
+
+        {
+            let mut meta = self.meta.write().await;
+            if let Some(meta) = meta.get_mut(id) {
+                meta.loading_more = false;
+                if page.is_ok() {
+                    meta.has_more = full;
+                    meta.keep += added;
+                }
+            }
+            *self.version.write().await += 1;
+        }
+
+        page.map(|_| added)
+    }
+
+    /// Paging state of a query
+    pub async fn get_meta(&self, id: &str) -> Option<QueryMeta> {
+        self.meta.read().await.get(id).cloned()
+    }
+
     /// Get events for a specific query
     pub async fn get_query_events(&self, id: &str) -> Option<Vec<Event>> {
         let queries = self.queries.read().await;
@@ -245,6 +538,11 @@ impl QueryRuntime {
             json_queries.insert("enrichedFeed".to_string(), json!(enriched));
         }
 
+        // Loading and paging flags beside the results (`queries.$meta.feed.loading`)
+        let meta = self.meta.read().await;
+        let meta_json: serde_json::Map<String, Value> = meta.iter().map(|(id, m)| (id.clone(), m.to_json())).collect();
+        json_queries.insert("$meta".to_string(), Value::Object(meta_json));
+
         json!(json_queries)
     }
 
@@ -276,25 +574,13 @@ impl QueryRuntime {
 
         // Load profiles (deduplicates automatically via AddressLoader)
         let new_profiles = self.address_loader.load_profiles(pubkeys).await?;
+        let new_profiles = self.shared().screen("profiles", new_profiles).await;
 
         // Store profiles in queries if we got any new ones
         if !new_profiles.is_empty() {
+            // Merge profiles (newest per pubkey)
             let mut queries = self.queries.write().await;
-
-            // Get existing profiles
-            let existing = queries.get("profiles").cloned().unwrap_or_default();
-
-            // Merge profiles (deduplicate by pubkey)
-            let mut all_profiles_map: HashMap<String, Event> = HashMap::new();
-            for event in existing {
-                all_profiles_map.insert(event.pubkey.to_hex(), event);
-            }
-            for event in new_profiles {
-                all_profiles_map.insert(event.pubkey.to_hex(), event);
-            }
-
-            let all_profiles: Vec<Event> = all_profiles_map.into_values().collect();
-            queries.insert("profiles".to_string(), all_profiles);
+            merge_events(queries.entry("profiles".to_string()).or_default(), new_profiles, &[], false);
 
             // Increment version to trigger UI update
             let mut version = self.version.write().await;
@@ -306,6 +592,200 @@ impl QueryRuntime {
     }
 }
 
+/// Handles a subscription task shares with its QueryRuntime
+#[derive(Clone)]
+struct Shared {
+    queries: Arc<RwLock<HashMap<String, Vec<Event>>>>,
+    meta: Arc<RwLock<HashMap<String, QueryMeta>>>,
+    version: Arc<RwLock<u64>>,
+    deletions: Arc<RwLock<Vec<Event>>>,
+    store: Option<EventStore>,
+    policy: EventPolicy,
+    /// Filters of every subscribed query, including ones added after this task started
+    filters: Arc<Mutex<HashMap<String, Filter>>>,
+}
+
+impl Shared {
+    /// Whether a query asked for deletion requests, so they stay in its results
+    fn shows_requests(&self, id: &str) -> bool {
+        self.filters.lock().unwrap().get(id).is_some_and(shows_requests)
+    }
+
+    /// Drop events that fail the policy, counting why in the query's `$meta.rejected`
+    async fn screen(&self, id: &str, events: Vec<Event>) -> Vec<Event> {
+        let now = Timestamp::now();
+        let mut kept = Vec::with_capacity(events.len());
+        let mut rejected = Vec::new();
+        for event in events {
+            match self.policy.check(&event, now) {
+                Ok(()) => kept.push(event),
+                Err(reason) => rejected.push(reason),
+            }
+        }
+
+        if !rejected.is_empty() {
+            eprintln!("  ⚠️  Rejected {} events for query '{}'", rejected.len(), id);
+            let mut meta = self.meta.write().await;
+            // Queries the runtime fills itself (`profiles`) get an entry here, with nothing to wait for
+            let meta = meta.entry(id.to_string()).or_insert_with(|| QueryMeta {
+                loading: false,
+                has_more: false,
+                ..QueryMeta::new(DEFAULT_PAGE_SIZE)
+            });
+            for reason in rejected {
+                *meta.rejected.entry(reason.name()).or_default() += 1;
+            }
+            *self.version.write().await += 1;
+        }
+        kept
+    }
+
+    /// Merge a batch into a query's stored events, stamp the update time and bump the version
+    /// The query gets an entry even for an empty batch, so "no results" differs from "not loaded"
+    async fn store_batch(&self, id: &str, batch: Vec<Event>, limited: bool) -> Vec<Event> {
+        let batch = self.screen(id, batch).await;
+
+        // Save for the next run
+        save_to(self.store.as_ref(), &batch);
+
+        let keep = {
+            let mut meta = self.meta.write().await;
+            let meta = meta.get_mut(id);
+            let keep = meta.as_ref().map(|m| m.keep);
+            if let Some(m) = meta {
+                m.updated_at = Some(Timestamp::now().as_u64());
+            }
+            keep
+        };
+
+        // Deletion requests the query asked for apply to every query
+        let deletions = {
+            let mut deletions = self.deletions.write().await;
+            for request in batch.iter().filter(|e| e.kind == Kind::EventDeletion) {
+                if !deletions.contains(request) {
+                    deletions.push(request.clone());
+                }
+            }
+            deletions.clone()
+        };
+
+        // Merge into what's stored (older pages included) and increment version
+        let mut queries_lock = self.queries.write().await;
+        let stored = queries_lock.entry(id.to_string()).or_default();
+        merge_events(stored, batch, &deletions, self.shows_requests(id));
+
+        // Keep only the most recent events (new ones push the oldest out)
+        if let (true, Some(keep)) = (limited, keep) {
+            stored.truncate(keep);
+        }
+
+        let mut version_lock = self.version.write().await;
+        *version_lock += 1;
+
+        println!("  ✓ Stored {} events in queries map (version: {})", stored.len(), *version_lock);
+        stored.clone()
+    }
+
+    /// An author deleted something: drop it from every query
+    async fn add_deletion(&self, id: &str, request: Event) {
+        let Some(request) = self.screen(id, vec![request]).await.pop() else { return };
+        let mut deletions = self.deletions.write().await;
+        if deletions.contains(&request) {
+            return;
+        }
+        save_to(self.store.as_ref(), std::slice::from_ref(&request));
+        deletions.push(request);
+
+        let mut queries = self.queries.write().await;
+        for (id, events) in queries.iter_mut() {
+            normalize(events, &deletions, self.shows_requests(id));
+        }
+        *self.version.write().await += 1;
+    }
+}
+
+/// Fetch profiles for the feed's authors in the background and add them to `profiles`
+fn spawn_profile_fetch(address_loader: AddressLoader, shared: Shared) {
+    tokio::spawn(async move {
+        // Extract unique pubkeys from feed
+        let pubkeys: Vec<PublicKey> = {
+            let queries_lock = shared.queries.read().await;
+            if let Some(feed_events) = queries_lock.get("feed") {
+                feed_events
+                    .iter()
+                    .map(|e| e.pubkey)
+                    .collect::<std::collections::HashSet<_>>()
+                    .into_iter()
+                    .collect()
+            } else {
+                vec![]
+            }
+        };
+
+        if !pubkeys.is_empty() {
+            // Load profiles (AddressLoader handles deduplication)
+            if let Ok(new_profiles) = address_loader.load_profiles(pubkeys).await {
+                let new_profiles = shared.screen("profiles", new_profiles).await;
+                if !new_profiles.is_empty() {
+                    // Merge with existing profiles (newest per pubkey)
+                    let mut queries_lock = shared.queries.write().await;
+                    merge_events(queries_lock.entry("profiles".to_string()).or_default(), new_profiles, &[], false);
+
+                    // Increment version
+                    let mut version_lock = shared.version.write().await;
+                    *version_lock += 1;
+                    println!("  ✓ Profiles updated (version: {})", *version_lock);
+                }
+            }
+        }
+    });
+}
+
+/// Deletion requests to follow live for a query: those by its authors
+/// A query without `authors` gets none, so its results only lose what other queries' deletions or
+/// stored ones remove (following `#e`/`#a` references would mean resubscribing as events arrive)
+fn deletion_filter(filter: &Filter) -> Option<Filter> {
+    let authors = filter.authors.as_ref()?;
+    Some(Filter::new().kind(Kind::EventDeletion).authors(authors.clone()))
+}
+
+/// Whether a query asked for deletion requests (kind 5) themselves, not only for what they delete
+fn shows_requests(filter: &Filter) -> bool {
+    filter.kinds.as_ref().is_some_and(|kinds| kinds.contains(&Kind::EventDeletion))
+}
+
+/// Events per page: the filter's `limit`, or a default
+fn page_size(filter: &Filter) -> usize {
+    filter.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+}
+
+/// Add events to a newest-first list and normalize it (see `normalize`): duplicates, older versions
+/// of replaceable events and events deleted by their author drop out
+/// Returns how many of the incoming events are new and kept
+pub fn merge_events(events: &mut Vec<Event>, incoming: Vec<Event>, deletions: &[Event], keep_requests: bool) -> usize {
+    let before: HashSet<EventId> = events.iter().map(|e| e.id).collect();
+    events.extend(incoming);
+    normalize(events, deletions, keep_requests);
+    events.iter().filter(|e| !before.contains(&e.id)).count()
+}
+
+/// Save events for the next run, if there is a store
+fn save_to(store: Option<&EventStore>, events: &[Event]) {
+    if let Some(store) = store {
+        if let Err(e) = store.save(events) {
+            eprintln!("⚠️  Failed to store events: {}", e);
+        }
+    }
+}
+
+/// `loadMore(<query>)` button action: the query id, written as `feed`, `"feed"` or `queries.feed`
+pub fn load_more_target(on_click: &str) -> Option<&str> {
+    let inner = on_click.trim().strip_prefix("loadMore(")?.strip_suffix(')')?.trim();
+    let inner = inner.strip_prefix("queries.").unwrap_or(inner);
+    let inner = inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(inner);
+    (!inner.is_empty()).then_some(inner)
+}
+
 /// Convert a Nostr Event to JSON
 fn event_to_json(event: &Event) -> Value {
     json!({
@@ -350,6 +830,244 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    fn note_at(keys: &Keys, content: &str, created_at: u64) -> Event {
+        EventBuilder::text_note(content)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_merge_events() {
+        let keys = Keys::generate();
+        let (a, b, c) = (note_at(&keys, "a", 300), note_at(&keys, "b", 200), note_at(&keys, "c", 100));
+
+        let mut events = vec![b.clone()];
+        assert_eq!(merge_events(&mut events, vec![c.clone(), b.clone(), a.clone()], &[], false), 2);
+        assert_eq!(events.iter().map(|e| e.content.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(merge_events(&mut events, vec![a.clone()], &[], false), 0);
+
+        // A newer profile replaces the old one, and deleted notes drop out
+        let profile = |name: &str, at| EventBuilder::metadata(&Metadata::new().name(name)).custom_created_at(Timestamp::from(at)).sign_with_keys(&keys).unwrap();
+        let deletion = EventBuilder::delete(EventDeletionRequest::new().id(b.id)).sign_with_keys(&keys).unwrap();
+        assert_eq!(merge_events(&mut events, vec![profile("old", 10), profile("new", 20)], &[deletion], false), 1);
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|e| e.id != b.id));
+        assert!(events.iter().any(|e| e.content.contains("new")));
+    }
+
+    #[test]
+    fn test_load_more_target() {
+        assert_eq!(load_more_target("loadMore(feed)"), Some("feed"));
+        assert_eq!(load_more_target(" loadMore(\"feed\") "), Some("feed"));
+        assert_eq!(load_more_target("loadMore(queries.feed)"), Some("feed"));
+        assert_eq!(load_more_target("loadMore()"), None);
+        assert_eq!(load_more_target("actions.post"), None);
+    }
+
+    #[tokio::test]
+    async fn test_load_more_pages() {
+        let keys = Keys::generate();
+        let runtime = QueryRuntime::new().await.unwrap();
+        runtime.filters.lock().unwrap().insert("feed".to_string(), Filter::new().kind(Kind::TextNote).limit(2));
+        runtime.meta.write().await.insert("feed".to_string(), QueryMeta::new(2));
+        runtime.queries.write().await.insert("feed".to_string(), vec![note_at(&keys, "new", 300), note_at(&keys, "old", 200)]);
+
+        // The next page ends just before the oldest event, and only one page loads at a time
+        let page = runtime.start_load_more("feed").await.unwrap().unwrap();
+        assert_eq!(page.until, Some(Timestamp::from(199)));
+        assert!(runtime.get_meta("feed").await.unwrap().loading_more);
+        assert!(runtime.start_load_more("feed").await.unwrap().is_none());
+        let meta = &runtime.to_json().await["$meta"]["feed"];
+        assert_eq!(meta["hasMore"], json!(true));
+        assert_eq!(meta["loadingMore"], json!(true));
+
+        // Events sharing a timestamp all arrive
+        let (older, also_older) = (note_at(&keys, "older", 100), note_at(&keys, "also older", 100));
+        let added = runtime.finish_load_more("feed", Ok(vec![older.clone(), also_older.clone()])).await.unwrap();
+        assert_eq!(added, 2);
+        let meta = runtime.get_meta("feed").await.unwrap();
+        assert!(meta.has_more && !meta.loading_more);
+        assert_eq!(meta.keep, 4);
+        assert_eq!(runtime.start_load_more("feed").await.unwrap().unwrap().until, Some(Timestamp::from(99)));
+
+        // A full page of events we already have is not the end
+        assert_eq!(runtime.finish_load_more("feed", Ok(vec![older, also_older])).await.unwrap(), 0);
+        assert!(runtime.get_meta("feed").await.unwrap().has_more);
+
+        // A short page means the end was reached
+        runtime.start_load_more("feed").await.unwrap().unwrap();
+        assert_eq!(runtime.finish_load_more("feed", Ok(vec![note_at(&keys, "oldest", 50)])).await.unwrap(), 1);
+        assert!(!runtime.get_meta("feed").await.unwrap().has_more);
+        assert!(runtime.start_load_more("feed").await.unwrap().is_none());
+
+        assert!(runtime.start_load_more("missing").await.is_err());
+    }
+
+    #[test]
+    fn test_closed_relays_are_not_eose() {
+        let mut meta = QueryMeta::new(10);
+        meta.relays = 2;
+
+        // A relay that refuses the subscription ends its part of loading without having sent everything
+        meta.relay_closed("wss://a".to_string(), "rate-limited: slow down".to_string());
+        assert!(meta.loading && meta.eose.is_empty());
+        meta.relay_done("wss://b".to_string());
+        assert!(!meta.loading);
+
+        let json = meta.to_json();
+        assert_eq!(json["eose"], json!(["wss://b"]));
+        assert_eq!(json["closed"], json!({ "wss://a": "rate-limited: slow down" }));
+        assert_eq!(json["error"], json!("wss://a: rate-limited: slow down"));
+
+        // Closing after EOSE doesn't count the relay twice
+        let mut meta = QueryMeta::new(10);
+        meta.relays = 2;
+        meta.relay_done("wss://a".to_string());
+        meta.relay_closed("wss://a".to_string(), "shutting down".to_string());
+        assert!(meta.loading);
+    }
+
+    #[tokio::test]
+    async fn test_query_loading_state() {
+        let mut meta = QueryMeta::new(10);
+        meta.relays = 2;
+        assert!(meta.loading);
+
+        meta.relay_done("wss://a".to_string());
+        meta.relay_done("wss://a".to_string());
+        assert!(meta.loading);
+        meta.relay_done("wss://b".to_string());
+        assert!(!meta.loading);
+
+        let json = meta.to_json();
+        assert_eq!(json["eose"], json!(["wss://a", "wss://b"]));
+        assert_eq!(json["closed"], json!({}));
+        assert_eq!(json["error"], Value::Null);
+        assert_eq!(json["updatedAt"], Value::Null);
+
+        // Stored events stamp the update time, and an empty batch still marks the query loaded
+        let runtime = QueryRuntime::new().await.unwrap();
+        runtime.meta.write().await.insert("feed".to_string(), meta);
+        assert!(runtime.shared().store_batch("feed", vec![], true).await.is_empty());
+        assert!(runtime.queries.read().await.contains_key("feed"));
+        assert!(runtime.get_meta("feed").await.unwrap().updated_at.is_some());
+        assert_eq!(runtime.get_version().await, 1);
+
+        // Failures stop loading instead of panicking
+        runtime.record_error("feed", "no relays".to_string()).await;
+        assert!(!runtime.is_loading().await);
+        assert_eq!(runtime.to_json().await["$meta"]["feed"]["error"], "no relays");
+    }
+
+    #[tokio::test]
+    async fn test_offline_queries_use_store() {
+        let dir = std::env::temp_dir().join(format!("html6-offline-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = EventStore::open(&dir).unwrap();
+        let keys = Keys::generate();
+        store.save(&[note_at(&keys, "stored", 100)]).unwrap();
+
+        let mut runtime = QueryRuntime::new().await.unwrap().with_store(store.clone()).with_offline(true);
+        let mine = Filter::new().kind(Kind::TextNote).author(keys.public_key());
+        let others = Filter::new().kind(Kind::TextNote).author(Keys::generate().public_key());
+        runtime.subscribe_filter("feed".to_string(), mine).await.unwrap();
+        runtime.subscribe_filter("other".to_string(), others).await.unwrap();
+
+        let json = runtime.to_json().await;
+        assert_eq!(json["feed"][0]["content"], "stored");
+        assert_eq!(json["other"], json!([]));
+        assert!(!runtime.is_loading().await);
+
+        store.flush();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_deletion_drops_event_from_every_query() {
+        let keys = Keys::generate();
+        let note = note_at(&keys, "gone", 100);
+        let runtime = QueryRuntime::new().await.unwrap();
+        let shared = runtime.shared();
+        shared.store_batch("feed", vec![note.clone(), note_at(&keys, "kept", 50)], false).await;
+        shared.store_batch("mine", vec![note.clone()], false).await;
+
+        let request = EventBuilder::delete(EventDeletionRequest::new().id(note.id)).sign_with_keys(&keys).unwrap();
+        shared.add_deletion("feed", request).await;
+
+        let json = runtime.to_json().await;
+        assert_eq!(json["feed"].as_array().unwrap().len(), 1);
+        assert_eq!(json["feed"][0]["content"], "kept");
+        assert_eq!(json["mine"], json!([]));
+    }
+
+    #[test]
+    fn test_deletion_filter_needs_authors() {
+        let author = Keys::generate().public_key();
+        let deletions = deletion_filter(&Filter::new().kind(Kind::TextNote).author(author)).unwrap();
+        assert_eq!(deletions, Filter::new().kind(Kind::EventDeletion).author(author));
+
+        // Without authors there is nobody whose deletions to follow
+        assert_eq!(deletion_filter(&Filter::new().kind(Kind::TextNote)), None);
+    }
+
+    #[tokio::test]
+    async fn test_deletion_requests_only_shown_when_asked_for() {
+        let keys = Keys::generate();
+        let note = note_at(&keys, "gone", 100);
+        let request = EventBuilder::delete(EventDeletionRequest::new().id(note.id)).sign_with_keys(&keys).unwrap();
+
+        let runtime = QueryRuntime::new().await.unwrap();
+        runtime.filters.lock().unwrap().insert("feed".to_string(), Filter::new().kinds([Kind::TextNote, Kind::EventDeletion]));
+        runtime.filters.lock().unwrap().insert("notes".to_string(), Filter::new().kind(Kind::TextNote));
+        let shared = runtime.shared();
+        shared.store_batch("feed", vec![note.clone(), request.clone()], false).await;
+        shared.store_batch("notes", vec![note, request.clone(), note_at(&keys, "kept", 50)], false).await;
+
+        let json = runtime.to_json().await;
+        assert_eq!(json["feed"].as_array().unwrap().len(), 1);
+        assert_eq!(json["feed"][0]["id"], request.id.to_hex());
+        assert_eq!(json["notes"].as_array().unwrap().len(), 1);
+        assert_eq!(json["notes"][0]["content"], "kept");
+
+        // A query added after `shared` was taken (as a later subscription is) still keeps its requests
+        // when an earlier query's task applies a new deletion
+        let other = note_at(&keys, "also gone", 90);
+        let second = EventBuilder::delete(EventDeletionRequest::new().id(other.id)).sign_with_keys(&keys).unwrap();
+        runtime.filters.lock().unwrap().insert("requests".to_string(), Filter::new().kind(Kind::EventDeletion));
+        runtime.queries.write().await.insert("requests".to_string(), vec![request.clone()]);
+        shared.add_deletion("notes", second).await;
+        let json = runtime.to_json().await;
+        assert_eq!(json["requests"].as_array().unwrap().len(), 1);
+        assert_eq!(json["requests"][0]["id"], request.id.to_hex());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_events_are_counted() {
+        let (keys, blocked) = (Keys::generate(), Keys::generate());
+        let runtime = QueryRuntime::new()
+            .await
+            .unwrap()
+            .with_policy(EventPolicy::default().with_blocked(blocked.public_key()));
+        runtime.meta.write().await.insert("feed".to_string(), QueryMeta::new(10));
+
+        let mut forged = note_at(&keys, "real", 100);
+        forged.content = "forged".to_string();
+        let batch = vec![note_at(&keys, "kept", 100), note_at(&blocked, "blocked", 100), forged];
+        let stored = runtime.shared().store_batch("feed", batch, false).await;
+
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].content, "kept");
+        let json = runtime.to_json().await;
+        assert_eq!(json["$meta"]["feed"]["rejected"], json!({"blocked": 1, "invalidId": 1}));
+
+        // Profiles have no subscription, but their rejections still show
+        runtime.shared().screen("profiles", vec![note_at(&blocked, "profile", 100)]).await;
+        let meta = runtime.get_meta("profiles").await.unwrap();
+        assert_eq!(meta.rejected.get("blocked"), Some(&1));
+        assert!(!meta.loading && !meta.has_more);
+    }
+
     #[test]
     fn test_event_to_json() {
         use nostr_sdk::prelude::*;
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use nostr_sdk::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::runtime::address::{content_sha256, AppAddress};
+
+/// On-disk cache of source events (documents/components) fetched from Nostr
+/// Events are stored as signed JSON so they can be re-verified when read back
+#[derive(Debug, Clone)]
+pub struct SourceCache {
+    dir: PathBuf,
+}
+
+impl SourceCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Default cache location: $HTML6_CACHE_DIR, $XDG_CACHE_HOME/html6 or ~/.cache/html6
+    pub fn default_dir() -> PathBuf {
+        if let Ok(dir) = std::env::var("HTML6_CACHE_DIR") {
+            return PathBuf::from(dir);
+        }
+        if let Ok(dir) = std::env::var("XDG_CACHE_HOME") {
+            return PathBuf::from(dir).join("html6");
+        }
+        if let Ok(home) = std::env::var("HOME") {
+            return PathBuf::from(home).join(".cache").join("html6");
+        }
+        PathBuf::from(".html6-cache")
+    }
+
+    /// Cache at the default location
+    pub fn open_default() -> Self {
+        Self::new(Self::default_dir().join("sources"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path_for(&self, address: &AppAddress) -> PathBuf {
+        // Identifiers can contain any characters, so key by hash of the coordinate
+        self.dir.join(format!("{}.json", content_sha256(&address.coordinate())))
+    }
+
+    /// Read a cached event for an address
+    pub fn get(&self, address: &AppAddress) -> Option<Event> {
+        let json = fs::read_to_string(self.path_for(address)).ok()?;
+        Event::from_json(json).ok()
+    }
+
+    /// Store an event for an address (replacing older versions)
+    pub fn put(&self, address: &AppAddress, event: &Event) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache dir {}", self.dir.display()))?;
+        let path = self.path_for(address);
+        fs::write(&path, event.as_json())
+            .with_context(|| format!("Failed to write cache file {}", path.display()))?;
+        Ok(())
+    }
+}
+
+impl Default for SourceCache {
+    fn default() -> Self {
+        Self::open_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::address::{source_event_builder, APP_KIND, COMPONENT_TAG};
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("html6-cache-test-{}", std::process::id()));
+        let cache = SourceCache::new(&dir);
+
+        let keys = Keys::generate();
+        let event = source_event_builder("card", "**hi**", COMPONENT_TAG)
+            .sign_with_keys(&keys)
+            .unwrap();
+        let addr = AppAddress::new(APP_KIND, keys.public_key(), "card");
+
+        assert!(cache.get(&addr).is_none());
+        cache.put(&addr, &event).unwrap();
+        assert_eq!(cache.get(&addr), Some(event));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,122 @@
+use nostr_sdk::prelude::*;
+use std::collections::HashSet;
+
+/// Why an event was kept from a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The author is blocked
+    Blocked,
+    /// The content is over the size limit
+    TooLarge,
+    /// `created_at` is further in the future than allowed
+    FromTheFuture,
+    /// The id isn't the hash of the event
+    InvalidId,
+    /// The signature doesn't match the id and author
+    InvalidSignature,
+}
+
+impl Rejection {
+    /// Key the rejection is counted under in `queries.$meta.<id>.rejected`
+    pub fn name(self) -> &'static str {
+        match self {
+            Rejection::Blocked => "blocked",
+            Rejection::TooLarge => "tooLarge",
+            Rejection::FromTheFuture => "future",
+            Rejection::InvalidId => "invalidId",
+            Rejection::InvalidSignature => "invalidSignature",
+        }
+    }
+}
+
+/// Rules events from relays must pass before they reach a page
+#[derive(Debug, Clone)]
+pub struct EventPolicy {
+    /// How far ahead of the local clock `created_at` may be, in seconds
+    pub max_future_skew_secs: u64,
+    /// Largest content accepted, in bytes
+    pub max_content_bytes: usize,
+    /// Authors whose events are dropped
+    pub blocked_pubkeys: HashSet<PublicKey>,
+}
+
+impl Default for EventPolicy {
+    fn default() -> Self {
+        Self {
+            max_future_skew_secs: 15 * 60,
+            max_content_bytes: 64 * 1024,
+            blocked_pubkeys: HashSet::new(),
+        }
+    }
+}
+
+impl EventPolicy {
+    /// Drop every event by this author
+    pub fn with_blocked(mut self, pubkey: PublicKey) -> Self {
+        self.blocked_pubkeys.insert(pubkey);
+        self
+    }
+
+    /// Check an event against the rules (cheap ones first), then its id and signature
+    pub fn check(&self, event: &Event, now: Timestamp) -> Result<(), Rejection> {
+        if self.blocked_pubkeys.contains(&event.pubkey) {
+            return Err(Rejection::Blocked);
+        }
+        if event.content.len() > self.max_content_bytes {
+            return Err(Rejection::TooLarge);
+        }
+        if event.created_at.as_u64() > now.as_u64() + self.max_future_skew_secs {
+            return Err(Rejection::FromTheFuture);
+        }
+        if !event.verify_id() {
+            return Err(Rejection::InvalidId);
+        }
+        if !event.verify_signature() {
+            return Err(Rejection::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_rules() {
+        let keys = Keys::generate();
+        let now = Timestamp::from(1_000_000);
+        let note = |content: &str, at: u64| {
+            EventBuilder::text_note(content)
+                .custom_created_at(Timestamp::from(at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let policy = EventPolicy::default();
+
+        assert_eq!(policy.check(&note("hi", 1_000_000), now), Ok(()));
+        assert_eq!(policy.check(&note("hi", 1_000_000 + 10 * 60), now), Ok(()));
+        assert_eq!(policy.check(&note("hi", 1_000_000 + 60 * 60), now), Err(Rejection::FromTheFuture));
+        assert_eq!(policy.check(&note(&"x".repeat(70_000), 1_000_000), now), Err(Rejection::TooLarge));
+
+        let blocking = policy.clone().with_blocked(keys.public_key());
+        assert_eq!(blocking.check(&note("hi", 1_000_000), now), Err(Rejection::Blocked));
+    }
+
+    #[test]
+    fn test_check_id_and_signature() {
+        let keys = Keys::generate();
+        let now = Timestamp::now();
+        let policy = EventPolicy::default();
+        let event = EventBuilder::text_note("original").sign_with_keys(&keys).unwrap();
+        let other = EventBuilder::text_note("other").sign_with_keys(&keys).unwrap();
+
+        let mut edited = event.clone();
+        edited.content = "edited".to_string();
+        assert_eq!(policy.check(&edited, now), Err(Rejection::InvalidId));
+
+        let mut resigned = event.clone();
+        resigned.sig = other.sig;
+        assert_eq!(policy.check(&resigned, now), Err(Rejection::InvalidSignature));
+    }
+}
@@ -0,0 +1,229 @@
+use html6::loader;
+use html6::runtime::address::{
+    content_sha256, source_event_builder, AppAddress, APP_KIND, COMPONENT_TAG, DOCUMENT_TAG,
+};
+use html6::runtime::{AddressLoader, ComponentRegistry, EventPolicy, NostrClient, SourceCache};
+use nostr_relay_builder::prelude::{MockRelay, RelayTestOptions};
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PROFILE_SOURCE: &str = r#"---
+props:
+  pubkey: string
+---
+
+**{props.pubkey}**
+"#;
+
+/// Connect a client to the mock relay and give it a moment to handshake
+async fn connect(url: &str) -> Arc<NostrClient> {
+    let client = NostrClient::new(vec![url.to_string()]).await.expect("Failed to create client");
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    Arc::new(client)
+}
+
+async fn publish(client: &NostrClient, keys: &Keys, identifier: &str, content: &str, file_tag: &str) {
+    let event = source_event_builder(identifier, content, file_tag)
+        .sign_with_keys(keys)
+        .expect("Failed to sign");
+    client.publish(event).await.expect("Failed to publish");
+}
+
+fn temp_cache(name: &str) -> SourceCache {
+    let dir = std::env::temp_dir().join(format!("html6-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    SourceCache::new(dir)
+}
+
+#[tokio::test]
+async fn test_load_component_from_relay() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let keys = Keys::generate();
+    let client = connect(&url).await;
+    publish(&client, &keys, "profile-card", PROFILE_SOURCE, COMPONENT_TAG).await;
+
+    let loader = AddressLoader::new(connect(&url).await);
+    let mut registry = ComponentRegistry::new(".").with_cache(temp_cache("component"));
+
+    let address = AppAddress::parse(&format!("nostr:30078:{}:profile-card", keys.public_key().to_hex()))
+        .unwrap();
+    registry
+        .load_remote_component("Profile", &address, &loader)
+        .await
+        .expect("Failed to load remote component");
+
+    assert!(registry.contains("Profile"));
+    assert!(registry.get("Profile").unwrap().props.contains_key("pubkey"));
+}
+
+#[tokio::test]
+async fn test_policy_applies_to_components() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let keys = Keys::generate();
+    let client = connect(&url).await;
+    publish(&client, &keys, "profile-card", PROFILE_SOURCE, COMPONENT_TAG).await;
+
+    let policy = EventPolicy::default().with_blocked(keys.public_key());
+    let loader = AddressLoader::new(connect(&url).await).with_policy(policy);
+    let mut registry = ComponentRegistry::new(".");
+
+    let address = AppAddress::new(APP_KIND, keys.public_key(), "profile-card");
+    let result = registry.load_remote_component("Profile", &address, &loader).await;
+
+    assert!(result.is_err(), "Components by a blocked author must not load");
+    assert!(!registry.contains("Profile"));
+}
+
+#[tokio::test]
+async fn test_pinned_hash_mismatch_is_rejected() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let keys = Keys::generate();
+    let client = connect(&url).await;
+    publish(&client, &keys, "profile-card", PROFILE_SOURCE, COMPONENT_TAG).await;
+
+    let loader = AddressLoader::new(connect(&url).await);
+    let mut registry = ComponentRegistry::new(".");
+
+    let address = AppAddress::new(APP_KIND, keys.public_key(), "profile-card")
+        .with_sha256(content_sha256("something else"));
+    let result = registry.load_remote_component("Profile", &address, &loader).await;
+
+    assert!(result.is_err(), "Tampered content must not load");
+    assert!(!registry.contains("Profile"));
+}
+
+#[tokio::test]
+async fn test_load_document_from_naddr() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let keys = Keys::generate();
+    let client = connect(&url).await;
+
+    let component = AppAddress::new(APP_KIND, keys.public_key(), "profile-card")
+        .with_sha256(content_sha256(PROFILE_SOURCE));
+    let document = format!(
+        "---\nimports:\n  Profile: \"{}\"\n---\n\n# Remote App\n\n<Profile pubkey=\"abc\" />\n",
+        component.to_reference()
+    );
+
+    publish(&client, &keys, "profile-card", PROFILE_SOURCE, COMPONENT_TAG).await;
+    publish(&client, &keys, "remote-app", &document, DOCUMENT_TAG).await;
+
+    let mut app = AppAddress::new(APP_KIND, keys.public_key(), "remote-app");
+    app.relays = vec![url.clone()];
+    let naddr = app.to_naddr().unwrap();
+
+    let cache = temp_cache("document");
+    let loader = AddressLoader::new(connect(&url).await);
+    let (doc, registry) = loader::load_hnmd_from_address(&naddr, &loader, Some(cache.clone()))
+        .await
+        .expect("Failed to load document from naddr");
+
+    assert!(doc.imports.contains_key("Profile"));
+    assert!(registry.contains("Profile"));
+
+    // The pinned component is now served from the cache without a relay
+    drop(relay);
+    let offline_loader = AddressLoader::new(Arc::new(NostrClient::new(vec![]).await.unwrap()));
+    let mut offline = ComponentRegistry::new(".").with_cache(cache);
+    offline
+        .load_remote_component("Profile", &component, &offline_loader)
+        .await
+        .expect("Pinned component should load from cache");
+    assert!(offline.contains("Profile"));
+}
+
+async fn publish_profile(client: &NostrClient, keys: &Keys, name: &str) -> Event {
+    let event = EventBuilder::metadata(&Metadata::new().name(name))
+        .sign_with_keys(keys)
+        .expect("Failed to sign");
+    client.publish(event.clone()).await.expect("Failed to publish");
+    event
+}
+
+#[tokio::test]
+async fn test_concurrent_loads_share_a_batch() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let (alice, bob) = (Keys::generate(), Keys::generate());
+    let client = connect(&url).await;
+    let alice_profile = publish_profile(&client, &alice, "alice").await;
+    let bob_profile = publish_profile(&client, &bob, "bob").await;
+
+    let loader = AddressLoader::new(connect(&url).await);
+    let (first, second, other) = tokio::join!(
+        loader.load(0, alice.public_key(), None),
+        loader.load(0, alice.public_key(), None),
+        loader.load(0, bob.public_key(), None),
+    );
+    assert_eq!(first.unwrap(), Some(alice_profile.clone()));
+    assert_eq!(second.unwrap(), Some(alice_profile));
+    assert_eq!(other.unwrap(), Some(bob_profile));
+
+    // Already loaded, so nothing new to return
+    let again = loader.load_profiles(vec![alice.public_key(), bob.public_key()]).await.unwrap();
+    assert!(again.is_empty());
+}
+
+#[tokio::test]
+async fn test_not_found_is_remembered_for_a_while() {
+    let relay = MockRelay::run().await.expect("Failed to start mock relay");
+    let url = relay.url().to_string();
+
+    let keys = Keys::generate();
+    let client = connect(&url).await;
+    let remembering = AddressLoader::new(connect(&url).await).with_not_found_ttl(Duration::from_secs(60));
+    let forgetting = AddressLoader::new(connect(&url).await).with_not_found_ttl(Duration::ZERO);
+
+    assert!(load_later(&remembering, &keys).await.is_none());
+    assert!(load_later(&forgetting, &keys).await.is_none());
+
+    publish(&client, &keys, "later", PROFILE_SOURCE, COMPONENT_TAG).await;
+
+    assert!(load_later(&remembering, &keys).await.is_none());
+    assert!(load_later(&forgetting, &keys).await.is_some());
+}
+
+async fn load_later(loader: &AddressLoader, keys: &Keys) -> Option<Event> {
+    loader
+        .load(APP_KIND, keys.public_key(), Some("later".to_string()))
+        .await
+        .expect("Failed to load")
+}
+
+#[tokio::test]
+async fn test_relay_timeout_is_not_not_found() {
+    // A relay that accepts connections but doesn't answer in time
+    let options = RelayTestOptions {
+        send_random_events: false,
+        unresponsive_connection: Some(Duration::from_secs(60)),
+    };
+    let relay = MockRelay::run_with_opts(options).await.expect("Failed to start mock relay");
+    let loader = AddressLoader::new(connect(&relay.url().to_string()).await)
+        .with_fetch_timeout(Duration::from_millis(200))
+        .with_not_found_ttl(Duration::from_secs(60));
+
+    // Every attempt times out, so the load fails instead of answering None
+    let keys = Keys::generate();
+    let started = std::time::Instant::now();
+    assert!(load_later_result(&loader, &keys).await.is_err());
+    assert!(started.elapsed() >= Duration::from_millis(600), "retried");
+
+    // Nothing was remembered as missing: the next load asks the relay again
+    let started = std::time::Instant::now();
+    assert!(load_later_result(&loader, &keys).await.is_err());
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+async fn load_later_result(loader: &AddressLoader, keys: &Keys) -> anyhow::Result<Option<Event>> {
+    loader.load(APP_KIND, keys.public_key(), Some("later".to_string())).await
+}